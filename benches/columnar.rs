@@ -0,0 +1,54 @@
+//! Column-oriented storage: summing a column and reading a buffer with ColumnarJMap against JMapInfo
+//!
+//! Run with `cargo bench --bench columnar`
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use lib_bcsv_jmap::{
+    from_buffer, from_buffer_columnar, no_lookup, to_buffer, FieldType, FieldValue, IoOptions, JMapInfo,
+};
+
+const ENTRIES: usize = 20_000;
+const FIELDS: usize = 30;
+const SUM_ROUNDS: usize = 100;
+const READ_ROUNDS: usize = 20;
+
+fn main() {
+    let mut jmap = JMapInfo::new(no_lookup());
+    for field in 0..FIELDS {
+        jmap.create_field(&format!("Field{field}"), FieldType::Long, FieldValue::Int(0)).unwrap();
+    }
+    let hashes: Vec<u32> = jmap.field_hashes().copied().collect();
+    for index in 0..ENTRIES {
+        let entry = jmap.create_entry();
+        for (field, &hash) in hashes.iter().enumerate() {
+            entry.set_by_hash(hash, FieldValue::Int((index * FIELDS + field) as i32));
+        }
+    }
+    let data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+    let hash = hashes[FIELDS / 2];
+
+    println!("{ENTRIES} entries of {FIELDS} Long fields");
+
+    let by_row = time_ms(SUM_ROUNDS, || {
+        jmap.entries().iter().filter_map(|entry| entry.get_by_hash(hash)?.as_int()).map(i64::from).sum::<i64>()
+    });
+    let columnar = from_buffer_columnar(no_lookup(), &data, &IoOptions::default()).unwrap();
+    let by_column =
+        time_ms(SUM_ROUNDS, || columnar.column_i32(hash).unwrap().iter().copied().map(i64::from).sum::<i64>());
+    println!("summing one column {SUM_ROUNDS} times: {by_row:.2} ms by row, {by_column:.2} ms by column");
+
+    let rows = time_ms(READ_ROUNDS, || from_buffer(no_lookup(), &data, &IoOptions::default()).unwrap());
+    let columns = time_ms(READ_ROUNDS, || from_buffer_columnar(no_lookup(), &data, &IoOptions::default()).unwrap());
+    println!("reading the buffer {READ_ROUNDS} times: {rows:.1} ms from_buffer, {columns:.1} ms from_buffer_columnar");
+}
+
+/// Run `f` `rounds` times, returning the total time taken in milliseconds
+fn time_ms<T>(rounds: usize, mut f: impl FnMut() -> T) -> f64 {
+    let start = Instant::now();
+    for _ in 0..rounds {
+        black_box(f());
+    }
+    start.elapsed().as_secs_f64() * 1000.0
+}
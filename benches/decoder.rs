@@ -0,0 +1,69 @@
+//! Field decoding: reading a wide table whose integer fields use their full mask, which skips the mask
+//! arithmetic, against the same table with masks one bit narrower, which goes through the generic path
+//!
+//! Run with `cargo bench --bench decoder`
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use lib_bcsv_jmap::{
+    from_buffer, from_buffer_columnar, no_lookup, to_buffer, Field, FieldType, FieldValue, IoOptions, JMapInfo,
+};
+
+const ENTRIES: usize = 20_000;
+const FIELDS: usize = 30;
+const READ_ROUNDS: usize = 5;
+
+const FIELD_TYPES: [FieldType; 5] =
+    [FieldType::Long, FieldType::Short, FieldType::Char, FieldType::UnsignedLong, FieldType::Float];
+
+fn main() {
+    let full = table(false);
+    let masked = table(true);
+    println!("{ENTRIES} entries of {FIELDS} Long, Short, Char, UnsignedLong and Float fields, best of {READ_ROUNDS}");
+
+    for (name, data) in [("full masks", &full), ("narrowed masks", &masked)] {
+        let rows = best_ms(|| from_buffer(no_lookup(), data, &IoOptions::default()).unwrap());
+        let columns = best_ms(|| from_buffer_columnar(no_lookup(), data, &IoOptions::default()).unwrap());
+        println!("{name}: from_buffer {rows:.1} ms, from_buffer_columnar {columns:.1} ms");
+    }
+}
+
+/// Write the benchmark table, with the integer masks one bit narrower than their type if `narrow`
+fn table(narrow: bool) -> Vec<u8> {
+    let mut jmap = JMapInfo::new(no_lookup());
+    for field in 0..FIELDS {
+        let field_type = FIELD_TYPES[field % FIELD_TYPES.len()];
+        let mask = match field_type {
+            FieldType::Float => field_type.default_mask(),
+            _ if narrow => field_type.default_mask() >> 1,
+            _ => field_type.default_mask(),
+        };
+        jmap.add_field(&format!("Field{field}"), Field::with_mask_shift(0, field_type, mask, 0)).unwrap();
+    }
+    let fields: Vec<(u32, FieldType)> = jmap.fields().map(|field| (field.hash, field.field_type)).collect();
+    for index in 0..ENTRIES {
+        let entry = jmap.create_entry();
+        for &(hash, field_type) in &fields {
+            // Small enough for the narrowed masks, so both tables hold the same values
+            let value = match field_type {
+                FieldType::Float => FieldValue::Float(index as f32 * 0.5),
+                FieldType::UnsignedLong => FieldValue::UInt((index % 100) as u32),
+                _ => FieldValue::Int((index % 100) as i32),
+            };
+            entry.set_by_hash(hash, value);
+        }
+    }
+    to_buffer(&jmap, &IoOptions::default()).unwrap()
+}
+
+/// Run `f` `READ_ROUNDS` times, returning the shortest time taken in milliseconds
+fn best_ms<T>(mut f: impl FnMut() -> T) -> f64 {
+    (0..READ_ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed().as_secs_f64() * 1000.0
+        })
+        .fold(f64::INFINITY, f64::min)
+}
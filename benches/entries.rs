@@ -0,0 +1,106 @@
+//! Entry storage: memory and lookup speed of slot-based entries against a `HashMap` per entry
+//!
+//! Run with `cargo bench --bench entries`
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use lib_bcsv_jmap::{from_buffer, no_lookup, to_buffer, Entry, FieldType, FieldValue, IoOptions, JMapInfo};
+
+const ENTRIES: usize = 20_000;
+const FIELDS: usize = 30;
+const LOOKUP_ROUNDS: usize = 10;
+
+/// Counts the bytes currently allocated, to measure what a structure keeps on the heap
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Measure the heap bytes still held by the value built by `build`
+fn retained<T>(build: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = build();
+    (value, ALLOCATED.load(Ordering::Relaxed) - before)
+}
+
+fn main() {
+    let mut jmap = JMapInfo::new(no_lookup());
+    for field in 0..FIELDS {
+        jmap.create_field(&format!("Field{field}"), FieldType::Long, FieldValue::Int(0)).unwrap();
+    }
+    let hashes: Vec<u32> = jmap.field_hashes().copied().collect();
+    for index in 0..ENTRIES {
+        let entry = jmap.create_entry();
+        for (field, &hash) in hashes.iter().enumerate() {
+            entry.set_by_hash(hash, FieldValue::Int((index * FIELDS + field) as i32));
+        }
+    }
+    let data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+    drop(jmap);
+
+    println!("{ENTRIES} entries of {FIELDS} Long fields");
+
+    let start = Instant::now();
+    let (jmap, table_bytes) = retained(|| from_buffer(no_lookup(), &data, &IoOptions::default()).unwrap());
+    println!("from_buffer: {:.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+
+    let (maps, map_bytes) = retained(|| {
+        jmap.entries()
+            .iter()
+            .map(|entry| entry.iter().map(|(&hash, value)| (hash, value.clone())).collect::<HashMap<u32, FieldValue>>())
+            .collect::<Vec<_>>()
+    });
+    println!("memory: {:.1} MB as HashMap entries, {:.1} MB as the table", mb(map_bytes), mb(table_bytes));
+
+    let lookups = ENTRIES * FIELDS * LOOKUP_ROUNDS;
+    let map_time = time_lookups(&hashes, || maps.iter(), |map, hash| map.get(&hash));
+    let slot_time = time_lookups(&hashes, || jmap.entries().iter(), Entry::get_by_hash);
+    println!("{lookups} get_by_hash calls: {map_time:.1} ms on HashMap entries, {slot_time:.1} ms on slot entries");
+}
+
+/// Look every field of every entry up `LOOKUP_ROUNDS` times, returning the time taken in milliseconds
+fn time_lookups<'a, T: 'a, I: Iterator<Item = &'a T>>(
+    hashes: &[u32],
+    entries: impl Fn() -> I,
+    get: impl Fn(&'a T, u32) -> Option<&'a FieldValue>,
+) -> f64 {
+    let start = Instant::now();
+    let mut sum = 0i64;
+    for _ in 0..LOOKUP_ROUNDS {
+        for entry in entries() {
+            for &hash in hashes {
+                sum += black_box(get(entry, black_box(hash))).and_then(FieldValue::as_int).unwrap_or(0) as i64;
+            }
+        }
+    }
+    black_box(sum);
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+fn mb(bytes: usize) -> f64 {
+    bytes as f64 / 1_000_000.0
+}
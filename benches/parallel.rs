@@ -0,0 +1,60 @@
+//! Parallel decoding: reading a 100k-entry buffer on one thread against the whole rayon pool
+//!
+//! Run with `cargo bench --features rayon --bench parallel`
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use lib_bcsv_jmap::{from_buffer, no_lookup, to_buffer, FieldType, FieldValue, IoOptions, JMapInfo};
+
+const ENTRIES: usize = 100_000;
+const FIELDS: usize = 20;
+const READ_ROUNDS: usize = 5;
+
+fn main() {
+    let mut jmap = JMapInfo::new(no_lookup());
+    for field in 0..FIELDS {
+        let (field_type, default) = match field % 4 {
+            0 => (FieldType::StringOffset, FieldValue::String(String::new())),
+            1 => (FieldType::Float, FieldValue::Float(0.0)),
+            2 => (FieldType::Short, FieldValue::Int(0)),
+            _ => (FieldType::Long, FieldValue::Int(0)),
+        };
+        jmap.create_field(&format!("Field{field}"), field_type, default).unwrap();
+    }
+    let fields: Vec<(u32, FieldType)> = jmap.fields().map(|field| (field.hash, field.field_type)).collect();
+    for index in 0..ENTRIES {
+        let entry = jmap.create_entry();
+        for &(hash, field_type) in &fields {
+            let value = match field_type {
+                FieldType::StringOffset => FieldValue::String(format!("Obj{}", index % 1000)),
+                FieldType::Float => FieldValue::Float(index as f32 * 0.5),
+                FieldType::Short => FieldValue::Int((index % 30_000) as i32),
+                _ => FieldValue::Int(index as i32),
+            };
+            entry.set_by_hash(hash, value);
+        }
+    }
+    let data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+    drop(jmap);
+
+    let read = || from_buffer(no_lookup(), &data, &IoOptions::default()).unwrap();
+    let single = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let one_thread = single.install(|| time_ms(READ_ROUNDS, read));
+    let pool = time_ms(READ_ROUNDS, read);
+
+    println!("{ENTRIES} entries of {FIELDS} fields, {:.1} MB", data.len() as f64 / 1_000_000.0);
+    println!(
+        "reading the buffer {READ_ROUNDS} times: {one_thread:.1} ms on 1 thread, {pool:.1} ms on {} threads",
+        rayon::current_num_threads()
+    );
+}
+
+/// Run `f` `rounds` times, returning the total time taken in milliseconds
+fn time_ms<T>(rounds: usize, mut f: impl FnMut() -> T) -> f64 {
+    let start = Instant::now();
+    for _ in 0..rounds {
+        black_box(f());
+    }
+    start.elapsed().as_secs_f64() * 1000.0
+}
@@ -0,0 +1,82 @@
+//! Serialization: time and allocations of `to_buffer` on a 10k-row table with shared strings
+//!
+//! Run with `cargo bench --bench serialize`
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use lib_bcsv_jmap::{no_lookup, to_buffer, FieldType, FieldValue, IoOptions, JMapInfo};
+
+const ENTRIES: usize = 10_000;
+const DISTINCT_STRINGS: usize = 2_000;
+const WRITE_ROUNDS: usize = 50;
+
+/// Counts the allocations made, to measure what a call allocates
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn main() {
+    let mut jmap = JMapInfo::new(no_lookup());
+    let fields = [(FieldType::Long, 8), (FieldType::Float, 2), (FieldType::StringOffset, 4)];
+    for (field_type, count) in fields {
+        for field in 0..count {
+            let name = format!("{}{field}", field_type.csv_name());
+            jmap.create_field(&name, field_type, FieldValue::default_for(field_type)).unwrap();
+        }
+    }
+    let hashes: Vec<(u32, FieldType)> = jmap.fields().map(|field| (field.hash, field.field_type)).collect();
+    for index in 0..ENTRIES {
+        let entry = jmap.create_entry();
+        for (column, &(hash, field_type)) in hashes.iter().enumerate() {
+            let value = match field_type {
+                FieldType::StringOffset => FieldValue::String(format!("Obj{column}_{}", index % DISTINCT_STRINGS)),
+                FieldType::Float => FieldValue::Float(index as f32 * 0.25),
+                _ => FieldValue::Int((index * column) as i32),
+            };
+            entry.set_by_hash(hash, value);
+        }
+    }
+
+    let options = IoOptions::default();
+    let size = to_buffer(&jmap, &options).unwrap().len();
+    println!(
+        "{ENTRIES} entries of 8 Long, 2 Float and 4 StringOffset fields with {DISTINCT_STRINGS} distinct strings each, \
+         {:.1} MB",
+        size as f64 / 1_000_000.0
+    );
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..WRITE_ROUNDS {
+        black_box(to_buffer(black_box(&jmap), &options).unwrap());
+    }
+    let elapsed = start.elapsed().as_secs_f64() * 1000.0;
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "to_buffer: {:.2} ms and {} allocations per call",
+        elapsed / WRITE_ROUNDS as f64,
+        allocations / WRITE_ROUNDS
+    );
+}
@@ -0,0 +1,250 @@
+//! Derive macros for `lib-bcsv-jmap`
+//!
+//! Generates `FromEntry` and `ToEntry` implementations for structs with named fields.
+//! Use them through the `derive` feature of `lib-bcsv-jmap` rather than depending on this crate directly
+//!
+//! ```rust,ignore
+//! use lib_bcsv_jmap::{FromEntry, ToEntry};
+//!
+//! #[derive(FromEntry, ToEntry)]
+//! struct ObjInfo {
+//!     #[bcsv(name = "name")]
+//!     obj_name: String,
+//!     pos_x: f32,
+//!     #[bcsv(hash = 0x0F72F5C3)]
+//!     shape_model_no: i32,
+//!     #[bcsv(default)]
+//!     optional_flag: i32,
+//!     comment: Option<String>,
+//! }
+//! ```
+//!
+//! ## Attributes
+//!
+//! - `name = "..."` - The field name to look up, defaults to the Rust field name
+//! - `hash = 0x...` - The field hash to look up, for fields whose name is unknown
+//! - `default` - Use `Default::default()` when the value is missing instead of failing
+//!
+//! Supported field types are `i32`, `u32`, `f32`, `String`, `bool` and `Option<T>` of those.
+//! `Option` fields are `None` when the value is missing and are not written when `None`
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Ident, LitInt, LitStr, PathArguments, Type};
+
+/// How a struct field is located in an entry
+enum FieldKey {
+    Name(String),
+    Hash(u32),
+}
+
+/// A parsed struct field with its `#[bcsv(...)]` attributes
+struct RowField {
+    ident: Ident,
+    key: FieldKey,
+    default: bool,
+    /// Inner type if the field is an `Option<T>`
+    optional: Option<Type>,
+    ty: Type,
+}
+
+impl RowField {
+    /// Expression looking up the raw `Option<&FieldValue>` in `entry`
+    fn lookup(&self) -> TokenStream2 {
+        match &self.key {
+            FieldKey::Name(name) => quote! { entry.get(hash_table, #name) },
+            FieldKey::Hash(hash) => quote! { entry.get_by_hash(#hash) },
+        }
+    }
+
+    /// Name used in `FieldNotFound` errors
+    fn display_name(&self) -> String {
+        match &self.key {
+            FieldKey::Name(name) => name.clone(),
+            FieldKey::Hash(hash) => format!("[{:08X}]", hash),
+        }
+    }
+
+    /// Statement storing `value` in `entry`
+    fn store(&self, value: TokenStream2) -> TokenStream2 {
+        match &self.key {
+            FieldKey::Name(name) => quote! {
+                entry.set(hash_table, #name, ::lib_bcsv_jmap::RowValue::to_value(#value));
+            },
+            FieldKey::Hash(hash) => quote! {
+                entry.set_by_hash(#hash, ::lib_bcsv_jmap::RowValue::to_value(#value));
+            },
+        }
+    }
+}
+
+/// Return the `T` of an `Option<T>` type
+fn option_inner(ty: &Type) -> Option<Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    match args.args.first()? {
+        GenericArgument::Type(inner) if args.args.len() == 1 => Some(inner.clone()),
+        _ => None,
+    }
+}
+
+/// Parse the named fields of a struct and their attributes
+fn parse_fields(input: &DeriveInput) -> syn::Result<Vec<RowField>> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "bcsv derives only support structs"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(&input.ident, "bcsv derives only support structs with named fields"));
+    };
+
+    let mut result = Vec::with_capacity(fields.named.len());
+    for field in &fields.named {
+        let ident = field.ident.clone().expect("named field");
+        let mut name: Option<LitStr> = None;
+        let mut hash: Option<LitInt> = None;
+        let mut default = false;
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("bcsv")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    name = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("hash") {
+                    hash = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    default = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown bcsv attribute, expected `name`, `hash` or `default`"))
+                }
+            })?;
+        }
+
+        let key = match (name, hash) {
+            (Some(_), Some(hash)) => {
+                return Err(syn::Error::new_spanned(hash, "`name` and `hash` cannot both be given"));
+            }
+            (Some(name), None) => FieldKey::Name(name.value()),
+            (None, Some(hash)) => FieldKey::Hash(hash.base10_parse()?),
+            (None, None) => FieldKey::Name(ident.to_string()),
+        };
+
+        let optional = option_inner(&field.ty);
+        if default && optional.is_some() {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "`default` has no effect on `Option` fields, which are `None` when missing",
+            ));
+        }
+
+        result.push(RowField {
+            ident,
+            key,
+            default,
+            optional,
+            ty: field.ty.clone(),
+        });
+    }
+
+    Ok(result)
+}
+
+/// Derive `FromEntry` for a struct with named fields
+#[proc_macro_derive(FromEntry, attributes(bcsv))]
+pub fn derive_from_entry(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_entry(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derive `ToEntry` for a struct with named fields
+#[proc_macro_derive(ToEntry, attributes(bcsv))]
+pub fn derive_to_entry(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_to_entry(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_from_entry(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = parse_fields(input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let inits = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let ty = &field.ty;
+        let lookup = field.lookup();
+        let name = field.display_name();
+
+        let value = if let Some(inner) = &field.optional {
+            quote! { ::lib_bcsv_jmap::row::read_optional::<#inner>(#lookup, #name)? }
+        } else if field.default {
+            quote! {
+                ::lib_bcsv_jmap::row::read_optional::<#ty>(#lookup, #name)?.unwrap_or_default()
+            }
+        } else {
+            quote! { ::lib_bcsv_jmap::row::read_required::<#ty>(#lookup, #name)? }
+        };
+
+        quote! { #ident: #value }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::lib_bcsv_jmap::FromEntry for #ident #ty_generics #where_clause {
+            fn from_entry<H: ::lib_bcsv_jmap::HashTable>(
+                entry: &::lib_bcsv_jmap::Entry,
+                hash_table: &H,
+            ) -> ::lib_bcsv_jmap::Result<Self> {
+                let _ = hash_table;
+                ::std::result::Result::Ok(Self {
+                    #(#inits,)*
+                })
+            }
+        }
+    })
+}
+
+fn expand_to_entry(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = parse_fields(input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let stores = fields.iter().map(|field| {
+        let ident = &field.ident;
+        if field.optional.is_some() {
+            let store = field.store(quote! { value });
+            quote! {
+                if let ::std::option::Option::Some(value) = &self.#ident {
+                    #store
+                }
+            }
+        } else {
+            field.store(quote! { &self.#ident })
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::lib_bcsv_jmap::ToEntry for #ident #ty_generics #where_clause {
+            fn apply_to<H: ::lib_bcsv_jmap::HashTable>(
+                &self,
+                entry: &mut ::lib_bcsv_jmap::Entry,
+                hash_table: &H,
+            ) {
+                let _ = hash_table;
+                #(#stores)*
+            }
+        }
+    })
+}
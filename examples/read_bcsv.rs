@@ -16,9 +16,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!();
 
     println!("Fields");
-    for field in jmap.fields() {
-        let name = jmap.field_name(field.hash);
-        println!("+0x{:X} - {} - {}", field.offset, name, field.field_type);
+    println!("{}", jmap.describe_fields());
+    println!();
+
+    println!("First entry");
+    if let Some(view) = jmap.view(0) {
+        println!("{}", view);
     }
     println!();
 
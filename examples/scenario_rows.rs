@@ -0,0 +1,57 @@
+//! Example: Map BCSV entries to a user-defined struct and back
+
+use std::path::Path;
+use lib_bcsv_jmap::{
+    from_file, smg_hash_table_with_lookup, Entry, FieldValue, FromEntry, HashTable, IoOptions, Result, ToEntry,
+};
+
+#[derive(Debug)]
+struct Scenario {
+    no: i32,
+    name: String,
+    power_star_id: i32,
+    is_hidden: bool,
+}
+
+impl FromEntry for Scenario {
+    fn from_entry<H: HashTable>(entry: &Entry, hash_table: &H) -> Result<Self> {
+        Ok(Self {
+            no: entry.require_int(hash_table, "ScenarioNo")?,
+            name: entry.require_string(hash_table, "ScenarioName")?.to_string(),
+            power_star_id: entry.require_int(hash_table, "PowerStarId")?,
+            is_hidden: entry.require_int(hash_table, "IsHidden")? != 0,
+        })
+    }
+}
+
+impl ToEntry for Scenario {
+    fn apply_to<H: HashTable>(&self, entry: &mut Entry, hash_table: &H) {
+        entry.set(hash_table, "ScenarioNo", FieldValue::Int(self.no));
+        entry.set(hash_table, "ScenarioName", FieldValue::from(self.name.as_str()));
+        entry.set(hash_table, "PowerStarId", FieldValue::Int(self.power_star_id));
+        entry.set_bool(hash_table, "IsHidden", self.is_hidden);
+    }
+}
+
+fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let lookup_path = Path::new("assets/strings_SMG.txt");
+    let hash_table = smg_hash_table_with_lookup(lookup_path)?;
+
+    let bcsv_path = Path::new("assets/examples/scenariodata.bcsv");
+    let mut jmap = from_file(hash_table, bcsv_path, &IoOptions::default())?;
+
+    let scenarios: Vec<Scenario> = jmap.collect_rows()?;
+    for scenario in &scenarios {
+        println!("{:?}", scenario);
+    }
+
+    jmap.extend_from_rows(&[Scenario {
+        no: 99,
+        name: "Bonus Scenario".to_string(),
+        power_star_id: 0,
+        is_hidden: true,
+    }]);
+    println!("\nEntries after adding a scenario: {}", jmap.len());
+
+    Ok(())
+}
@@ -8,7 +8,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let hash_table = smg_hash_table_with_lookup(lookup_path)?;
 
     let csv_path = Path::new("assets/examples/scenariodata.csv");
-    let jmap = from_csv(hash_table, csv_path, None)?;
+    let mut jmap = from_csv(hash_table, csv_path, None)?;
+
+    // Append a new scenario, the fields left out get their default values
+    jmap.entry_builder()
+        .set("ScenarioNo", 99)
+        .set("ScenarioName", "Bonus Scenario")
+        .set("ErrorCheck", 1)
+        .insert()?;
 
     println!("CSV Info");
     println!("Entries: {}", jmap.len());
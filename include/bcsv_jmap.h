@@ -0,0 +1,53 @@
+/* C interface of lib-bcsv-jmap, built with the `ffi` feature. See src/ffi.rs for details. */
+#ifndef BCSV_JMAP_H
+#define BCSV_JMAP_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <sys/types.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct BcsvHashTable BcsvHashTable;
+typedef struct BcsvJMap BcsvJMap;
+
+typedef struct BcsvIoOptions {
+    bool big_endian;
+    bool utf8;
+} BcsvIoOptions;
+
+/* Message of the last error on this thread, valid until the next failing call, or NULL */
+const char *bcsv_last_error(void);
+
+BcsvHashTable *bcsv_hashtable_load(const char *path);
+void bcsv_hashtable_free(BcsvHashTable *hash_table);
+
+/* options may be NULL for big-endian Shift-JIS */
+BcsvJMap *bcsv_jmap_from_file(const BcsvHashTable *hash_table, const char *path, const BcsvIoOptions *options);
+int bcsv_jmap_to_file(const BcsvJMap *jmap, const char *path, const BcsvIoOptions *options);
+void bcsv_jmap_free(BcsvJMap *jmap);
+
+size_t bcsv_jmap_num_entries(const BcsvJMap *jmap);
+size_t bcsv_jmap_num_fields(const BcsvJMap *jmap);
+ssize_t bcsv_jmap_create_entry(BcsvJMap *jmap);
+
+int bcsv_entry_get_int(const BcsvJMap *jmap, size_t index, const char *name, int32_t *out);
+int bcsv_entry_get_float(const BcsvJMap *jmap, size_t index, const char *name, float *out);
+/* Returns the length of the value, the copy being truncated to buffer_len - 1 bytes */
+ssize_t bcsv_entry_get_string(const BcsvJMap *jmap, size_t index, const char *name, char *buffer, size_t buffer_len);
+/* Release the result with bcsv_string_free */
+char *bcsv_entry_get_string_owned(const BcsvJMap *jmap, size_t index, const char *name);
+void bcsv_string_free(char *s);
+
+int bcsv_entry_set_int(BcsvJMap *jmap, size_t index, const char *name, int32_t value);
+int bcsv_entry_set_float(BcsvJMap *jmap, size_t index, const char *name, float value);
+int bcsv_entry_set_string(BcsvJMap *jmap, size_t index, const char *name, const char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
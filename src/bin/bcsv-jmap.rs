@@ -0,0 +1,364 @@
+//! Command line tool for BCSV files
+//!
+//! ```text
+//! bcsv-jmap convert scenariodata.bcsv scenariodata.csv --names hashtable_smg.txt
+//! bcsv-jmap convert --recursive in_dir out_dir --to csv
+//! bcsv-jmap diff vanilla.bcsv modified.bcsv --names hashtable_smg.txt --key ZoneName,ScenarioNo
+//! bcsv-jmap inspect scenariodata.bcsv --names hashtable_smg.txt --strict
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use lib_bcsv_jmap::{
+    convert_dir_bcsv_to_csv, convert_dir_csv_to_bcsv, create_patch, detect_big_endian, from_buffer,
+    from_csv_with_options, from_file, read_header, smg_hash_table, smg_hash_table_with_lookup, to_csv_with_options,
+    to_file, CsvOptions, Encoding, FieldValue, FileHashTable, IoOptions, JMapError, JMapInfo, Patch, RowKey,
+};
+
+const USAGE: &str = "\
+Usage:
+    bcsv-jmap convert <input> <output> [options]
+    bcsv-jmap convert --recursive <input_dir> <output_dir> [options]
+    bcsv-jmap diff <old.bcsv> <new.bcsv> [--key <field,...>] [--format <text|json>] [options]
+    bcsv-jmap inspect <file.bcsv> [--strict] [--dump] [options]
+
+Options:
+    --names <file>              Lookup file of field names
+    --to <csv|bcsv>             Output format, inferred from the extensions by default
+    --little-endian             Read and write little-endian BCSV (default: big-endian,
+                                or detected by inspect)
+    --encoding <shift-jis|utf-8>
+                                String encoding of BCSV files (default: shift-jis)
+    --header-delimiter <char>   Separator of CSV header cells (default: ':')
+    --recursive                 Convert every file of a directory tree
+    --key <field,...>           Fields matching rows in diff (default: rows are matched by index)
+    --format <text|json>        Output of diff (default: text)
+    --strict                    Make inspect fail if the file has validation issues
+    --dump                      Make inspect print every field and value in the text dump format
+    -h, --help                  Print this help
+
+Exit codes: 0 on success, 1 if a file could not be converted or read, if diff found differences
+or if inspect --strict found issues, 2 on invalid arguments";
+
+/// Arguments that could not be understood, exiting with status 2
+struct UsageError(String);
+
+/// Flags taking a value
+const VALUE_FLAGS: &[&str] = &["--names", "--to", "--encoding", "--header-delimiter", "--key", "--format"];
+/// Flags without a value
+const SWITCHES: &[&str] = &["--little-endian", "--recursive", "--strict", "--dump"];
+
+/// Parsed command line, positional arguments and flags being kept in order
+struct Args {
+    positional: Vec<String>,
+    flags: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, UsageError> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            flags: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            if VALUE_FLAGS.contains(&arg.as_str()) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| UsageError(format!("{} needs a value", arg)))?;
+                parsed.flags.push((arg, Some(value)));
+            } else if SWITCHES.contains(&arg.as_str()) {
+                parsed.flags.push((arg, None));
+            } else if arg.starts_with("--") {
+                return Err(UsageError(format!("unknown option {}", arg)));
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn value(&self, flag: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(name, _)| name == flag)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn switch(&self, flag: &str) -> bool {
+        self.flags.iter().any(|(name, _)| name == flag)
+    }
+}
+
+/// Reason the tool stopped, and the exit status it gets
+enum Failure {
+    Usage(UsageError),
+    /// Already reported on stderr
+    Reported,
+    File(PathBuf, JMapError),
+}
+
+impl From<UsageError> for Failure {
+    fn from(err: UsageError) -> Self {
+        Failure::Usage(err)
+    }
+}
+
+fn io_options(args: &Args) -> Result<IoOptions, UsageError> {
+    let encoding = match args.value("--encoding") {
+        None | Some("shift-jis") | Some("shift_jis") | Some("sjis") => Encoding::ShiftJis,
+        Some("utf-8") | Some("utf8") => Encoding::Utf8,
+        Some(other) => return Err(UsageError(format!("unknown encoding {}", other))),
+    };
+    Ok(IoOptions {
+        big_endian: !args.switch("--little-endian"),
+        encoding,
+        ..IoOptions::default()
+    })
+}
+
+fn csv_options(args: &Args) -> Result<CsvOptions, UsageError> {
+    let mut options = CsvOptions::default();
+    if let Some(delimiter) = args.value("--header-delimiter") {
+        let mut chars = delimiter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => options.header_delimiter = c,
+            _ => return Err(UsageError("--header-delimiter must be a single character".to_string())),
+        }
+    }
+    Ok(options)
+}
+
+fn hash_table(args: &Args) -> Result<FileHashTable, Failure> {
+    match args.value("--names") {
+        Some(path) => smg_hash_table_with_lookup(path).map_err(|err| Failure::File(path.into(), err)),
+        None => Ok(smg_hash_table()),
+    }
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv") || ext.eq_ignore_ascii_case("tsv"))
+}
+
+/// Whether the output is CSV, from `--to` or else from the extensions
+fn to_csv_direction(args: &Args, input: &Path, output: &Path) -> Result<bool, UsageError> {
+    match args.value("--to") {
+        Some("csv") => Ok(true),
+        Some("bcsv") => Ok(false),
+        Some(other) => Err(UsageError(format!("unknown output format {}", other))),
+        None if is_csv(output) => Ok(true),
+        None if is_csv(input) => Ok(false),
+        None => Ok(true),
+    }
+}
+
+fn convert(args: &Args) -> Result<(), Failure> {
+    let [input, output] = args.positional.as_slice() else {
+        return Err(UsageError("convert needs an input and an output".to_string()).into());
+    };
+    let (input, output) = (Path::new(input), Path::new(output));
+    let io_options = io_options(args)?;
+    let mut csv_options = csv_options(args)?;
+    let mut hash_table = hash_table(args)?;
+
+    if args.switch("--recursive") {
+        // Directories have no extension to go by, so BCSV to CSV unless told otherwise
+        let to_csv = args.value("--to").map_or(Ok(true), |_| to_csv_direction(args, input, output))?;
+        let report = if to_csv {
+            convert_dir_bcsv_to_csv(input, output, &mut hash_table, &io_options, &csv_options)
+        } else {
+            convert_dir_csv_to_bcsv(input, output, &mut hash_table, &io_options, &csv_options)
+        }
+        .map_err(|err| Failure::File(input.into(), err))?;
+
+        for (path, err) in &report.failures {
+            eprintln!("error: {}: {}", input.join(path).display(), err);
+        }
+        println!("Converted {} files, {} failed", report.converted.len(), report.failures.len());
+        return if report.failures.is_empty() { Ok(()) } else { Err(Failure::Reported) };
+    }
+
+    if to_csv_direction(args, input, output)? {
+        let jmap = from_file(hash_table, input, &io_options).map_err(|err| Failure::File(input.into(), err))?;
+        to_csv_with_options(&jmap, output, &csv_options).map_err(|err| Failure::File(output.into(), err))
+    } else {
+        if input.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("tsv")) {
+            csv_options.delimiter = b'\t';
+        }
+        let jmap = from_csv_with_options(hash_table, input, &csv_options)
+            .map_err(|err| Failure::File(input.into(), err))?;
+        to_file(&jmap, output, &io_options).map_err(|err| Failure::File(output.into(), err))
+    }
+}
+
+fn key_text(key: &RowKey) -> String {
+    match key {
+        RowKey::Index(index) => index.to_string(),
+        RowKey::Values(values) => values.iter().map(|value| value.to_string()).collect::<Vec<_>>().join(","),
+    }
+}
+
+fn value_text(value: Option<&FieldValue>) -> String {
+    match value {
+        Some(FieldValue::String(s)) => format!("{:?}", s),
+        Some(value) => value.to_string(),
+        None => "(none)".to_string(),
+    }
+}
+
+fn print_diff_text(old: &JMapInfo<FileHashTable>, new: &JMapInfo<FileHashTable>, patch: &Patch) {
+    for field in &patch.added_fields {
+        println!("+ field {} ({})", new.field_name(field.hash), field.field_type.csv_name());
+    }
+    for hash in &patch.removed_fields {
+        println!("- field {}", old.field_name(*hash));
+    }
+    for entry in &patch.added_rows {
+        let key = match patch.key_fields.as_slice() {
+            [] => String::new(),
+            hashes => hashes
+                .iter()
+                .map(|hash| value_text(entry.get_by_hash(*hash)))
+                .collect::<Vec<_>>()
+                .join(","),
+        };
+        println!("+ row [{}]", key);
+    }
+    for key in &patch.removed_rows {
+        println!("- row [{}]", key_text(key));
+    }
+    for row in &patch.changed_rows {
+        for change in &row.changes {
+            println!(
+                "row[{}] {}: {} -> {}",
+                key_text(&row.key),
+                new.field_name(change.hash),
+                value_text(change.before.as_ref()),
+                value_text(change.after.as_ref())
+            );
+        }
+    }
+}
+
+fn diff(args: &Args) -> Result<(), Failure> {
+    let [old_path, new_path] = args.positional.as_slice() else {
+        return Err(UsageError("diff needs two files".to_string()).into());
+    };
+    let json = match args.value("--format") {
+        None | Some("text") => false,
+        Some("json") => true,
+        Some(other) => return Err(UsageError(format!("unknown diff format {}", other)).into()),
+    };
+    let io_options = io_options(args)?;
+    let hash_table = hash_table(args)?;
+    let old = from_file(hash_table.clone(), old_path, &io_options).map_err(|err| Failure::File(old_path.into(), err))?;
+    let new = from_file(hash_table, new_path, &io_options).map_err(|err| Failure::File(new_path.into(), err))?;
+
+    let keys: Vec<&str> = args
+        .value("--key")
+        .map(|keys| keys.split(',').map(str::trim).filter(|key| !key.is_empty()).collect())
+        .unwrap_or_default();
+    for key in &keys {
+        if !old.contains_field(key) || !new.contains_field(key) {
+            return Err(UsageError(format!("key field {} is not in both files", key)).into());
+        }
+    }
+
+    let patch = create_patch(&old, &new, &keys);
+    if json {
+        let diff = serde_json::to_string_pretty(&patch.to_diff(&old, &new))
+            .map_err(|err| Failure::File(new_path.into(), err.into()))?;
+        println!("{}", diff);
+    } else {
+        print_diff_text(&old, &new, &patch);
+    }
+    if patch.is_empty() { Ok(()) } else { Err(Failure::Reported) }
+}
+
+fn inspect(args: &Args) -> Result<(), Failure> {
+    let [path] = args.positional.as_slice() else {
+        return Err(UsageError("inspect needs a file".to_string()).into());
+    };
+    let data = std::fs::read(path).map_err(|err| Failure::File(path.into(), err.into()))?;
+    let mut io_options = io_options(args)?;
+    let endianness = if args.switch("--little-endian") {
+        "little-endian (from --little-endian)"
+    } else {
+        io_options.big_endian =
+            detect_big_endian(&data).ok_or_else(|| Failure::File(path.into(), JMapError::InvalidHeader))?;
+        if io_options.big_endian { "big-endian (detected)" } else { "little-endian (detected)" }
+    };
+    let header = read_header(&data, &io_options).map_err(|err| Failure::File(path.into(), err))?;
+    let jmap = from_buffer(hash_table(args)?, &data, &io_options).map_err(|err| Failure::File(path.into(), err))?;
+    let issues = jmap.validate(&io_options);
+
+    if args.switch("--dump") {
+        print!("{}", jmap.to_dump_string());
+    } else {
+        let stats = jmap.resolution_stats();
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        println!("File: {}", path);
+        println!("Size: {} bytes", data.len());
+        println!("Endianness: {}", endianness);
+        println!("Entries: {}", header.num_entries);
+        println!("Fields: {}", header.num_fields);
+        println!("Entry size: 0x{:X}", header.entry_size);
+        println!("Data offset: 0x{:X}", header.off_data);
+        println!("String table: {} bytes", (data.len() as u64).saturating_sub(header.off_strings()));
+        println!("Resolved names: {}/{}", stats.resolved, stats.resolved + stats.unresolved);
+        println!();
+        println!("{}", jmap.describe_fields());
+        println!();
+        println!("Issues: {}", issues.len());
+        for issue in &issues {
+            println!("  {}", issue);
+        }
+    }
+
+    if args.switch("--strict") && !issues.is_empty() {
+        if args.switch("--dump") {
+            eprintln!("error: {}: {} validation issues", path, issues.len());
+        }
+        return Err(Failure::Reported);
+    }
+    Ok(())
+}
+
+fn run(mut argv: impl Iterator<Item = String>) -> Result<(), Failure> {
+    let command = argv.next();
+    let args = Args::parse(argv)?;
+    match command.as_deref() {
+        Some("convert") => convert(&args),
+        Some("diff") => diff(&args),
+        Some("inspect") => inspect(&args),
+        Some("help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => Err(UsageError(format!("unknown command {}", other)).into()),
+        None => Err(UsageError("no command given".to_string()).into()),
+    }
+}
+
+fn main() -> ExitCode {
+    let argv: Vec<String> = std::env::args().skip(1).collect();
+    if argv.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    match run(argv.into_iter()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Usage(UsageError(message))) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            ExitCode::from(2)
+        }
+        Err(Failure::Reported) => ExitCode::from(1),
+        Err(Failure::File(path, err)) => {
+            eprintln!("error: {}: {}", path.display(), err);
+            ExitCode::from(1)
+        }
+    }
+}
@@ -0,0 +1,680 @@
+//! Column-oriented storage, for scanning a few fields over many entries
+//!
+//! A [`ColumnarJMap`] stores each field as one typed vector instead of one value per entry, so summing
+//! a column reads contiguous memory. It converts losslessly to and from [`JMapInfo`], and can be read
+//! straight from BCSV data with [`from_buffer_columnar`]
+//!
+//! Only a small editing API is provided: appending entries, setting values and mutable column slices.
+//! Convert to a [`JMapInfo`] for everything else
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::path::Path;
+
+use indexmap::IndexMap;
+
+use crate::csv::{to_csv_writer, CsvOptions};
+use crate::entry::{Entry, FieldKey};
+use crate::error::{JMapError, Result};
+use crate::field::{Field, FieldType, FieldValue};
+use crate::hash::HashTable;
+use crate::io::{check_field_conflicts, read_header, read_unique_fields, readable_entries, to_buffer, Decoder, IoOptions, ValueReader};
+use crate::strictness::Report;
+use crate::jmap::JMapInfo;
+
+/// The values of one field, typed by the field type
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    /// Values of Long, Short and Char fields
+    Int(Vec<i32>),
+    /// Values of UnsignedLong fields
+    UInt(Vec<u32>),
+    /// Values of Float fields
+    Float(Vec<f32>),
+    /// Values of String and StringOffset fields
+    String(Vec<String>),
+}
+
+impl Column {
+    /// Create an empty column for a field type
+    fn with_capacity(field_type: FieldType, capacity: usize) -> Self {
+        match field_type {
+            FieldType::Long | FieldType::Short | FieldType::Char => Column::Int(Vec::with_capacity(capacity)),
+            FieldType::UnsignedLong => Column::UInt(Vec::with_capacity(capacity)),
+            FieldType::Float => Column::Float(Vec::with_capacity(capacity)),
+            FieldType::String | FieldType::StringOffset => Column::String(Vec::with_capacity(capacity)),
+        }
+    }
+
+    /// Get the number of values
+    pub fn len(&self) -> usize {
+        match self {
+            Column::Int(values) => values.len(),
+            Column::UInt(values) => values.len(),
+            Column::Float(values) => values.len(),
+            Column::String(values) => values.len(),
+        }
+    }
+
+    /// Check if the column has no values
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get a value
+    ///
+    /// # Returns
+    /// The value, or `None` if the index is out of bounds
+    pub fn get(&self, index: usize) -> Option<FieldValue> {
+        match self {
+            Column::Int(values) => values.get(index).map(|&v| FieldValue::Int(v)),
+            Column::UInt(values) => values.get(index).map(|&v| FieldValue::UInt(v)),
+            Column::Float(values) => values.get(index).map(|&v| FieldValue::Float(v)),
+            Column::String(values) => values.get(index).map(|v| FieldValue::String(v.clone())),
+        }
+    }
+
+    /// Append a value, `Int` values of `UInt` columns being reinterpreted as `u32`
+    ///
+    /// # Returns
+    /// The rejected value if it does not match the column type
+    fn push(&mut self, value: FieldValue) -> std::result::Result<(), FieldValue> {
+        match (self, value) {
+            (Column::Int(values), FieldValue::Int(v)) => values.push(v),
+            (Column::UInt(values), FieldValue::UInt(v)) => values.push(v),
+            (Column::UInt(values), FieldValue::Int(v)) => values.push(v as u32),
+            (Column::Float(values), FieldValue::Float(v)) => values.push(v),
+            (Column::String(values), FieldValue::String(v)) => values.push(v),
+            (_, value) => return Err(value),
+        }
+        Ok(())
+    }
+
+    /// Replace a value, with the same conversions as [`Column::push`]. `index` must be in bounds
+    fn set(&mut self, index: usize, value: FieldValue) -> std::result::Result<(), FieldValue> {
+        match (self, value) {
+            (Column::Int(values), FieldValue::Int(v)) => values[index] = v,
+            (Column::UInt(values), FieldValue::UInt(v)) => values[index] = v,
+            (Column::UInt(values), FieldValue::Int(v)) => values[index] = v as u32,
+            (Column::Float(values), FieldValue::Float(v)) => values[index] = v,
+            (Column::String(values), FieldValue::String(v)) => values[index] = v,
+            (_, value) => return Err(value),
+        }
+        Ok(())
+    }
+}
+
+/// Which values of a column are missing, one bit per entry
+///
+/// No memory is used until a value is missing
+#[derive(Debug, Clone, Default, PartialEq)]
+struct NullMask {
+    words: Vec<u64>,
+}
+
+impl NullMask {
+    fn is_null(&self, index: usize) -> bool {
+        self.words
+            .get(index / 64)
+            .is_some_and(|word| (word >> (index % 64)) & 1 == 1)
+    }
+
+    fn set(&mut self, index: usize, null: bool) {
+        let word = index / 64;
+        if word >= self.words.len() {
+            if !null {
+                return;
+            }
+            self.words.resize(word + 1, 0);
+        }
+        let bit = 1u64 << (index % 64);
+        if null {
+            self.words[word] |= bit;
+        } else {
+            self.words[word] &= !bit;
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+}
+
+/// A column with its missing values
+#[derive(Debug, Clone, PartialEq)]
+struct ColumnData {
+    values: Column,
+    nulls: NullMask,
+}
+
+/// A table storing each field as a typed column
+///
+/// Missing values are tracked by a null bitmap per column, and hold the field default in the column vector.
+/// Values of `UnsignedLong` fields are stored as `u32`, so `Int` values written to such fields come back as `UInt`
+#[derive(Debug)]
+pub struct ColumnarJMap<H: HashTable> {
+    /// The hash table used for field name lookups
+    hash_table: H,
+    /// Fields indexed by their hash, in the same order as `columns`
+    fields: IndexMap<u32, Field>,
+    /// The values of each field
+    columns: Vec<ColumnData>,
+    /// Number of entries
+    len: usize,
+    /// Size of a single entry in bytes, as read from the file
+    entry_size: u32,
+}
+
+impl<H: HashTable> ColumnarJMap<H> {
+    /// Convert a JMapInfo to columns
+    ///
+    /// Values stored for hashes that are not fields of the table are dropped
+    ///
+    /// # Arguments
+    /// - `jmap` - The table to convert
+    ///
+    /// # Errors
+    /// - `JMapError::InEntry` with a `JMapError::TypeMismatch` if a value does not match its field type
+    ///
+    /// # Returns
+    /// The same table, stored by column
+    pub fn from_jmap(jmap: JMapInfo<H>) -> Result<Self> {
+        let entry_size = jmap.entry_size;
+        let (hash_table, fields, entries) = jmap.into_parts();
+
+        let mut columns = Vec::with_capacity(fields.len());
+        for field in fields.values() {
+            let mut column = ColumnData {
+                values: Column::with_capacity(field.field_type, entries.len()),
+                nulls: NullMask::default(),
+            };
+            for (index, entry) in entries.iter().enumerate() {
+                let value = match entry.get_by_hash(field.hash) {
+                    Some(value) => value.clone(),
+                    None => {
+                        column.nulls.set(index, true);
+                        field.default.clone()
+                    }
+                };
+                column.values.push(value).map_err(|value| JMapError::InEntry {
+                    index,
+                    source: Box::new(JMapError::TypeMismatch {
+                        field: hash_table.find(field.hash),
+                        expected: field.field_type.csv_name(),
+                        got: value.type_name(),
+                    }),
+                })?;
+            }
+            columns.push(column);
+        }
+
+        Ok(Self {
+            hash_table,
+            fields,
+            columns,
+            len: entries.len(),
+            entry_size,
+        })
+    }
+
+    /// Convert back to a JMapInfo, missing values staying missing
+    pub fn into_jmap(self) -> JMapInfo<H> {
+        let ColumnarJMap {
+            hash_table,
+            fields,
+            columns,
+            len,
+            entry_size,
+        } = self;
+        let mut jmap = JMapInfo::new(hash_table);
+        fill_jmap(&mut jmap, &fields, &columns, len, entry_size);
+        jmap
+    }
+
+    /// Get a reference to the hash table
+    pub fn hash_table(&self) -> &H {
+        &self.hash_table
+    }
+
+    /// Get the number of entries (rows)
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the number of fields (columns)
+    pub fn num_fields(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Get an iterator over all fields, in column order
+    pub fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.fields.values()
+    }
+
+    /// Get a field by key
+    pub fn get_field(&self, key: impl Into<FieldKey>) -> Option<&Field> {
+        self.fields.get(&key.into().resolve(&self.hash_table))
+    }
+
+    /// Get the name of a field by its hash, or `[XXXXXXXX]` if unknown
+    pub fn field_name(&self, hash: u32) -> String {
+        self.hash_table.find(hash)
+    }
+
+    /// Find the position of a field's column
+    fn column_index(&self, key: impl Into<FieldKey>) -> Option<usize> {
+        self.fields.get_index_of(&key.into().resolve(&self.hash_table))
+    }
+
+    /// Get the values of a field
+    ///
+    /// Missing values hold the field default, see [`ColumnarJMap::is_null`]
+    pub fn column(&self, key: impl Into<FieldKey>) -> Option<&Column> {
+        let index = self.column_index(key)?;
+        Some(&self.columns[index].values)
+    }
+
+    /// Get the values of a Long, Short or Char field
+    pub fn column_i32(&self, key: impl Into<FieldKey>) -> Option<&[i32]> {
+        match self.column(key)? {
+            Column::Int(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Get the values of an UnsignedLong field
+    pub fn column_u32(&self, key: impl Into<FieldKey>) -> Option<&[u32]> {
+        match self.column(key)? {
+            Column::UInt(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Get the values of a Float field
+    pub fn column_f32(&self, key: impl Into<FieldKey>) -> Option<&[f32]> {
+        match self.column(key)? {
+            Column::Float(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Get the values of a String or StringOffset field
+    pub fn column_str(&self, key: impl Into<FieldKey>) -> Option<&[String]> {
+        match self.column(key)? {
+            Column::String(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Get the values of a field mutably
+    fn column_mut(&mut self, key: impl Into<FieldKey>) -> Option<&mut Column> {
+        let index = self.column_index(key)?;
+        Some(&mut self.columns[index].values)
+    }
+
+    /// Get the values of a Long, Short or Char field mutably
+    ///
+    /// Writing through the slice does not change which values are missing
+    pub fn column_i32_mut(&mut self, key: impl Into<FieldKey>) -> Option<&mut [i32]> {
+        match self.column_mut(key)? {
+            Column::Int(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Get the values of an UnsignedLong field mutably, see [`ColumnarJMap::column_i32_mut`]
+    pub fn column_u32_mut(&mut self, key: impl Into<FieldKey>) -> Option<&mut [u32]> {
+        match self.column_mut(key)? {
+            Column::UInt(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Get the values of a Float field mutably, see [`ColumnarJMap::column_i32_mut`]
+    pub fn column_f32_mut(&mut self, key: impl Into<FieldKey>) -> Option<&mut [f32]> {
+        match self.column_mut(key)? {
+            Column::Float(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Get the values of a String or StringOffset field mutably, see [`ColumnarJMap::column_i32_mut`]
+    pub fn column_str_mut(&mut self, key: impl Into<FieldKey>) -> Option<&mut [String]> {
+        match self.column_mut(key)? {
+            Column::String(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Check whether an entry has no value for a field
+    ///
+    /// # Returns
+    /// `false` for unknown fields and out of bounds indices
+    pub fn is_null(&self, index: usize, key: impl Into<FieldKey>) -> bool {
+        self.column_index(key)
+            .is_some_and(|column| index < self.len && self.columns[column].nulls.is_null(index))
+    }
+
+    /// Count the entries without a value for a field
+    pub fn null_count(&self, key: impl Into<FieldKey>) -> usize {
+        self.column_index(key)
+            .map_or(0, |column| self.columns[column].nulls.count())
+    }
+
+    /// Get a value of an entry
+    ///
+    /// # Returns
+    /// The value, or `None` if the field or entry does not exist or the value is missing
+    pub fn get(&self, index: usize, key: impl Into<FieldKey>) -> Option<FieldValue> {
+        let column = &self.columns[self.column_index(key)?];
+        if column.nulls.is_null(index) {
+            return None;
+        }
+        column.values.get(index)
+    }
+
+    /// Set a value of an entry, checking it against the field's type
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if the field does not exist
+    /// - `JMapError::EntryIndexOutOfBounds` if the index is out of bounds
+    /// - `JMapError::TypeMismatch` if the value is not compatible with the field type
+    pub fn set(&mut self, index: usize, key: impl Into<FieldKey>, value: FieldValue) -> Result<()> {
+        let key = key.into();
+        let position = self
+            .column_index(key.clone())
+            .ok_or_else(|| JMapError::FieldNotFound(key.to_string()))?;
+        if index >= self.len {
+            return Err(JMapError::EntryIndexOutOfBounds { index, len: self.len });
+        }
+
+        let field_type = self.fields[position].field_type;
+        let column = &mut self.columns[position];
+        column.values.set(index, value).map_err(|value| JMapError::TypeMismatch {
+            field: key.to_string(),
+            expected: field_type.csv_name(),
+            got: value.type_name(),
+        })?;
+        column.nulls.set(index, false);
+        Ok(())
+    }
+
+    /// Append an entry, fields without a value in `entry` being missing
+    ///
+    /// Values of hashes that are not fields of the table are ignored
+    ///
+    /// # Errors
+    /// - `JMapError::TypeMismatch` if a value is not compatible with its field type. Nothing is appended
+    ///
+    /// # Returns
+    /// The index of the new entry
+    pub fn push_entry(&mut self, entry: &Entry) -> Result<usize> {
+        for field in self.fields.values() {
+            if let Some(value) = entry.get_by_hash(field.hash)
+                && !value.is_compatible_with(field.field_type)
+            {
+                return Err(JMapError::TypeMismatch {
+                    field: self.hash_table.find(field.hash),
+                    expected: field.field_type.csv_name(),
+                    got: value.type_name(),
+                });
+            }
+        }
+
+        let index = self.len;
+        for (field, column) in self.fields.values().zip(&mut self.columns) {
+            let value = match entry.get_by_hash(field.hash) {
+                Some(value) => value.clone(),
+                None => {
+                    column.nulls.set(index, true);
+                    field.default.clone()
+                }
+            };
+            column.values.push(value).expect("value was just checked");
+        }
+        self.len += 1;
+        Ok(index)
+    }
+
+    /// Copy an entry out of the columns
+    ///
+    /// # Returns
+    /// The entry, or `None` if the index is out of bounds
+    pub fn entry(&self, index: usize) -> Option<Entry> {
+        if index >= self.len {
+            return None;
+        }
+        let mut entry = Entry::with_capacity(self.fields.len());
+        for (field, column) in self.fields.values().zip(&self.columns) {
+            if !column.nulls.is_null(index) {
+                entry.set_by_hash(field.hash, column.values.get(index).expect("index was just checked"));
+            }
+        }
+        Some(entry)
+    }
+
+    /// Copy the table to a JMapInfo borrowing the hash table, for the row-based writers
+    fn to_rows(&self) -> JMapInfo<Borrowed<'_, H>> {
+        let mut jmap = JMapInfo::new(Borrowed(&self.hash_table));
+        fill_jmap(&mut jmap, &self.fields, &self.columns, self.len, self.entry_size);
+        jmap
+    }
+
+    /// Write the table as BCSV, see [`to_buffer`]
+    ///
+    /// The entries are copied to the row representation first
+    pub fn to_buffer(&self, options: &IoOptions) -> Result<Vec<u8>> {
+        to_buffer(&self.to_rows(), options)
+    }
+
+    /// Write the table to a BCSV file, see [`ColumnarJMap::to_buffer`]
+    pub fn to_file<P: AsRef<Path>>(&self, path: P, options: &IoOptions) -> Result<()> {
+        std::fs::write(path, self.to_buffer(options)?)?;
+        Ok(())
+    }
+
+    /// Write the table as CSV, see [`to_csv_writer`]
+    ///
+    /// The entries are copied to the row representation first
+    pub fn to_csv_writer<W: Write>(&self, writer: W, options: &CsvOptions) -> Result<()> {
+        to_csv_writer(&self.to_rows(), writer, options)
+    }
+
+    /// Write the table as CSV text, see [`ColumnarJMap::to_csv_writer`]
+    pub fn to_csv_string(&self, options: &CsvOptions) -> Result<String> {
+        let mut data = Vec::new();
+        self.to_csv_writer(&mut data, options)?;
+        String::from_utf8(data).map_err(|err| JMapError::EncodingError(err.to_string()))
+    }
+}
+
+/// Add the fields and entries of a columnar table to an empty JMapInfo
+fn fill_jmap<T: HashTable>(
+    jmap: &mut JMapInfo<T>,
+    fields: &IndexMap<u32, Field>,
+    columns: &[ColumnData],
+    len: usize,
+    entry_size: u32,
+) {
+    jmap.entry_size = entry_size;
+    for field in fields.values() {
+        jmap.fields_map_mut().insert(field.hash, field.clone());
+    }
+
+    let mut entries = Vec::with_capacity(len);
+    for index in 0..len {
+        let mut entry = jmap.new_entry();
+        for (field, column) in fields.values().zip(columns) {
+            if !column.nulls.is_null(index) {
+                entry.set_by_hash(field.hash, column.values.get(index).expect("columns have one value per entry"));
+            }
+        }
+        entries.push(entry);
+    }
+    *jmap.entries_vec_mut() = entries;
+}
+
+/// Read-only access to a hash table, for writing a [`ColumnarJMap`] with the row-based writers
+///
+/// The writers never add names, `add` only computes the hash
+struct Borrowed<'a, H: HashTable>(&'a H);
+
+impl<H: HashTable> HashTable for Borrowed<'_, H> {
+    fn calc(&self, field_name: &str) -> u32 {
+        self.0.calc(field_name)
+    }
+
+    fn find(&self, hash: u32) -> String {
+        self.0.find(hash)
+    }
+
+    fn contains(&self, hash: u32) -> bool {
+        self.0.contains(hash)
+    }
+
+    fn find_opt(&self, hash: u32) -> Option<Cow<'_, str>> {
+        self.0.find_opt(hash)
+    }
+
+    fn add(&mut self, field_name: &str) -> u32 {
+        self.0.calc(field_name)
+    }
+}
+
+/// Read a columnar table from a byte buffer
+///
+/// Values are decoded straight into their columns, without building entries.
+/// The result is the same as converting [`from_buffer`](crate::io::from_buffer) with [`ColumnarJMap::from_jmap`]
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups
+/// - `data` - The byte buffer containing the BCSV data
+/// - `options` - Options for endianness, string encoding and strictness. `keep_raw_values` is ignored, and so are
+///   the problems tolerated by the strictness
+///
+/// # Returns
+/// The table, or an error if parsing fails
+pub fn from_buffer_columnar<H: HashTable>(hash_table: H, data: &[u8], options: &IoOptions) -> Result<ColumnarJMap<H>> {
+    let header = read_header(data, options)?;
+    let (off_data, entry_size) = (header.off_data as usize, header.entry_size as usize);
+
+    let mut values = ValueReader {
+        data,
+        off_strings: header.off_strings() as usize,
+        options,
+        report: Report::default(),
+    };
+    let mut fields = IndexMap::new();
+    for field in read_unique_fields(data, &header, options, &hash_table, &mut values.report)? {
+        fields.insert(field.hash, field);
+    }
+    check_field_conflicts(fields.values(), options, &hash_table, &mut values.report)?;
+    let num_entries = readable_entries(&header, data.len(), options, &mut values.report)?;
+
+    let mut columns = Vec::with_capacity(fields.len());
+    for field in fields.values() {
+        let mut column = Column::with_capacity(field.field_type, num_entries);
+        let (decoder, name) = (Decoder::for_field(field), hash_table.find(field.hash));
+        for index in 0..num_entries {
+            let offset = off_data + index * entry_size + field.offset as usize;
+            let value = values.read(decoder, field, offset, index, &name)?;
+            column.push(value).expect("values are read with the field type");
+        }
+        columns.push(ColumnData {
+            values: column,
+            nulls: NullMask::default(),
+        });
+    }
+
+    Ok(ColumnarJMap {
+        hash_table,
+        fields,
+        columns,
+        len: num_entries,
+        entry_size: header.entry_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{smg_hash_table_with_lookup, FileHashTable, HashAlgorithm};
+    use crate::io::from_buffer;
+
+    fn scenario() -> Vec<u8> {
+        std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples/scenariodata.bcsv")).unwrap()
+    }
+
+    fn names() -> FileHashTable {
+        smg_hash_table_with_lookup(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/strings_SMG.txt")).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let options = IoOptions::default();
+        let jmap = from_buffer(names(), &scenario(), &options).unwrap();
+        let entries = jmap.entries().to_vec();
+        let entry_size = jmap.entry_size;
+        let bytes = to_buffer(&jmap, &options).unwrap();
+        let csv = crate::csv::to_csv_string(&jmap, &CsvOptions::default()).unwrap();
+
+        let columnar = ColumnarJMap::from_jmap(jmap).unwrap();
+        assert_eq!(columnar.len(), 6);
+        assert_eq!(columnar.to_buffer(&options).unwrap(), bytes);
+        assert_eq!(columnar.to_csv_string(&CsvOptions::default()).unwrap(), csv);
+
+        let direct = from_buffer_columnar(names(), &scenario(), &options).unwrap();
+        assert_eq!(direct.columns, columnar.columns);
+        assert_eq!(direct.column_i32("ScenarioNo"), Some(&[1, 2, 3, 4, 5, 6][..]));
+        assert_eq!(direct.column_u32("ScenarioNo"), None);
+
+        let back = columnar.into_jmap();
+        assert_eq!(back.entries(), &entries[..]);
+        assert_eq!(back.entry_size, entry_size);
+    }
+
+    #[test]
+    fn test_editing() {
+        let mut jmap = JMapInfo::new(FileHashTable::new(HashAlgorithm::SMG));
+        jmap.create_field("Id", FieldType::Long, FieldValue::Int(-1)).unwrap();
+        jmap.create_field("Flags", FieldType::UnsignedLong, FieldValue::UInt(0)).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(1.0)).unwrap();
+        jmap.create_entry();
+        let mut columnar = ColumnarJMap::from_jmap(jmap).unwrap();
+
+        let ht = FileHashTable::new(HashAlgorithm::SMG);
+        let mut entry = Entry::new();
+        entry.set(&ht, "Id", FieldValue::Int(5));
+        entry.set(&ht, "Flags", FieldValue::Int(-1));
+        assert_eq!(columnar.push_entry(&entry).unwrap(), 1);
+        assert_eq!(columnar.get(1, "Flags"), Some(FieldValue::UInt(u32::MAX)));
+        assert!(columnar.is_null(1, "Scale"));
+        assert_eq!(columnar.get(1, "Scale"), None);
+        assert_eq!(columnar.column_f32("Scale"), Some(&[1.0, 1.0][..]));
+        assert_eq!(columnar.null_count("Scale"), 1);
+
+        columnar.set(1, "Scale", FieldValue::Float(2.5)).unwrap();
+        assert!(!columnar.is_null(1, "Scale"));
+        assert!(matches!(columnar.set(1, "Scale", FieldValue::Int(2)), Err(JMapError::TypeMismatch { .. })));
+        assert!(matches!(columnar.set(2, "Scale", FieldValue::Float(2.0)), Err(JMapError::EntryIndexOutOfBounds { .. })));
+        assert!(matches!(columnar.set(0, "Missing", FieldValue::Int(2)), Err(JMapError::FieldNotFound(_))));
+
+        entry.set(&ht, "Scale", FieldValue::from("big"));
+        assert!(matches!(columnar.push_entry(&entry), Err(JMapError::TypeMismatch { .. })));
+        assert_eq!(columnar.len(), 2);
+
+        for id in columnar.column_i32_mut("Id").unwrap() {
+            *id *= 10;
+        }
+        assert_eq!(columnar.entry(1).unwrap().get_by_hash(ht.calc("Id")), Some(&FieldValue::Int(50)));
+
+        let jmap = columnar.into_jmap();
+        assert_eq!(jmap.get_value(0, "Id"), Some(&FieldValue::Int(-10)));
+        assert_eq!(jmap.get_value(1, "Scale"), Some(&FieldValue::Float(2.5)));
+    }
+}
@@ -1,12 +1,210 @@
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufReader, BufWriter, Cursor, Read, Write};
 use std::path::Path;
+use std::sync::Arc;
 
 use crate::entry::Entry;
 use crate::error::{JMapError, Result};
-use crate::field::{Field, FieldType, FieldValue};
-use crate::hash::HashTable;
+use crate::field::{parse_int_literal, Field, FieldType, FieldValue};
+use crate::hash::{parse_hash_name, HashTable};
 use crate::jmap::JMapInfo;
+use crate::layout::load_sidecar;
+use crate::schema::Schema;
+use crate::strictness::{Category, Report, Strictness, Warning};
+
+mod batch;
+pub use batch::{
+    convert_dir_bcsv_to_csv, convert_dir_bcsv_to_csv_filtered, convert_dir_csv_to_bcsv, convert_dir_csv_to_bcsv_filtered,
+    BatchReport,
+};
+
+/// When cells are quoted in written CSV files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvQuoting {
+    /// Quote cells containing the delimiter, quotes or line breaks
+    #[default]
+    Necessary,
+    /// Quote every cell
+    Always,
+    /// Never quote cells, even if that makes the file ambiguous
+    Never,
+}
+
+impl CsvQuoting {
+    fn quote_style(self) -> csv::QuoteStyle {
+        match self {
+            CsvQuoting::Necessary => csv::QuoteStyle::Necessary,
+            CsvQuoting::Always => csv::QuoteStyle::Always,
+            CsvQuoting::Never => csv::QuoteStyle::Never,
+        }
+    }
+}
+
+/// How floats are written to CSV cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatFormat {
+    /// The shortest representation that reads back to the same value, such as `1.5`
+    #[default]
+    Shortest,
+    /// A fixed number of decimals, such as `1.500`, which may lose precision
+    Fixed(usize),
+    /// The raw bit pattern in hex, such as `0x3FC00000`, exact even for NaN payloads
+    HexBits,
+}
+
+/// Order of the columns of an exported CSV file
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ColumnOrder {
+    /// The order of the fields in the JMapInfo
+    #[default]
+    FieldOrder,
+    /// The order of the fields in an entry of the binary file, offsets must be computed
+    ByOffset,
+    /// Alphabetical order of the field names, fields with unknown names come last, sorted by hash
+    Alphabetical,
+    /// The given field names first, then the remaining fields in field order
+    ///
+    /// Names can also be given in the `[XXXXXXXX]` form
+    /// Exporting fails with `JMapError::FieldNotFound` if a name is not a field of the JMapInfo
+    Explicit(Vec<String>),
+}
+
+/// Layout of the header of a CSV file, to exchange files with other BCSV tools
+///
+/// Dialects other than `NameTypeDefault` cannot hold the default value of a field, which is then the type's,
+/// nor a mask or shift, exporting a bit-packed field fails with `JMapError::InvalidField`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvDialect {
+    /// `Name:Type:Default`, with `:Mask:Shift` for bit-packed fields
+    #[default]
+    NameTypeDefault,
+    /// `Name:Type`
+    NameType,
+    /// `Name(Type)`
+    NameParenType,
+    /// A row of names followed by a row of types
+    TwoRowHeader,
+    /// Detect the dialect from the first two rows on import, `NameTypeDefault` on export
+    Auto,
+}
+
+/// Comment lines written at the top of exported CSV files, see [`CsvOptions::banner`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvBanner {
+    /// Whether the name and version of this library are written
+    pub tool_version: bool,
+    /// The file the table was read from
+    pub source: Option<String>,
+    /// Whether the time of the export is written, turn off for reproducible files
+    pub timestamp: bool,
+    /// More lines to write
+    pub lines: Vec<String>,
+}
+
+impl Default for CsvBanner {
+    fn default() -> Self {
+        Self {
+            tool_version: true,
+            source: None,
+            timestamp: true,
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl CsvBanner {
+    /// Get the text of each comment line, without the comment character
+    fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if self.tool_version {
+            lines.push(format!("Written by {} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")));
+        }
+        if let Some(source) = &self.source {
+            lines.push(format!("Source: {}", source));
+        }
+        if self.timestamp {
+            let seconds = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            lines.push(format!("Written at: {} (Unix time)", seconds));
+        }
+        lines.extend(self.lines.iter().flat_map(|line| line.lines()).map(str::to_string));
+        lines
+    }
+}
+
+/// Options for reading/writing CSV files
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Byte that separates cells, such as `b','`, `b';'` or `b'\t'`
+    pub delimiter: u8,
+    /// When cells are quoted on export, quoted cells are always accepted on import
+    pub quoting: CsvQuoting,
+    /// Character that separates field name, type, and default value in the header
+    pub header_delimiter: char,
+    /// Whether `true`/`false` cells are accepted in integer columns (read as 1/0)
+    pub parse_bools: bool,
+    /// How floats are written, all forms are accepted on import
+    pub float_format: FloatFormat,
+    /// What to do with malformed data on import, see [`Strictness`]. Rows without one cell per column and
+    /// fields defined twice are `Structure` problems, cells that cannot be parsed are `Values` problems
+    pub strictness: Strictness,
+    /// Whether resolved names are exported with their hash, as `Name[3666C077]:Type:Default`
+    pub include_hashes: bool,
+    /// Whether every column is exported as `[3666C077]:Type:Default`, without names
+    pub hash_headers: bool,
+    /// Order of the exported columns, imports always follow the header
+    pub column_order: ColumnOrder,
+    /// Whether exported files start with a UTF-8 byte order mark, which Excel needs to detect UTF-8.
+    /// A leading byte order mark is always skipped on import
+    pub write_bom: bool,
+    /// Byte starting comment lines, which are skipped on import, such as `Some(b'#')`.
+    /// On export, cells starting with it at the beginning of a line are quoted
+    pub comment_char: Option<u8>,
+    /// Comment lines written at the top of exported files, with `comment_char` or `#` if it is `None`
+    pub banner: Option<CsvBanner>,
+    /// Layout of the header
+    pub dialect: CsvDialect,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quoting: CsvQuoting::Necessary,
+            header_delimiter: ':',
+            parse_bools: false,
+            float_format: FloatFormat::Shortest,
+            strictness: Strictness::strict(),
+            include_hashes: false,
+            hash_headers: false,
+            column_order: ColumnOrder::FieldOrder,
+            write_bom: false,
+            comment_char: None,
+            banner: None,
+            dialect: CsvDialect::NameTypeDefault,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Default options with the given header delimiter, or ':' if `None`
+    fn with_header_delimiter(header_delimiter: Option<char>) -> Self {
+        Self {
+            header_delimiter: header_delimiter.unwrap_or(':'),
+            ..Self::default()
+        }
+    }
+
+    /// Default options for tab-separated files
+    pub fn tsv() -> Self {
+        Self {
+            delimiter: b'\t',
+            ..Self::default()
+        }
+    }
+}
 
 /// Read a JMapInfo from a CSV file
 ///
@@ -14,7 +212,12 @@ use crate::jmap::JMapInfo;
 /// `FieldName:Type:DefaultValue`
 ///
 /// For example: `ScenarioNo:Int:0,ZoneName:String:0`
-/// The delimiter between the parts can be customized (default is ':') and should not appear in field names or type names
+/// The name can also be `[XXXXXXXX]` for unknown hashes, or `Name[XXXXXXXX]` to give both, in which case the hash wins
+/// Bit-packed fields have two more parts, the mask in hex and the shift: `Flags:Int:0:0x0000FF00:8`
+/// Empty cells get the default value of their column. For string columns, a default of `0` means an empty string
+/// The delimiter between the parts can be customized (default is ':'). A delimiter or backslash inside a name
+/// or default value is escaped with a backslash, such as `Obj\:Arg:Int:0`
+/// Headers written by other tools are read with [`CsvOptions::dialect`]
 ///
 /// # Arguments
 /// - `hash_table` - The hash table to use for field name lookups. Field names from the CSV will be added to this hash table
@@ -27,79 +230,248 @@ pub fn from_csv<H: HashTable, P: AsRef<Path>>(
     hash_table: H,
     path: P,
     header_delimiter: Option<char>,
+) -> Result<JMapInfo<H>> {
+    from_csv_with_options(hash_table, path, &CsvOptions::with_header_delimiter(header_delimiter))
+}
 
+/// Read a JMapInfo from a CSV file with the given options
+///
+/// See [`from_csv`] for the header format
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups. Field names from the CSV will be added to this hash table
+/// - `path` - The path to the CSV file to read
+/// - `options` - Options for parsing the header and cells
+///
+/// # Errors
+/// - `JMapError::HashCollision` if a field name has the same hash as a different name already in the hash table
+///
+/// # Returns
+/// A JMapInfo populated with fields and entries from the CSV file
+pub fn from_csv_with_options<H: HashTable, P: AsRef<Path>>(
+    hash_table: H,
+    path: P,
+    options: &CsvOptions,
 ) -> Result<JMapInfo<H>> {
-    let delimiter = header_delimiter.unwrap_or(':');
     let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let mut csv_reader = csv::ReaderBuilder::new()
-        .has_headers(false)
-        .from_reader(reader);
+    from_csv_reader(hash_table, BufReader::new(file), options)
+}
+
+/// Read a JMapInfo from CSV text held in memory
+///
+/// See [`from_csv`] for the header format
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups. Field names from the CSV will be added to this hash table
+/// - `text` - The CSV text
+/// - `options` - Options for parsing the header and cells
+///
+/// # Returns
+/// A JMapInfo populated with fields and entries from the CSV text
+pub fn from_csv_str<H: HashTable>(hash_table: H, text: &str, options: &CsvOptions) -> Result<JMapInfo<H>> {
+    from_csv_reader(hash_table, text.as_bytes(), options)
+}
+
+/// Read a JMapInfo from any CSV source
+///
+/// See [`from_csv`] for the header format
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups. Field names from the CSV will be added to this hash table
+/// - `reader` - The CSV source, buffering is done internally
+/// - `options` - Options for parsing the header and cells
+///
+/// # Errors
+/// - `JMapError::HashCollision` if a field name has the same hash as a different name already in the hash table
+///
+/// # Returns
+/// A JMapInfo populated with fields and entries from the CSV source
+pub fn from_csv_reader<H: HashTable, R: Read>(hash_table: H, reader: R, options: &CsvOptions) -> Result<JMapInfo<H>> {
+    from_csv_reader_with_report(hash_table, reader, options).map(|(jmap, _)| jmap)
+}
+
+/// Read a JMapInfo from any CSV source, also returning the problems that did not stop the import
+///
+/// See [`from_csv_reader`]
+///
+/// # Returns
+/// The JMapInfo and the import report
+pub fn from_csv_reader_with_report<H: HashTable, R: Read>(
+    hash_table: H,
+    reader: R,
+    options: &CsvOptions,
+) -> Result<(JMapInfo<H>, Report)> {
+    let mut report = Report::default();
+    let mut csv_reader = csv_reader(reader, options)?;
 
     let mut jmap = JMapInfo::new(hash_table);
-    let mut records = csv_reader.records();
+    let mut records = csv_reader.records().peekable();
 
     // Parse header
     let header = records
         .next()
         .ok_or_else(|| JMapError::CsvError("CSV file is empty".to_string()))??;
+    let types = match records.peek() {
+        Some(Ok(record)) => Some(record),
+        _ => None,
+    };
+    let (dialect, cells) = parse_header(&header, types, options)?;
+    let first_row = if dialect == CsvDialect::TwoRowHeader {
+        records.next().transpose()?;
+        3
+    } else {
+        2
+    };
 
-    let mut field_infos: Vec<(u32, FieldType)> = Vec::new();
+    let mut field_infos: Vec<Column> = Vec::new();
 
-    for field_desc in header.iter() {
-        let parts: Vec<&str> = field_desc.split(delimiter).collect();
+    for (column, cell) in cells.into_iter().enumerate() {
+        let HeaderCell { name: field_name, field_type, default: default_str, mask_shift } = cell;
+        let field_name = field_name.as_str();
 
-        if parts.len() != 3 {
-            return Err(JMapError::InvalidCsvFieldDescriptor(format!(
-                "Expected 3 parts (name{}type{}default), got: {}",
-                delimiter,
-                delimiter,
-                field_desc
-            )));
+        let hash = parse_header_name(jmap.hash_table_mut(), field_name, column, &mut report)?;
+
+        let default = match default_str {
+            Some(default_str) => parse_default(&default_str, field_type, options)?,
+            None => FieldValue::default_for(field_type),
+        };
+        let mut field = Field::with_default(hash, field_type, default.clone());
+        if let Some((mask, shift)) = &mask_shift {
+            field.mask = parse_mask(mask).ok_or_else(|| {
+                JMapError::InvalidCsvFieldDescriptor(format!("Invalid mask: {}", mask))
+            })?;
+            field.shift = shift.parse().map_err(|_| {
+                JMapError::InvalidCsvFieldDescriptor(format!("Invalid shift: {}", shift))
+            })?;
+            field.validate()?;
+        }
+        if let Some(first) = field_infos.iter_mut().find(|c| c.hash == hash && !c.skip) {
+            let message = format!(
+                "'{}' in column {} is the same field as '{}' in column {}",
+                field_name,
+                column + 1,
+                first.name,
+                first.column + 1
+            );
+            let warning = Warning::csv(
+                Category::Structure,
+                Some(1),
+                Some(column + 1),
+                format!("{}, keeping column {}", message, column + 1),
+            );
+            options.strictness.check(&mut report, warning, || JMapError::FieldAlreadyExists(message))?;
+            first.skip = true;
         }
 
-        let field_name = parts[0];
-        let type_name = parts[1];
-        let _default_str = parts[2];
+        debug!(column, hash, name = field_name, field_type = ?field_type, "read CSV column");
+        jmap.fields_map_mut().insert(hash, field);
+        field_infos.push(Column {
+            hash,
+            field_type,
+            default,
+            name: field_name.to_string(),
+            column,
+            skip: false,
+        });
+    }
 
-        if field_name.is_empty() {
-            return Err(JMapError::InvalidCsvFieldDescriptor(
-                "Field name cannot be empty".to_string(),
-            ));
-        }
+    // Parse data rows, the header being row 1, or rows 1 and 2
+    read_rows(&mut jmap, records, &field_infos, first_row, options, &mut report)?;
+    debug!(columns = field_infos.len(), entries = jmap.len(), warnings = report.warnings.len(), "read CSV");
 
-        let field_type = FieldType::from_csv_name(type_name).ok_or_else(|| {
-            JMapError::InvalidCsvFieldDescriptor(format!("Unknown field type: {}", type_name))
-        })?;
+    Ok((jmap, report))
+}
 
-        // Parse hash from [XXXXXXXX] format or compute from name
-        let hash = if field_name.starts_with('[') && field_name.ends_with(']') {
-            let hex_str = &field_name[1..field_name.len() - 1];
-            u32::from_str_radix(hex_str, 16).map_err(|_| {
-                JMapError::InvalidCsvFieldDescriptor(format!("Invalid hash: {}", field_name))
-            })?
-        } else {
-            jmap.hash_table_mut().add(field_name)
-        };
+/// Read a JMapInfo from a CSV file and give it the binary layout saved by [`save_sidecar`](crate::layout::save_sidecar)
+///
+/// Written with [`LayoutStrategy::Preserve`](crate::layout::LayoutStrategy::Preserve), the table gets the
+/// field order, offsets, masks, shifts and entry size of the BCSV file the CSV file was exported from.
+/// Columns missing from the layout are appended after the entry, layout fields missing from the CSV file
+/// are `Structure` problems handled according to `options.strictness`, see [`LayoutSidecar::apply`](crate::layout::LayoutSidecar::apply)
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups. Field names from the CSV will be added to this hash table
+/// - `csv_path` - The path to the CSV file to read
+/// - `layout_path` - The path to the layout written by [`save_sidecar`](crate::layout::save_sidecar)
+/// - `options` - Options for parsing the CSV file
+///
+/// # Errors
+/// - `JMapError::InvalidField` if the layout file is malformed
+/// - `JMapError::InvalidCsvFieldDescriptor` if a column has another type than in the layout
+///
+/// # Returns
+/// The JMapInfo and the import report, including the differences between the columns and the layout
+pub fn from_csv_with_layout<H: HashTable, P: AsRef<Path>, L: AsRef<Path>>(
+    hash_table: H,
+    csv_path: P,
+    layout_path: L,
+    options: &CsvOptions,
+) -> Result<(JMapInfo<H>, Report)> {
+    let sidecar = load_sidecar(layout_path)?;
+    let file = File::open(csv_path)?;
+    let (mut jmap, mut report) = from_csv_reader_with_report(hash_table, BufReader::new(file), options)?;
+    sidecar.apply_unrecorded(&mut jmap, &options.strictness, &mut report)?;
+    Ok((jmap, report))
+}
 
-        let default = FieldValue::default_for(field_type);
-        let field = Field::with_default(hash, field_type, default);
-        jmap.fields_map_mut().insert(hash, field);
-        field_infos.push((hash, field_type));
-    }
+/// Create a CSV reader for the given options, without headers since they are parsed by hand
+fn csv_reader<R: Read>(reader: R, options: &CsvOptions) -> Result<csv::Reader<impl Read>> {
+    Ok(csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(options.delimiter)
+        .comment(options.comment_char)
+        .from_reader(skip_bom(reader)?))
+}
 
-    // Parse data rows
-    for result in records {
+/// Parse the data rows of a CSV file into entries of `jmap`
+///
+/// `first_row` is the 1-based number of the first data row, used in errors and warnings
+fn read_rows<H: HashTable>(
+    jmap: &mut JMapInfo<H>,
+    records: impl Iterator<Item = csv::Result<csv::StringRecord>>,
+    field_infos: &[Column],
+    first_row: usize,
+    options: &CsvOptions,
+    report: &mut Report,
+) -> Result<()> {
+    let layout = jmap.slot_layout();
+    for (row, result) in records.enumerate().map(|(i, r)| (i + first_row, r)) {
         let record = result?;
-        let mut entry = Entry::with_capacity(field_infos.len());
+        let mut entry = Entry::with_layout(Arc::clone(&layout));
+
+        if record.len() != field_infos.len() {
+            let message = format!("expected {} cells, got {}", field_infos.len(), record.len());
+            let error = || JMapError::CsvError(format!("Row {}: {}", row, message));
+            let warning = Warning::csv(Category::Structure, Some(row), None, message.clone());
+            options.strictness.check(report, warning, error)?;
+        }
 
-        for (i, (hash, field_type)) in field_infos.iter().enumerate() {
+        for (i, info) in field_infos.iter().enumerate().filter(|(_, info)| !info.skip) {
+            let Column { hash, field_type, default, name: field_name, .. } = info;
             let value_str = record.get(i).unwrap_or("");
 
             let value = if value_str.is_empty() {
-                FieldValue::default_for(*field_type)
+                default.clone()
             } else {
-                parse_field_value(value_str, *field_type)?
+                match parse_field_value(value_str, *field_type, options) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        let message = match err {
+                            JMapError::CsvError(message) => message,
+                            other => other.to_string(),
+                        };
+                        let warning = Warning::csv(
+                            Category::Values,
+                            Some(row),
+                            Some(i + 1),
+                            format!("{}, using the default of '{}'", message, field_name),
+                        );
+                        let error = || JMapError::CsvError(format!("Row {}, column '{}': {}", row, field_name, message));
+                        options.strictness.check(report, warning, error)?;
+                        default.clone()
+                    }
+                }
             };
 
             entry.set_by_hash(*hash, value);
@@ -108,13 +480,501 @@ pub fn from_csv<H: HashTable, P: AsRef<Path>>(
         jmap.entries_vec_mut().push(entry);
     }
 
+    Ok(())
+}
+
+/// UTF-8 byte order mark
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Wrap a reader so that a leading UTF-8 byte order mark is skipped
+fn skip_bom<R: Read>(mut reader: R) -> Result<impl Read> {
+    let mut prefix = [0; 3];
+    let mut len = 0;
+    while len < prefix.len() {
+        match reader.read(&mut prefix[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    let start = if &prefix[..len] == BOM { len } else { 0 };
+    Ok(Cursor::new(prefix[start..len].to_vec()).chain(reader))
+}
+
+/// A header column before its name is hashed
+struct HeaderCell {
+    name: String,
+    field_type: FieldType,
+    /// `None` if the dialect has no default values
+    default: Option<String>,
+    mask_shift: Option<(String, String)>,
+}
+
+/// Split the header of a CSV file into columns, detecting the dialect if needed
+///
+/// `types` is the row after the header, which holds the types in the `TwoRowHeader` dialect
+fn parse_header(
+    header: &csv::StringRecord,
+    types: Option<&csv::StringRecord>,
+    options: &CsvOptions,
+) -> Result<(CsvDialect, Vec<HeaderCell>)> {
+    let delimiter = options.header_delimiter;
+    if options.dialect != CsvDialect::Auto {
+        let cells = parse_header_cells(options.dialect, header, types, delimiter)?;
+        return Ok((options.dialect, cells));
+    }
+
+    // Errors are reported for the default dialect, the others being fallbacks
+    let err = match parse_header_cells(CsvDialect::NameTypeDefault, header, types, delimiter) {
+        Ok(cells) => return Ok((CsvDialect::NameTypeDefault, cells)),
+        Err(err) => err,
+    };
+    for dialect in [CsvDialect::NameType, CsvDialect::NameParenType, CsvDialect::TwoRowHeader] {
+        if let Ok(cells) = parse_header_cells(dialect, header, types, delimiter) {
+            return Ok((dialect, cells));
+        }
+    }
+    Err(err)
+}
+
+fn parse_header_cells(
+    dialect: CsvDialect,
+    header: &csv::StringRecord,
+    types: Option<&csv::StringRecord>,
+    delimiter: char,
+) -> Result<Vec<HeaderCell>> {
+    let types = match dialect {
+        CsvDialect::TwoRowHeader => {
+            let types = types.ok_or_else(|| {
+                JMapError::InvalidCsvFieldDescriptor("Missing the row of types after the row of names".to_string())
+            })?;
+            if types.len() != header.len() {
+                return Err(JMapError::InvalidCsvFieldDescriptor(format!(
+                    "Expected {} types in row 2, got {}",
+                    header.len(),
+                    types.len()
+                )));
+            }
+            Some(types)
+        }
+        _ => None,
+    };
+
+    let mut cells = Vec::with_capacity(header.len());
+    for (column, desc) in header.iter().enumerate() {
+        let (name, type_name, default, mask_shift) = match dialect {
+            CsvDialect::NameTypeDefault | CsvDialect::Auto => match &split_header(desc, delimiter)[..] {
+                [name, type_name, default] => (name.clone(), type_name.clone(), Some(default.clone()), None),
+                [name, type_name, default, mask, shift] => (
+                    name.clone(),
+                    type_name.clone(),
+                    Some(default.clone()),
+                    Some((mask.clone(), shift.clone())),
+                ),
+                _ => {
+                    return Err(JMapError::InvalidCsvFieldDescriptor(format!(
+                        "Column {}: expected 3 parts (name{d}type{d}default) or 5 parts (name{d}type{d}default{d}mask{d}shift), got: {}",
+                        column + 1,
+                        desc,
+                        d = delimiter,
+                    )));
+                }
+            },
+            CsvDialect::NameType => match &split_header(desc, delimiter)[..] {
+                [name, type_name] => (name.clone(), type_name.clone(), None, None),
+                _ => {
+                    return Err(JMapError::InvalidCsvFieldDescriptor(format!(
+                        "Column {}: expected 2 parts (name{d}type), got: {}",
+                        column + 1,
+                        desc,
+                        d = delimiter,
+                    )));
+                }
+            },
+            CsvDialect::NameParenType => match desc.strip_suffix(')').and_then(|rest| rest.rsplit_once('(')) {
+                Some((name, type_name)) => (name.to_string(), type_name.to_string(), None, None),
+                None => {
+                    return Err(JMapError::InvalidCsvFieldDescriptor(format!(
+                        "Column {}: expected name(type), got: {}",
+                        column + 1,
+                        desc
+                    )));
+                }
+            },
+            CsvDialect::TwoRowHeader => {
+                let type_name = types.and_then(|types| types.get(column)).unwrap_or_default();
+                (desc.to_string(), type_name.to_string(), None, None)
+            }
+        };
+
+        if name.is_empty() {
+            return Err(JMapError::InvalidCsvFieldDescriptor(
+                "Field name cannot be empty".to_string(),
+            ));
+        }
+
+        let field_type = FieldType::from_csv_name(&type_name).ok_or_else(|| {
+            JMapError::InvalidCsvFieldDescriptor(format!("Unknown field type: {}", type_name))
+        })?;
+
+        cells.push(HeaderCell { name, field_type, default, mask_shift });
+    }
+    Ok(cells)
+}
+
+/// A parsed header column
+struct Column {
+    hash: u32,
+    field_type: FieldType,
+    default: FieldValue,
+    name: String,
+    /// 0-based position in the header
+    column: usize,
+    /// Whether a later column defines the same field, making this one ignored
+    skip: bool,
+}
+
+/// Get the hash of a header field name
+///
+/// The name is either `[XXXXXXXX]`, a plain name that is hashed and added to the hash table, or
+/// `Name[XXXXXXXX]` where the embedded hash wins and a warning is recorded if the name hashes differently
+fn parse_header_name<H: HashTable>(
+    hash_table: &mut H,
+    field_name: &str,
+    column: usize,
+    report: &mut Report,
+) -> Result<u32> {
+    let invalid = || JMapError::InvalidCsvFieldDescriptor(format!("Invalid hash: {}", field_name));
+
+    let Some(open) = field_name.rfind('[').filter(|_| field_name.ends_with(']')) else {
+        return hash_table.try_add(field_name);
+    };
+    let hash = parse_hash_name(&field_name[open..]).ok_or_else(invalid)?;
+    let name = &field_name[..open];
+    if name.is_empty() {
+        return Ok(hash);
+    }
+
+    let name_hash = hash_table.calc(name);
+    if name_hash == hash {
+        hash_table.try_add(name)?;
+    } else {
+        // The embedded hash is authoritative, so this is reported whatever the strictness
+        report.warnings.push(Warning::csv(
+            Category::Structure,
+            Some(1),
+            Some(column + 1),
+            format!(
+                "'{}' hashes to [{:08X}] but the header says [{:08X}], using [{:08X}]",
+                name, name_hash, hash, hash
+            ),
+        ));
+    }
+    Ok(hash)
+}
+
+/// Options for [`from_csv_with_schema`]
+#[derive(Debug, Clone, Default)]
+pub struct SchemaOptions {
+    /// Options for parsing the CSV file
+    pub csv: CsvOptions,
+    /// Whether schema fields may be missing from the CSV file, in which case every entry gets their default.
+    /// Otherwise they fail the import like columns missing from the schema
+    pub allow_missing_fields: bool,
+}
+
+/// Read a JMapInfo from a CSV file whose field definitions come from a schema
+///
+/// Header cells only need the field name, such as `ScenarioNo,ZoneName`. A header cell with a type, such as
+/// `ScenarioNo:Int:0`, is accepted if the type matches the schema, and the rest of the cell is ignored.
+/// The fields of the table are the schema's, in schema order
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups. Schema field names will be added to this hash table
+/// - `path` - The path to the CSV file to read
+/// - `schema` - The field definitions of the table
+/// - `options` - Options for parsing the CSV file and handling missing fields
+///
+/// # Errors
+/// - `JMapError::InvalidCsvFieldDescriptor` listing the columns missing from the schema and the schema fields
+///   missing from the CSV file, or if a header type differs from the schema's
+///
+/// # Returns
+/// A JMapInfo with the fields of the schema and the entries of the CSV file
+pub fn from_csv_with_schema<H: HashTable, P: AsRef<Path>>(
+    hash_table: H,
+    path: P,
+    schema: &Schema,
+    options: &SchemaOptions,
+) -> Result<JMapInfo<H>> {
+    let file = File::open(path)?;
+    from_csv_reader_with_schema(hash_table, BufReader::new(file), schema, options)
+}
+
+/// Read a JMapInfo from any CSV source whose field definitions come from a schema, see [`from_csv_with_schema`]
+pub fn from_csv_reader_with_schema<H: HashTable, R: Read>(
+    hash_table: H,
+    reader: R,
+    schema: &Schema,
+    options: &SchemaOptions,
+) -> Result<JMapInfo<H>> {
+    let csv_options = &options.csv;
+    let mut report = Report::default();
+    let mut csv_reader = csv_reader(reader, csv_options)?;
+    let mut jmap = JMapInfo::new(hash_table);
+    let mut records = csv_reader.records();
+
+    let header = records
+        .next()
+        .ok_or_else(|| JMapError::CsvError("CSV file is empty".to_string()))??;
+
+    let mut fields = Vec::with_capacity(schema.len());
+    for schema_field in schema.fields() {
+        let hash = match parse_hash_name(&schema_field.name) {
+            Some(hash) => hash,
+            None => jmap.hash_table_mut().try_add(&schema_field.name)?,
+        };
+        let field = schema_field.to_field(hash);
+        field.validate()?;
+        fields.push((schema_field, field));
+    }
+
+    let mut field_infos: Vec<Column> = Vec::with_capacity(header.len());
+    let mut unknown = Vec::new();
+    for (column, desc) in header.iter().enumerate() {
+        let parts = split_header(desc, csv_options.header_delimiter);
+        let field_name = parts[0].as_str();
+        let hash = parse_header_name(jmap.hash_table_mut(), field_name, column, &mut report)?;
+        let Some((schema_field, field)) = fields.iter().find(|(_, field)| field.hash == hash) else {
+            unknown.push(field_name.to_string());
+            continue;
+        };
+
+        if let Some(type_name) = parts.get(1)
+            && FieldType::from_csv_name(type_name) != Some(field.field_type)
+        {
+            return Err(JMapError::InvalidCsvFieldDescriptor(format!(
+                "Column {}: '{}' is {} in the header but {} in the schema",
+                column + 1,
+                field_name,
+                type_name,
+                field.field_type.csv_name()
+            )));
+        }
+        if let Some(first) = field_infos.iter().find(|c| c.hash == hash) {
+            return Err(JMapError::FieldAlreadyExists(format!(
+                "'{}' in column {} is the same field as '{}' in column {}",
+                field_name,
+                column + 1,
+                first.name,
+                first.column + 1
+            )));
+        }
+
+        field_infos.push(Column {
+            hash,
+            field_type: field.field_type,
+            default: field.default.clone(),
+            name: schema_field.name.clone(),
+            column,
+            skip: false,
+        });
+    }
+
+    let missing: Vec<&str> = fields
+        .iter()
+        .filter(|(_, field)| !field_infos.iter().any(|c| c.hash == field.hash))
+        .map(|(schema_field, _)| schema_field.name.as_str())
+        .collect();
+    let mut problems = Vec::new();
+    if !unknown.is_empty() {
+        problems.push(format!("columns not in the schema: {}", unknown.join(", ")));
+    }
+    if !missing.is_empty() && !options.allow_missing_fields {
+        problems.push(format!("schema fields not in the CSV: {}", missing.join(", ")));
+    }
+    if !problems.is_empty() {
+        return Err(JMapError::InvalidCsvFieldDescriptor(problems.join("; ")));
+    }
+
+    let defaults: Vec<(u32, FieldValue)> = fields
+        .iter()
+        .filter(|(_, field)| field_infos.iter().all(|c| c.hash != field.hash))
+        .map(|(_, field)| (field.hash, field.default.clone()))
+        .collect();
+    for (_, field) in fields {
+        jmap.fields_map_mut().insert(field.hash, field);
+    }
+    read_rows(&mut jmap, records, &field_infos, 2, csv_options, &mut report)?;
+
+    // Fields without a column have their default in every entry
+    for (hash, default) in defaults {
+        for entry in jmap.entries_vec_mut() {
+            entry.set_by_hash(hash, default.clone());
+        }
+    }
+
     Ok(jmap)
 }
 
+/// Options for [`merge_csv_into`]
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    /// Options for parsing the CSV file
+    pub csv: CsvOptions,
+    /// Whether CSV columns missing from the table are added to it, otherwise they fail with `JMapError::FieldNotFound`
+    pub create_missing_columns: bool,
+}
+
+/// Outcome of [`merge_csv_into`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Existing entries with at least one changed value
+    pub updated: usize,
+    /// Rows appended as new entries
+    pub added: usize,
+    /// Existing entries whose values were already the same
+    pub unchanged: usize,
+    /// Columns skipped because their CSV type differs from the table's, as `name: csv type vs table type`
+    pub type_conflicts: Vec<String>,
+    /// Key values used by several CSV rows, later rows win
+    pub duplicate_keys: Vec<String>,
+    /// Problems found while reading the CSV file
+    pub warnings: Vec<Warning>,
+}
+
+/// Merge a CSV file into an existing JMapInfo, matching rows to entries by a key column
+///
+/// Only the columns present in the CSV file are updated. Rows whose key is not in the table are appended,
+/// with the field defaults for the columns the CSV file does not have
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to update
+/// - `path` - The path to the CSV file, which must have the key column
+/// - `key_field` - The name of the field identifying rows, such as `ScenarioNo`
+/// - `options` - Options for parsing the CSV file and handling unknown columns
+///
+/// # Errors
+/// - `JMapError::FieldNotFound` if the key column is missing from the CSV file or the table,
+///   or if a column is missing from the table without `create_missing_columns`
+///
+/// # Returns
+/// How many entries were updated, added, or unchanged
+pub fn merge_csv_into<H: HashTable, P: AsRef<Path>>(
+    jmap: &mut JMapInfo<H>,
+    path: P,
+    key_field: &str,
+    options: &MergeOptions,
+) -> Result<MergeReport> {
+    let file = File::open(path)?;
+    merge_csv_reader_into(jmap, BufReader::new(file), key_field, options)
+}
+
+/// Merge CSV data from any source into an existing JMapInfo, see [`merge_csv_into`]
+pub fn merge_csv_reader_into<H: HashTable, R: Read>(
+    jmap: &mut JMapInfo<H>,
+    reader: R,
+    key_field: &str,
+    options: &MergeOptions,
+) -> Result<MergeReport> {
+    let mut report = MergeReport::default();
+    let (fields, rows) = {
+        let (csv, csv_report) = from_csv_reader_with_report(jmap.hash_table_mut(), reader, &options.csv)?;
+        report.warnings = csv_report.warnings;
+        let fields: Vec<Field> = csv.fields().cloned().collect();
+        (fields, csv.into_iter().collect::<Vec<Entry>>())
+    };
+
+    let key_hash = jmap.hash_table().calc(key_field);
+    if !fields.iter().any(|field| field.hash == key_hash) || !jmap.contains_field_hash(key_hash) {
+        return Err(JMapError::FieldNotFound(key_field.to_string()));
+    }
+
+    // Columns to copy, skipping the ones whose type differs from the table's
+    let mut columns = Vec::with_capacity(fields.len());
+    for field in fields {
+        match jmap.get_field_by_hash(field.hash) {
+            Some(existing) if existing.field_type != field.field_type => {
+                report.type_conflicts.push(format!(
+                    "{}: {} vs {}",
+                    jmap.field_name(field.hash),
+                    field.field_type.csv_name(),
+                    existing.field_type.csv_name()
+                ));
+                continue;
+            }
+            Some(_) => {}
+            None if options.create_missing_columns => {
+                jmap.fields_map_mut().insert(field.hash, field.clone());
+                jmap.sync_entry_layout();
+                for entry in jmap.entries_mut() {
+                    entry.set_by_hash(field.hash, field.default.clone());
+                }
+            }
+            None => return Err(JMapError::FieldNotFound(jmap.field_name(field.hash))),
+        }
+        columns.push(field.hash);
+    }
+
+    let key_of = |entry: &Entry| entry.get_by_hash(key_hash).map(|value| value.to_string());
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (i, entry) in jmap.entries().iter().enumerate() {
+        if let Some(key) = key_of(entry) {
+            index.entry(key).or_insert(i);
+        }
+    }
+
+    let mut seen: HashSet<String> = HashSet::new();
+    for row in rows {
+        let Some(key) = key_of(&row) else {
+            continue;
+        };
+        if !seen.insert(key.clone()) && !report.duplicate_keys.contains(&key) {
+            report.duplicate_keys.push(key.clone());
+        }
+
+        let position = match index.get(&key) {
+            Some(&position) => position,
+            None => {
+                jmap.create_entry();
+                let position = jmap.len() - 1;
+                index.insert(key, position);
+                report.added += 1;
+                merge_row(&mut jmap.entries_vec_mut()[position], &row, &columns);
+                continue;
+            }
+        };
+
+        if merge_row(&mut jmap.entries_vec_mut()[position], &row, &columns) {
+            jmap.touch(position);
+            report.updated += 1;
+        } else {
+            report.unchanged += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Copy the given columns of `row` into `entry`, returning whether a value changed
+fn merge_row(entry: &mut Entry, row: &Entry, columns: &[u32]) -> bool {
+    let mut changed = false;
+    for &hash in columns {
+        if let Some(value) = row.get_by_hash(hash)
+            && entry.get_by_hash(hash) != Some(value)
+        {
+            entry.set_by_hash(hash, value.clone());
+            changed = true;
+        }
+    }
+    changed
+}
+
 /// Write a JMapInfo to a CSV file
 ///
 /// The CSV format uses a header row where each column is formatted as:
-/// `FieldName:Type:DefaultValue`
+/// `FieldName:Type:DefaultValue`, followed by `:Mask:Shift` for fields whose mask or shift differ from the type's
 ///
 /// # Arguments
 /// - `jmap` - The JMapInfo to export to CSV
@@ -124,32 +984,135 @@ pub fn from_csv<H: HashTable, P: AsRef<Path>>(
 /// # Returns
 /// Ok(()) if the export was successful, or an error if the file could not be written
 pub fn to_csv<H: HashTable, P: AsRef<Path>>(jmap: &JMapInfo<H>, path: P, header_delimiter: Option<char>) -> Result<()> {
-    let delimiter = header_delimiter.unwrap_or(':');
+    to_csv_with_options(jmap, path, &CsvOptions::with_header_delimiter(header_delimiter))
+}
+
+/// Write a JMapInfo to a CSV file with the given options
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to export to CSV
+/// - `path` - The path to the CSV file to write
+/// - `options` - Options for formatting the header and cells
+///
+/// # Returns
+/// Ok(()) if the export was successful, or an error if the file could not be written
+pub fn to_csv_with_options<H: HashTable, P: AsRef<Path>>(
+    jmap: &JMapInfo<H>,
+    path: P,
+    options: &CsvOptions,
+) -> Result<()> {
     let file = File::create(path)?;
-    let writer = BufWriter::new(file);
-    let mut csv_writer = csv::Writer::from_writer(writer);
+    to_csv_writer(jmap, BufWriter::new(file), options)
+}
+
+/// Write a JMapInfo to a CSV string
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to export to CSV
+/// - `options` - Options for formatting the header and cells
+///
+/// # Returns
+/// The CSV text
+pub fn to_csv_string<H: HashTable>(jmap: &JMapInfo<H>, options: &CsvOptions) -> Result<String> {
+    let mut data = Vec::new();
+    to_csv_writer(jmap, &mut data, options)?;
+    String::from_utf8(data).map_err(|err| JMapError::EncodingError(err.to_string()))
+}
+
+/// Write a JMapInfo to any CSV destination
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to export to CSV
+/// - `writer` - The destination, buffering is done internally
+/// - `options` - Options for formatting the header and cells
+///
+/// # Returns
+/// Ok(()) if the export was successful, or an error if the destination could not be written
+pub fn to_csv_writer<H: HashTable, W: Write>(jmap: &JMapInfo<H>, mut writer: W, options: &CsvOptions) -> Result<()> {
+    let delimiter = options.header_delimiter;
+    if options.write_bom {
+        writer.write_all(BOM)?;
+    }
+    if let Some(banner) = &options.banner {
+        let comment = char::from(options.comment_char.unwrap_or(b'#'));
+        for line in banner.lines() {
+            writeln!(writer, "{} {}", comment, line)?;
+        }
+    }
+    let mut csv_writer = csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .quote_style(options.quoting.quote_style())
+        .comment(options.comment_char)
+        .from_writer(writer);
+
+    let columns = ordered_fields(jmap, &options.column_order)?;
 
     // Write header
-    let headers: Vec<String> = jmap
-        .fields()
-        .map(|field| {
-            let name = jmap.field_name(field.hash);
-            let type_name = field.field_type.csv_name();
-            let default = default_csv_value(field.field_type);
-            format!("{}{}{}{}{}", name, delimiter, type_name, delimiter, default)
-        })
-        .collect();
+    let mut names = Vec::with_capacity(columns.len());
+    for field in &columns {
+        let mut name = jmap.field_name(field.hash);
+        if options.hash_headers {
+            name = format!("[{:08X}]", field.hash);
+        } else if options.include_hashes && jmap.hash_table().contains(field.hash) {
+            name = format!("{}[{:08X}]", name, field.hash);
+        }
+        let packed = field.mask != field.field_type.default_mask() || field.shift != 0;
+        if packed && !matches!(options.dialect, CsvDialect::NameTypeDefault | CsvDialect::Auto) {
+            return Err(JMapError::InvalidField(format!(
+                "{} has a mask or shift, which the {:?} CSV dialect cannot represent",
+                name, options.dialect
+            )));
+        }
+        names.push(name);
+    }
+
+    let headers: Vec<String> = match options.dialect {
+        CsvDialect::NameTypeDefault | CsvDialect::Auto => columns
+            .iter()
+            .zip(&names)
+            .map(|(field, name)| {
+                let name = escape_header_part(name, delimiter);
+                let type_name = field.field_type.csv_name();
+                let default = escape_header_part(&format_default(field, options), delimiter);
+                if field.mask != field.field_type.default_mask() || field.shift != 0 {
+                    format!(
+                        "{name}{d}{type_name}{d}{default}{d}0x{:08X}{d}{}",
+                        field.mask,
+                        field.shift,
+                        d = delimiter
+                    )
+                } else {
+                    format!("{}{}{}{}{}", name, delimiter, type_name, delimiter, default)
+                }
+            })
+            .collect(),
+        CsvDialect::NameType => columns
+            .iter()
+            .zip(&names)
+            .map(|(field, name)| {
+                format!("{}{}{}", escape_header_part(name, delimiter), delimiter, field.field_type.csv_name())
+            })
+            .collect(),
+        CsvDialect::NameParenType => columns
+            .iter()
+            .zip(&names)
+            .map(|(field, name)| format!("{}({})", name, field.field_type.csv_name()))
+            .collect(),
+        CsvDialect::TwoRowHeader => {
+            csv_writer.write_record(&names)?;
+            columns.iter().map(|field| field.field_type.csv_name().to_string()).collect()
+        }
+    };
 
     csv_writer.write_record(&headers)?;
 
     // Write entries
     for entry in jmap.entries() {
-        let values: Vec<String> = jmap
-            .fields()
-            .map(|field| {
-                entry
-                    .get_by_hash(field.hash)
-                    .map(|v| v.to_string())
+        let values: Vec<String> = entry
+            .iter_ordered(columns.iter().copied())
+            .map(|(field, value)| {
+                value
+                    .map(|v| format_field_value(v, field.field_type, options))
                     .unwrap_or_default()
             })
             .collect();
@@ -161,24 +1124,163 @@ pub fn to_csv<H: HashTable, P: AsRef<Path>>(jmap: &JMapInfo<H>, path: P, header_
     Ok(())
 }
 
-fn parse_field_value(s: &str, field_type: FieldType) -> Result<FieldValue> {
+/// Sort the fields of a JMapInfo in the order of the exported columns
+fn ordered_fields<'a, H: HashTable>(jmap: &'a JMapInfo<H>, order: &ColumnOrder) -> Result<Vec<&'a Field>> {
+    let mut fields: Vec<&Field> = jmap.fields().collect();
+    match order {
+        ColumnOrder::FieldOrder => {}
+        ColumnOrder::ByOffset => fields.sort_by_key(|field| field.offset),
+        ColumnOrder::Alphabetical => {
+            fields.sort_by_cached_key(|field| match jmap.hash_table().find_opt(field.hash) {
+                Some(name) => (false, name.into_owned(), field.hash),
+                None => (true, String::new(), field.hash),
+            })
+        }
+        ColumnOrder::Explicit(names) => {
+            let mut ordered = Vec::with_capacity(fields.len());
+            for name in names {
+                let hash = parse_hash_name(name).unwrap_or_else(|| jmap.hash_table().calc(name));
+                let field = jmap
+                    .get_field_by_hash(hash)
+                    .ok_or_else(|| JMapError::FieldNotFound(name.clone()))?;
+                if !ordered.iter().any(|f: &&Field| f.hash == field.hash) {
+                    ordered.push(field);
+                }
+            }
+            fields.retain(|field| !ordered.iter().any(|f| f.hash == field.hash));
+            ordered.append(&mut fields);
+            fields = ordered;
+        }
+    }
+    Ok(fields)
+}
+
+fn parse_field_value(s: &str, field_type: FieldType, options: &CsvOptions) -> Result<FieldValue> {
+    if options.parse_bools && field_type.is_integer() {
+        if s.eq_ignore_ascii_case("true") {
+            return Ok(FieldValue::Int(1));
+        }
+        if s.eq_ignore_ascii_case("false") {
+            return Ok(FieldValue::Int(0));
+        }
+    }
+
+    let text = FieldValue::String(s.to_string());
     match field_type {
-        FieldType::Long | FieldType::UnsignedLong | FieldType::Short | FieldType::Char => {
-            let v: i32 = s.parse().map_err(|_| {
-                JMapError::CsvError(format!("Cannot parse '{}' as integer", s))
-            })?;
-            Ok(FieldValue::Int(v))
+        FieldType::UnsignedLong => {
+            // Accept the whole u32 range as well as negative values written by older exports
+            let v = parse_int_literal(s)
+                .and_then(|v| u32::try_from(v).ok().or_else(|| i32::try_from(v).ok().map(|v| v as u32)))
+                .ok_or_else(|| JMapError::CsvError(format!("Cannot parse '{}' as unsigned integer", s)))?;
+            Ok(FieldValue::UInt(v))
+        }
+        FieldType::Long | FieldType::Short | FieldType::Char => {
+            let v = parse_int_literal(s)
+                .and_then(|v| i32::try_from(v).ok().or_else(|| hex_bit_pattern(s, v, field_type)))
+                .ok_or_else(|| JMapError::CsvError(format!("Cannot parse '{}' as integer", s)))?;
+            let value = FieldValue::Int(v);
+            let in_range = match field_type {
+                FieldType::Short => value.to_i16().map(drop),
+                FieldType::Char => value.to_i8().map(drop),
+                _ => Ok(()),
+            };
+            in_range.map_err(|err| JMapError::CsvError(format!("Cannot parse '{}' as {}: {}", s, field_type, err)))?;
+            Ok(value)
         }
         FieldType::Float => {
-            let v: f32 = s.parse().map_err(|_| {
-                JMapError::CsvError(format!("Cannot parse '{}' as float", s))
-            })?;
+            let trimmed = s.trim();
+            let bits = trimmed.strip_prefix("0x").or_else(|| trimmed.strip_prefix("0X"));
+            let v = match bits {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().map(f32::from_bits),
+                None => text.to_f32().ok(),
+            }
+            .ok_or_else(|| JMapError::CsvError(format!("Cannot parse '{}' as float", s)))?;
             Ok(FieldValue::Float(v))
         }
-        FieldType::String | FieldType::StringOffset => Ok(FieldValue::String(s.to_string())),
+        FieldType::String | FieldType::StringOffset => Ok(text),
     }
 }
 
+/// Reinterpret a hex literal outside the `i32` range as the bit pattern of a field, such as `0xFFFFFFFF` for -1
+fn hex_bit_pattern(s: &str, value: i64, field_type: FieldType) -> Option<i32> {
+    let unsigned = s.trim().trim_start_matches('+');
+    if !(unsigned.starts_with("0x") || unsigned.starts_with("0X")) {
+        return None;
+    }
+    match field_type {
+        FieldType::Long => u32::try_from(value).ok().map(|v| v as i32),
+        _ => None,
+    }
+}
+
+/// Format a value for a CSV cell, writing `UnsignedLong` values in the unsigned range
+fn format_field_value(value: &FieldValue, field_type: FieldType, options: &CsvOptions) -> String {
+    match (field_type, value) {
+        (FieldType::UnsignedLong, FieldValue::Int(v)) => (*v as u32).to_string(),
+        (_, FieldValue::Float(v)) if options.float_format == FloatFormat::HexBits => format!("0x{:08X}", v.to_bits()),
+        (_, FieldValue::Float(_)) => match options.float_format {
+            FloatFormat::Fixed(decimals) => value.format_float(Some(decimals)),
+            _ => value.format_float(None),
+        },
+        _ => value.to_string(),
+    }
+}
+
+/// Split a header cell on the delimiter, a backslash escaping the next character
+fn split_header(desc: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = desc.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    parts.last_mut().expect("at least one part").push(escaped);
+                }
+            }
+            c if c == delimiter => parts.push(String::new()),
+            c => parts.last_mut().expect("at least one part").push(c),
+        }
+    }
+    parts
+}
+
+/// Escape the delimiter and backslashes in a part of a header cell, the reverse of `split_header`
+fn escape_header_part(part: &str, delimiter: char) -> String {
+    let mut escaped = String::with_capacity(part.len());
+    for c in part.chars() {
+        if c == '\\' || c == delimiter {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Parse the hex mask of a five-part header column, with or without `0x`
+fn parse_mask(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Parse the default value of a header column, empty cells of the column get this value
+///
+/// For string columns `0` is the placeholder written for an empty default, so it reads as an empty string
+fn parse_default(s: &str, field_type: FieldType, options: &CsvOptions) -> Result<FieldValue> {
+    let is_string = matches!(field_type, FieldType::String | FieldType::StringOffset);
+    if s.is_empty() || (is_string && s == "0") {
+        return Ok(FieldValue::default_for(field_type));
+    }
+    parse_field_value(s, field_type, options)
+}
+
+/// Format the default value of a field for the header, the reverse of `parse_default`
+fn format_default(field: &Field, options: &CsvOptions) -> String {
+    if field.default == FieldValue::default_for(field.field_type) {
+        return default_csv_value(field.field_type).to_string();
+    }
+    format_field_value(&field.default, field.field_type, options)
+}
+
 fn default_csv_value(field_type: FieldType) -> &'static str {
     match field_type {
         FieldType::Long | FieldType::UnsignedLong | FieldType::Short | FieldType::Char => "0",
@@ -186,3 +1288,636 @@ fn default_csv_value(field_type: FieldType) -> &'static str {
         FieldType::String | FieldType::StringOffset => "0",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{calc_hash, smg_hash_table};
+    use crate::strictness::Level;
+
+    #[test]
+    fn test_parse_bools() {
+        let options = CsvOptions {
+            parse_bools: true,
+            ..CsvOptions::default()
+        };
+        assert_eq!(parse_field_value("true", FieldType::Char, &options).unwrap(), FieldValue::Int(1));
+        assert_eq!(parse_field_value("FALSE", FieldType::Short, &options).unwrap(), FieldValue::Int(0));
+        assert!(parse_field_value("true", FieldType::Char, &CsvOptions::default()).is_err());
+        assert_eq!(
+            parse_field_value("true", FieldType::StringOffset, &options).unwrap(),
+            FieldValue::from("true")
+        );
+    }
+
+    #[test]
+    fn test_unsigned_round_trip() {
+        let path = std::env::temp_dir().join("bcsv_jmap_test_unsigned.csv");
+
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Switch", FieldType::UnsignedLong, FieldValue::Int(0)).unwrap();
+        jmap.create_entry().set(&smg_hash_table(), "Switch", FieldValue::Int(-1));
+
+        to_csv(&jmap, &path, None).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("4294967295"));
+
+        let read = from_csv(smg_hash_table(), &path, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.entries()[0].get(read.hash_table(), "Switch"), Some(&FieldValue::UInt(u32::MAX)));
+        assert_eq!(read.entries()[0].get_uint(read.hash_table(), "Switch"), Some(u32::MAX));
+    }
+
+    #[test]
+    fn test_no_lookup_round_trip() {
+        use crate::hash::no_lookup;
+        use crate::io::{from_buffer, to_buffer, IoOptions};
+
+        let path = std::env::temp_dir().join("bcsv_jmap_test_no_lookup.csv");
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_entry().set(&smg_hash_table(), "ScenarioNo", FieldValue::Int(3));
+        jmap.recalculate_offsets();
+        let data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+
+        let read = from_buffer(no_lookup(), &data, &IoOptions::default()).unwrap();
+        to_csv(&read, &path, None).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().contains("[3666C077]:String"));
+
+        let back = from_csv(no_lookup(), &path, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let hashes: Vec<u32> = back.field_hashes().copied().collect();
+        assert_eq!(hashes, read.field_hashes().copied().collect::<Vec<_>>());
+        assert_eq!(back.entries()[0].get_by_hash(0xED08B591), Some(&FieldValue::Int(3)));
+        assert_eq!(to_buffer(&back, &IoOptions::default()).unwrap(), data);
+    }
+
+    #[test]
+    fn test_import_collision() {
+        let path = std::env::temp_dir().join("bcsv_jmap_test_collision.csv");
+        std::fs::write(&path, "PosBB:Int:0\n1\n").unwrap();
+
+        let mut table = smg_hash_table();
+        table.add("PosAa");
+        let result = from_csv(table, &path, None);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(JMapError::HashCollision { .. })));
+    }
+
+    #[test]
+    fn test_header_default() {
+        let path = std::env::temp_dir().join("bcsv_jmap_test_default.csv");
+        std::fs::write(&path, "ScenarioNo:Int:-1,ZoneName:String:0\n,\n2,Zone\n").unwrap();
+
+        let jmap = from_csv(smg_hash_table(), &path, None).unwrap();
+        let field = jmap.get_field("ScenarioNo").unwrap();
+        assert_eq!(field.default, FieldValue::Int(-1));
+        assert_eq!(jmap.entries()[0].get_int(jmap.hash_table(), "ScenarioNo"), Some(-1));
+        assert_eq!(jmap.entries()[0].get_string(jmap.hash_table(), "ZoneName"), Some(""));
+        assert_eq!(jmap.entries()[1].get_int(jmap.hash_table(), "ScenarioNo"), Some(2));
+
+        to_csv(&jmap, &path, None).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(text.starts_with("ScenarioNo:Int:-1,ZoneName:String:0\n"));
+    }
+
+    #[test]
+    fn test_tsv_round_trip() {
+        let path = std::env::temp_dir().join("bcsv_jmap_test_tsv.tsv");
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        let entry = jmap.create_entry();
+        entry.set(&smg_hash_table(), "ZoneName", FieldValue::from("Red, Blue, Green"));
+        entry.set(&smg_hash_table(), "ScenarioNo", FieldValue::Int(2));
+
+        to_csv_with_options(&jmap, &path, &CsvOptions::tsv()).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains("Red, Blue, Green\t2"));
+
+        let read = from_csv_with_options(smg_hash_table(), &path, &CsvOptions::tsv()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.entries()[0].get_string(read.hash_table(), "ZoneName"), Some("Red, Blue, Green"));
+        assert_eq!(read.entries()[0].get_int(read.hash_table(), "ScenarioNo"), Some(2));
+    }
+
+    #[test]
+    fn test_in_memory_round_trip() {
+        let text = "ScenarioNo:Int:0,ZoneName:String:0\n1,\"Red, Blue\"\n2,Zone\n";
+        let jmap = from_csv_str(smg_hash_table(), text, &CsvOptions::default()).unwrap();
+        assert_eq!(jmap.len(), 2);
+        assert_eq!(jmap.entries()[0].get_string(jmap.hash_table(), "ZoneName"), Some("Red, Blue"));
+        assert_eq!(to_csv_string(&jmap, &CsvOptions::default()).unwrap(), text);
+
+        let mut data = Vec::new();
+        to_csv_writer(&jmap, &mut data, &CsvOptions::tsv()).unwrap();
+        let read = from_csv_reader(smg_hash_table(), data.as_slice(), &CsvOptions::tsv()).unwrap();
+        assert_eq!(read.entries(), jmap.entries());
+    }
+
+    #[test]
+    fn test_mask_shift_round_trip() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        let field = Field::with_mask_shift(0, FieldType::Long, 0x0000FF00, 8);
+        jmap.add_field("Flags", field).unwrap();
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_entry().set(&smg_hash_table(), "Flags", FieldValue::Int(3));
+
+        let text = to_csv_string(&jmap, &CsvOptions::default()).unwrap();
+        assert!(text.starts_with("Flags:Int:0:0x0000FF00:8,ScenarioNo:Int:0\n"));
+
+        let read = from_csv_str(smg_hash_table(), &text, &CsvOptions::default()).unwrap();
+        let field = read.get_field("Flags").unwrap();
+        assert_eq!((field.mask, field.shift), (0x0000FF00, 8));
+        let original = jmap.get_field("Flags").unwrap();
+        assert_eq!((field.hash, field.field_type, &field.default), (original.hash, original.field_type, &original.default));
+        assert_eq!(read.entries()[0].get_int(read.hash_table(), "Flags"), Some(3));
+
+        assert!(from_csv_str(smg_hash_table(), "Flags:Char:0:0xFFFF:0\n", &CsvOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_hash_annotated_headers() {
+        let options = CsvOptions {
+            include_hashes: true,
+            ..CsvOptions::default()
+        };
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.fields_map_mut().insert(0x12345678, Field::new(0x12345678, FieldType::Long));
+        let text = to_csv_string(&jmap, &options).unwrap();
+        assert!(text.starts_with("ZoneName[3666C077]:String:0,[12345678]:Int:0\n"));
+
+        let (read, report) = from_csv_reader_with_report(smg_hash_table(), text.as_bytes(), &options).unwrap();
+        assert!(report.warnings.is_empty());
+        assert_eq!(read.field_name(0x3666C077), "ZoneName");
+
+        let tampered = text.replace("ZoneName[", "ZoneNam[");
+        let (read, report) = from_csv_reader_with_report(smg_hash_table(), tampered.as_bytes(), &options).unwrap();
+        assert!(read.contains_field_hash(0x3666C077));
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!((report.warnings[0].row, report.warnings[0].column), (Some(1), Some(1)));
+        assert!(report.warnings[0].to_string().contains("'ZoneNam' hashes to"));
+    }
+
+    #[test]
+    fn test_integer_literals() {
+        let options = CsvOptions::default();
+        let parse = |s, field_type| parse_field_value(s, field_type, &options).unwrap();
+        assert_eq!(parse("0xFFFFFFFF", FieldType::Long), FieldValue::Int(-1));
+        assert_eq!(parse("0xFFFFFFFF", FieldType::UnsignedLong), FieldValue::UInt(u32::MAX));
+        assert_eq!(parse("4294967295", FieldType::UnsignedLong), FieldValue::UInt(u32::MAX));
+        assert_eq!(parse("-1", FieldType::UnsignedLong), FieldValue::UInt(u32::MAX));
+        assert_eq!(parse("-0x10", FieldType::Long), FieldValue::Int(-16));
+        assert_eq!(parse("+5", FieldType::Short), FieldValue::Int(5));
+        assert_eq!(parse("0X7f", FieldType::Char), FieldValue::Int(0x7F));
+        assert_eq!(parse("-32768", FieldType::Short), FieldValue::Int(-32768));
+        assert_eq!(parse("-128", FieldType::Char), FieldValue::Int(-128));
+        let out_of_range = [("65535", FieldType::Short), ("0xFFFF", FieldType::Short)];
+        for (s, field_type) in out_of_range.into_iter().chain([("255", FieldType::Char), ("0xFF", FieldType::Char)]) {
+            assert!(parse_field_value(s, field_type, &options).is_err());
+        }
+        assert!(parse_field_value("4294967295", FieldType::Long, &options).is_err());
+        assert!(parse_field_value("0x100000000", FieldType::UnsignedLong, &options).is_err());
+
+        let text = "ScenarioNo:Int:0,ZoneName:String:0\n1,A\nx,B\n";
+        let err = from_csv_str(smg_hash_table(), text, &options).unwrap_err();
+        assert_eq!(err.to_string(), "CSV error: Row 3, column 'ScenarioNo': Cannot parse 'x' as integer");
+    }
+
+    #[test]
+    fn test_float_formats() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("pos_x", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+        let values = [f32::from_bits(0x7FC00001), f32::from_bits(1), f32::INFINITY, f32::NEG_INFINITY, 1.5];
+        for v in values {
+            jmap.create_entry().set(&smg_hash_table(), "pos_x", FieldValue::Float(v));
+        }
+        let read_back = |format| {
+            let options = CsvOptions {
+                float_format: format,
+                ..CsvOptions::default()
+            };
+            let text = to_csv_string(&jmap, &options).unwrap();
+            let read = from_csv_str(smg_hash_table(), &text, &CsvOptions::default()).unwrap();
+            let floats: Vec<f32> = read.entries().iter().map(|e| e.get_float(read.hash_table(), "pos_x").unwrap()).collect();
+            (text, floats)
+        };
+
+        let (text, floats) = read_back(FloatFormat::HexBits);
+        assert!(text.contains("0x7FC00001\n0x00000001\n0x7F800000\n0xFF800000\n0x3FC00000"));
+        let bits = |v: &[f32]| v.iter().map(|f| f.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits(&floats), bits(&values));
+
+        let (text, floats) = read_back(FloatFormat::Shortest);
+        assert!(text.contains("NaN\n") && text.contains("-inf\n"));
+        assert!(floats[0].is_nan());
+        assert_eq!(floats[1..], values[1..]);
+
+        let (text, floats) = read_back(FloatFormat::Fixed(2));
+        assert!(text.ends_with("1.50\n"));
+        assert_eq!(floats[1], 0.0);
+        assert_eq!(floats[2], f32::INFINITY);
+    }
+
+    #[test]
+    fn test_row_strictness() {
+        let text = "ScenarioNo:Int:-1,ZoneName:String:0\n1\n2,A,extra\nx,B\n3,C\n";
+
+        let err = from_csv_str(smg_hash_table(), text, &CsvOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "CSV error: Row 2: expected 2 cells, got 1");
+        let err = from_csv_str(smg_hash_table(), "ScenarioNo:Int:0\n1,2\n", &CsvOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "CSV error: Row 2: expected 1 cells, got 2");
+
+        let options = CsvOptions {
+            strictness: Strictness::permissive(),
+            ..CsvOptions::default()
+        };
+        let (jmap, report) = from_csv_reader_with_report(smg_hash_table(), text.as_bytes(), &options).unwrap();
+        assert_eq!(jmap.len(), 4);
+        assert_eq!(jmap.entries()[0].get_string(jmap.hash_table(), "ZoneName"), Some(""));
+        assert_eq!(jmap.entries()[1].get_string(jmap.hash_table(), "ZoneName"), Some("A"));
+        assert_eq!(jmap.entries()[2].get_int(jmap.hash_table(), "ScenarioNo"), Some(-1));
+        assert_eq!(jmap.entries()[3].get_int(jmap.hash_table(), "ScenarioNo"), Some(3));
+
+        let locations: Vec<_> = report.warnings.iter().map(|w| (w.row, w.column)).collect();
+        assert_eq!(locations, [(Some(2), None), (Some(3), None), (Some(4), Some(1))]);
+        assert_eq!(report.warnings[1].to_string(), "row 3: expected 2 cells, got 3");
+    }
+
+    #[test]
+    fn test_duplicate_columns() {
+        let err = from_csv_str(smg_hash_table(), "ZoneName:String:0,ZoneName:String:0\nA,B\n", &CsvOptions::default());
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "Field already exists: 'ZoneName' in column 2 is the same field as 'ZoneName' in column 1"
+        );
+
+        let text = "ZoneName:String:0,ScenarioNo:Int:0,[3666C077]:String:0\nA,1,B\n";
+        assert!(matches!(
+            from_csv_str(smg_hash_table(), text, &CsvOptions::default()),
+            Err(JMapError::FieldAlreadyExists(_))
+        ));
+
+        let options = CsvOptions {
+            strictness: Strictness {
+                structure: Level::Warn,
+                ..Strictness::strict()
+            },
+            ..CsvOptions::default()
+        };
+        let (jmap, report) = from_csv_reader_with_report(smg_hash_table(), text.as_bytes(), &options).unwrap();
+        assert_eq!(jmap.num_fields(), 2);
+        assert_eq!(jmap.entries()[0].get_string(jmap.hash_table(), "ZoneName"), Some("B"));
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].column, Some(3));
+    }
+
+    #[test]
+    fn test_escaped_header_names() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Odd:Name\\Here", FieldType::StringOffset, FieldValue::from("a:b")).unwrap();
+        jmap.create_entry();
+
+        let text = to_csv_string(&jmap, &CsvOptions::default()).unwrap();
+        assert!(text.starts_with("Odd\\:Name\\\\Here:String:a\\:b\n"));
+        let read = from_csv_str(smg_hash_table(), &text, &CsvOptions::default()).unwrap();
+        let field = read.get_field("Odd:Name\\Here").unwrap();
+        assert_eq!(field.default, FieldValue::from("a:b"));
+        assert_eq!(read.entries()[0].get_string(read.hash_table(), "Odd:Name\\Here"), Some("a:b"));
+
+        let options = CsvOptions {
+            hash_headers: true,
+            ..CsvOptions::default()
+        };
+        let hash = calc_hash("Odd:Name\\Here");
+        let text = to_csv_string(&jmap, &options).unwrap();
+        assert!(text.starts_with(&format!("[{:08X}]:String:", hash)));
+        assert!(from_csv_str(smg_hash_table(), &text, &options).unwrap().contains_field_hash(hash));
+
+        let err = from_csv_str(smg_hash_table(), "ScenarioNo:Int:0,Zone:Name:String:0\n", &CsvOptions::default());
+        assert!(err.unwrap_err().to_string().contains("Column 2: expected 3 parts"));
+    }
+
+    #[test]
+    fn test_column_order() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.fields_map_mut().insert(0x12345678, Field::new(0x12345678, FieldType::Char));
+        jmap.create_entry().set(&smg_hash_table(), "ScenarioNo", FieldValue::Int(7));
+        jmap.recalculate_offsets();
+
+        let header = |order| {
+            let options = CsvOptions {
+                column_order: order,
+                ..CsvOptions::default()
+            };
+            let text = to_csv_string(&jmap, &options).unwrap();
+            text.lines().next().unwrap().to_string()
+        };
+        assert_eq!(header(ColumnOrder::FieldOrder), "ZoneName:String:0,ScenarioNo:Int:0,[12345678]:Char:0");
+        assert_eq!(header(ColumnOrder::ByOffset), "ScenarioNo:Int:0,[12345678]:Char:0,ZoneName:String:0");
+        assert_eq!(header(ColumnOrder::Alphabetical), "ScenarioNo:Int:0,ZoneName:String:0,[12345678]:Char:0");
+        assert_eq!(
+            header(ColumnOrder::Explicit(vec!["[12345678]".to_string(), "ZoneName".to_string()])),
+            "[12345678]:Char:0,ZoneName:String:0,ScenarioNo:Int:0"
+        );
+
+        let options = CsvOptions {
+            column_order: ColumnOrder::Explicit(vec!["ScenarioNo".to_string()]),
+            ..CsvOptions::default()
+        };
+        assert!(to_csv_string(&jmap, &options).unwrap().ends_with("\n7,,0\n"));
+        let options = CsvOptions {
+            column_order: ColumnOrder::Explicit(vec!["Missing".to_string()]),
+            ..CsvOptions::default()
+        };
+        assert!(matches!(to_csv_string(&jmap, &options), Err(JMapError::FieldNotFound(_))));
+    }
+
+    #[test]
+    fn test_merge_csv() {
+        let mut jmap = from_csv_str(
+            smg_hash_table(),
+            "ScenarioNo:Int:0,ScenarioName:String:0,PowerStarId:Int:0\n1,First,10\n2,Second,20\n",
+            &CsvOptions::default(),
+        )
+        .unwrap();
+
+        let patch = "ScenarioName:String:0,ScenarioNo:Int:0\nPremier,1\nSecond,2\nTroisieme,3\nDernier,3\n";
+        let report = merge_csv_reader_into(&mut jmap, patch.as_bytes(), "ScenarioNo", &MergeOptions::default()).unwrap();
+        assert_eq!((report.updated, report.added, report.unchanged), (2, 1, 1));
+        assert_eq!(report.duplicate_keys, ["3"]);
+
+        assert_eq!(jmap.len(), 3);
+        let ht = smg_hash_table();
+        assert_eq!(jmap.entries()[0].get_string(&ht, "ScenarioName"), Some("Premier"));
+        assert_eq!(jmap.entries()[0].get_int(&ht, "PowerStarId"), Some(10));
+        assert_eq!(jmap.entries()[2].get_string(&ht, "ScenarioName"), Some("Dernier"));
+        assert_eq!(jmap.entries()[2].get_int(&ht, "PowerStarId"), Some(0));
+
+        let patch = "ScenarioNo:Int:0,PowerStarId:Float:0.0,Comet:String:0\n1,1.5,Red\n";
+        let err = merge_csv_reader_into(&mut jmap, patch.as_bytes(), "ScenarioNo", &MergeOptions::default());
+        assert!(matches!(err, Err(JMapError::FieldNotFound(name)) if name == "Comet"));
+
+        let options = MergeOptions {
+            create_missing_columns: true,
+            ..MergeOptions::default()
+        };
+        let report = merge_csv_reader_into(&mut jmap, patch.as_bytes(), "ScenarioNo", &options).unwrap();
+        assert_eq!(report.type_conflicts, ["PowerStarId: Float vs Int"]);
+        assert_eq!(jmap.entries()[0].get_string(&ht, "Comet"), Some("Red"));
+        assert_eq!(jmap.entries()[1].get_string(&ht, "Comet"), Some(""));
+        assert_eq!(jmap.entries()[0].get_int(&ht, "PowerStarId"), Some(10));
+
+        let err = merge_csv_reader_into(&mut jmap, "Comet:String:0\nA\n".as_bytes(), "ScenarioNo", &options);
+        assert!(matches!(err, Err(JMapError::FieldNotFound(_))));
+    }
+
+    #[test]
+    fn test_bom_and_crlf() {
+        let text = "\u{feff}ScenarioNo:Int:0,ZoneName:String:0\r\n1,Galaxy\r\n2,\r\n";
+        let jmap = from_csv_str(smg_hash_table(), text, &CsvOptions::default()).unwrap();
+        let hash = calc_hash("ScenarioNo");
+        assert!(jmap.contains_field_hash(hash));
+        assert_eq!(jmap.len(), 2);
+        assert_eq!(jmap.entries()[0].get_by_hash(calc_hash("ZoneName")), Some(&FieldValue::String("Galaxy".into())));
+        assert_eq!(jmap.entries()[1].get_by_hash(calc_hash("ZoneName")), Some(&FieldValue::String(String::new())));
+
+        // A reader handing out one byte at a time still has its BOM skipped
+        struct Trickle<'a>(&'a [u8]);
+        impl Read for Trickle<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let Some((&first, rest)) = self.0.split_first() else {
+                    return Ok(0);
+                };
+                buf[0] = first;
+                self.0 = rest;
+                Ok(1)
+            }
+        }
+        let trickled = from_csv_reader(smg_hash_table(), Trickle(text.as_bytes()), &CsvOptions::default()).unwrap();
+        assert!(trickled.contains_field_hash(hash));
+
+        let options = CsvOptions {
+            write_bom: true,
+            ..CsvOptions::default()
+        };
+        let written = to_csv_string(&jmap, &options).unwrap();
+        assert!(written.starts_with("\u{feff}ScenarioNo:Int:0"));
+        let back = from_csv_str(smg_hash_table(), &written, &options).unwrap();
+        assert_eq!(back.entries(), jmap.entries());
+        assert!(!to_csv_string(&jmap, &CsvOptions::default()).unwrap().starts_with('\u{feff}'));
+    }
+
+    #[test]
+    fn test_dialects() {
+        let text = "ScenarioNo:Int:0,ZoneName:String:0,Scale:Float:0.0\n1,Galaxy,1.5\n2,Dome,0.25\n";
+        let jmap = from_csv_str(smg_hash_table(), text, &CsvOptions::default()).unwrap();
+
+        let fixtures = [
+            (CsvDialect::NameType, "ScenarioNo:Int,ZoneName:String,Scale:Float\n"),
+            (CsvDialect::NameParenType, "ScenarioNo(Int),ZoneName(String),Scale(Float)\n"),
+            (CsvDialect::TwoRowHeader, "ScenarioNo,ZoneName,Scale\nInt,String,Float\n"),
+            (CsvDialect::NameTypeDefault, "ScenarioNo:Int:0,ZoneName:String:0,Scale:Float:0.0\n"),
+        ];
+        for (dialect, header) in fixtures {
+            let options = CsvOptions {
+                dialect,
+                ..CsvOptions::default()
+            };
+            let written = to_csv_string(&jmap, &options).unwrap();
+            assert_eq!(written, format!("{}1,Galaxy,1.5\n2,Dome,0.25\n", header), "{:?}", dialect);
+
+            for dialect in [dialect, CsvDialect::Auto] {
+                let options = CsvOptions {
+                    dialect,
+                    ..CsvOptions::default()
+                };
+                let back = from_csv_str(smg_hash_table(), &written, &options).unwrap();
+                assert_eq!(back.len(), 2, "{:?}", dialect);
+                assert_eq!(back.entries(), jmap.entries(), "{:?}", dialect);
+            }
+        }
+
+        // A bit-packed field cannot be written without defaults
+        let mut packed = JMapInfo::new(smg_hash_table());
+        let mut field = Field::new(calc_hash("Flags"), FieldType::Long);
+        field.mask = 0xFF00;
+        field.shift = 8;
+        packed.add_field("Flags", field).unwrap();
+        let options = CsvOptions {
+            dialect: CsvDialect::NameType,
+            ..CsvOptions::default()
+        };
+        assert!(matches!(to_csv_string(&packed, &options), Err(JMapError::InvalidField(_))));
+
+        let options = CsvOptions {
+            dialect: CsvDialect::Auto,
+            ..CsvOptions::default()
+        };
+        assert!(matches!(
+            from_csv_str(smg_hash_table(), "ScenarioNo,ZoneName\n1,Galaxy\n", &options),
+            Err(JMapError::InvalidCsvFieldDescriptor(_))
+        ));
+    }
+
+    #[test]
+    fn test_schema_import() {
+        let schema = Schema::new()
+            .with_field("ScenarioNo", FieldType::Long)
+            .with_field("ZoneName", FieldType::StringOffset)
+            .with_field("PowerStarId", FieldType::Short);
+        let path = std::env::temp_dir().join("bcsv_jmap_test_schema.csv");
+        std::fs::write(&path, "ZoneName,ScenarioNo:Int\nGalaxy,1\nDome,2\n").unwrap();
+
+        let options = SchemaOptions {
+            allow_missing_fields: true,
+            ..SchemaOptions::default()
+        };
+        let jmap = from_csv_with_schema(smg_hash_table(), &path, &schema, &options).unwrap();
+        let names: Vec<String> = jmap.fields().map(|field| jmap.field_name(field.hash)).collect();
+        assert_eq!(names, ["ScenarioNo", "ZoneName", "PowerStarId"]);
+        assert_eq!(jmap.len(), 2);
+        assert_eq!(jmap.entries()[1].get_by_hash(calc_hash("ScenarioNo")), Some(&FieldValue::Int(2)));
+        assert_eq!(jmap.entries()[1].get_by_hash(calc_hash("PowerStarId")), Some(&FieldValue::Int(0)));
+
+        let err = from_csv_with_schema(smg_hash_table(), &path, &schema, &SchemaOptions::default()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid CSV field descriptor: schema fields not in the CSV: PowerStarId"
+        );
+
+        std::fs::write(&path, "ZoneName,ScenarioNp\nGalaxy,1\n").unwrap();
+        let err = from_csv_with_schema(smg_hash_table(), &path, &schema, &options).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid CSV field descriptor: columns not in the schema: ScenarioNp");
+
+        std::fs::write(&path, "ZoneName,ScenarioNo:Float\nGalaxy,1\n").unwrap();
+        let err = from_csv_with_schema(smg_hash_table(), &path, &schema, &options).unwrap_err();
+        assert!(matches!(err, JMapError::InvalidCsvFieldDescriptor(_)));
+    }
+
+    #[test]
+    fn test_comments() {
+        let options = CsvOptions {
+            comment_char: Some(b'#'),
+            ..CsvOptions::default()
+        };
+        let text = "# Scenario list\nScenarioNo:Int:0,ZoneName:String:0\n1,Galaxy\n# green star run\n2,\"Dome\n# not a comment\"\n";
+        let jmap = from_csv_str(smg_hash_table(), text, &options).unwrap();
+        assert_eq!(jmap.len(), 2);
+        let zone = calc_hash("ZoneName");
+        assert_eq!(
+            jmap.entries()[1].get_by_hash(zone),
+            Some(&FieldValue::String("Dome\n# not a comment".into()))
+        );
+        assert!(from_csv_str(smg_hash_table(), text, &CsvOptions::default()).is_err());
+
+        let mut tagged = JMapInfo::new(smg_hash_table());
+        tagged.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        tagged.create_entry().set_by_hash(zone, FieldValue::String("#tag".into()));
+
+        let options = CsvOptions {
+            banner: Some(CsvBanner {
+                source: Some("scenariodata.bcsv".to_string()),
+                timestamp: false,
+                lines: vec!["Edited by hand".to_string()],
+                ..CsvBanner::default()
+            }),
+            ..options
+        };
+        let written = to_csv_string(&tagged, &options).unwrap();
+        assert_eq!(
+            written,
+            format!(
+                "# Written by {} {}\n# Source: scenariodata.bcsv\n# Edited by hand\nZoneName:String:0\n\"#tag\"\n",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        let back = from_csv_str(smg_hash_table(), &written, &options).unwrap();
+        assert_eq!(back.entries(), tagged.entries());
+
+        // The quoted cell spanning a line starting with the comment character survives the round trip
+        let written = to_csv_string(&jmap, &options).unwrap();
+        assert_eq!(from_csv_str(smg_hash_table(), &written, &options).unwrap().entries(), jmap.entries());
+    }
+
+    #[test]
+    fn test_layout_sidecar_round_trip() {
+        use crate::io::{from_buffer, read_header, to_buffer, IoOptions};
+        use crate::layout::{save_sidecar, LayoutStrategy};
+
+        let names = || {
+            let mut table = smg_hash_table();
+            for name in ["Flags", "Low", "ZoneName", "Scale", "Extra"] {
+                table.add(name);
+            }
+            table
+        };
+
+        // Fields out of type order, two of them packed in one word, and padding at the end of each entry
+        let mut jmap = JMapInfo::new(names());
+        for (name, field_type, offset, mask, shift) in [
+            ("Flags", FieldType::Long, 8, 0x0000FF00, 8),
+            ("Low", FieldType::Long, 8, 0x000000FF, 0),
+            ("ZoneName", FieldType::StringOffset, 0, 0xFFFFFFFF, 0),
+            ("Scale", FieldType::Float, 4, 0xFFFFFFFF, 0),
+        ] {
+            jmap.create_field(name, field_type, FieldValue::default_for(field_type)).unwrap();
+            let field = &mut jmap.fields_map_mut()[&calc_hash(name)];
+            (field.offset, field.mask, field.shift) = (offset, mask, shift);
+        }
+        jmap.entry_size = 0x14;
+        let table = names();
+        let entry = jmap.create_entry();
+        entry.set(&table, "Flags", FieldValue::Int(3));
+        entry.set(&table, "Low", FieldValue::Int(7));
+        entry.set(&table, "ZoneName", FieldValue::from("Galaxy"));
+        entry.set(&table, "Scale", FieldValue::Float(1.5));
+        let preserve = IoOptions {
+            layout: LayoutStrategy::Preserve,
+            ..IoOptions::default()
+        };
+        let data = to_buffer(&jmap, &preserve).unwrap();
+
+        let read = from_buffer(names(), &data, &IoOptions::default()).unwrap();
+        let csv_path = std::env::temp_dir().join("bcsv_jmap_test_sidecar.csv");
+        let layout_path = std::env::temp_dir().join("bcsv_jmap_test_sidecar.layout");
+        to_csv(&read, &csv_path, None).unwrap();
+        save_sidecar(&read, &layout_path).unwrap();
+        assert!(std::fs::read_to_string(&layout_path).unwrap().contains("0x0000FF00 8 # Flags"));
+
+        let (back, report) = from_csv_with_layout(names(), &csv_path, &layout_path, &CsvOptions::default()).unwrap();
+        assert!(report.is_empty());
+        let rebuilt = to_buffer(&back, &preserve).unwrap();
+        let field_table = read_header(&data, &preserve).unwrap().off_data as usize;
+        assert_eq!(rebuilt[..field_table], data[..field_table]);
+        assert_eq!(back.entry_size, 0x14);
+        assert_eq!(rebuilt, data);
+
+        // A column missing from the CSV file fails strict imports, a new column is appended after the entry
+        std::fs::write(&csv_path, "Flags:Int:0,Low:Int:0,ZoneName:String:0,Extra:Short:0\n3,7,Galaxy,2\n").unwrap();
+        let err = from_csv_with_layout(names(), &csv_path, &layout_path, &CsvOptions::default()).unwrap_err();
+        assert!(matches!(err, JMapError::FieldNotFound(_)), "{}", err);
+
+        let options = CsvOptions {
+            strictness: Strictness::permissive(),
+            ..CsvOptions::default()
+        };
+        let (back, report) = from_csv_with_layout(names(), &csv_path, &layout_path, &options).unwrap();
+        std::fs::remove_file(&csv_path).unwrap();
+        std::fs::remove_file(&layout_path).unwrap();
+        assert_eq!(report.warnings.len(), 2);
+        assert!(report.warnings[0].message.contains("'Scale' is in the layout but not in the table"));
+        assert!(report.warnings[1].message.contains("'Extra' is not in the layout, appending it at offset 0x14"));
+        let extra = back.get_field("Extra").unwrap();
+        assert_eq!(extra.offset, 0x14);
+        assert_eq!(back.entry_size, 0x18);
+        let names: Vec<String> = back.fields().map(|field| back.hash_table().find(field.hash)).collect();
+        assert_eq!(names, ["Flags", "Low", "ZoneName", "Extra"]);
+    }
+}
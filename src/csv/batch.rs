@@ -0,0 +1,232 @@
+//! Convert whole directory trees between BCSV and CSV
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::csv::{from_csv_with_options, to_csv_with_options, CsvOptions};
+use crate::error::{JMapError, Result};
+use crate::hash::HashTable;
+use crate::io::{from_file, to_file, IoOptions};
+
+/// Outcome of a directory conversion
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// Files written, relative to the destination root
+    pub converted: Vec<PathBuf>,
+    /// Files and directories that could not be converted or read, relative to the source root, with the error
+    pub failures: Vec<(PathBuf, JMapError)>,
+}
+
+/// Convert every `.bcsv` file under `src_root` to a `.csv` file under `dst_root`, mirroring the directory structure
+///
+/// All files share `hash_table`. A file that fails to convert is recorded in [`BatchReport::failures`]
+/// rather than aborting the walk
+///
+/// # Arguments
+/// - `src_root` - The directory to walk
+/// - `dst_root` - The directory to write CSV files to, created if needed
+/// - `hash_table` - The hash table used for every file
+/// - `io_options` - Options for reading the BCSV files
+/// - `csv_options` - Options for writing the CSV files
+///
+/// # Errors
+/// - `JMapError::IoError` if `src_root` itself cannot be read
+///
+/// # Returns
+/// The files converted and the ones that failed
+pub fn convert_dir_bcsv_to_csv<H: HashTable, P: AsRef<Path>, Q: AsRef<Path>>(
+    src_root: P,
+    dst_root: Q,
+    hash_table: &mut H,
+    io_options: &IoOptions,
+    csv_options: &CsvOptions,
+) -> Result<BatchReport> {
+    convert_dir_bcsv_to_csv_filtered(src_root, dst_root, hash_table, io_options, csv_options, |_| true)
+}
+
+/// Like [`convert_dir_bcsv_to_csv`], only converting the files for which `filter` returns true
+///
+/// `filter` is given each file's path relative to `src_root`
+pub fn convert_dir_bcsv_to_csv_filtered<H, P, Q, F>(
+    src_root: P,
+    dst_root: Q,
+    hash_table: &mut H,
+    io_options: &IoOptions,
+    csv_options: &CsvOptions,
+    filter: F,
+) -> Result<BatchReport>
+where
+    H: HashTable,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(&Path) -> bool,
+{
+    convert_dir(src_root.as_ref(), dst_root.as_ref(), "bcsv", "csv", filter, |src, dst| {
+        let jmap = from_file(&mut *hash_table, src, io_options)?;
+        to_csv_with_options(&jmap, dst, csv_options)
+    })
+}
+
+/// Convert every `.csv` file under `src_root` to a `.bcsv` file under `dst_root`, mirroring the directory structure
+///
+/// All files share `hash_table`, so field names read from one CSV file resolve in the others.
+/// A file that fails to convert is recorded in [`BatchReport::failures`] rather than aborting the walk
+///
+/// # Arguments
+/// - `src_root` - The directory to walk
+/// - `dst_root` - The directory to write BCSV files to, created if needed
+/// - `hash_table` - The hash table used for every file
+/// - `io_options` - Options for writing the BCSV files
+/// - `csv_options` - Options for reading the CSV files
+///
+/// # Errors
+/// - `JMapError::IoError` if `src_root` itself cannot be read
+///
+/// # Returns
+/// The files converted and the ones that failed
+pub fn convert_dir_csv_to_bcsv<H: HashTable, P: AsRef<Path>, Q: AsRef<Path>>(
+    src_root: P,
+    dst_root: Q,
+    hash_table: &mut H,
+    io_options: &IoOptions,
+    csv_options: &CsvOptions,
+) -> Result<BatchReport> {
+    convert_dir_csv_to_bcsv_filtered(src_root, dst_root, hash_table, io_options, csv_options, |_| true)
+}
+
+/// Like [`convert_dir_csv_to_bcsv`], only converting the files for which `filter` returns true
+///
+/// `filter` is given each file's path relative to `src_root`
+pub fn convert_dir_csv_to_bcsv_filtered<H, P, Q, F>(
+    src_root: P,
+    dst_root: Q,
+    hash_table: &mut H,
+    io_options: &IoOptions,
+    csv_options: &CsvOptions,
+    filter: F,
+) -> Result<BatchReport>
+where
+    H: HashTable,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(&Path) -> bool,
+{
+    convert_dir(src_root.as_ref(), dst_root.as_ref(), "csv", "bcsv", filter, |src, dst| {
+        let jmap = from_csv_with_options(&mut *hash_table, src, csv_options)?;
+        to_file(&jmap, dst, io_options)
+    })
+}
+
+fn convert_dir(
+    src_root: &Path,
+    dst_root: &Path,
+    from_ext: &str,
+    to_ext: &str,
+    mut filter: impl FnMut(&Path) -> bool,
+    mut convert: impl FnMut(&Path, &Path) -> Result<()>,
+) -> Result<BatchReport> {
+    let mut report = BatchReport::default();
+    let mut pending = vec![PathBuf::new()];
+    let mut is_root = true;
+
+    while let Some(dir) = pending.pop() {
+        let mut paths = match list_dir(&src_root.join(&dir)) {
+            Ok(paths) => paths,
+            Err(err) if !is_root => {
+                report.failures.push((dir, err.into()));
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        is_root = false;
+
+        paths.sort_unstable();
+        let mut subdirs = Vec::new();
+        for name in paths {
+            let relative = dir.join(name);
+            let src = src_root.join(&relative);
+            if src.is_dir() {
+                subdirs.push(relative);
+                continue;
+            }
+            if !src.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(from_ext)) || !filter(&relative) {
+                continue;
+            }
+
+            let output = relative.with_extension(to_ext);
+            let dst = dst_root.join(&output);
+            let result = match dst.parent() {
+                Some(parent) => fs::create_dir_all(parent).map_err(JMapError::from),
+                None => Ok(()),
+            };
+            match result.and_then(|()| convert(&src, &dst)) {
+                Ok(()) => report.converted.push(output),
+                Err(err) => report.failures.push((relative, err)),
+            }
+        }
+
+        // Reversed so that popping from the stack walks subdirectories in name order
+        pending.extend(subdirs.into_iter().rev());
+    }
+
+    Ok(report)
+}
+
+fn list_dir(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fs::read_dir(dir)?.map(|entry| entry.map(|entry| PathBuf::from(entry.file_name()))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{FieldType, FieldValue};
+    use crate::hash::{calc_hash, smg_hash_table, FileHashTable, HashAlgorithm};
+    use crate::io::to_buffer;
+    use crate::jmap::JMapInfo;
+
+    #[test]
+    fn test_convert_dir() {
+        let root = std::env::temp_dir().join("bcsv_jmap_test_batch");
+        let _ = fs::remove_dir_all(&root);
+        let bcsv = root.join("bcsv");
+        fs::create_dir_all(bcsv.join("StageData/Zone")).unwrap();
+
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_entry().set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(3));
+        let data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+        fs::write(bcsv.join("StageData/ScenarioData.bcsv"), &data).unwrap();
+        fs::write(bcsv.join("StageData/Zone/ZoneList.bcsv"), &data).unwrap();
+        fs::write(bcsv.join("StageData/Zone/Broken.bcsv"), b"not a bcsv").unwrap();
+        fs::write(bcsv.join("StageData/readme.txt"), b"ignored").unwrap();
+
+        let io = IoOptions::default();
+        let csv = CsvOptions::default();
+        let mut ht = smg_hash_table();
+        ht.add("ScenarioNo");
+        let report = convert_dir_bcsv_to_csv(&bcsv, root.join("csv"), &mut ht, &io, &csv).unwrap();
+        assert_eq!(
+            report.converted,
+            [PathBuf::from("StageData/ScenarioData.csv"), PathBuf::from("StageData/Zone/ZoneList.csv")]
+        );
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].0, PathBuf::from("StageData/Zone/Broken.bcsv"));
+
+        let text = fs::read_to_string(root.join("csv/StageData/Zone/ZoneList.csv")).unwrap();
+        assert!(text.starts_with("ScenarioNo:Int:0"));
+
+        // An empty table learns the names from the CSV headers
+        let mut ht = FileHashTable::new(HashAlgorithm::SMG);
+        let report = convert_dir_csv_to_bcsv_filtered(root.join("csv"), root.join("out"), &mut ht, &io, &csv, |path| {
+            path.starts_with("StageData/Zone")
+        })
+        .unwrap();
+        assert_eq!(report.converted, [PathBuf::from("StageData/Zone/ZoneList.bcsv")]);
+        assert!(report.failures.is_empty());
+        assert_eq!(fs::read(root.join("out/StageData/Zone/ZoneList.bcsv")).unwrap(), data);
+        assert!(!root.join("out/StageData/ScenarioData.bcsv").exists());
+        assert_eq!(ht.find(calc_hash("ScenarioNo")), "ScenarioNo");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
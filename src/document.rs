@@ -0,0 +1,279 @@
+//! A self-contained, serializable form of a JMapInfo
+
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::entry::Entry;
+use crate::error::{JMapError, Result};
+use crate::field::{Field, FieldType, FieldValue};
+use crate::hash::{parse_hash_name, HashTable};
+use crate::jmap::JMapInfo;
+
+/// A field of a [`JMapDocument`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentField {
+    /// Field name, or `[XXXXXXXX]` for a field known only by its hash
+    pub name: String,
+    /// Data type of the field
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    /// Default value for new entries and missing values
+    pub default: FieldValue,
+    /// Bitmask for the field value, `None` for the type's default mask
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mask: Option<u32>,
+    /// Bit shift amount, `None` for no shift
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shift: Option<u8>,
+}
+
+/// A JMapInfo without its hash table, with field names written out
+///
+/// Entries map field names, as written in [`DocumentField::name`], to values.
+/// Values missing from an entry get the field's default when the document is turned back into a JMapInfo
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct JMapDocument {
+    /// The fields, in field order
+    pub fields: Vec<DocumentField>,
+    /// The entries, each holding values in field order
+    pub entries: Vec<IndexMap<String, FieldValue>>,
+}
+
+impl<H: HashTable> JMapInfo<H> {
+    /// Convert to a document that can be serialized without the hash table
+    ///
+    /// # Returns
+    /// The fields and entries, with field names resolved through the hash table
+    pub fn to_document(&self) -> JMapDocument {
+        let names: Vec<(u32, String)> = self
+            .fields()
+            .map(|field| (field.hash, self.field_name(field.hash)))
+            .collect();
+
+        let fields = self
+            .fields()
+            .zip(&names)
+            .map(|(field, (_, name))| DocumentField {
+                name: name.clone(),
+                field_type: field.field_type,
+                default: field.default.clone(),
+                mask: (field.mask != field.field_type.default_mask()).then_some(field.mask),
+                shift: (field.shift != 0).then_some(field.shift),
+            })
+            .collect();
+
+        let entries = self
+            .entries()
+            .iter()
+            .map(|entry| {
+                names
+                    .iter()
+                    .filter_map(|(hash, name)| entry.get_by_hash(*hash).map(|value| (name.clone(), value.clone())))
+                    .collect()
+            })
+            .collect();
+
+        JMapDocument { fields, entries }
+    }
+
+    /// Build a JMapInfo from a document
+    ///
+    /// Names are added to the hash table, `[XXXXXXXX]` names are used as hashes. Integers are accepted
+    /// for float fields, since some formats write `1.0` as `1`, as well as `0x` strings holding the bit pattern,
+    /// which is how JSON writes floats that are not finite
+    ///
+    /// # Arguments
+    /// - `hash_table` - The hash table to use for field name lookups
+    /// - `document` - The document to read
+    ///
+    /// # Errors
+    /// - `JMapError::FieldAlreadyExists` if two fields have the same hash
+    /// - `JMapError::InvalidField` if a default value does not match its field type, or a mask or shift is invalid
+    /// - `JMapError::InEntry` if a value does not match its field type, or names an unknown field
+    ///
+    /// # Returns
+    /// A JMapInfo with the fields and entries of the document
+    pub fn from_document(hash_table: H, document: JMapDocument) -> Result<Self> {
+        let mut jmap = JMapInfo::new(hash_table);
+        let mut columns = DocumentColumns::default();
+        columns.add_fields(&mut jmap, document.fields)?;
+
+        for (index, values) in document.entries.into_iter().enumerate() {
+            let entry = columns.entry(jmap.new_entry(), values).map_err(|source| JMapError::InEntry {
+                index,
+                source: Box::new(source),
+            })?;
+            jmap.entries_vec_mut().push(entry);
+        }
+
+        Ok(jmap)
+    }
+}
+
+/// The fields of a document being read, by the names used in its entries
+#[derive(Debug, Default)]
+pub(crate) struct DocumentColumns {
+    columns: HashMap<String, (u32, FieldType)>,
+    defaults: Vec<(u32, FieldValue)>,
+}
+
+impl DocumentColumns {
+    /// Get the hash and type of a field by its document name
+    pub(crate) fn get(&self, name: &str) -> Option<(u32, FieldType)> {
+        self.columns.get(name).copied()
+    }
+
+    /// Add the fields of a document to `jmap`, filling existing entries with their defaults
+    ///
+    /// # Errors
+    /// - `JMapError::FieldAlreadyExists` if two fields have the same hash
+    /// - `JMapError::InvalidField` if a default value does not match its field type, or a mask or shift is invalid
+    pub(crate) fn add_fields<H: HashTable>(
+        &mut self,
+        jmap: &mut JMapInfo<H>,
+        fields: impl IntoIterator<Item = DocumentField>,
+    ) -> Result<()> {
+        for doc_field in fields {
+            let hash = match parse_hash_name(&doc_field.name) {
+                Some(hash) => hash,
+                None => jmap.hash_table_mut().try_add(&doc_field.name)?,
+            };
+            if jmap.contains_field_hash(hash) {
+                return Err(JMapError::FieldAlreadyExists(doc_field.name));
+            }
+
+            let field_type = doc_field.field_type;
+            let default = coerce_value(doc_field.default, field_type).map_err(|value| {
+                JMapError::InvalidField(format!(
+                    "{}: default {} '{}' is not a valid {}",
+                    doc_field.name,
+                    value.type_name(),
+                    value,
+                    field_type.csv_name()
+                ))
+            })?;
+            let mut field = Field::with_default(hash, field_type, default.clone());
+            field.mask = doc_field.mask.unwrap_or(field_type.default_mask());
+            field.shift = doc_field.shift.unwrap_or(0);
+            field.validate()?;
+
+            for entry in jmap.entries_vec_mut() {
+                entry.set_by_hash(hash, default.clone());
+            }
+            jmap.fields_map_mut().insert(hash, field);
+            self.columns.insert(doc_field.name, (hash, field_type));
+            self.defaults.push((hash, default));
+        }
+        Ok(())
+    }
+
+    /// Fill an empty entry from document values, fields without a value getting their default
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if a value names an unknown field
+    /// - `JMapError::InvalidField` if a value does not match its field type
+    pub(crate) fn entry(&self, mut entry: Entry, values: IndexMap<String, FieldValue>) -> Result<Entry> {        for (hash, default) in &self.defaults {
+            entry.set_by_hash(*hash, default.clone());
+        }
+        for (name, value) in values {
+            let (hash, field_type) = self.get(&name).ok_or_else(|| JMapError::FieldNotFound(name.clone()))?;
+            let value = coerce_value(value, field_type).map_err(|value| {
+                JMapError::InvalidField(format!(
+                    "{}: expected {}, got {} '{}'",
+                    name,
+                    field_type.csv_name(),
+                    value.type_name(),
+                    value
+                ))
+            })?;
+            entry.set_by_hash(hash, value);
+        }
+        Ok(entry)
+    }
+}
+
+/// Check a deserialized value against its field type, converting integers where the type allows it
+///
+/// # Returns
+/// The value to store, or the rejected value
+fn coerce_value(value: FieldValue, field_type: FieldType) -> std::result::Result<FieldValue, FieldValue> {
+    match (value, field_type) {
+        (value @ (FieldValue::Int(_) | FieldValue::UInt(_)), FieldType::Long | FieldType::Short | FieldType::Char) => {
+            let converted = match field_type {
+                FieldType::Short => value.to_i16().map(i32::from),
+                FieldType::Char => value.to_i8().map(i32::from),
+                _ => value.to_i32(),
+            };
+            converted.map(FieldValue::Int).map_err(|_| value)
+        }
+        (FieldValue::Int(v), FieldType::UnsignedLong) if v >= 0 => Ok(FieldValue::UInt(v as u32)),
+        (FieldValue::Int(v), FieldType::Float) => Ok(FieldValue::Float(v as f32)),
+        (FieldValue::UInt(v), FieldType::Float) => Ok(FieldValue::Float(v as f32)),
+        (FieldValue::String(s), FieldType::Float) => {
+            let bits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"));
+            match bits.and_then(|bits| u32::from_str_radix(bits, 16).ok()) {
+                Some(bits) => Ok(FieldValue::Float(f32::from_bits(bits))),
+                None => Err(FieldValue::String(s)),
+            }
+        }
+        (value, field_type) if value.is_compatible_with(field_type) => Ok(value),
+        (value, _) => Err(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{calc_hash, no_lookup, smg_hash_table, FileHashTable, HashAlgorithm};
+
+    #[test]
+    fn test_document_round_trip() {
+        let mut ht = FileHashTable::new(HashAlgorithm::SMG);
+        ht.add("ScenarioNo");
+        let mut jmap = JMapInfo::new(ht);
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(1.0)).unwrap();
+        jmap.create_field("Id", FieldType::UnsignedLong, FieldValue::UInt(0)).unwrap();
+        let mut flags = Field::new(0xDEADBEEF, FieldType::Long);
+        flags.mask = 0xFF00;
+        flags.shift = 8;
+        jmap.fields_map_mut().insert(flags.hash, flags);
+
+        let entry = jmap.create_entry();
+        entry.set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(-1));
+        entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String("Dôme \"étoilé\"".into()));
+        entry.set_by_hash(calc_hash("Id"), FieldValue::UInt(0xFFFFFFF0));
+        entry.set_by_hash(0xDEADBEEF, FieldValue::Int(3));
+        jmap.create_entry();
+
+        let json = serde_json::to_string(&jmap.to_document()).unwrap();
+        assert!(json.contains(r#"{"name":"[DEADBEEF]","type":"Long","default":0,"mask":65280,"shift":8}"#));
+        let document: JMapDocument = serde_json::from_str(&json).unwrap();
+
+        let back = JMapInfo::from_document(no_lookup(), document).unwrap();
+        assert_eq!(back.entries(), jmap.entries());
+        assert_eq!(back.get_field_by_hash(0xDEADBEEF).map(|field| (field.mask, field.shift)), Some((0xFF00, 8)));
+        assert_eq!(back.entries()[1].get_by_hash(calc_hash("Scale")), Some(&FieldValue::Float(1.0)));
+
+        // Entries alone are keyed by hash, or by name with a hash table
+        let entry = &jmap.entries()[0];
+        let by_hash = serde_json::to_string(entry).unwrap();
+        assert_eq!(serde_json::from_str::<Entry>(&by_hash).unwrap(), *entry);
+        let by_name = serde_json::to_value(entry.named(jmap.hash_table())).unwrap();
+        assert_eq!(by_name["ScenarioNo"], -1);
+        assert_eq!(by_name["[DEADBEEF]"], 3);
+
+        let mut document = jmap.to_document();
+        document.entries[1].insert("ScenarioNo".to_string(), FieldValue::String("one".into()));
+        let err = JMapInfo::from_document(smg_hash_table(), document).unwrap_err();
+        assert_eq!(err.to_string(), "Entry 1: Invalid field: ScenarioNo: expected Int, got String 'one'");
+
+        assert_eq!(coerce_value(FieldValue::UInt(200), FieldType::Short), Ok(FieldValue::Int(200)));
+        assert_eq!(coerce_value(FieldValue::Int(-32768), FieldType::Short), Ok(FieldValue::Int(-32768)));
+        assert_eq!(coerce_value(FieldValue::Int(65535), FieldType::Short), Err(FieldValue::Int(65535)));
+        assert_eq!(coerce_value(FieldValue::UInt(200), FieldType::Char), Err(FieldValue::UInt(200)));
+    }
+}
@@ -0,0 +1,189 @@
+//! Plain text dump of a table, for debugging, snapshot tests and diffing two files
+//!
+//! The dump has one `key = value` line per item, so it can be searched with `grep` and compared with `diff`:
+//!
+//! ```text
+//! entries = 2
+//! fields = 2
+//! entry_size = 0x8
+//! field[0].name = ScenarioNo
+//! field[0].hash = 0xED08B591
+//! ...
+//! entry[1].ZoneName = "Beach"
+//! ```
+//!
+//! The format is stable: lines are only ever added, after the existing ones of the same section,
+//! so snapshots taken with an older version only change when the table does
+
+use std::fmt;
+
+use crate::field::{Field, FieldValue};
+use crate::hash::HashTable;
+use crate::jmap::JMapInfo;
+
+/// Options for [`JMapInfo::dump`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpOptions {
+    /// Whether `Int` and `UInt` values are written in hexadecimal, as their 32-bit pattern
+    pub hex_integers: bool,
+    /// Maximum number of entries written, the others being counted in a final line
+    pub max_entries: Option<usize>,
+    /// Whether fields whose name is unknown to the hash table are written, named `[XXXXXXXX]`
+    pub include_unresolved: bool,
+}
+
+impl Default for DumpOptions {
+    fn default() -> Self {
+        Self {
+            hex_integers: false,
+            max_entries: None,
+            include_unresolved: true,
+        }
+    }
+}
+
+impl<H: HashTable> JMapInfo<H> {
+    /// Write the header stats, the fields and the entries of this JMapInfo, one item per line
+    ///
+    /// Fields and values are written in field order and entries in entry order, so the output only depends
+    /// on the content of the table. Strings are quoted and escaped, so every value fits on one line
+    ///
+    /// # Arguments
+    /// - `out` - Where the dump is written
+    /// - `options` - How values are written and which ones
+    ///
+    /// # Errors
+    /// Any error returned by `out`
+    pub fn dump<W: fmt::Write>(&self, mut out: W, options: &DumpOptions) -> fmt::Result {
+        let fields: Vec<(String, &Field)> = self
+            .fields()
+            .filter(|field| options.include_unresolved || self.hash_table().find_opt(field.hash).is_some())
+            .map(|field| (self.field_name(field.hash), field))
+            .collect();
+
+        writeln!(out, "entries = {}", self.len())?;
+        writeln!(out, "fields = {}", self.num_fields())?;
+        writeln!(out, "entry_size = 0x{:X}", self.entry_size)?;
+
+        for (index, (name, field)) in fields.iter().enumerate() {
+            writeln!(out, "field[{}].name = {}", index, name)?;
+            writeln!(out, "field[{}].hash = 0x{:08X}", index, field.hash)?;
+            writeln!(out, "field[{}].type = {}", index, field.field_type.csv_name())?;
+            writeln!(out, "field[{}].offset = 0x{:X}", index, field.offset)?;
+            writeln!(out, "field[{}].mask = 0x{:08X}", index, field.mask)?;
+            writeln!(out, "field[{}].shift = {}", index, field.shift)?;
+            writeln!(out, "field[{}].default = {}", index, DumpValue(&field.default, options))?;
+        }
+
+        let shown = options.max_entries.unwrap_or(usize::MAX).min(self.len());
+        for (index, entry) in self.entries()[..shown].iter().enumerate() {
+            for (name, field) in &fields {
+                match entry.get_by_hash(field.hash) {
+                    Some(value) => writeln!(out, "entry[{}].{} = {}", index, name, DumpValue(value, options))?,
+                    None => writeln!(out, "entry[{}].{} = <missing>", index, name)?,
+                }
+            }
+        }
+        if shown < self.len() {
+            writeln!(out, "... {} more entries", self.len() - shown)?;
+        }
+
+        Ok(())
+    }
+
+    /// Dump this JMapInfo to a string with the default options, see [`JMapInfo::dump`]
+    pub fn to_dump_string(&self) -> String {
+        let mut out = String::new();
+        self.dump(&mut out, &DumpOptions::default())
+            .expect("writing to a String cannot fail");
+        out
+    }
+}
+
+struct DumpValue<'a>(&'a FieldValue, &'a DumpOptions);
+
+impl fmt::Display for DumpValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.0, self.1.hex_integers) {
+            (FieldValue::Int(v), true) => write!(f, "0x{:08X}", *v as u32),
+            (FieldValue::UInt(v), true) => write!(f, "0x{:08X}", v),
+            // The debug form keeps the fractional part, so `1.0` is not mistaken for an integer
+            (FieldValue::Float(v), _) => write!(f, "{:?}", v),
+            (FieldValue::String(v), _) => write!(f, "{:?}", v),
+            (value, false) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::FieldType;
+    use crate::hash::{calc_hash, FileHashTable, HashAlgorithm};
+    use crate::io::{from_buffer, to_buffer, IoOptions};
+
+    fn fixture() -> JMapInfo<FileHashTable> {
+        let mut ht = FileHashTable::new(HashAlgorithm::SMG);
+        ht.add("ScenarioNo");
+        ht.add("ZoneName");
+        let mut jmap = JMapInfo::new(ht);
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.fields_map_mut().insert(0x1234ABCD, Field::new(0x1234ABCD, FieldType::Float));
+        for (scenario, zone, mystery) in [(1, "Beach", 1.0), (-2, "Line\n\"two\"", 0.5)] {
+            let entry = jmap.create_entry();
+            entry.set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(scenario));
+            entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String(zone.into()));
+            entry.set_by_hash(0x1234ABCD, FieldValue::Float(mystery));
+        }
+        let data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+        from_buffer(jmap.hash_table().clone(), &data, &IoOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_dump() {
+        let jmap = fixture();
+        let expected = r#"entries = 2
+fields = 3
+entry_size = 0xC
+field[0].name = [1234ABCD]
+field[0].hash = 0x1234ABCD
+field[0].type = Float
+field[0].offset = 0x0
+field[0].mask = 0xFFFFFFFF
+field[0].shift = 0
+field[0].default = 0.0
+field[1].name = ScenarioNo
+field[1].hash = 0xED08B591
+field[1].type = Int
+field[1].offset = 0x4
+field[1].mask = 0xFFFFFFFF
+field[1].shift = 0
+field[1].default = 0
+field[2].name = ZoneName
+field[2].hash = 0x3666C077
+field[2].type = String
+field[2].offset = 0x8
+field[2].mask = 0xFFFFFFFF
+field[2].shift = 0
+field[2].default = ""
+entry[0].[1234ABCD] = 1.0
+entry[0].ScenarioNo = 1
+entry[0].ZoneName = "Beach"
+entry[1].[1234ABCD] = 0.5
+entry[1].ScenarioNo = -2
+entry[1].ZoneName = "Line\n\"two\""
+"#;
+        assert_eq!(jmap.to_dump_string(), expected);
+
+        let options = DumpOptions {
+            hex_integers: true,
+            max_entries: Some(1),
+            include_unresolved: false,
+        };
+        let mut out = String::new();
+        jmap.dump(&mut out, &options).unwrap();
+        assert!(out.ends_with("entry[0].ScenarioNo = 0x00000001\nentry[0].ZoneName = \"Beach\"\n... 1 more entries\n"));
+        assert!(!out.contains("1234ABCD"));
+    }
+}
@@ -1,9 +1,18 @@
 //! Entry (row) implementation for JMap containers
 
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
 
-use crate::field::FieldValue;
-use crate::hash::HashTable;
+use indexmap::IndexMap;
+
+use crate::error::{JMapError, Result};
+use crate::field::{Field, FieldValue};
+use crate::hash::{parse_hash_name, HashTable};
+use crate::jmap::JMapInfo;
 
 /// A key that can be used to access field values
 #[derive(Debug, Clone)]
@@ -14,6 +23,33 @@ pub enum FieldKey {
     Name(String),
 }
 
+impl FieldKey {
+    /// Resolve this key to a field hash
+    ///
+    /// Hash keys are returned as-is without touching the hash table
+    ///
+    /// # Arguments
+    /// - `hash_table` - The hash table used to hash `FieldKey::Name` keys
+    ///
+    /// # Returns
+    /// The hash of the field this key refers to
+    pub fn resolve<H: HashTable + ?Sized>(&self, hash_table: &H) -> u32 {
+        match self {
+            FieldKey::Hash(hash) => *hash,
+            FieldKey::Name(name) => hash_table.calc(name),
+        }
+    }
+}
+
+impl fmt::Display for FieldKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldKey::Hash(hash) => write!(f, "[{:08X}]", hash),
+            FieldKey::Name(name) => write!(f, "{}", name),
+        }
+    }
+}
+
 impl From<u32> for FieldKey {
     fn from(hash: u32) -> Self {
         FieldKey::Hash(hash)
@@ -32,37 +68,237 @@ impl From<String> for FieldKey {
     }
 }
 
+/// A single value that differs between two entries
+///
+/// `before` is `None` when the value was added, `after` is `None` when it was removed
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FieldChange {
+    /// Hash of the field that changed
+    pub hash: u32,
+    /// Resolved field name, only filled by [`Entry::diff_with_names`]
+    pub name: Option<String>,
+    /// Value in the original entry
+    pub before: Option<FieldValue>,
+    /// Value in the other entry
+    pub after: Option<FieldValue>,
+}
+
+/// Compare two values, floats being compared by bit pattern so NaN equals itself
+fn values_equal(a: &FieldValue, b: &FieldValue) -> bool {
+    match (a, b) {
+        (FieldValue::Float(a), FieldValue::Float(b)) => a.to_bits() == b.to_bits(),
+        _ => a == b,
+    }
+}
+
+/// Hash a value consistently with [`values_equal`]
+fn hash_value<S: Hasher>(value: &FieldValue, state: &mut S) {
+    match value {
+        FieldValue::Int(v) => {
+            0u8.hash(state);
+            v.hash(state);
+        }
+        FieldValue::Float(v) => {
+            1u8.hash(state);
+            v.to_bits().hash(state);
+        }
+        FieldValue::String(v) => {
+            2u8.hash(state);
+            v.hash(state);
+        }
+        FieldValue::UInt(v) => {
+            3u8.hash(state);
+            v.hash(state);
+        }
+    }
+}
+
+/// A field value used as a map key, such as the keys of [`JMapInfo::split_by`](crate::JMapInfo::split_by)
+///
+/// Floats are compared and hashed by bit pattern, so NaN equals itself and `0.0` differs from `-0.0`
+#[derive(Debug, Clone)]
+pub struct ValueKey(pub FieldValue);
+
+impl PartialEq for ValueKey {
+    fn eq(&self, other: &Self) -> bool {
+        values_equal(&self.0, &other.0)
+    }
+}
+
+impl Eq for ValueKey {}
+
+impl Hash for ValueKey {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        hash_value(&self.0, state);
+    }
+}
+
+/// Hasher for field hashes, which are already well distributed and only need mixing into 64 bits
+#[derive(Debug, Default)]
+pub(crate) struct SlotHasher(u64);
+
+impl Hasher for SlotHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u32(byte as u32);
+        }
+    }
+
+    fn write_u32(&mut self, hash: u32) {
+        let mixed = (self.0 ^ hash as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        self.0 = mixed ^ (mixed >> 32);
+    }
+}
+
+/// The slot of each field hash in the values of an entry
+///
+/// A table shares one layout between all of its entries, so each entry only stores its values.
+/// Slots are only ever appended, so a layout extended with new hashes stays valid for the entries
+/// of the original one
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SlotLayout {
+    hashes: Vec<u32>,
+    slots: HashMap<u32, usize, BuildHasherDefault<SlotHasher>>,
+}
+
+impl SlotLayout {
+    /// Create an empty layout with room for `capacity` hashes
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            hashes: Vec::with_capacity(capacity),
+            slots: HashMap::with_capacity_and_hasher(capacity, BuildHasherDefault::default()),
+        }
+    }
+
+    /// Get the slot of a hash
+    pub(crate) fn slot(&self, hash: u32) -> Option<usize> {
+        self.slots.get(&hash).copied()
+    }
+
+    /// Get the slot of a hash, adding one if the hash is new
+    pub(crate) fn push(&mut self, hash: u32) -> usize {
+        *self.slots.entry(hash).or_insert_with(|| {
+            self.hashes.push(hash);
+            self.hashes.len() - 1
+        })
+    }
+
+    /// Get the number of slots
+    pub(crate) fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Estimate the heap memory used by the layout, in bytes
+    pub(crate) fn heap_size(&self) -> usize {
+        // hashbrown stores one control byte per bucket next to the pairs
+        self.hashes.capacity() * size_of::<u32>() + self.slots.capacity() * (size_of::<(u32, usize)>() + 1)
+    }
+
+    /// Check whether the slots of `other` are the first slots of this layout
+    fn extends(&self, other: &SlotLayout) -> bool {
+        self.hashes.starts_with(&other.hashes)
+    }
+}
+
 /// An entry (row) in a JMap container
+///
+/// Values are stored by slot, the slot of each field hash being given by a layout shared with the other
+/// entries of the table. Setting a value for a hash that the layout does not know gives the entry its own
+/// copy of the layout, so standalone entries and entries of several tables can be mixed freely
+///
+/// Entries compare equal when they hold the same hash -> value pairs, regardless of insertion order.
+/// Floats are compared by bit pattern, so `NaN` equals itself but `0.0` and `-0.0` differ.
+/// This makes the equality total, which is why `Entry` also implements `Eq` and `Hash`
 #[derive(Debug, Clone)]
 pub struct Entry {
-    /// Data stored as hash -> value mappings
-    data: HashMap<u32, FieldValue>,
+    /// The slot of each field hash
+    layout: Arc<SlotLayout>,
+    /// Values by slot, `None` for missing values. Slots past the end are missing too
+    values: Vec<Option<FieldValue>>,
 }
 
 impl Entry {
     /// Create a new empty entry
     pub(crate) fn new() -> Self {
         Self {
-            data: HashMap::new(),
+            layout: Arc::default(),
+            values: Vec::new(),
         }
     }
 
+    /// Get the layout giving the slot of each value
+    pub(crate) fn layout(&self) -> &Arc<SlotLayout> {
+        &self.layout
+    }
+
+    /// Get the number of slots the entry has room for without reallocating
+    pub(crate) fn slots_capacity(&self) -> usize {
+        self.values.capacity()
+    }
+
     /// Create an entry with pre-allocated capacity
     pub(crate) fn with_capacity(capacity: usize) -> Self {
         Self {
-            data: HashMap::with_capacity(capacity),
+            layout: Arc::new(SlotLayout::with_capacity(capacity)),
+            values: Vec::with_capacity(capacity),
         }
     }
 
+    /// Create an empty entry using a shared layout
+    pub(crate) fn with_layout(layout: Arc<SlotLayout>) -> Self {
+        let mut values = Vec::new();
+        values.resize_with(layout.len(), || None);
+        Self { layout, values }
+    }
+
+    /// Switch to another layout, keeping every value
+    ///
+    /// Nothing changes if `layout` is missing a hash this entry holds a value for
+    pub(crate) fn adopt_layout(&mut self, layout: &Arc<SlotLayout>) {
+        if Arc::ptr_eq(&self.layout, layout) {
+            return;
+        }
+        if layout.extends(&self.layout) {
+            self.layout = Arc::clone(layout);
+            return;
+        }
+        if self.iter().any(|(hash, _)| layout.slot(*hash).is_none()) {
+            return;
+        }
+
+        let mut values = Vec::new();
+        values.resize_with(layout.len(), || None);
+        for (hash, value) in self.layout.hashes.iter().zip(self.values.drain(..)) {
+            if let Some(value) = value {
+                values[layout.slot(*hash).expect("hash was just checked")] = Some(value);
+            }
+        }
+        self.layout = Arc::clone(layout);
+        self.values = values;
+    }
+
     /// Get a value by hash
     pub fn get_by_hash(&self, hash: u32) -> Option<&FieldValue> {
-        self.data.get(&hash)
+        let slot = self.layout.slot(hash)?;
+        self.values.get(slot)?.as_ref()
+    }
+
+    /// Get a mutable value by hash
+    ///
+    /// Writes through this reference are not type-checked
+    pub fn get_mut_by_hash(&mut self, hash: u32) -> Option<&mut FieldValue> {
+        let slot = self.layout.slot(hash)?;
+        self.values.get_mut(slot)?.as_mut()
     }
 
     /// Get a value by name, using the provided hash table
     pub fn get<H: HashTable>(&self, hash_table: &H, name: &str) -> Option<&FieldValue> {
-        let hash = hash_table.calc(name);
-        self.data.get(&hash)
+        self.get_by_hash(hash_table.calc(name))
     }
 
     /// Get an integer value by hash
@@ -75,6 +311,30 @@ impl Entry {
         self.get(hash_table, name).and_then(|v| v.as_int())
     }
 
+    /// Get an unsigned integer value by hash, reinterpreting the stored bits as `u32`
+    pub fn get_uint_by_hash(&self, hash: u32) -> Option<u32> {
+        self.get_by_hash(hash).and_then(|v| v.as_uint())
+    }
+
+    /// Get an unsigned integer value by name, reinterpreting the stored bits as `u32`
+    pub fn get_uint<H: HashTable>(&self, hash_table: &H, name: &str) -> Option<u32> {
+        self.get(hash_table, name).and_then(|v| v.as_uint())
+    }
+
+    /// Get a boolean value by hash
+    ///
+    /// Any nonzero integer is `true`, including sentinels such as `-1`
+    pub fn get_bool_by_hash(&self, hash: u32) -> Option<bool> {
+        self.get_by_hash(hash).and_then(|v| v.as_bool())
+    }
+
+    /// Get a boolean value by name
+    ///
+    /// Any nonzero integer is `true`, including sentinels such as `-1`
+    pub fn get_bool<H: HashTable>(&self, hash_table: &H, name: &str) -> Option<bool> {
+        self.get(hash_table, name).and_then(|v| v.as_bool())
+    }
+
     /// Get a float value by hash
     pub fn get_float_by_hash(&self, hash: u32) -> Option<f32> {
         self.get_by_hash(hash).and_then(|v| v.as_float())
@@ -95,46 +355,298 @@ impl Entry {
         self.get(hash_table, name).and_then(|v| v.as_str())
     }
 
+    /// Get a value by name, failing if it is missing
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if the entry has no value for this name
+    pub fn require<H: HashTable>(&self, hash_table: &H, name: &str) -> Result<&FieldValue> {
+        self.get(hash_table, name)
+            .ok_or_else(|| JMapError::FieldNotFound(name.to_string()))
+    }
+
+    /// Get an integer value by name, failing if it is missing or not an integer
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if the entry has no value for this name
+    /// - `JMapError::TypeMismatch` if the value is not an integer
+    pub fn require_int<H: HashTable>(&self, hash_table: &H, name: &str) -> Result<i32> {
+        let value = self.require(hash_table, name)?;
+        value.as_int().ok_or_else(|| JMapError::TypeMismatch {
+            field: name.to_string(),
+            expected: "Int",
+            got: value.type_name(),
+        })
+    }
+
+    /// Get a float value by name, failing if it is missing or not a float
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if the entry has no value for this name
+    /// - `JMapError::TypeMismatch` if the value is not a float
+    pub fn require_float<H: HashTable>(&self, hash_table: &H, name: &str) -> Result<f32> {
+        let value = self.require(hash_table, name)?;
+        value.as_float().ok_or_else(|| JMapError::TypeMismatch {
+            field: name.to_string(),
+            expected: "Float",
+            got: value.type_name(),
+        })
+    }
+
+    /// Get a string value by name, failing if it is missing or not a string
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if the entry has no value for this name
+    /// - `JMapError::TypeMismatch` if the value is not a string
+    pub fn require_string<H: HashTable>(&self, hash_table: &H, name: &str) -> Result<&str> {
+        let value = self.require(hash_table, name)?;
+        value.as_str().ok_or_else(|| JMapError::TypeMismatch {
+            field: name.to_string(),
+            expected: "String",
+            got: value.type_name(),
+        })
+    }
+
     /// Set a value by hash
     pub fn set_by_hash(&mut self, hash: u32, value: FieldValue) {
-        self.data.insert(hash, value);
+        let slot = match self.layout.slot(hash) {
+            Some(slot) => slot,
+            None => Arc::make_mut(&mut self.layout).push(hash),
+        };
+        if slot >= self.values.len() {
+            self.values.resize_with(slot + 1, || None);
+        }
+        self.values[slot] = Some(value);
+    }
+
+    /// Remove a value by hash
+    ///
+    /// # Returns
+    /// The removed value, or `None` if the entry had no value for the hash
+    pub fn remove_by_hash(&mut self, hash: u32) -> Option<FieldValue> {
+        let slot = self.layout.slot(hash)?;
+        self.values.get_mut(slot)?.take()
     }
 
     /// Set a value by name, using the provided hash table
     pub fn set<H: HashTable>(&mut self, hash_table: &H, name: &str, value: FieldValue) {
-        let hash = hash_table.calc(name);
-        self.data.insert(hash, value);
+        self.set_by_hash(hash_table.calc(name), value);
+    }
+
+    /// Get a value by key, resolving names through the provided hash table
+    pub fn get_key<H: HashTable>(&self, key: impl Into<FieldKey>, hash_table: &H) -> Option<&FieldValue> {
+        self.get_by_hash(key.into().resolve(hash_table))
+    }
+
+    /// Set a value by key, resolving names through the provided hash table
+    pub fn set_key<H: HashTable>(&mut self, key: impl Into<FieldKey>, hash_table: &H, value: FieldValue) {
+        self.set_by_hash(key.into().resolve(hash_table), value);
+    }
+
+    /// Set a boolean value by name, stored as `1` or `0`
+    pub fn set_bool<H: HashTable>(&mut self, hash_table: &H, name: &str, value: bool) {
+        self.set(hash_table, name, FieldValue::from(value));
     }
 
     /// Check if this entry contains a field by hash
     pub fn contains_hash(&self, hash: u32) -> bool {
-        self.data.contains_key(&hash)
+        self.get_by_hash(hash).is_some()
     }
 
     /// Check if this entry contains a field by name
     pub fn contains<H: HashTable>(&self, hash_table: &H, name: &str) -> bool {
-        let hash = hash_table.calc(name);
-        self.data.contains_key(&hash)
+        self.contains_hash(hash_table.calc(name))
     }
 
     /// Get the number of fields in this entry
     pub fn len(&self) -> usize {
-        self.data.len()
+        self.values.iter().flatten().count()
     }
 
     /// Check if this entry is empty
     pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+        self.values.iter().all(Option::is_none)
     }
 
     /// Iterate over all hash-value pairs
+    ///
+    /// Values of the table's fields come in the order the fields were added, which is usually field order
     pub fn iter(&self) -> impl Iterator<Item = (&u32, &FieldValue)> {
-        self.data.iter()
+        self.layout
+            .hashes
+            .iter()
+            .zip(&self.values)
+            .filter_map(|(hash, value)| Some((hash, value.as_ref()?)))
+    }
+
+    /// Iterate over values following the given field order
+    ///
+    /// Unlike [`Entry::iter`], the order is stable and matches the order of `fields`.
+    /// Missing values are yielded as `None` instead of being skipped, so columns stay aligned
+    ///
+    /// # Arguments
+    /// - `fields` - The fields to walk, usually `jmap.fields()`
+    ///
+    /// # Returns
+    /// An iterator over each field paired with the value stored for it, if any
+    pub fn iter_ordered<'a>(
+        &'a self,
+        fields: impl Iterator<Item = &'a Field>,
+    ) -> impl Iterator<Item = (&'a Field, Option<&'a FieldValue>)> {
+        fields.map(move |field| (field, self.get_by_hash(field.hash)))
     }
 
-    /// Get mutable access to the internal data map
-    pub(crate) fn data_mut(&mut self) -> &mut HashMap<u32, FieldValue> {
-        &mut self.data
+    /// Convert this entry to a map keyed by field names
+    ///
+    /// Names are resolved with `hash_table.find`, so unknown hashes appear as `[XXXXXXXX]` keys.
+    /// Keys are ordered by hash so the output is stable
+    ///
+    /// # Arguments
+    /// - `hash_table` - The hash table used to resolve names
+    ///
+    /// # Returns
+    /// An `IndexMap` of field name to value
+    pub fn to_named_map<H: HashTable>(&self, hash_table: &H) -> IndexMap<String, FieldValue> {
+        let mut pairs: Vec<(&u32, &FieldValue)> = self.iter().collect();
+        pairs.sort_by_key(|(hash, _)| **hash);
+        pairs
+            .into_iter()
+            .map(|(hash, value)| (hash_table.find(*hash), value.clone()))
+            .collect()
+    }
+
+    /// Create an entry from a map keyed by field names
+    ///
+    /// Names are added to the hash table, except for `[XXXXXXXX]` keys which are parsed as hashes
+    ///
+    /// # Arguments
+    /// - `hash_table` - The hash table used to hash names
+    /// - `map` - The map of field name to value
+    ///
+    /// # Returns
+    /// A new `Entry` holding the values of the map
+    pub fn from_named_map<H: HashTable>(hash_table: &mut H, map: IndexMap<String, FieldValue>) -> Entry {
+        let mut entry = Entry::with_capacity(map.len());
+        for (name, value) in map {
+            let hash = parse_hash_name(&name).unwrap_or_else(|| hash_table.add(&name));
+            entry.set_by_hash(hash, value);
+        }
+        entry
+    }
+
+    /// Compute the values that differ between this entry and another
+    ///
+    /// Changed, added and removed values are all reported. Floats are compared by bit pattern
+    ///
+    /// # Arguments
+    /// - `other` - The entry to compare against, seen as the newer version
+    ///
+    /// # Returns
+    /// The changes sorted by field hash, empty if both entries hold the same values
+    pub fn diff(&self, other: &Entry) -> Vec<FieldChange> {
+        let mut changes: Vec<FieldChange> = Vec::new();
+
+        for (hash, before) in self.iter() {
+            match other.get_by_hash(*hash) {
+                Some(after) if values_equal(before, after) => {}
+                after => changes.push(FieldChange {
+                    hash: *hash,
+                    name: None,
+                    before: Some(before.clone()),
+                    after: after.cloned(),
+                }),
+            }
+        }
+
+        for (hash, after) in other.iter() {
+            if !self.contains_hash(*hash) {
+                changes.push(FieldChange {
+                    hash: *hash,
+                    name: None,
+                    before: None,
+                    after: Some(after.clone()),
+                });
+            }
+        }
+
+        changes.sort_by_key(|change| change.hash);
+        changes
+    }
+
+    /// Compare two entries, allowing floats to differ by up to `epsilon`
+    ///
+    /// See [`FieldValue::approx_eq`]
+    pub fn approx_eq(&self, other: &Entry, epsilon: f32) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(hash, value)| {
+                other
+                    .get_by_hash(*hash)
+                    .is_some_and(|other_value| value.approx_eq(other_value, epsilon))
+            })
+    }
+
+    /// Same as [`Entry::diff`], with field names resolved through the provided hash table
+    pub fn diff_with_names<H: HashTable>(&self, other: &Entry, hash_table: &H) -> Vec<FieldChange> {
+        let mut changes = self.diff(other);
+        for change in &mut changes {
+            change.name = Some(hash_table.find(change.hash));
+        }
+        changes
+    }
+
+    /// Copy values from another entry into this one
+    ///
+    /// No type checking is done since entries don't know their field types.
+    /// Use [`JMapInfo::set_value`] when values need to be checked against the table
+    ///
+    /// # Arguments
+    /// - `other` - The entry to copy values from
+    /// - `overwrite` - Whether existing values are replaced. If `false`, only missing values are filled
+    ///
+    /// # Returns
+    /// The number of values copied
+    pub fn merge_from(&mut self, other: &Entry, overwrite: bool) -> usize {
+        let mut copied = 0;
+        for (hash, value) in other.iter() {
+            if overwrite || !self.contains_hash(*hash) {
+                self.set_by_hash(*hash, value.clone());
+                copied += 1;
+            }
+        }
+        copied
+    }
+
+    /// Same as [`Entry::merge_from`], restricted to the given field hashes
+    ///
+    /// # Arguments
+    /// - `other` - The entry to copy values from
+    /// - `hashes` - The hashes of the fields to copy. Hashes missing from `other` are skipped
+    /// - `overwrite` - Whether existing values are replaced. If `false`, only missing values are filled
+    ///
+    /// # Returns
+    /// The number of values copied
+    pub fn merge_from_fields(&mut self, other: &Entry, hashes: &[u32], overwrite: bool) -> usize {
+        let mut copied = 0;
+        for hash in hashes {
+            if let Some(value) = other.get_by_hash(*hash)
+                && (overwrite || !self.contains_hash(*hash))
+            {
+                self.set_by_hash(*hash, value.clone());
+                copied += 1;
+            }
+        }
+        copied
+    }
+
+    /// Compute a hash of the entry's content, independent of the order values were inserted in
+    ///
+    /// Consistent with the `PartialEq` implementation
+    pub fn content_hash(&self) -> u64 {
+        self.iter().fold(0u64, |acc, (hash, value)| {
+            let mut hasher = DefaultHasher::new();
+            hash.hash(&mut hasher);
+            hash_value(value, &mut hasher);
+            acc.wrapping_add(hasher.finish())
+        })
     }
 }
 
@@ -143,3 +655,752 @@ impl Default for Entry {
         Self::new()
     }
 }
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        if Arc::ptr_eq(&self.layout, &other.layout) {
+            let len = self.values.len().max(other.values.len());
+            return (0..len).all(|slot| {
+                match (self.values.get(slot).and_then(Option::as_ref), other.values.get(slot).and_then(Option::as_ref)) {
+                    (Some(a), Some(b)) => values_equal(a, b),
+                    (a, b) => a.is_none() && b.is_none(),
+                }
+            });
+        }
+
+        self.len() == other.len()
+            && self.iter().all(|(hash, value)| {
+                other
+                    .get_by_hash(*hash)
+                    .is_some_and(|other_value| values_equal(value, other_value))
+            })
+    }
+}
+
+impl Eq for Entry {}
+
+impl Hash for Entry {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        state.write_u64(self.content_hash());
+    }
+}
+
+/// Entries are serialized as a map from field hash to value, sorted by hash.
+/// Use [`Entry::named`] to key them by field name instead
+#[cfg(feature = "serde")]
+impl serde::Serialize for Entry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut values: Vec<_> = self.iter().collect();
+        values.sort_unstable_by_key(|(hash, _)| **hash);
+        let mut map = serializer.serialize_map(Some(values.len()))?;
+        for (hash, value) in values {
+            map.serialize_entry(hash, value)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Entry {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let mut values: Vec<(u32, FieldValue)> = HashMap::<u32, FieldValue>::deserialize(deserializer)?.into_iter().collect();
+        values.sort_unstable_by_key(|(hash, _)| *hash);
+        let mut entry = Entry::with_capacity(values.len());
+        for (hash, value) in values {
+            entry.set_by_hash(hash, value);
+        }
+        Ok(entry)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Entry {
+    /// Serialize this entry as a map from field name to value, sorted by hash
+    ///
+    /// Fields with an unknown name are keyed `[XXXXXXXX]`
+    pub fn named<'a, H: HashTable>(&'a self, hash_table: &'a H) -> NamedEntry<'a, H> {
+        NamedEntry { entry: self, hash_table }
+    }
+}
+
+/// An entry serialized with field names as keys, see [`Entry::named`]
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy)]
+pub struct NamedEntry<'a, H: HashTable> {
+    entry: &'a Entry,
+    hash_table: &'a H,
+}
+
+#[cfg(feature = "serde")]
+impl<H: HashTable> serde::Serialize for NamedEntry<'_, H> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut values: Vec<_> = self.entry.iter().collect();
+        values.sort_unstable_by_key(|(hash, _)| **hash);
+        let mut map = serializer.serialize_map(Some(values.len()))?;
+        for (&hash, value) in values {
+            let name = match self.hash_table.find_opt(hash) {
+                Some(name) => name.into_owned(),
+                None => format!("[{:08X}]", hash),
+            };
+            map.serialize_entry(&name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// A fluent builder for a new entry of a table
+///
+/// Values are validated on [`EntryBuilder::insert`], fields that were not set get their default value.
+/// Created with [`JMapInfo::entry_builder`]
+#[derive(Debug)]
+pub struct EntryBuilder<'a, H: HashTable> {
+    jmap: &'a mut JMapInfo<H>,
+    values: Vec<(FieldKey, FieldValue)>,
+}
+
+impl<'a, H: HashTable> EntryBuilder<'a, H> {
+    pub(crate) fn new(jmap: &'a mut JMapInfo<H>) -> Self {
+        Self {
+            jmap,
+            values: Vec::new(),
+        }
+    }
+
+    /// Set a value by field name
+    pub fn set(mut self, name: &str, value: impl Into<FieldValue>) -> Self {
+        self.values.push((FieldKey::from(name), value.into()));
+        self
+    }
+
+    /// Set a value by field hash
+    pub fn set_hash(mut self, hash: u32, value: impl Into<FieldValue>) -> Self {
+        self.values.push((FieldKey::Hash(hash), value.into()));
+        self
+    }
+
+    /// Validate the values and build the entry, filling unset fields from their defaults
+    fn build(&mut self) -> Result<Entry> {
+        let mut entry = self.jmap.new_entry();
+        for field in self.jmap.fields() {
+            entry.set_by_hash(field.hash, field.default.clone());
+        }
+
+        for (key, value) in &self.values {
+            let hash = key.resolve(self.jmap.hash_table());
+            let field = self
+                .jmap
+                .get_field_by_hash(hash)
+                .ok_or_else(|| JMapError::FieldNotFound(key.to_string()))?;
+
+            if !value.is_compatible_with(field.field_type) {
+                return Err(JMapError::TypeMismatch {
+                    field: key.to_string(),
+                    expected: field.field_type.csv_name(),
+                    got: value.type_name(),
+                });
+            }
+
+            entry.set_by_hash(hash, value.clone());
+        }
+
+        Ok(entry)
+    }
+
+    /// Build the entry and append it to the table
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if a value was set for an unknown field
+    /// - `JMapError::TypeMismatch` if a value is not compatible with its field type
+    ///
+    /// # Returns
+    /// The index of the new entry. On error, the table is left untouched
+    pub fn insert(mut self) -> Result<usize> {
+        let entry = self.build()?;
+        self.jmap.entries_vec_mut().push(entry);
+        let index = self.jmap.len() - 1;
+        self.jmap.touch(index);
+        Ok(index)
+    }
+
+    /// Build the entry and insert it at the given index, shifting later entries
+    ///
+    /// # Errors
+    /// - `JMapError::EntryIndexOutOfBounds` if `index` is greater than the number of entries
+    /// - `JMapError::FieldNotFound` if a value was set for an unknown field
+    /// - `JMapError::TypeMismatch` if a value is not compatible with its field type
+    ///
+    /// # Returns
+    /// The index of the new entry. On error, the table is left untouched
+    pub fn insert_at(mut self, index: usize) -> Result<usize> {
+        let len = self.jmap.len();
+        if index > len {
+            return Err(JMapError::EntryIndexOutOfBounds { index, len });
+        }
+
+        let entry = self.build()?;
+        self.jmap.entries_vec_mut().insert(index, entry);
+        self.jmap.touch_from(index);
+        Ok(index)
+    }
+}
+
+/// Index an entry by field hash
+///
+/// # Panics
+/// If the entry has no value for the hash
+impl Index<u32> for Entry {
+    type Output = FieldValue;
+
+    fn index(&self, hash: u32) -> &FieldValue {
+        self.get_by_hash(hash)
+            .unwrap_or_else(|| panic!("Entry has no value for field [{:08X}]", hash))
+    }
+}
+
+impl IndexMut<u32> for Entry {
+    fn index_mut(&mut self, hash: u32) -> &mut FieldValue {
+        self.get_mut_by_hash(hash)
+            .unwrap_or_else(|| panic!("Entry has no value for field [{:08X}]", hash))
+    }
+}
+
+/// A borrowed handle to an entry, with name-based access through the table's hash table
+///
+/// Created with [`JMapInfo::view`]
+#[derive(Debug)]
+pub struct EntryView<'a, H: HashTable> {
+    jmap: &'a JMapInfo<H>,
+    entry: &'a Entry,
+}
+
+impl<'a, H: HashTable> EntryView<'a, H> {
+    pub(crate) fn new(jmap: &'a JMapInfo<H>, entry: &'a Entry) -> Self {
+        Self { jmap, entry }
+    }
+
+    /// Get the underlying entry
+    pub fn entry(&self) -> &'a Entry {
+        self.entry
+    }
+
+    /// Get a value by field name
+    pub fn get(&self, name: &str) -> Option<&'a FieldValue> {
+        self.entry.get(self.jmap.hash_table(), name)
+    }
+
+    /// Get an integer value by field name
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        self.get(name).and_then(|v| v.as_int())
+    }
+
+    /// Get an unsigned integer value by field name
+    pub fn get_uint(&self, name: &str) -> Option<u32> {
+        self.get(name).and_then(|v| v.as_uint())
+    }
+
+    /// Get a boolean value by field name
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        self.get(name).and_then(|v| v.as_bool())
+    }
+
+    /// Get a float value by field name
+    pub fn get_float(&self, name: &str) -> Option<f32> {
+        self.get(name).and_then(|v| v.as_float())
+    }
+
+    /// Get a string value by field name
+    pub fn get_str(&self, name: &str) -> Option<&'a str> {
+        self.get(name).and_then(|v| v.as_str())
+    }
+
+    /// Get the resolved names of the table's fields, in field order
+    pub fn field_names(&self) -> impl Iterator<Item = String> + 'a {
+        let jmap = self.jmap;
+        jmap.fields().map(move |field| jmap.field_name(field.hash))
+    }
+}
+
+/// Index a view by field key. Names are resolved through the table's hash table
+///
+/// # Panics
+/// If the entry has no value for the key
+impl<H: HashTable> Index<FieldKey> for EntryView<'_, H> {
+    type Output = FieldValue;
+
+    fn index(&self, key: FieldKey) -> &FieldValue {
+        let hash = key.resolve(self.jmap.hash_table());
+        self.entry
+            .get_by_hash(hash)
+            .unwrap_or_else(|| panic!("Entry has no value for field {}", key))
+    }
+}
+
+impl<H: HashTable> Index<&str> for EntryView<'_, H> {
+    type Output = FieldValue;
+
+    fn index(&self, name: &str) -> &FieldValue {
+        &self[FieldKey::from(name)]
+    }
+}
+
+/// Renders the entry as `name=value` pairs in field order, separated by `, `
+impl<H: HashTable> fmt::Display for EntryView<'_, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (field, value)) in self.entry.iter_ordered(self.jmap.fields()).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}=", self.jmap.field_name(field.hash))?;
+            if let Some(value) = value {
+                write!(f, "{}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A mutably borrowed handle to an entry, with name-based and type-checked access
+///
+/// Created with [`JMapInfo::view_mut`]
+#[derive(Debug)]
+pub struct EntryViewMut<'a, H: HashTable> {
+    hash_table: &'a H,
+    fields: &'a IndexMap<u32, Field>,
+    entry: &'a mut Entry,
+}
+
+impl<'a, H: HashTable> EntryViewMut<'a, H> {
+    pub(crate) fn new(hash_table: &'a H, fields: &'a IndexMap<u32, Field>, entry: &'a mut Entry) -> Self {
+        Self {
+            hash_table,
+            fields,
+            entry,
+        }
+    }
+
+    /// Get the underlying entry
+    pub fn entry(&self) -> &Entry {
+        self.entry
+    }
+
+    /// Get a value by field name
+    pub fn get(&self, name: &str) -> Option<&FieldValue> {
+        self.entry.get(self.hash_table, name)
+    }
+
+    /// Get an integer value by field name
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        self.get(name).and_then(|v| v.as_int())
+    }
+
+    /// Get an unsigned integer value by field name
+    pub fn get_uint(&self, name: &str) -> Option<u32> {
+        self.get(name).and_then(|v| v.as_uint())
+    }
+
+    /// Get a boolean value by field name
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        self.get(name).and_then(|v| v.as_bool())
+    }
+
+    /// Get a float value by field name
+    pub fn get_float(&self, name: &str) -> Option<f32> {
+        self.get(name).and_then(|v| v.as_float())
+    }
+
+    /// Get a string value by field name
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(|v| v.as_str())
+    }
+
+    /// Get a mutable value by key, resolving names through the table's hash table
+    ///
+    /// Unlike [`EntryViewMut::set`], writes through this reference are not type-checked
+    pub fn get_mut(&mut self, key: impl Into<FieldKey>) -> Option<&mut FieldValue> {
+        let hash = key.into().resolve(self.hash_table);
+        self.entry.get_mut_by_hash(hash)
+    }
+
+    /// Set a value by field name, checking it against the field's type
+    ///
+    /// # Arguments
+    /// - `name` - The name of the field to set
+    /// - `value` - The value to store
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if the table has no field with this name
+    /// - `JMapError::TypeMismatch` if the value is not compatible with the field type
+    ///
+    /// # Returns
+    /// Ok(()) if the value was stored
+    pub fn set(&mut self, name: &str, value: impl Into<FieldValue>) -> Result<()> {
+        let value = value.into();
+        let hash = self.hash_table.calc(name);
+        let field = self
+            .fields
+            .get(&hash)
+            .ok_or_else(|| JMapError::FieldNotFound(name.to_string()))?;
+
+        if !value.is_compatible_with(field.field_type) {
+            return Err(JMapError::TypeMismatch {
+                field: name.to_string(),
+                expected: field.field_type.csv_name(),
+                got: value.type_name(),
+            });
+        }
+
+        self.entry.set_by_hash(hash, value);
+        Ok(())
+    }
+}
+
+/// Index a mutable view by field key. Names are resolved through the table's hash table
+///
+/// # Panics
+/// If the entry has no value for the key
+impl<H: HashTable> Index<FieldKey> for EntryViewMut<'_, H> {
+    type Output = FieldValue;
+
+    fn index(&self, key: FieldKey) -> &FieldValue {
+        let hash = key.resolve(self.hash_table);
+        self.entry
+            .get_by_hash(hash)
+            .unwrap_or_else(|| panic!("Entry has no value for field {}", key))
+    }
+}
+
+impl<H: HashTable> IndexMut<FieldKey> for EntryViewMut<'_, H> {
+    fn index_mut(&mut self, key: FieldKey) -> &mut FieldValue {
+        let hash = key.resolve(self.hash_table);
+        self.entry
+            .get_mut_by_hash(hash)
+            .unwrap_or_else(|| panic!("Entry has no value for field {}", key))
+    }
+}
+
+impl<H: HashTable> Index<&str> for EntryViewMut<'_, H> {
+    type Output = FieldValue;
+
+    fn index(&self, name: &str) -> &FieldValue {
+        &self[FieldKey::from(name)]
+    }
+}
+
+impl<H: HashTable> IndexMut<&str> for EntryViewMut<'_, H> {
+    fn index_mut(&mut self, name: &str) -> &mut FieldValue {
+        &mut self[FieldKey::from(name)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FieldKey;
+    use crate::error::JMapError;
+    use crate::field::{FieldType, FieldValue};
+    use crate::hash::{calc_hash, smg_hash_table, HashTable};
+    use crate::jmap::JMapInfo;
+
+    #[test]
+    fn test_iter_ordered() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        for name in ["Zeta", "Alpha", "Mid", "Beta", "Omega"] {
+            jmap.create_field(name, FieldType::Long, FieldValue::Int(0)).unwrap();
+        }
+
+        let entry = jmap.create_entry();
+        entry.remove_by_hash(calc_hash("Mid"));
+
+        let entry = &jmap.entries()[0];
+        for _ in 0..3 {
+            let hashes: Vec<u32> = entry.iter_ordered(jmap.fields()).map(|(f, _)| f.hash).collect();
+            let expected: Vec<u32> = jmap.field_hashes().copied().collect();
+            assert_eq!(hashes, expected);
+        }
+
+        // Missing values are reported instead of skipped
+        let values: Vec<_> = entry.iter_ordered(jmap.fields()).map(|(_, v)| v).collect();
+        assert_eq!(values.len(), 5);
+        assert!(values[2].is_none());
+        assert_eq!(values[0], Some(&FieldValue::Int(0)));
+    }
+
+    #[test]
+    fn test_entry_view() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(1)).unwrap();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("Galaxy")).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(1.5)).unwrap();
+        jmap.create_entry();
+
+        let mut view = jmap.view_mut(0).unwrap();
+        view.set("ScenarioNo", 3).unwrap();
+        assert!(matches!(
+            view.set("ScenarioNo", "oops"),
+            Err(JMapError::TypeMismatch { .. })
+        ));
+        assert!(matches!(view.set("Missing", 0), Err(JMapError::FieldNotFound(_))));
+        assert_eq!(view.get_int("ScenarioNo"), Some(3));
+
+        let view = jmap.view(0).unwrap();
+        assert_eq!(view.get_int("ScenarioNo"), Some(3));
+        assert_eq!(view.get_str("ZoneName"), Some("Galaxy"));
+        assert_eq!(view.get_float("Scale"), Some(1.5));
+        assert_eq!(view.get("Missing"), None);
+        assert_eq!(
+            view.field_names().collect::<Vec<_>>(),
+            ["ScenarioNo", "ZoneName", "Scale"]
+        );
+        assert_eq!(view.to_string(), "ScenarioNo=3, ZoneName=Galaxy, Scale=1.5");
+        assert!(jmap.view(1).is_none());
+    }
+
+    /// A hash table that fails the test if it is ever consulted
+    struct PanickingHashTable;
+
+    impl HashTable for PanickingHashTable {
+        fn calc(&self, _: &str) -> u32 {
+            panic!("hash table should not be used for hash keys")
+        }
+
+        fn find(&self, hash: u32) -> String {
+            format!("[{:08X}]", hash)
+        }
+
+        fn add(&mut self, field_name: &str) -> u32 {
+            calc_hash(field_name)
+        }
+    }
+
+    #[test]
+    fn test_field_key() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(1)).unwrap();
+        jmap.create_entry();
+        let hash = calc_hash("ScenarioNo");
+
+        // Hash keys
+        let entry = &mut jmap.entries_mut()[0];
+        assert_eq!(entry[hash], FieldValue::Int(1));
+        entry[hash] = FieldValue::Int(2);
+        assert_eq!(entry.get_key(hash, &PanickingHashTable), Some(&FieldValue::Int(2)));
+        entry.set_key(hash, &PanickingHashTable, FieldValue::Int(3));
+
+        // Name keys
+        assert_eq!(jmap.get_value(0, "ScenarioNo"), Some(&FieldValue::Int(3)));
+        jmap.set_value(0, "ScenarioNo", FieldValue::Int(4)).unwrap();
+        assert_eq!(jmap.get_value(0, hash), Some(&FieldValue::Int(4)));
+        assert!(matches!(
+            jmap.set_value(0, FieldKey::Hash(hash), FieldValue::Float(1.0)),
+            Err(JMapError::TypeMismatch { .. })
+        ));
+        assert!(matches!(
+            jmap.set_value(1, hash, FieldValue::Int(0)),
+            Err(JMapError::EntryIndexOutOfBounds { .. })
+        ));
+
+        let mut view = jmap.view_mut(0).unwrap();
+        view["ScenarioNo"] = FieldValue::Int(5);
+        assert_eq!(view[FieldKey::Hash(hash)], FieldValue::Int(5));
+        assert_eq!(jmap.view(0).unwrap()["ScenarioNo"], FieldValue::Int(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Entry has no value for field Missing")]
+    fn test_index_missing_panics() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_entry();
+        let _ = &jmap.view(0).unwrap()["Missing"];
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(1)).unwrap();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("Galaxy")).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(f32::NAN)).unwrap();
+        jmap.create_entry();
+
+        let before = jmap.entries()[0].clone();
+        assert!(before.diff(&before.clone()).is_empty());
+
+        let mut after = before.clone();
+        after.set(jmap.hash_table(), "ScenarioNo", FieldValue::Int(2));
+        after.remove_by_hash(calc_hash("ZoneName"));
+
+        let changes = before.diff_with_names(&after, jmap.hash_table());
+        assert_eq!(changes.len(), 2);
+
+        let changed = changes.iter().find(|c| c.hash == calc_hash("ScenarioNo")).unwrap();
+        assert_eq!(changed.name.as_deref(), Some("ScenarioNo"));
+        assert_eq!(changed.before, Some(FieldValue::Int(1)));
+        assert_eq!(changed.after, Some(FieldValue::Int(2)));
+
+        let removed = changes.iter().find(|c| c.hash == calc_hash("ZoneName")).unwrap();
+        assert_eq!(removed.before, Some(FieldValue::from("Galaxy")));
+        assert_eq!(removed.after, None);
+
+        let added = after.diff(&before);
+        assert!(added.iter().any(|c| c.before.is_none() && c.hash == calc_hash("ZoneName")));
+    }
+
+    #[test]
+    fn test_shared_layout() {
+        use std::sync::Arc;
+
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(1)).unwrap();
+        jmap.create_entry();
+        jmap.create_entry();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("Galaxy")).unwrap();
+        jmap.entry_builder().set("ScenarioNo", 2).insert().unwrap();
+        let entries = jmap.entries();
+        assert!(entries.iter().all(|entry| Arc::ptr_eq(&entry.layout, &entries[0].layout)));
+        assert_eq!(entries[1].get_by_hash(calc_hash("ZoneName")), Some(&FieldValue::from("Galaxy")));
+
+        // A standalone entry with the same values in another order is equal, and can adopt the layout
+        let ht = smg_hash_table();
+        let mut other = super::Entry::new();
+        other.set(&ht, "ZoneName", FieldValue::from("Galaxy"));
+        other.set(&ht, "ScenarioNo", FieldValue::Int(1));
+        assert_eq!(other, entries[0]);
+        other.adopt_layout(&entries[0].layout);
+        assert!(Arc::ptr_eq(&other.layout, &entries[0].layout));
+        assert_eq!(other, entries[0]);
+
+        // Setting an unknown hash copies the layout instead of changing the table's
+        let mut extra = entries[0].clone();
+        extra.set_by_hash(0xDEADBEEF, FieldValue::Int(7));
+        assert!(!Arc::ptr_eq(&extra.layout, &entries[0].layout));
+        assert_eq!(jmap.entries()[0].iter().count(), 2);
+        assert_eq!(extra.remove_by_hash(0xDEADBEEF), Some(FieldValue::Int(7)));
+        assert_eq!(extra, jmap.entries()[0]);
+    }
+
+    #[test]
+    fn test_bool_accessors() {
+        let ht = smg_hash_table();
+        let mut entry = super::Entry::new();
+        entry.set_bool(&ht, "IsValid", true);
+        entry.set(&ht, "Sentinel", FieldValue::Int(-1));
+        entry.set(&ht, "Name", FieldValue::from("a"));
+
+        assert_eq!(entry.get_int(&ht, "IsValid"), Some(1));
+        assert_eq!(entry.get_bool(&ht, "IsValid"), Some(true));
+        assert_eq!(entry.get_bool(&ht, "Sentinel"), Some(true));
+        assert_eq!(entry.get_bool(&ht, "Name"), None);
+
+        entry.set_bool(&ht, "IsValid", false);
+        assert_eq!(entry.get_bool_by_hash(calc_hash("IsValid")), Some(false));
+        assert_eq!(FieldValue::from(true), FieldValue::Int(1));
+    }
+
+    #[test]
+    fn test_named_map_round_trip() {
+        let mut ht = smg_hash_table();
+        let mut entry = super::Entry::new();
+        entry.set_by_hash(ht.add("ZoneName"), FieldValue::from("Galaxy"));
+        entry.set_by_hash(0xDEADBEEF, FieldValue::Int(7));
+
+        let map = entry.to_named_map(&ht);
+        assert_eq!(map.get("ZoneName"), Some(&FieldValue::from("Galaxy")));
+        assert_eq!(map.get("[DEADBEEF]"), Some(&FieldValue::Int(7)));
+
+        let back = super::Entry::from_named_map(&mut ht, map);
+        assert_eq!(back.get_by_hash(calc_hash("ZoneName")), Some(&FieldValue::from("Galaxy")));
+        assert_eq!(back.get_by_hash(0xDEADBEEF), Some(&FieldValue::Int(7)));
+        assert_eq!(back.len(), 2);
+    }
+
+    #[test]
+    fn test_entry_builder() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(1)).unwrap();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("Galaxy")).unwrap();
+
+        let index = jmap.entry_builder().set("ScenarioNo", 5).insert().unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(jmap.entries()[0].get_int(jmap.hash_table(), "ScenarioNo"), Some(5));
+        assert_eq!(jmap.entries()[0].get_string(jmap.hash_table(), "ZoneName"), Some("Galaxy"));
+
+        let index = jmap
+            .entry_builder()
+            .set_hash(calc_hash("ZoneName"), "Other")
+            .insert_at(0)
+            .unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(jmap.entries()[0].get_string(jmap.hash_table(), "ZoneName"), Some("Other"));
+
+        let err = jmap.entry_builder().set("ScenarioNo", 1).set("Unknown", 1).insert();
+        assert!(matches!(err, Err(JMapError::FieldNotFound(_))));
+        let err = jmap.entry_builder().set("ZoneName", 1.0f32).insert();
+        assert!(matches!(err, Err(JMapError::TypeMismatch { .. })));
+        let err = jmap.entry_builder().insert_at(3);
+        assert!(matches!(err, Err(JMapError::EntryIndexOutOfBounds { .. })));
+        assert_eq!(jmap.len(), 2);
+    }
+
+    #[test]
+    fn test_entry_equality() {
+        let mut a = super::Entry::new();
+        a.set_by_hash(1, FieldValue::Int(1));
+        a.set_by_hash(2, FieldValue::Float(f32::NAN));
+        a.set_by_hash(3, FieldValue::from("x"));
+
+        let mut b = super::Entry::new();
+        b.set_by_hash(3, FieldValue::from("x"));
+        b.set_by_hash(2, FieldValue::Float(f32::NAN));
+        b.set_by_hash(1, FieldValue::Int(1));
+
+        assert_eq!(a, b);
+        assert_eq!(a.content_hash(), b.content_hash());
+
+        let set: std::collections::HashSet<_> = [a.clone(), b].into_iter().collect();
+        assert_eq!(set.len(), 1);
+
+        let mut base = a.clone();
+        base.set_by_hash(4, FieldValue::Float(1.0));
+        let mut near = base.clone();
+        near.set_by_hash(4, FieldValue::Float(1.005));
+        assert_ne!(base, near);
+        assert!(base.approx_eq(&near, 0.01));
+        let mut far = base.clone();
+        far.set_by_hash(4, FieldValue::Float(1.02));
+        assert!(!base.approx_eq(&far, 0.01));
+
+        let mut c = a.clone();
+        c.set_by_hash(2, FieldValue::Float(-f32::NAN));
+        assert_ne!(a, c);
+        c.remove_by_hash(2);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_merge_from() {
+        let mut base = super::Entry::new();
+        base.set_by_hash(1, FieldValue::Int(1));
+        base.set_by_hash(2, FieldValue::Int(2));
+
+        let mut patch = super::Entry::new();
+        patch.set_by_hash(2, FieldValue::Int(20));
+        patch.set_by_hash(3, FieldValue::Int(30));
+
+        let mut merged = base.clone();
+        assert_eq!(merged.merge_from(&patch, false), 1);
+        assert_eq!(merged.get_int_by_hash(2), Some(2));
+        assert_eq!(merged.get_int_by_hash(3), Some(30));
+
+        let mut merged = base.clone();
+        assert_eq!(merged.merge_from(&patch, true), 2);
+        assert_eq!(merged.get_int_by_hash(1), Some(1));
+        assert_eq!(merged.get_int_by_hash(2), Some(20));
+
+        let mut merged = base.clone();
+        assert_eq!(merged.merge_from_fields(&patch, &[2, 4], true), 1);
+        assert_eq!(merged.get_int_by_hash(2), Some(20));
+        assert!(!merged.contains_hash(3));
+    }
+}
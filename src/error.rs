@@ -1,8 +1,22 @@
 use thiserror::Error;
 
+use crate::hash::HashAlgorithm;
+
 /// Result type alias for JMap operations
 pub type Result<T> = std::result::Result<T, JMapError>;
 
+/// A value could not be converted to the requested type without loss
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Cannot convert {from} value '{value}' to {to}")]
+pub struct ConversionError {
+    /// Type name of the source value
+    pub from: &'static str,
+    /// Name of the target type
+    pub to: &'static str,
+    /// The source value, formatted
+    pub value: String,
+}
+
 /// Errors that can occur during JMap operations
 #[derive(Error, Debug)]
 pub enum JMapError {
@@ -14,17 +28,44 @@ pub enum JMapError {
     #[error("Field not found: {0}")]
     FieldNotFound(String),
 
+    /// Field definition is inconsistent
+    #[error("Invalid field: {0}")]
+    InvalidField(String),
+
     /// Field already exists in the container
     #[error("Field already exists: {0}")]
     FieldAlreadyExists(String),
 
     /// Type mismatch when setting a field value
-    #[error("Type mismatch: expected {expected}, got {got}")]
+    #[error("Type mismatch for {field}: expected {expected}, got {got}")]
     TypeMismatch {
+        /// Name of the field, or its hash as `[XXXXXXXX]` when no name is known
+        field: String,
         expected: &'static str,
         got: &'static str,
     },
 
+    /// Error raised while processing a specific entry
+    #[error("Entry {index}: {source}")]
+    InEntry {
+        index: usize,
+        #[source]
+        source: Box<JMapError>,
+    },
+
+    /// Error raised while reading or writing a value of BCSV data, with where it happened
+    #[error("At offset 0x{offset:X}{}: {source}", location(*.entry, .field.as_deref()))]
+    Parse {
+        /// Offset of the value in the BCSV data
+        offset: usize,
+        /// Index of the entry holding the value
+        entry: Option<usize>,
+        /// Name of the field, or its `[XXXXXXXX]` form if the name is unknown
+        field: Option<String>,
+        #[source]
+        source: Box<JMapError>,
+    },
+
     /// Entry index out of bounds
     #[error("Entry index out of bounds: {index} (len: {len})")]
     EntryIndexOutOfBounds { index: usize, len: usize },
@@ -33,6 +74,26 @@ pub enum JMapError {
     #[error("Buffer too small: expected at least {expected} bytes, got {got}")]
     BufferTooSmall { expected: usize, got: usize },
 
+    /// A field table defines the same hash twice, see [`DuplicateFieldPolicy`](crate::io::DuplicateFieldPolicy)
+    #[error("Duplicate field [{hash:08X}]: defined by fields {first_index} and {second_index}")]
+    DuplicateField {
+        /// Hash of the field name
+        hash: u32,
+        /// Index of the first record defining it in the field table
+        first_index: usize,
+        /// Index of the record defining it again
+        second_index: usize,
+    },
+
+    /// An offset, a count or the length of BCSV data does not fit in the 32 bits of the format
+    #[error("{what} of {value} does not fit in 32 bits")]
+    SizeOverflow {
+        /// What overflowed, such as `string table offset`
+        what: &'static str,
+        /// The value, `u64::MAX` if it does not fit in 64 bits either
+        value: u64,
+    },
+
     /// Invalid BCSV header
     #[error("Invalid BCSV header")]
     InvalidHeader,
@@ -45,6 +106,22 @@ pub enum JMapError {
     #[error("CSV error: {0}")]
     CsvError(String),
 
+    /// JSON parsing or writing error
+    #[error("JSON error: {0}")]
+    JsonError(String),
+
+    /// YAML parsing error
+    #[error("YAML error: {0}")]
+    YamlError(String),
+
+    /// SQLite error
+    #[error("SQLite error: {0}")]
+    SqliteError(String),
+
+    /// Value conversion error
+    #[error(transparent)]
+    Conversion(#[from] ConversionError),
+
     /// I/O error
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
@@ -53,9 +130,43 @@ pub enum JMapError {
     #[error("Lookup file not found: {0}")]
     LookupFileNotFound(String),
 
+    /// Two different field names have the same hash
+    #[error("Hash collision for [{hash:08X}]: {existing}, {new}")]
+    HashCollision {
+        hash: u32,
+        /// The name the hash already belongs to
+        existing: String,
+        /// The name that was being added
+        new: String,
+    },
+
+    /// Two hash tables use different algorithms
+    #[error("Hash algorithm mismatch: {0:?} and {1:?}")]
+    HashAlgorithmMismatch(HashAlgorithm, HashAlgorithm),
+
+    /// A patch does not apply cleanly
+    #[error("Patch conflict: {0}")]
+    PatchConflict(String),
+
     /// Invalid CSV field descriptor format
     #[error("Invalid CSV field descriptor: {0}")]
     InvalidCsvFieldDescriptor(String),
+
+    /// An argument cannot be used, such as an empty list of tables
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+}
+
+/// Format the entry and field of a `JMapError::Parse`, like `, entry 37, field ShapeModelNo`
+fn location(entry: Option<usize>, field: Option<&str>) -> String {
+    let mut location = String::new();
+    if let Some(entry) = entry {
+        location += &format!(", entry {}", entry);
+    }
+    if let Some(field) = field {
+        location += &format!(", field {}", field);
+    }
+    location
 }
 
 impl From<csv::Error> for JMapError {
@@ -63,3 +174,17 @@ impl From<csv::Error> for JMapError {
         JMapError::CsvError(err.to_string())
     }
 }
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for JMapError {
+    fn from(err: serde_json::Error) -> Self {
+        JMapError::JsonError(err.to_string())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for JMapError {
+    fn from(err: rusqlite::Error) -> Self {
+        JMapError::SqliteError(err.to_string())
+    }
+}
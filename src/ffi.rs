@@ -0,0 +1,467 @@
+//! C bindings for reading, querying, editing and writing BCSV files
+//!
+//! Handles are opaque pointers created by `bcsv_*_load` / `bcsv_jmap_from_file` and released with the
+//! matching `*_free` function. Strings are NUL-terminated UTF-8, both in and out.
+//!
+//! Functions returning `int` return `0` on success and `-1` on failure. Functions returning a pointer return
+//! `NULL` on failure. After a failure, [`bcsv_last_error`] describes it. Panics are caught at the boundary
+//! and reported as errors. The declarations are in `include/bcsv_jmap.h`
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use crate::error::{JMapError, Result};
+use crate::field::FieldValue;
+use crate::hash::{smg_hash_table_with_lookup, FileHashTable};
+use crate::io::{from_file, to_file, Encoding, IoOptions};
+use crate::jmap::JMapInfo;
+
+/// Opaque handle to a hash table
+pub type BcsvHashTable = FileHashTable;
+/// Opaque handle to a JMapInfo
+pub type BcsvJMap = JMapInfo<FileHashTable>;
+
+/// Options for reading and writing, see [`IoOptions`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct BcsvIoOptions {
+    /// Whether data is big-endian
+    pub big_endian: bool,
+    /// Whether strings are UTF-8 rather than Shift-JIS
+    pub utf8: bool,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', "\\0")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run `f`, turning errors and panics into `failure` and the last error
+fn guard<T>(failure: T, f: impl FnOnce() -> Result<T>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            set_last_error(err.to_string());
+            failure
+        }
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(format!("Panic: {}", message));
+            failure
+        }
+    }
+}
+
+unsafe fn string_arg<'a>(s: *const c_char, what: &str) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(JMapError::InvalidField(format!("{} is NULL", what)));
+    }
+    unsafe { CStr::from_ptr(s) }
+        .to_str()
+        .map_err(|_| JMapError::EncodingError(format!("{} is not valid UTF-8", what)))
+}
+
+unsafe fn handle<'a, T>(p: *const T) -> Result<&'a T> {
+    unsafe { p.as_ref() }.ok_or_else(|| JMapError::InvalidField("handle is NULL".to_string()))
+}
+
+unsafe fn handle_mut<'a, T>(p: *mut T) -> Result<&'a mut T> {
+    unsafe { p.as_mut() }.ok_or_else(|| JMapError::InvalidField("handle is NULL".to_string()))
+}
+
+unsafe fn io_options(options: *const BcsvIoOptions) -> IoOptions {
+    let mut io = IoOptions::default();
+    if let Some(options) = unsafe { options.as_ref() } {
+        io.big_endian = options.big_endian;
+        io.encoding = if options.utf8 { Encoding::Utf8 } else { Encoding::ShiftJis };
+    }
+    io
+}
+
+fn value<'a>(jmap: &'a BcsvJMap, index: usize, name: &str) -> Result<&'a FieldValue> {
+    if index >= jmap.len() {
+        return Err(JMapError::EntryIndexOutOfBounds { index, len: jmap.len() });
+    }
+    jmap.get_value(index, name)
+        .ok_or_else(|| JMapError::FieldNotFound(name.to_string()))
+}
+
+fn mismatch(name: &str, expected: &'static str, value: &FieldValue) -> JMapError {
+    JMapError::TypeMismatch {
+        field: name.to_string(),
+        expected,
+        got: value.type_name(),
+    }
+}
+
+/// Get the message of the last error raised on this thread
+///
+/// # Returns
+/// The message, valid until the next failing call on this thread, or `NULL` if nothing failed yet
+#[unsafe(no_mangle)]
+pub extern "C" fn bcsv_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+/// Load a Super Mario Galaxy hash table from a lookup file
+///
+/// # Safety
+/// `path` must be a NUL-terminated string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_hashtable_load(path: *const c_char) -> *mut BcsvHashTable {
+    guard(ptr::null_mut(), || {
+        let path = unsafe { string_arg(path, "path") }?;
+        Ok(Box::into_raw(Box::new(smg_hash_table_with_lookup(path)?)))
+    })
+}
+
+/// Release a hash table, `NULL` being ignored
+///
+/// # Safety
+/// `hash_table` must come from [`bcsv_hashtable_load`] and not be used afterwards
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_hashtable_free(hash_table: *mut BcsvHashTable) {
+    if !hash_table.is_null() {
+        drop(unsafe { Box::from_raw(hash_table) });
+    }
+}
+
+/// Read a BCSV file, the JMapInfo getting its own copy of the hash table
+///
+/// # Safety
+/// `hash_table` must be a live handle, `path` a NUL-terminated string and `options` `NULL` for the defaults
+/// or a valid pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_jmap_from_file(
+    hash_table: *const BcsvHashTable,
+    path: *const c_char,
+    options: *const BcsvIoOptions,
+) -> *mut BcsvJMap {
+    guard(ptr::null_mut(), || {
+        let hash_table = unsafe { handle(hash_table) }?.clone();
+        let path = unsafe { string_arg(path, "path") }?;
+        let jmap = from_file(hash_table, path, &unsafe { io_options(options) })?;
+        Ok(Box::into_raw(Box::new(jmap)))
+    })
+}
+
+/// Write a JMapInfo to a BCSV file
+///
+/// # Safety
+/// `jmap` must be a live handle, `path` a NUL-terminated string and `options` `NULL` for the defaults
+/// or a valid pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_jmap_to_file(
+    jmap: *const BcsvJMap,
+    path: *const c_char,
+    options: *const BcsvIoOptions,
+) -> c_int {
+    guard(-1, || {
+        let jmap = unsafe { handle(jmap) }?;
+        let path = unsafe { string_arg(path, "path") }?;
+        to_file(jmap, path, &unsafe { io_options(options) })?;
+        Ok(0)
+    })
+}
+
+/// Release a JMapInfo, `NULL` being ignored
+///
+/// # Safety
+/// `jmap` must come from [`bcsv_jmap_from_file`] and not be used afterwards
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_jmap_free(jmap: *mut BcsvJMap) {
+    if !jmap.is_null() {
+        drop(unsafe { Box::from_raw(jmap) });
+    }
+}
+
+/// Get the number of entries, `0` for `NULL`
+///
+/// # Safety
+/// `jmap` must be a live handle or `NULL`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_jmap_num_entries(jmap: *const BcsvJMap) -> usize {
+    guard(0, || Ok(unsafe { handle(jmap) }?.len()))
+}
+
+/// Get the number of fields, `0` for `NULL`
+///
+/// # Safety
+/// `jmap` must be a live handle or `NULL`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_jmap_num_fields(jmap: *const BcsvJMap) -> usize {
+    guard(0, || Ok(unsafe { handle(jmap) }?.num_fields()))
+}
+
+/// Append an entry filled with the field defaults
+///
+/// # Safety
+/// `jmap` must be a live handle
+///
+/// # Returns
+/// The index of the new entry, or `-1`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_jmap_create_entry(jmap: *mut BcsvJMap) -> isize {
+    guard(-1, || {
+        let jmap = unsafe { handle_mut(jmap) }?;
+        jmap.create_entry();
+        Ok(jmap.len() as isize - 1)
+    })
+}
+
+/// Read an integer value (`Int`, `Short` or `Char` field) into `out`
+///
+/// # Safety
+/// `jmap` must be a live handle, `name` a NUL-terminated string and `out` a valid pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_entry_get_int(
+    jmap: *const BcsvJMap,
+    index: usize,
+    name: *const c_char,
+    out: *mut i32,
+) -> c_int {
+    guard(-1, || {
+        let out = unsafe { handle_mut(out) }?;
+        let name = unsafe { string_arg(name, "name") }?;
+        match value(unsafe { handle(jmap) }?, index, name)? {
+            FieldValue::Int(v) => *out = *v,
+            FieldValue::UInt(v) => *out = *v as i32,
+            other => return Err(mismatch(name, "Int", other)),
+        }
+        Ok(0)
+    })
+}
+
+/// Read a float value into `out`
+///
+/// # Safety
+/// `jmap` must be a live handle, `name` a NUL-terminated string and `out` a valid pointer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_entry_get_float(
+    jmap: *const BcsvJMap,
+    index: usize,
+    name: *const c_char,
+    out: *mut f32,
+) -> c_int {
+    guard(-1, || {
+        let out = unsafe { handle_mut(out) }?;
+        let name = unsafe { string_arg(name, "name") }?;
+        match value(unsafe { handle(jmap) }?, index, name)? {
+            FieldValue::Float(v) => *out = *v,
+            other => return Err(mismatch(name, "Float", other)),
+        }
+        Ok(0)
+    })
+}
+
+unsafe fn get_string<'a>(jmap: *const BcsvJMap, index: usize, name: *const c_char) -> Result<&'a str> {
+    let jmap = unsafe { handle(jmap) }?;
+    let name = unsafe { string_arg(name, "name") }?;
+    match value(jmap, index, name)? {
+        FieldValue::String(v) => Ok(v.as_str()),
+        other => Err(mismatch(name, "String", other)),
+    }
+}
+
+/// Copy a string value into a buffer provided by the caller
+///
+/// The copy is truncated to `buffer_len - 1` bytes and always NUL-terminated when `buffer_len` is not zero.
+/// Call with a `NULL` buffer to get the size to allocate
+///
+/// # Safety
+/// `jmap` must be a live handle, `name` a NUL-terminated string and `buffer` `NULL` or valid for `buffer_len` bytes
+///
+/// # Returns
+/// The length of the value in bytes, without the terminator, or `-1`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_entry_get_string(
+    jmap: *const BcsvJMap,
+    index: usize,
+    name: *const c_char,
+    buffer: *mut c_char,
+    buffer_len: usize,
+) -> isize {
+    guard(-1, || {
+        let value = unsafe { get_string(jmap, index, name) }?;
+        if !buffer.is_null() && buffer_len > 0 {
+            let copied = value.len().min(buffer_len - 1);
+            unsafe {
+                ptr::copy_nonoverlapping(value.as_ptr(), buffer.cast::<u8>(), copied);
+                *buffer.add(copied) = 0;
+            }
+        }
+        Ok(value.len() as isize)
+    })
+}
+
+/// Get a copy of a string value, to release with [`bcsv_string_free`]
+///
+/// # Safety
+/// `jmap` must be a live handle and `name` a NUL-terminated string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_entry_get_string_owned(
+    jmap: *const BcsvJMap,
+    index: usize,
+    name: *const c_char,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let value = unsafe { get_string(jmap, index, name) }?;
+        let value = CString::new(value)
+            .map_err(|_| JMapError::EncodingError("string contains a NUL byte".to_string()))?;
+        Ok(value.into_raw())
+    })
+}
+
+/// Release a string returned by [`bcsv_entry_get_string_owned`], `NULL` being ignored
+///
+/// # Safety
+/// `s` must come from [`bcsv_entry_get_string_owned`] and not be used afterwards
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+unsafe fn set(
+    jmap: *mut BcsvJMap,
+    index: usize,
+    name: *const c_char,
+    value: impl FnOnce() -> Result<FieldValue>,
+) -> c_int {
+    guard(-1, || {
+        let jmap = unsafe { handle_mut(jmap) }?;
+        jmap.set_value(index, unsafe { string_arg(name, "name") }?, value()?)?;
+        Ok(0)
+    })
+}
+
+/// Set an integer value
+///
+/// # Safety
+/// `jmap` must be a live handle and `name` a NUL-terminated string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_entry_set_int(jmap: *mut BcsvJMap, index: usize, name: *const c_char, value: i32) -> c_int {
+    unsafe { set(jmap, index, name, || Ok(FieldValue::Int(value))) }
+}
+
+/// Set a float value
+///
+/// # Safety
+/// `jmap` must be a live handle and `name` a NUL-terminated string
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_entry_set_float(
+    jmap: *mut BcsvJMap,
+    index: usize,
+    name: *const c_char,
+    value: f32,
+) -> c_int {
+    unsafe { set(jmap, index, name, || Ok(FieldValue::Float(value))) }
+}
+
+/// Set a string value
+///
+/// # Safety
+/// `jmap` must be a live handle, `name` and `value` NUL-terminated strings
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bcsv_entry_set_string(
+    jmap: *mut BcsvJMap,
+    index: usize,
+    name: *const c_char,
+    value: *const c_char,
+) -> c_int {
+    unsafe {
+        set(jmap, index, name, || {
+            Ok(FieldValue::String(string_arg(value, "value")?.to_string()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::FieldType;
+    use crate::hash::smg_hash_table;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(bcsv_last_error()) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_ffi() {
+        let dir = std::env::temp_dir();
+        let names_path = c(dir.join("bcsv_jmap_test_ffi_names.txt").to_str().unwrap());
+        let bcsv_path = c(dir.join("bcsv_jmap_test_ffi.bcsv").to_str().unwrap());
+        std::fs::write(names_path.to_str().unwrap(), "ZoneName\nScenarioNo\nPosX\n").unwrap();
+
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("PosX", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+        let entry = jmap.create_entry();
+        entry.set_by_hash(crate::hash::calc_hash("ZoneName"), FieldValue::String("Beach".into()));
+        to_file(&jmap, bcsv_path.to_str().unwrap(), &IoOptions::default()).unwrap();
+
+        unsafe {
+            let ht = bcsv_hashtable_load(names_path.as_ptr());
+            assert!(!ht.is_null());
+            let handle = bcsv_jmap_from_file(ht, bcsv_path.as_ptr(), ptr::null());
+            bcsv_hashtable_free(ht);
+            assert!(!handle.is_null());
+            assert_eq!((bcsv_jmap_num_entries(handle), bcsv_jmap_num_fields(handle)), (1, 3));
+
+            assert_eq!(bcsv_jmap_create_entry(handle), 1);
+            assert_eq!(bcsv_entry_set_int(handle, 1, c("ScenarioNo").as_ptr(), 7), 0);
+            assert_eq!(bcsv_entry_set_float(handle, 1, c("PosX").as_ptr(), 1.5), 0);
+            assert_eq!(bcsv_entry_set_string(handle, 1, c("ZoneName").as_ptr(), c("Dome").as_ptr()), 0);
+            let options = BcsvIoOptions {
+                big_endian: false,
+                utf8: true,
+            };
+            assert_eq!(bcsv_jmap_to_file(handle, bcsv_path.as_ptr(), &options), 0);
+            bcsv_jmap_free(handle);
+
+            let ht = bcsv_hashtable_load(names_path.as_ptr());
+            let handle = bcsv_jmap_from_file(ht, bcsv_path.as_ptr(), &options);
+            bcsv_hashtable_free(ht);
+            let (mut int, mut float) = (0, 0.0);
+            assert_eq!(bcsv_entry_get_int(handle, 1, c("ScenarioNo").as_ptr(), &mut int), 0);
+            assert_eq!(bcsv_entry_get_float(handle, 1, c("PosX").as_ptr(), &mut float), 0);
+            assert_eq!((int, float), (7, 1.5));
+
+            let mut buffer = [0 as c_char; 3];
+            let len = bcsv_entry_get_string(handle, 0, c("ZoneName").as_ptr(), buffer.as_mut_ptr(), buffer.len());
+            assert_eq!(len, 5);
+            assert_eq!(CStr::from_ptr(buffer.as_ptr()).to_str(), Ok("Be"));
+            let owned = bcsv_entry_get_string_owned(handle, 1, c("ZoneName").as_ptr());
+            assert_eq!(CStr::from_ptr(owned).to_str(), Ok("Dome"));
+            bcsv_string_free(owned);
+
+            assert_eq!(bcsv_entry_get_int(handle, 0, c("PosX").as_ptr(), &mut int), -1);
+            assert_eq!(last_error(), "Type mismatch for PosX: expected Int, got Float");
+            assert_eq!(bcsv_entry_get_int(handle, 9, c("ScenarioNo").as_ptr(), &mut int), -1);
+            assert!(last_error().starts_with("Entry index out of bounds"));
+            assert_eq!(bcsv_entry_set_int(handle, 0, c("ZoneName").as_ptr(), 1), -1);
+            assert_eq!(bcsv_entry_get_int(handle, 0, ptr::null(), &mut int), -1);
+            assert_eq!(last_error(), "Invalid field: name is NULL");
+            bcsv_jmap_free(handle);
+
+            assert!(bcsv_jmap_from_file(ptr::null(), bcsv_path.as_ptr(), ptr::null()).is_null());
+            assert_eq!(bcsv_jmap_num_entries(ptr::null()), 0);
+        }
+    }
+}
@@ -1,12 +1,21 @@
+use std::cmp::Ordering;
 use std::fmt;
 
+use crate::error::{ConversionError, JMapError, Result};
+use crate::hash::HashTable;
+
+/// Size in bytes of inline `String` fields in Nintendo's files, see
+/// [`IoOptions::inline_string_size`](crate::io::IoOptions::inline_string_size)
+pub const DEFAULT_INLINE_STRING_SIZE: usize = 32;
+
 /// Data types supported by BCSV format
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum FieldType {
     /// Signed 32-bit integer - (4 bytes)
     Long = 0,
-    /// Inline string - (32 bytes fixed). Deprecated
+    /// Inline string - (32 bytes fixed, or `IoOptions::inline_string_size`). Deprecated
     String = 1,
     /// 32-bit floating point (4 bytes)
     Float = 2,
@@ -21,11 +30,16 @@ pub enum FieldType {
 }
 
 impl FieldType {
-    /// Size in bytes for this field type
+    /// Size in bytes for this field type, with inline strings of [`DEFAULT_INLINE_STRING_SIZE`] bytes
     pub const fn size(&self) -> usize {
+        self.size_with(DEFAULT_INLINE_STRING_SIZE)
+    }
+
+    /// Size in bytes for this field type, with inline strings of `inline_string_size` bytes
+    pub const fn size_with(&self, inline_string_size: usize) -> usize {
         match self {
             FieldType::Long => 4,
-            FieldType::String => 32,
+            FieldType::String => inline_string_size,
             FieldType::Float => 4,
             FieldType::UnsignedLong => 4,
             FieldType::Short => 2,
@@ -60,6 +74,14 @@ impl FieldType {
         }
     }
 
+    /// Whether values of this type are stored as integers
+    pub const fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            FieldType::Long | FieldType::UnsignedLong | FieldType::Short | FieldType::Char
+        )
+    }
+
     /// Parse field type from raw byte value
     pub fn from_raw(value: u8) -> Option<Self> {
         match value {
@@ -108,11 +130,50 @@ impl fmt::Display for FieldType {
     }
 }
 
+/// Same format as [`Field::describe`], with the hash in `[XXXXXXXX]` form in place of the name
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.describe_columns(&format!("[{:08X}]", self.hash), [0; 3]))
+    }
+}
+
+/// How floats are converted to integers by the checked conversions of `FieldValue`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatToInt {
+    /// Only floats holding a whole number are accepted
+    #[default]
+    Exact,
+    /// The fractional part is dropped
+    Truncate,
+    /// The float is rounded to the nearest integer, halfway cases away from zero
+    Round,
+}
+
+/// Parse an integer literal, accepting an optional sign and a `0x`/`0X` prefix for hex
+pub(crate) fn parse_int_literal(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (negative, digits) = match s.as_bytes().first()? {
+        b'-' => (true, &s[1..]),
+        b'+' => (false, &s[1..]),
+        _ => (false, s),
+    };
+
+    let magnitude = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None if digits.bytes().all(|b| b.is_ascii_digit()) => digits.parse::<i64>().ok()?,
+        None => return None,
+    };
+
+    Some(if negative { -magnitude } else { magnitude })
+}
+
 /// A value that can be stored in a JMap field
 #[derive(Debug, Clone, PartialEq)]
 pub enum FieldValue {
-    /// Integer value (for Long, UnsignedLong, Short, Char)
+    /// Integer value (for Long, Short, Char, and accepted for UnsignedLong)
     Int(i32),
+    /// Unsigned integer value (for UnsignedLong)
+    UInt(u32),
     /// Floating point value
     Float(f32),
     /// String value (for String or StringOffset)
@@ -123,33 +184,57 @@ impl FieldValue {
     /// Get the default value for a field type
     pub fn default_for(field_type: FieldType) -> Self {
         match field_type {
-            FieldType::Long
-            | FieldType::UnsignedLong
-            | FieldType::Short
-            | FieldType::Char => FieldValue::Int(0),
+            FieldType::Long | FieldType::Short | FieldType::Char => FieldValue::Int(0),
+            FieldType::UnsignedLong => FieldValue::UInt(0),
             FieldType::Float => FieldValue::Float(0.0),
             FieldType::String | FieldType::StringOffset => FieldValue::String(String::new()),
         }
     }
 
     /// Check if this value is compatible with a field type
+    ///
+    /// `Int` values are also accepted for `UnsignedLong` fields, their bits are reinterpreted as `u32` when written
     pub fn is_compatible_with(&self, field_type: FieldType) -> bool {
-        match (self, field_type) {
+        matches!(
+            (self, field_type),
             (FieldValue::Int(_), FieldType::Long)
-            | (FieldValue::Int(_), FieldType::UnsignedLong)
-            | (FieldValue::Int(_), FieldType::Short)
-            | (FieldValue::Int(_), FieldType::Char) => true,
-            (FieldValue::Float(_), FieldType::Float) => true,
-            (FieldValue::String(_), FieldType::String)
-            | (FieldValue::String(_), FieldType::StringOffset) => true,
-            _ => false,
-        }
+                | (FieldValue::Int(_), FieldType::UnsignedLong)
+                | (FieldValue::Int(_), FieldType::Short)
+                | (FieldValue::Int(_), FieldType::Char)
+                | (FieldValue::UInt(_), FieldType::UnsignedLong)
+                | (FieldValue::Float(_), FieldType::Float)
+                | (FieldValue::String(_), FieldType::String)
+                | (FieldValue::String(_), FieldType::StringOffset)
+        )
     }
 
-    /// Get as integer, if this is an Int value
+    /// Get as integer, if this is an Int value or a UInt value that fits in an `i32`
     pub fn as_int(&self) -> Option<i32> {
         match self {
             FieldValue::Int(v) => Some(*v),
+            FieldValue::UInt(v) => i32::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Get as unsigned integer, if this is a UInt or Int value
+    ///
+    /// The bits of Int values are reinterpreted as `u32`, so `-1` reads as `0xFFFFFFFF`
+    pub fn as_uint(&self) -> Option<u32> {
+        match self {
+            FieldValue::UInt(v) => Some(*v),
+            FieldValue::Int(v) => Some(*v as u32),
+            _ => None,
+        }
+    }
+
+    /// Get as boolean, if this is an Int or UInt value
+    ///
+    /// Any nonzero value is `true`, so sentinels such as `-1` also read as `true`
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FieldValue::Int(v) => Some(*v != 0),
+            FieldValue::UInt(v) => Some(*v != 0),
             _ => None,
         }
     }
@@ -170,10 +255,160 @@ impl FieldValue {
         }
     }
 
+    /// Build the error for a failed conversion of this value
+    fn conversion_error(&self, to: &'static str) -> ConversionError {
+        ConversionError {
+            from: self.type_name(),
+            to,
+            value: self.to_string(),
+        }
+    }
+
+    /// Convert to a wide integer, the shared step of the checked integer conversions
+    fn to_i64(&self, to: &'static str, mode: FloatToInt) -> std::result::Result<i64, ConversionError> {
+        match self {
+            FieldValue::Int(v) => Ok(*v as i64),
+            FieldValue::UInt(v) => Ok(*v as i64),
+            FieldValue::Float(v) => {
+                let converted = match mode {
+                    FloatToInt::Exact if v.fract() == 0.0 => *v,
+                    FloatToInt::Exact => return Err(self.conversion_error(to)),
+                    FloatToInt::Truncate => v.trunc(),
+                    FloatToInt::Round => v.round(),
+                };
+                // NaN and infinities fail this range check too
+                if converted >= i64::MIN as f32 && converted < i64::MAX as f32 {
+                    Ok(converted as i64)
+                } else {
+                    Err(self.conversion_error(to))
+                }
+            }
+            FieldValue::String(s) => parse_int_literal(s).ok_or_else(|| self.conversion_error(to)),
+        }
+    }
+
+    /// Convert to an integer type, failing if the value does not fit
+    fn to_integer<T: TryFrom<i64>>(&self, to: &'static str, mode: FloatToInt) -> std::result::Result<T, ConversionError> {
+        let wide = self.to_i64(to, mode)?;
+        T::try_from(wide).map_err(|_| self.conversion_error(to))
+    }
+
+    /// Convert to `i32`, failing instead of truncating
+    ///
+    /// Floats must hold a whole number and strings are parsed, accepting `0x` hex literals
+    pub fn to_i32(&self) -> std::result::Result<i32, ConversionError> {
+        self.to_i32_with(FloatToInt::Exact)
+    }
+
+    /// Convert to `i32`, converting floats with the given mode
+    pub fn to_i32_with(&self, mode: FloatToInt) -> std::result::Result<i32, ConversionError> {
+        self.to_integer("i32", mode)
+    }
+
+    /// Convert to `u32`, failing instead of truncating
+    ///
+    /// Negative values fail, use [`FieldValue::as_uint`] to reinterpret the bits instead
+    pub fn to_u32(&self) -> std::result::Result<u32, ConversionError> {
+        self.to_u32_with(FloatToInt::Exact)
+    }
+
+    /// Convert to `u32`, converting floats with the given mode
+    pub fn to_u32_with(&self, mode: FloatToInt) -> std::result::Result<u32, ConversionError> {
+        self.to_integer("u32", mode)
+    }
+
+    /// Convert to `i16`, failing instead of truncating
+    pub fn to_i16(&self) -> std::result::Result<i16, ConversionError> {
+        self.to_i16_with(FloatToInt::Exact)
+    }
+
+    /// Convert to `i16`, converting floats with the given mode
+    pub fn to_i16_with(&self, mode: FloatToInt) -> std::result::Result<i16, ConversionError> {
+        self.to_integer("i16", mode)
+    }
+
+    /// Convert to `i8`, failing instead of truncating
+    pub fn to_i8(&self) -> std::result::Result<i8, ConversionError> {
+        self.to_i8_with(FloatToInt::Exact)
+    }
+
+    /// Convert to `i8`, converting floats with the given mode
+    pub fn to_i8_with(&self, mode: FloatToInt) -> std::result::Result<i8, ConversionError> {
+        self.to_integer("i8", mode)
+    }
+
+    /// Convert to `f32`
+    ///
+    /// Integers are always accepted, even if large values lose precision. Strings are parsed,
+    /// accepting `NaN`, `inf` and `-inf`
+    pub fn to_f32(&self) -> std::result::Result<f32, ConversionError> {
+        match self {
+            FieldValue::Int(v) => Ok(*v as f32),
+            FieldValue::UInt(v) => Ok(*v as f32),
+            FieldValue::Float(v) => Ok(*v),
+            FieldValue::String(s) => s.trim().parse().map_err(|_| self.conversion_error("f32")),
+        }
+    }
+
+    /// Format the value, with an optional fixed number of decimals for floats
+    ///
+    /// With `None`, floats use the shortest representation that reads back to the same value,
+    /// like `Display`. Non-float values are always formatted with `Display`
+    pub fn format_float(&self, precision: Option<usize>) -> String {
+        match (self, precision) {
+            (FieldValue::Float(v), Some(precision)) if v.is_finite() => format!("{:.*}", precision, v),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Compare two values, allowing floats to differ by up to `epsilon`
+    ///
+    /// `NaN` equals `NaN` and infinities only equal themselves. Other values must be equal
+    pub fn approx_eq(&self, other: &FieldValue, epsilon: f32) -> bool {
+        match (self, other) {
+            (FieldValue::Float(a), FieldValue::Float(b)) => {
+                if a.is_nan() || b.is_nan() {
+                    a.is_nan() && b.is_nan()
+                } else if a.is_infinite() || b.is_infinite() {
+                    a == b
+                } else {
+                    (a - b).abs() <= epsilon
+                }
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Compare two values with a total order, for sorting
+    ///
+    /// `Int` and `UInt` compare by numeric value and floats use [`f32::total_cmp`]. Values of
+    /// different kinds order numbers first, then floats, then strings
+    pub fn total_cmp(&self, other: &FieldValue) -> Ordering {
+        let rank = |value: &FieldValue| match value {
+            FieldValue::Int(_) | FieldValue::UInt(_) => 0,
+            FieldValue::Float(_) => 1,
+            FieldValue::String(_) => 2,
+        };
+        match (self, other) {
+            (FieldValue::Float(a), FieldValue::Float(b)) => a.total_cmp(b),
+            (FieldValue::String(a), FieldValue::String(b)) => a.cmp(b),
+            (FieldValue::Int(_) | FieldValue::UInt(_), FieldValue::Int(_) | FieldValue::UInt(_)) => {
+                let number = |value: &FieldValue| match *value {
+                    FieldValue::Int(v) => v as i64,
+                    FieldValue::UInt(v) => v as i64,
+                    _ => unreachable!(),
+                };
+                number(self).cmp(&number(other))
+            }
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+
     /// Get the type name for error messages
     pub fn type_name(&self) -> &'static str {
         match self {
             FieldValue::Int(_) => "Int",
+            FieldValue::UInt(_) => "UInt",
             FieldValue::Float(_) => "Float",
             FieldValue::String(_) => "String",
         }
@@ -184,18 +419,94 @@ impl fmt::Display for FieldValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             FieldValue::Int(v) => write!(f, "{}", v),
+            FieldValue::UInt(v) => write!(f, "{}", v),
             FieldValue::Float(v) => write!(f, "{}", v),
             FieldValue::String(v) => write!(f, "{}", v),
         }
     }
 }
 
+/// Values are serialized untagged, as a bare number or string, so `Int(3)` is `3` and `Float(1.5)` is `1.5`
+///
+/// On deserialization integers become `Int`, or `UInt` above `i32::MAX`, fractional numbers become `Float`
+/// and strings become `String`. Formats that do not keep `1.0` apart from `1` lose the distinction,
+/// which [`JMapInfo::from_document`](crate::jmap::JMapInfo::from_document) restores from the field type
+#[cfg(feature = "serde")]
+impl serde::Serialize for FieldValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            FieldValue::Int(v) => serializer.serialize_i32(*v),
+            FieldValue::UInt(v) => serializer.serialize_u32(*v),
+            FieldValue::Float(v) => serializer.serialize_f32(*v),
+            FieldValue::String(v) => serializer.serialize_str(v),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FieldValue {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = FieldValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a 32-bit integer, a float or a string")
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> std::result::Result<FieldValue, E> {
+                if let Ok(v) = i32::try_from(v) {
+                    Ok(FieldValue::Int(v))
+                } else if let Ok(v) = u32::try_from(v) {
+                    Ok(FieldValue::UInt(v))
+                } else {
+                    Err(E::invalid_value(serde::de::Unexpected::Signed(v), &self))
+                }
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> std::result::Result<FieldValue, E> {
+                match i64::try_from(v) {
+                    Ok(v) => self.visit_i64(v),
+                    Err(_) => Err(E::invalid_value(serde::de::Unexpected::Unsigned(v), &self)),
+                }
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, v: f64) -> std::result::Result<FieldValue, E> {
+                Ok(FieldValue::Float(v as f32))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> std::result::Result<FieldValue, E> {
+                Ok(FieldValue::String(v.to_string()))
+            }
+
+            fn visit_string<E: serde::de::Error>(self, v: String) -> std::result::Result<FieldValue, E> {
+                Ok(FieldValue::String(v))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
 impl From<i32> for FieldValue {
     fn from(v: i32) -> Self {
         FieldValue::Int(v)
     }
 }
 
+impl From<u32> for FieldValue {
+    fn from(v: u32) -> Self {
+        FieldValue::UInt(v)
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(v: bool) -> Self {
+        FieldValue::Int(v as i32)
+    }
+}
+
 impl From<f32> for FieldValue {
     fn from(v: f32) -> Self {
         FieldValue::Float(v)
@@ -216,6 +527,7 @@ impl From<&str> for FieldValue {
 
 /// Definition of a field (column) in a BCSV
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Field {
     /// Hash of the field name
     pub hash: u32,
@@ -257,8 +569,194 @@ impl Field {
         }
     }
 
+    /// Create a new bit-packed field with an explicit mask and shift
+    ///
+    /// The field is not validated, see [`Field::validate`]
+    pub fn with_mask_shift(hash: u32, field_type: FieldType, mask: u32, shift: u8) -> Self {
+        Self {
+            hash,
+            field_type,
+            mask,
+            shift,
+            offset: 0,
+            default: FieldValue::default_for(field_type),
+        }
+    }
+
     /// Size of this field in bytes
     pub fn size(&self) -> usize {
         self.field_type.size()
     }
+
+    /// Size of this field in bytes, with inline strings of `inline_string_size` bytes
+    pub fn size_with(&self, inline_string_size: usize) -> usize {
+        self.field_type.size_with(inline_string_size)
+    }
+
+    /// Describe this field with its name resolved through the hash table
+    ///
+    /// For example: `+0x10 ShapeModelNo Int mask=0xFFFFFFFF shift=0 default=0`
+    pub fn describe<H: HashTable>(&self, hash_table: &H) -> String {
+        self.describe_columns(&hash_table.find(self.hash), [0; 3])
+    }
+
+    /// Format the description with the offset, name and type columns padded to the given widths
+    pub(crate) fn describe_columns(&self, name: &str, widths: [usize; 3]) -> String {
+        format!(
+            "{:<w0$} {:<w1$} {:<w2$} mask=0x{:08X} shift={} default={}",
+            format!("+0x{:X}", self.offset),
+            name,
+            self.field_type.csv_name(),
+            self.mask,
+            self.shift,
+            self.default,
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+        )
+    }
+
+    /// Check that the mask and shift are consistent with the field type
+    ///
+    /// # Errors
+    /// - `JMapError::InvalidField` if the shift is 32 or more, the mask of a numeric field is zero,
+    ///   the mask does not fit within the type's byte width, or a string field has a non-canonical mask
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(JMapError::InvalidField(format!("[{:08X}] {}", self.hash, reason)));
+
+        if self.shift >= 32 {
+            return invalid(format!("shift {} must be less than 32", self.shift));
+        }
+
+        match self.field_type {
+            FieldType::String | FieldType::StringOffset => {
+                let canonical = self.field_type.default_mask();
+                if self.mask != canonical {
+                    return invalid(format!(
+                        "{} fields must have mask 0x{:08X}, got 0x{:08X}",
+                        self.field_type, canonical, self.mask
+                    ));
+                }
+            }
+            _ => {
+                if self.mask == 0 {
+                    return invalid("mask must not be zero".to_string());
+                }
+                let width = self.field_type.default_mask();
+                if self.mask & !width != 0 {
+                    return invalid(format!(
+                        "mask 0x{:08X} does not fit in a {}-byte {}",
+                        self.mask,
+                        self.size(),
+                        self.field_type
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let mut field = Field::with_mask_shift(0x1176D409, FieldType::Short, 0xFF00, 8);
+        field.offset = 0x10;
+        assert_eq!(field.to_string(), "+0x10 [1176D409] Short mask=0x0000FF00 shift=8 default=0");
+
+        let mut table = crate::hash::smg_hash_table();
+        table.add("ShapeModelNo");
+        assert_eq!(
+            field.describe(&table),
+            "+0x10 ShapeModelNo Short mask=0x0000FF00 shift=8 default=0"
+        );
+    }
+
+    #[test]
+    fn test_float_formatting() {
+        let value = FieldValue::Float(0.1 + 0.2);
+        assert_eq!(value.format_float(None), value.to_string());
+        let fixed = value.format_float(Some(3));
+        assert_eq!(fixed, "0.300");
+        assert_eq!(FieldValue::from(fixed.as_str()).to_f32().unwrap().to_bits(), 0.3f32.to_bits());
+
+        assert_eq!(FieldValue::Float(f32::NAN).format_float(Some(2)), "NaN");
+        assert_eq!(FieldValue::Float(f32::INFINITY).format_float(Some(2)), "inf");
+        assert_eq!(FieldValue::Float(f32::NEG_INFINITY).format_float(None), "-inf");
+        assert_eq!(FieldValue::Int(3).format_float(Some(2)), "3");
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let a = FieldValue::Float(1.0);
+        assert!(a.approx_eq(&FieldValue::Float(1.0), 0.0));
+        assert!(a.approx_eq(&FieldValue::Float(1.25), 0.25));
+        assert!(!a.approx_eq(&FieldValue::Float(1.25), 0.24));
+        assert!(FieldValue::Float(f32::NAN).approx_eq(&FieldValue::Float(f32::NAN), 0.0));
+        assert!(!FieldValue::Float(f32::NAN).approx_eq(&a, f32::INFINITY));
+        assert!(!FieldValue::Float(f32::INFINITY).approx_eq(&FieldValue::Float(f32::MAX), f32::MAX));
+        assert!(!FieldValue::Int(1).approx_eq(&a, 1.0));
+    }
+
+    #[test]
+    fn test_total_cmp() {
+        assert_eq!(FieldValue::Int(-1).total_cmp(&FieldValue::UInt(u32::MAX)), Ordering::Less);
+        assert_eq!(FieldValue::UInt(3).total_cmp(&FieldValue::Int(3)), Ordering::Equal);
+        assert_eq!(FieldValue::Float(-0.5).total_cmp(&FieldValue::Float(0.25)), Ordering::Less);
+        assert_eq!(FieldValue::Float(f32::NAN).total_cmp(&FieldValue::Float(f32::INFINITY)), Ordering::Greater);
+        assert_eq!(FieldValue::from("b").total_cmp(&FieldValue::from("a")), Ordering::Greater);
+        assert_eq!(FieldValue::Int(7).total_cmp(&FieldValue::Float(1.0)), Ordering::Less);
+    }
+
+    #[test]
+    fn test_integer_boundaries() {
+        assert_eq!(FieldValue::Int(i32::MIN).to_i32(), Ok(i32::MIN));
+        assert_eq!(FieldValue::Int(i16::MAX as i32).to_i16(), Ok(i16::MAX));
+        assert!(FieldValue::Int(i16::MAX as i32 + 1).to_i16().is_err());
+        assert_eq!(FieldValue::Int(i8::MIN as i32).to_i8(), Ok(i8::MIN));
+        assert!(FieldValue::Int(i8::MIN as i32 - 1).to_i8().is_err());
+        assert!(FieldValue::Int(-1).to_u32().is_err());
+        assert_eq!(FieldValue::UInt(u32::MAX).to_u32(), Ok(u32::MAX));
+        assert!(FieldValue::UInt(i32::MAX as u32 + 1).to_i32().is_err());
+
+        let err = FieldValue::Int(300).to_i8().unwrap_err();
+        assert_eq!(err.to_string(), "Cannot convert Int value '300' to i8");
+    }
+
+    #[test]
+    fn test_float_to_int() {
+        assert_eq!(FieldValue::Float(3.0).to_i32(), Ok(3));
+        assert!(FieldValue::Float(2.5).to_i32().is_err());
+        assert_eq!(FieldValue::Float(2.5).to_i32_with(FloatToInt::Truncate), Ok(2));
+        assert_eq!(FieldValue::Float(2.5).to_i32_with(FloatToInt::Round), Ok(3));
+        assert_eq!(FieldValue::Float(-2.5).to_i32_with(FloatToInt::Round), Ok(-3));
+        assert_eq!(FieldValue::Float(-1.9).to_i8_with(FloatToInt::Truncate), Ok(-1));
+
+        for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 1e30, -1e30] {
+            assert!(FieldValue::Float(value).to_i32_with(FloatToInt::Truncate).is_err());
+        }
+        assert!(FieldValue::Float(3e9).to_i32_with(FloatToInt::Round).is_err());
+        assert_eq!(FieldValue::Float(3e9).to_u32_with(FloatToInt::Round), Ok(3_000_000_000));
+    }
+
+    #[test]
+    fn test_string_to_number() {
+        assert_eq!(FieldValue::from("42").to_i32(), Ok(42));
+        assert_eq!(FieldValue::from("+42").to_i16(), Ok(42));
+        assert_eq!(FieldValue::from("0xFFFFFFFF").to_u32(), Ok(u32::MAX));
+        assert_eq!(FieldValue::from("-0x10").to_i32(), Ok(-16));
+        assert_eq!(FieldValue::from("0x7f").to_i8(), Ok(127));
+        assert!(FieldValue::from("0x80").to_i8().is_err());
+        assert!(FieldValue::from("abc").to_i32().is_err());
+        assert!(FieldValue::from("").to_i32().is_err());
+
+        assert_eq!(FieldValue::from("1.5").to_f32(), Ok(1.5));
+        assert!(FieldValue::from("NaN").to_f32().unwrap().is_nan());
+        assert_eq!(FieldValue::from("-inf").to_f32(), Ok(f32::NEG_INFINITY));
+        assert_eq!(FieldValue::Int(i32::MAX).to_f32(), Ok(i32::MAX as f32));
+    }
 }
@@ -1,10 +1,20 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::Path;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use indexmap::IndexMap;
 
 use crate::error::{JMapError, Result};
 
+pub mod bruteforce;
+mod harvest;
+
+pub use harvest::{harvest_dir, HarvestReport};
+
 /// The hash function used by Super Mario Galaxy 1
 ///
 /// # Arguments
@@ -28,6 +38,18 @@ pub fn calc_hash(field_name: &str) -> u32 {
     hash
 }
 
+/// Parse a field name written in the `[XXXXXXXX]` hex form used for unknown hashes
+///
+/// # Arguments
+/// - `name` - The field name to parse
+///
+/// # Returns
+/// The hash if the name is in bracketed hex form, `None` otherwise
+pub fn parse_hash_name(name: &str) -> Option<u32> {
+    let hex = name.strip_prefix('[')?.strip_suffix(']')?;
+    u32::from_str_radix(hex, 16).ok()
+}
+
 /// Trait for hash table implementations
 pub trait HashTable {
     /// Calculate the hash for a field name
@@ -48,6 +70,34 @@ pub trait HashTable {
     /// The field name corresponding to the given hash, or a hex string if not found
     fn find(&self, hash: u32) -> String;
 
+    /// Check whether a name is known for a given hash
+    ///
+    /// The default implementation checks whether `find` falls back to the `[XXXXXXXX]` form,
+    /// implementations with a real lookup should override it
+    ///
+    /// # Arguments
+    /// - `hash` - The hash value to look up
+    ///
+    /// # Returns
+    /// `true` if `find` returns a name rather than the hex form
+    fn contains(&self, hash: u32) -> bool {
+        parse_hash_name(&self.find(hash)) != Some(hash)
+    }
+
+    /// Find the field name for a given hash, without the hex fallback
+    ///
+    /// The default implementation goes through `contains` and `find`, implementations that store
+    /// their names should override it to borrow them
+    ///
+    /// # Arguments
+    /// - `hash` - The hash value to look up
+    ///
+    /// # Returns
+    /// The field name, or `None` if the hash is unknown
+    fn find_opt(&self, hash: u32) -> Option<Cow<'_, str>> {
+        self.contains(hash).then(|| Cow::Owned(self.find(hash)))
+    }
+
     /// Add a field name to the lookup table and return its hash
     ///
     /// # Arguments
@@ -56,14 +106,106 @@ pub trait HashTable {
     /// # Returns
     /// The hash value corresponding to the added field name
     fn add(&mut self, field_name: &str) -> u32;
+
+    /// Add a field name to the lookup table, failing if its hash already belongs to a different name
+    ///
+    /// # Arguments
+    /// - `field_name` - The field name to add to the lookup table
+    ///
+    /// # Errors
+    /// - `JMapError::HashCollision` with the existing and the new name if the hash is already used
+    ///
+    /// # Returns
+    /// The hash value corresponding to the added field name
+    fn try_add(&mut self, field_name: &str) -> Result<u32> {
+        let hash = self.calc(field_name);
+        if self.contains(hash) {
+            let existing = self.find(hash);
+            if existing != field_name {
+                return Err(JMapError::HashCollision {
+                    hash,
+                    existing,
+                    new: field_name.to_string(),
+                });
+            }
+        }
+        Ok(self.add(field_name))
+    }
+}
+
+/// The hash function used by the JMap files of Luigi's Mansion and Super Mario Sunshine
+///
+/// Each byte is shifted into the hash, which is kept below the prime 33554393 (0x1FFFFD9)
+///
+/// # Arguments
+/// - `field_name` - The ASCII field name to hash
+///
+/// # Returns
+/// A hash value below 0x1FFFFD9
+pub fn calc_hash_gamecube(field_name: &str) -> u32 {
+    const MODULUS: u64 = 33554393;
+    let mut hash: u64 = 0;
+
+    for byte in field_name.bytes() {
+        // hash < 2^25, so the shift needs more than 32 bits
+        hash = ((hash << 8) + byte as u64) % MODULUS;
+    }
+
+    hash as u32
+}
+
+/// The CRC32 (IEEE) of the lowercase field name, used by some community formats
+///
+/// # Arguments
+/// - `field_name` - The ASCII field name to hash
+///
+/// # Returns
+/// A 32-bit hash value
+pub fn calc_hash_crc32_lowercase(field_name: &str) -> u32 {
+    let mut crc: u32 = 0xFFFFFFFF;
+
+    for byte in field_name.bytes() {
+        crc ^= byte.to_ascii_lowercase() as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+
+    !crc
+}
+
+/// Trim a line of a lookup file, returning `None` for comments and empty lines
+pub(crate) fn lookup_line(line: &str) -> Option<&str> {
+    let line = line.trim();
+    (!line.is_empty() && !line.starts_with('#')).then_some(line)
 }
 
 /// Type of hash algorithm to use
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy)]
 pub enum HashAlgorithm {
+    /// Super Mario Galaxy 1/2, see [`calc_hash`]
     SMG,
+    /// Luigi's Mansion and Super Mario Sunshine, see [`calc_hash_gamecube`]
+    GameCube,
+    /// CRC32 of the lowercase name, see [`calc_hash_crc32_lowercase`]
+    Crc32Lowercase,
+    /// Any other hash function
+    Custom(fn(&str) -> u32),
 }
 
+/// `Custom` algorithms are equal when they point to the same function
+impl PartialEq for HashAlgorithm {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (HashAlgorithm::Custom(a), HashAlgorithm::Custom(b)) => std::ptr::fn_addr_eq(*a, *b),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for HashAlgorithm {}
+
 impl HashAlgorithm {
     /// Calculate hash using this algorithm
     ///
@@ -75,7 +217,74 @@ impl HashAlgorithm {
     pub fn calc(&self, field_name: &str) -> u32 {
         match self {
             HashAlgorithm::SMG => calc_hash(field_name),
+            HashAlgorithm::GameCube => calc_hash_gamecube(field_name),
+            HashAlgorithm::Crc32Lowercase => calc_hash_crc32_lowercase(field_name),
+            HashAlgorithm::Custom(hash) => hash(field_name),
+        }
+    }
+}
+
+/// Which name is kept when two lines of a lookup file have the same hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CollisionPolicy {
+    /// Keep the name that appears first in the file
+    #[default]
+    KeepFirst,
+    /// Keep the name that appears last in the file
+    KeepLast,
+    /// Fail with `JMapError::HashCollision`
+    Error,
+}
+
+/// Outcome of [`FileHashTable::merge`] and [`FileHashTable::extend_from_names`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeStats {
+    /// Names whose hash was not in the table yet
+    pub added: usize,
+    /// Names that were already in the table
+    pub identical: usize,
+    /// Names whose hash was already used by a different name, as `(hash, kept, rejected)`
+    pub conflicts: Vec<(u32, String, String)>,
+}
+
+/// Which names of a table are used by a set of files, see [`FileHashTable::coverage`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    /// Hashes seen in the files with a name in the table, sorted by hash
+    pub resolved: Vec<(u32, String)>,
+    /// Hashes seen in the files without a name in the table, sorted by hash
+    pub unresolved: Vec<u32>,
+    /// Names of the table never seen in the files, sorted by hash
+    pub unused: Vec<(u32, String)>,
+    /// Number of files each seen hash appears in
+    pub file_counts: BTreeMap<u32, usize>,
+}
+
+impl CoverageReport {
+    /// Format the report as CSV lines, starting with a `hash,name,status,files` header
+    ///
+    /// `status` is `resolved`, `unresolved` or `unused`, and unresolved hashes have an empty name
+    pub fn to_csv_lines(&self) -> Vec<String> {
+        let count = |hash: &u32| self.file_counts.get(hash).copied().unwrap_or(0).to_string();
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        let mut records: Vec<[String; 4]> = Vec::new();
+        records.extend(self.resolved.iter().map(|(hash, name)| {
+            [format!("{:08X}", hash), name.clone(), "resolved".to_string(), count(hash)]
+        }));
+        records.extend(self.unresolved.iter().map(|hash| {
+            [format!("{:08X}", hash), String::new(), "unresolved".to_string(), count(hash)]
+        }));
+        records.extend(self.unused.iter().map(|(hash, name)| {
+            [format!("{:08X}", hash), name.clone(), "unused".to_string(), "0".to_string()]
+        }));
+
+        // Writing to a Vec cannot fail
+        writer.write_record(["hash", "name", "status", "files"]).expect("write to memory");
+        for record in &records {
+            writer.write_record(record).expect("write to memory");
         }
+        let data = writer.into_inner().expect("write to memory");
+        String::from_utf8_lossy(&data).lines().map(str::to_string).collect()
     }
 }
 
@@ -84,6 +293,7 @@ impl HashAlgorithm {
 pub struct FileHashTable {
     algorithm: HashAlgorithm,
     lookup: HashMap<u32, String>,
+    collisions: Vec<(u32, Vec<String>)>,
 }
 
 impl FileHashTable {
@@ -98,6 +308,7 @@ impl FileHashTable {
         Self {
             algorithm,
             lookup: HashMap::new(),
+            collisions: Vec::new(),
         }
     }
 
@@ -106,6 +317,9 @@ impl FileHashTable {
     /// The lookup file should contain one field name per line
     /// Lines starting with '#' are treated as comments
     ///
+    /// When two different names have the same hash, the first one is kept and the collision is recorded,
+    /// see [`FileHashTable::collisions`]
+    ///
     /// # Arguments
     /// - `algorithm` - The hash algorithm to use for calculating hashes
     /// - `path` - The path to the lookup file containing field names
@@ -119,28 +333,240 @@ impl FileHashTable {
     /// # Returns
     /// A `Result` containing the new `FileHashTable` instance if successful, or a `JMapError` if the file cannot be read
     pub fn from_file<P: AsRef<Path>>(algorithm: HashAlgorithm, path: P) -> Result<Self> {
+        Self::from_file_with_policy(algorithm, path, CollisionPolicy::KeepFirst)
+    }
+
+    /// Create a new hash table from a lookup file, failing if two different names have the same hash
+    ///
+    /// # Errors
+    /// - `JMapError::LookupFileNotFound` if the file cannot be opened
+    /// - `JMapError::HashCollision` on the first collision
+    pub fn from_file_strict<P: AsRef<Path>>(algorithm: HashAlgorithm, path: P) -> Result<Self> {
+        Self::from_file_with_policy(algorithm, path, CollisionPolicy::Error)
+    }
+
+    /// Create a new hash table from a lookup file with the given collision policy
+    ///
+    /// # Arguments
+    /// - `algorithm` - The hash algorithm to use for calculating hashes
+    /// - `path` - The path to the lookup file containing field names
+    /// - `policy` - Which name to keep when two different names have the same hash
+    ///
+    /// # Errors
+    /// - `JMapError::LookupFileNotFound` if the file cannot be opened
+    /// - `JMapError::HashCollision` on the first collision, with `CollisionPolicy::Error`
+    pub fn from_file_with_policy<P: AsRef<Path>>(
+        algorithm: HashAlgorithm,
+        path: P,
+        policy: CollisionPolicy,
+    ) -> Result<Self> {
         let path = path.as_ref();
         let file = File::open(path).map_err(|_| {
             JMapError::LookupFileNotFound(path.display().to_string())
         })?;
 
         let reader = BufReader::new(file);
-        let mut lookup = HashMap::new();
+        let mut lookup: HashMap<u32, String> = HashMap::new();
+        let mut collisions: IndexMap<u32, Vec<String>> = IndexMap::new();
 
         for line in reader.lines() {
             let line = line?;
-            let line = line.trim();
+            let Some(line) = lookup_line(&line) else {
+                continue;
+            };
 
-            // Skip comments and empty lines
-            if line.is_empty() || line.starts_with('#') {
+            let hash = algorithm.calc(line);
+            let Some(existing) = lookup.get_mut(&hash) else {
+                lookup.insert(hash, line.to_string());
+                continue;
+            };
+            if existing == line {
                 continue;
             }
 
-            let hash = algorithm.calc(line);
-            lookup.insert(hash, line.to_string());
+            warn!(hash, existing = %existing, new = line, policy = ?policy, "hash collision in lookup file");
+            let names = collisions.entry(hash).or_insert_with(|| vec![existing.clone()]);
+            if !names.iter().any(|name| name == line) {
+                names.push(line.to_string());
+            }
+
+            match policy {
+                CollisionPolicy::KeepFirst => {}
+                CollisionPolicy::KeepLast => *existing = line.to_string(),
+                CollisionPolicy::Error => {
+                    return Err(JMapError::HashCollision {
+                        hash,
+                        existing: existing.clone(),
+                        new: line.to_string(),
+                    });
+                }
+            }
+        }
+
+        debug!(path = %path.display(), names = lookup.len(), collisions = collisions.len(), "loaded lookup file");
+        Ok(Self {
+            algorithm,
+            lookup,
+            collisions: collisions.into_iter().collect(),
+        })
+    }
+
+    /// Get the hash collisions found while loading the lookup file
+    ///
+    /// # Returns
+    /// Each colliding hash with all of its names, in file order
+    pub fn collisions(&self) -> &[(u32, Vec<String>)] {
+        &self.collisions
+    }
+
+    /// Import every name of another table
+    ///
+    /// Names of `self` win over conflicting names of `other`, conflicts are also added to [`FileHashTable::collisions`]
+    ///
+    /// # Arguments
+    /// - `other` - The table to import names from
+    ///
+    /// # Errors
+    /// - `JMapError::HashAlgorithmMismatch` if the tables use different algorithms, as their hashes cannot be compared
+    ///
+    /// # Returns
+    /// How many names were added, already known, or conflicting
+    pub fn merge(&mut self, other: &FileHashTable) -> Result<MergeStats> {
+        if self.algorithm != other.algorithm {
+            return Err(JMapError::HashAlgorithmMismatch(self.algorithm, other.algorithm));
+        }
+
+        let mut stats = MergeStats::default();
+        // Sort for a deterministic conflict order
+        let mut names: Vec<(&u32, &String)> = other.lookup.iter().collect();
+        names.sort_unstable_by_key(|(hash, _)| **hash);
+        for (&hash, name) in names {
+            self.import_name(hash, name, &mut stats);
+        }
+        Ok(stats)
+    }
+
+    /// Add names from a raw list, keeping existing names on conflict
+    ///
+    /// # Arguments
+    /// - `names` - The names to add, hashed with the algorithm of this table
+    ///
+    /// # Returns
+    /// How many names were added, already known, or conflicting
+    pub fn extend_from_names<I, S>(&mut self, names: I) -> MergeStats
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut stats = MergeStats::default();
+        for name in names {
+            let name = name.as_ref();
+            let hash = self.algorithm.calc(name);
+            self.import_name(hash, name, &mut stats);
+        }
+        stats
+    }
+
+    /// Insert a name unless its hash is already known, updating `stats`
+    fn import_name(&mut self, hash: u32, name: &str, stats: &mut MergeStats) {
+        let Some(existing) = self.lookup.get(&hash) else {
+            self.lookup.insert(hash, name.to_string());
+            stats.added += 1;
+            return;
+        };
+        if existing == name {
+            stats.identical += 1;
+            return;
+        }
+
+        match self.collisions.iter_mut().find(|(h, _)| *h == hash) {
+            Some((_, names)) => {
+                if !names.iter().any(|n| n == name) {
+                    names.push(name.to_string());
+                }
+            }
+            None => self.collisions.push((hash, vec![existing.clone(), name.to_string()])),
+        }
+        stats.conflicts.push((hash, existing.clone(), name.to_string()));
+    }
+
+    /// Report which names of this table are used by a set of files
+    ///
+    /// # Arguments
+    /// - `files` - The field hashes of each file, such as `jmaps.iter().map(|jmap| jmap.field_hashes().copied())`
+    ///
+    /// # Returns
+    /// The resolved, unresolved and unused hashes, with the number of files each hash appears in
+    pub fn coverage<I, F>(&self, files: I) -> CoverageReport
+    where
+        I: IntoIterator<Item = F>,
+        F: IntoIterator<Item = u32>,
+    {
+        let mut file_counts: BTreeMap<u32, usize> = BTreeMap::new();
+        for file in files {
+            let hashes: BTreeSet<u32> = file.into_iter().collect();
+            for hash in hashes {
+                *file_counts.entry(hash).or_default() += 1;
+            }
         }
 
-        Ok(Self { algorithm, lookup })
+        let mut report = CoverageReport::default();
+        for &hash in file_counts.keys() {
+            match self.lookup.get(&hash) {
+                Some(name) => report.resolved.push((hash, name.clone())),
+                None => report.unresolved.push(hash),
+            }
+        }
+        report.unused = self
+            .lookup
+            .iter()
+            .filter(|(hash, _)| !file_counts.contains_key(hash))
+            .map(|(&hash, name)| (hash, name.clone()))
+            .collect();
+        report.unused.sort_unstable();
+        report.file_counts = file_counts;
+        report
+    }
+
+    /// Find the field name for a given hash without allocating
+    ///
+    /// # Returns
+    /// The field name, or `None` if the hash is unknown
+    pub fn find_opt(&self, hash: u32) -> Option<&str> {
+        self.lookup.get(&hash).map(String::as_str)
+    }
+
+    /// Find the field names of several hashes at once
+    ///
+    /// # Returns
+    /// The field name of each hash, in the same order, or `None` for unknown hashes
+    pub fn resolve_all(&self, hashes: &[u32]) -> Vec<Option<&str>> {
+        hashes.iter().map(|&hash| self.find_opt(hash)).collect()
+    }
+
+    /// Iterate over every known hash and its name, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.lookup.iter().map(|(&hash, name)| (hash, name.as_str()))
+    }
+
+    /// Write every known name to a lookup file, one per line in alphabetical order
+    ///
+    /// The file can be loaded back with [`FileHashTable::from_file`]. Names dropped on collision are not written
+    ///
+    /// # Arguments
+    /// - `path` - The path of the lookup file to create or overwrite
+    ///
+    /// # Errors
+    /// - `JMapError::IoError` if the file cannot be written
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut names: Vec<&str> = self.lookup.values().map(String::as_str).collect();
+        names.sort_unstable();
+        let mut writer = BufWriter::new(File::create(path)?);
+        for name in names {
+            writeln!(writer, "{}", name)?;
+        }
+        writer.flush()?;
+        Ok(())
     }
 
     /// Get the hash algorithm used by this table
@@ -166,6 +592,14 @@ impl HashTable for FileHashTable {
             .unwrap_or_else(|| format!("[{:08X}]", hash))
     }
 
+    fn contains(&self, hash: u32) -> bool {
+        self.lookup.contains_key(&hash)
+    }
+
+    fn find_opt(&self, hash: u32) -> Option<Cow<'_, str>> {
+        FileHashTable::find_opt(self, hash).map(Cow::Borrowed)
+    }
+
     fn add(&mut self, field_name: &str) -> u32 {
         let hash = self.calc(field_name);
         self.lookup.entry(hash).or_insert_with(|| field_name.to_string());
@@ -173,17 +607,266 @@ impl HashTable for FileHashTable {
     }
 }
 
+/// Forward to the borrowed table, so a table can be lent to a `JMapInfo` for a while
+impl<T: HashTable + ?Sized> HashTable for &mut T {
+    fn calc(&self, field_name: &str) -> u32 {
+        (**self).calc(field_name)
+    }
+
+    fn find(&self, hash: u32) -> String {
+        (**self).find(hash)
+    }
+
+    fn contains(&self, hash: u32) -> bool {
+        (**self).contains(hash)
+    }
+
+    fn find_opt(&self, hash: u32) -> Option<Cow<'_, str>> {
+        (**self).find_opt(hash)
+    }
+
+    fn add(&mut self, field_name: &str) -> u32 {
+        (**self).add(field_name)
+    }
+
+    fn try_add(&mut self, field_name: &str) -> Result<u32> {
+        (**self).try_add(field_name)
+    }
+}
+
+/// Forward to the boxed table, so the implementation can be chosen at runtime with `Box<dyn HashTable>`
+impl<T: HashTable + ?Sized> HashTable for Box<T> {
+    fn calc(&self, field_name: &str) -> u32 {
+        (**self).calc(field_name)
+    }
+
+    fn find(&self, hash: u32) -> String {
+        (**self).find(hash)
+    }
+
+    fn contains(&self, hash: u32) -> bool {
+        (**self).contains(hash)
+    }
+
+    fn find_opt(&self, hash: u32) -> Option<Cow<'_, str>> {
+        (**self).find_opt(hash)
+    }
+
+    fn add(&mut self, field_name: &str) -> u32 {
+        (**self).add(field_name)
+    }
+
+    fn try_add(&mut self, field_name: &str) -> Result<u32> {
+        (**self).try_add(field_name)
+    }
+}
+
+/// A hash table shared between several `JMapInfo`s
+///
+/// Clones refer to the same table, so names added through one of them are visible to all.
+/// Each trait call only holds the lock for its own duration, so calls can be nested freely
+#[derive(Debug, Default)]
+pub struct SharedHashTable<T: HashTable = FileHashTable> {
+    inner: Arc<RwLock<T>>,
+}
+
+impl<T: HashTable> SharedHashTable<T> {
+    /// Wrap a table so it can be shared
+    pub fn new(table: T) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(table)),
+        }
+    }
+
+    /// Lock the table for reading
+    ///
+    /// Do not call `add` on a clone while holding the guard, as it would wait for the guard to be dropped
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the table for writing
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: HashTable> Clone for SharedHashTable<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T: HashTable> HashTable for SharedHashTable<T> {
+    fn calc(&self, field_name: &str) -> u32 {
+        self.read().calc(field_name)
+    }
+
+    fn find(&self, hash: u32) -> String {
+        self.read().find(hash)
+    }
+
+    fn contains(&self, hash: u32) -> bool {
+        self.read().contains(hash)
+    }
+
+    fn find_opt(&self, hash: u32) -> Option<Cow<'_, str>> {
+        self.read().find_opt(hash).map(|name| Cow::Owned(name.into_owned()))
+    }
+
+    fn add(&mut self, field_name: &str) -> u32 {
+        self.write().add(field_name)
+    }
+
+    /// Checks and adds under a single lock, so another clone cannot add a colliding name in between
+    fn try_add(&mut self, field_name: &str) -> Result<u32> {
+        self.write().try_add(field_name)
+    }
+}
+
+/// A hash table wrapper that remembers the hash of every name it computed
+///
+/// Name-based accessors such as `Entry::get` hash the name on every call, this makes repeated
+/// lookups of the same names cheap. In hot loops, looking up by a precomputed hash
+/// (`Entry::get_by_hash`) avoids hashing altogether
+#[derive(Debug, Clone)]
+pub struct CachedHashTable<H: HashTable> {
+    inner: H,
+    memo: RefCell<HashMap<String, u32>>,
+}
+
+impl<H: HashTable> CachedHashTable<H> {
+    /// Wrap a hash table
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            memo: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Get the wrapped hash table
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    /// Unwrap the hash table, dropping the memo
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+}
+
+impl<H: HashTable> HashTable for CachedHashTable<H> {
+    fn calc(&self, field_name: &str) -> u32 {
+        if let Some(&hash) = self.memo.borrow().get(field_name) {
+            return hash;
+        }
+        let hash = self.inner.calc(field_name);
+        self.memo.borrow_mut().insert(field_name.to_string(), hash);
+        hash
+    }
+
+    fn find(&self, hash: u32) -> String {
+        self.inner.find(hash)
+    }
+
+    fn contains(&self, hash: u32) -> bool {
+        self.inner.contains(hash)
+    }
+
+    fn find_opt(&self, hash: u32) -> Option<Cow<'_, str>> {
+        self.inner.find_opt(hash)
+    }
+
+    fn add(&mut self, field_name: &str) -> u32 {
+        let hash = self.inner.add(field_name);
+        self.memo.get_mut().insert(field_name.to_string(), hash);
+        hash
+    }
+
+    fn try_add(&mut self, field_name: &str) -> Result<u32> {
+        let hash = self.inner.try_add(field_name)?;
+        self.memo.get_mut().insert(field_name.to_string(), hash);
+        Ok(hash)
+    }
+}
+
+/// A hash table that never resolves names, for conversions that only need hashes
+///
+/// `calc` uses the SMG algorithm, `find` always returns the `[XXXXXXXX]` form and `add` stores nothing.
+/// CSV files exported with it have bracketed hashes as headers, which are parsed back to the same hashes on import
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HexHashTable;
+
+impl HashTable for HexHashTable {
+    fn calc(&self, field_name: &str) -> u32 {
+        calc_hash(field_name)
+    }
+
+    fn find(&self, hash: u32) -> String {
+        format!("[{:08X}]", hash)
+    }
+
+    fn contains(&self, _hash: u32) -> bool {
+        false
+    }
+
+    fn find_opt(&self, _hash: u32) -> Option<Cow<'_, str>> {
+        None
+    }
+
+    fn add(&mut self, field_name: &str) -> u32 {
+        self.calc(field_name)
+    }
+}
+
+/// Create a hash table that never resolves names, see [`HexHashTable`]
+pub fn no_lookup() -> HexHashTable {
+    HexHashTable
+}
+
+/// The field names bundled with the `bundled-names` feature, in the lookup file format
+#[cfg(feature = "bundled-names")]
+pub const BUNDLED_SMG_NAMES: &str = include_str!("../assets/strings_SMG.txt");
+
 /// Create a hash table configured for Super Mario Galaxy 1/2
 ///
-/// This uses the JGadget hash algorithm and loads the lookup file
-/// from the default location if available
+/// This uses the SMG hash algorithm. With the `bundled-names` feature, the table is populated with
+/// the bundled field names, otherwise it starts empty
+///
+/// Enabling the feature changes what this returns for every user of the crate: hashes that were shown as
+/// `[XXXXXXXX]` resolve to their names. Use `FileHashTable::new(HashAlgorithm::SMG)` for a table that is
+/// empty either way
 pub fn smg_hash_table() -> FileHashTable {
-    FileHashTable::new(HashAlgorithm::SMG)
+    #[allow(unused_mut)]
+    let mut table = FileHashTable::new(HashAlgorithm::SMG);
+    #[cfg(feature = "bundled-names")]
+    table.extend_from_names(BUNDLED_SMG_NAMES.lines().filter_map(lookup_line));
+    table
 }
 
 /// Create a hash table for Super Mario Galaxy with a custom lookup file
+///
+/// With the `bundled-names` feature, the bundled field names are added too, names from the file win on conflict
 pub fn smg_hash_table_with_lookup<P: AsRef<Path>>(path: P) -> Result<FileHashTable> {
-    FileHashTable::from_file(HashAlgorithm::SMG, path)
+    #[allow(unused_mut)]
+    let mut table = hash_table_with_lookup(HashAlgorithm::SMG, path)?;
+    #[cfg(feature = "bundled-names")]
+    table.extend_from_names(BUNDLED_SMG_NAMES.lines().filter_map(lookup_line));
+    Ok(table)
+}
+
+/// Create a hash table for any algorithm with a custom lookup file
+///
+/// # Arguments
+/// - `algorithm` - The hash algorithm of the game the files come from
+/// - `path` - The path to the lookup file containing field names
+///
+/// # Errors
+/// - If the file cannot be opened, a `JMapError::LookupFileNotFound` error is returned with the file path
+pub fn hash_table_with_lookup<P: AsRef<Path>>(algorithm: HashAlgorithm, path: P) -> Result<FileHashTable> {
+    FileHashTable::from_file(algorithm, path)
 }
 
 #[cfg(test)]
@@ -192,11 +875,38 @@ mod tests {
 
     #[test]
     fn test_hash() {
-        /// Known hash values from Super Mario Galaxy (verified with this [hash calculator](https://mariogalaxy.org/hash))
+        // Known hash values from Super Mario Galaxy (verified with this [hash calculator](https://mariogalaxy.org/hash))
         assert_eq!(calc_hash("ScenarioNo"), 0xED08B591);
         assert_eq!(calc_hash("ZoneName"), 0x3666C077);
     }
 
+    #[test]
+    fn test_hash_algorithms() {
+        // Below the modulus the hash is the big-endian value of the bytes, "abcd" is
+        // 0x61626364 = 48 * 33554393 + 0x1626AB4
+        assert_eq!(calc_hash_gamecube("ab"), 0x6162);
+        assert_eq!(calc_hash_gamecube("abc"), 0x616263);
+        assert_eq!(calc_hash_gamecube("abcd"), 0x1626AB4);
+
+        // Published CRC32 (IEEE) check values
+        assert_eq!(calc_hash_crc32_lowercase(""), 0x00000000);
+        assert_eq!(calc_hash_crc32_lowercase("a"), 0xE8B7BE43);
+        assert_eq!(calc_hash_crc32_lowercase("abc"), 0x352441C2);
+        assert_eq!(calc_hash_crc32_lowercase("123456789"), 0xCBF43926);
+        assert_eq!(calc_hash_crc32_lowercase("ABC"), 0x352441C2);
+
+        fn length_hash(name: &str) -> u32 {
+            name.len() as u32
+        }
+        assert_eq!(HashAlgorithm::Custom(length_hash).calc("ZoneName"), 8);
+        assert_eq!(HashAlgorithm::GameCube.calc("abcd"), 0x1626AB4);
+
+        let mut table = FileHashTable::new(HashAlgorithm::Crc32Lowercase);
+        let hash = table.add("ABC");
+        assert_eq!(table.find(0x352441C2), "ABC");
+        assert_eq!(hash, 0x352441C2);
+    }
+
     #[test]
     fn test_hash_table() {
         let mut table = smg_hash_table();
@@ -206,7 +916,210 @@ mod tests {
         assert_eq!(table.find(hash), "TestField");
         
         // Unknown hash should return hex representation
+        assert!(table.contains(hash));
+        assert!(!table.contains(0xDEADBEEF));
         let unknown = table.find(0xDEADBEEF);
         assert_eq!(unknown, "[DEADBEEF]");
+        assert_eq!(parse_hash_name(&unknown), Some(0xDEADBEEF));
+        assert_eq!(parse_hash_name("TestField"), None);
+    }
+
+    #[test]
+    fn test_lookup_collisions() {
+        // "Aa" and "BB" have the same hash, so do names ending with them
+        assert_eq!(calc_hash("PosAa"), calc_hash("PosBB"));
+        let path = std::env::temp_dir().join("bcsv_jmap_test_collisions.txt");
+        std::fs::write(&path, "# names\nPosAa\nZoneName\nPosBB\nZoneName\n").unwrap();
+
+        let table = FileHashTable::from_file(HashAlgorithm::SMG, &path).unwrap();
+        assert_eq!(table.find(calc_hash("PosBB")), "PosAa");
+        assert_eq!(table.collisions(), [(calc_hash("PosAa"), vec!["PosAa".to_string(), "PosBB".to_string()])]);
+
+        let table = FileHashTable::from_file_with_policy(HashAlgorithm::SMG, &path, CollisionPolicy::KeepLast).unwrap();
+        assert_eq!(table.find(calc_hash("PosAa")), "PosBB");
+
+        let err = FileHashTable::from_file_strict(HashAlgorithm::SMG, &path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(err, JMapError::HashCollision { hash, .. } if hash == calc_hash("PosAa")));
+    }
+
+    #[cfg(feature = "bundled-names")]
+    #[test]
+    fn test_bundled_names() {
+        let table = smg_hash_table();
+        assert_eq!(table.find(0xED08B591), "ScenarioNo");
+        assert_eq!(table.find(0x3666C077), "ZoneName");
+        assert_eq!(FileHashTable::new(HashAlgorithm::SMG).find(0xED08B591), "[ED08B591]");
+    }
+
+    #[cfg(not(feature = "bundled-names"))]
+    #[test]
+    fn test_no_bundled_names() {
+        assert_eq!(smg_hash_table().find(0xED08B591), "[ED08B591]");
+    }
+
+    #[test]
+    fn test_shared_hash_table() {
+        use crate::field::{FieldType, FieldValue};
+        use crate::jmap::JMapInfo;
+
+        let shared = SharedHashTable::new(FileHashTable::new(HashAlgorithm::SMG));
+        let mut first = JMapInfo::new(shared.clone());
+        let second = JMapInfo::new(shared.clone());
+
+        first.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        assert_eq!(second.field_name(calc_hash("ZoneName")), "ZoneName");
+        assert!(shared.read().contains(calc_hash("ZoneName")));
+
+        // Nested calls each take the lock on their own
+        let mut table = shared.clone();
+        let name = shared.find(table.add("ScenarioNo"));
+        assert_eq!(name, "ScenarioNo");
+
+        let mut owned = FileHashTable::new(HashAlgorithm::SMG);
+        {
+            let mut borrowed = JMapInfo::new(&mut owned);
+            borrowed.create_field("PosAa", FieldType::Long, FieldValue::Int(0)).unwrap();
+        }
+        assert_eq!(owned.find(calc_hash("PosAa")), "PosAa");
+    }
+
+    #[test]
+    fn test_coverage() {
+        use crate::field::{FieldType, FieldValue};
+        use crate::jmap::JMapInfo;
+
+        let mut table = FileHashTable::new(HashAlgorithm::SMG);
+        table.extend_from_names(["ScenarioNo", "ZoneName", "PosAa"]);
+
+        let mut scenario = JMapInfo::new(no_lookup());
+        scenario.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        scenario.create_field("Unknown", FieldType::Long, FieldValue::Int(0)).unwrap();
+        let mut zone = JMapInfo::new(no_lookup());
+        zone.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        zone.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+
+        let report = table.coverage([&scenario, &zone].map(|jmap| jmap.field_hashes().copied()));
+        assert_eq!(report.unresolved, [calc_hash("Unknown")]);
+        assert_eq!(report.unused, [(calc_hash("PosAa"), "PosAa".to_string())]);
+        assert_eq!(report.resolved.len(), 2);
+        assert_eq!(report.file_counts[&calc_hash("ScenarioNo")], 2);
+
+        let lines = report.to_csv_lines();
+        assert_eq!(lines[0], "hash,name,status,files");
+        assert!(lines.contains(&"ED08B591,ScenarioNo,resolved,2".to_string()));
+        assert!(lines.contains(&format!("{:08X},,unresolved,1", calc_hash("Unknown"))));
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn test_cached_hash_table() {
+        use crate::entry::Entry;
+        use crate::field::FieldValue;
+        use std::cell::Cell;
+
+        #[derive(Default)]
+        struct CountingHashTable {
+            calls: Cell<usize>,
+        }
+
+        impl HashTable for CountingHashTable {
+            fn calc(&self, field_name: &str) -> u32 {
+                self.calls.set(self.calls.get() + 1);
+                calc_hash(field_name)
+            }
+
+            fn find(&self, hash: u32) -> String {
+                format!("[{:08X}]", hash)
+            }
+
+            fn add(&mut self, field_name: &str) -> u32 {
+                self.calc(field_name)
+            }
+        }
+
+        let mut entry = Entry::new();
+        entry.set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(3));
+
+        let table = CachedHashTable::new(CountingHashTable::default());
+        for _ in 0..100 {
+            assert_eq!(entry.get_int(&table, "ScenarioNo"), Some(3));
+        }
+        assert_eq!(table.inner().calls.get(), 1);
+
+        let mut table = table;
+        table.add("ZoneName");
+        assert_eq!(table.calc("ZoneName"), calc_hash("ZoneName"));
+        assert_eq!(table.into_inner().calls.get(), 2);
+    }
+
+    #[test]
+    fn test_try_add() {
+        let mut table = FileHashTable::new(HashAlgorithm::SMG);
+        assert_eq!(table.try_add("PosAa").unwrap(), calc_hash("PosAa"));
+        assert_eq!(table.try_add("PosAa").unwrap(), calc_hash("PosAa"));
+
+        let err = table.try_add("PosBB").unwrap_err();
+        assert!(matches!(err, JMapError::HashCollision { hash, existing, new }
+            if hash == calc_hash("PosAa") && existing == "PosAa" && new == "PosBB"));
+        // add keeps its lenient behavior
+        assert_eq!(table.add("PosBB"), calc_hash("PosAa"));
+        assert_eq!(table.find(calc_hash("PosBB")), "PosAa");
+
+        let mut shared = SharedHashTable::new(table);
+        assert!(shared.try_add("PosBB").is_err());
+        assert!(no_lookup().try_add("PosBB").is_ok());
+    }
+
+    #[test]
+    fn test_find_opt() {
+        let mut table = FileHashTable::new(HashAlgorithm::SMG);
+        table.extend_from_names(["ScenarioNo", "ZoneName"]);
+
+        assert_eq!(table.find_opt(0xED08B591), Some("ScenarioNo"));
+        assert_eq!(table.find_opt(0xDEADBEEF), None);
+        assert_eq!(table.resolve_all(&[0x3666C077, 0xDEADBEEF]), [Some("ZoneName"), None]);
+        assert_eq!(HashTable::find_opt(&table, 0x3666C077).as_deref(), Some("ZoneName"));
+        assert_eq!(no_lookup().find_opt(0x3666C077), None);
+
+        let mut names: Vec<(u32, &str)> = table.iter().collect();
+        names.sort_unstable();
+        assert_eq!(names, [(0x3666C077, "ZoneName"), (0xED08B591, "ScenarioNo")]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut table = FileHashTable::new(HashAlgorithm::SMG);
+        table.extend_from_names(["ScenarioNo", "PosAa"]);
+
+        let mut disjoint = FileHashTable::new(HashAlgorithm::SMG);
+        disjoint.add("ZoneName");
+        let stats = table.merge(&disjoint).unwrap();
+        assert_eq!(stats, MergeStats { added: 1, identical: 0, conflicts: vec![] });
+        assert_eq!(table.find(calc_hash("ZoneName")), "ZoneName");
+
+        let stats = table.extend_from_names(["ZoneName", "ScenarioNo", "PosBB"]);
+        assert_eq!(stats.added, 0);
+        assert_eq!(stats.identical, 2);
+        assert_eq!(stats.conflicts, [(calc_hash("PosAa"), "PosAa".to_string(), "PosBB".to_string())]);
+        assert_eq!(table.find(calc_hash("PosBB")), "PosAa");
+        assert_eq!(table.collisions().len(), 1);
+
+        let other = FileHashTable::new(HashAlgorithm::GameCube);
+        assert!(matches!(table.merge(&other), Err(JMapError::HashAlgorithmMismatch(..))));
+    }
+
+    #[test]
+    fn test_to_file() {
+        let mut table = FileHashTable::new(HashAlgorithm::SMG);
+        table.extend_from_names(["ZoneName", "ScenarioNo", "PosAa", "PosBB"]);
+        let path = std::env::temp_dir().join("bcsv_jmap_test_names.txt");
+        table.to_file(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "PosAa\nScenarioNo\nZoneName\n");
+
+        let loaded = FileHashTable::from_file(HashAlgorithm::SMG, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.find(calc_hash("ZoneName")), "ZoneName");
+        assert_eq!(loaded.iter().count(), 3);
     }
 }
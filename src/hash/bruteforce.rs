@@ -0,0 +1,235 @@
+//! Brute-force search for field names matching unknown hashes
+//!
+//! Candidates are built as `prefix + body + suffix`, where the body enumerates every string of `charset`
+//! characters up to `max_len` long, shortest first. Hashes are computed incrementally with the SMG algorithm,
+//! and a `String` is only allocated for matches
+
+use std::collections::{HashMap, HashSet};
+
+use crate::hash::calc_hash;
+
+/// Lowercase letters, digits and underscore, the usual charset for SMG field names such as `obj_arg0`
+pub const LOWERCASE_DIGITS_UNDERSCORE: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789_";
+
+/// Upper and lowercase letters and digits, for CamelCase names such as `ScenarioNo`
+pub const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Number of candidates between two calls of the `should_continue` callback
+const CHECK_INTERVAL: u64 = 4096;
+
+/// The set of candidate names to enumerate
+#[derive(Debug, Clone, Copy)]
+pub struct SearchSpace<'a> {
+    /// Characters the body of a candidate is made of
+    pub charset: &'a [u8],
+    /// Maximum length of the body
+    pub max_len: usize,
+    /// Fixed text before the body, such as `Obj_arg`
+    pub prefix: &'a str,
+    /// Fixed text after the body
+    pub suffix: &'a str,
+}
+
+/// Outcome of a [`search`]
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    /// Matching names for each target hash that had at least one match
+    pub matches: HashMap<u32, Vec<String>>,
+    /// Number of candidates that were hashed
+    pub iterations: u64,
+    /// Whether the whole search space was enumerated (`false` if cancelled or capped)
+    pub completed: bool,
+}
+
+/// Hash contribution of a single byte, matching `calc_hash`
+fn byte_value(byte: u8) -> u32 {
+    byte as i8 as i32 as u32
+}
+
+/// Find every name in the search space whose hash is `target`
+///
+/// # Arguments
+/// - `target` - The hash to recover a name for
+/// - `charset` - Characters the body of a candidate is made of
+/// - `max_len` - Maximum length of the body
+/// - `prefix` - Fixed text before the body
+/// - `suffix` - Fixed text after the body
+///
+/// # Returns
+/// The matching names, shortest first
+pub fn find_names(target: u32, charset: &[u8], max_len: usize, prefix: &str, suffix: &str) -> Vec<String> {
+    find_names_many(&[target], charset, max_len, prefix, suffix)
+        .remove(&target)
+        .unwrap_or_default()
+}
+
+/// Find names for several hashes at once, sharing a single enumeration of the search space
+///
+/// # Returns
+/// The matching names for each target hash that had at least one match
+pub fn find_names_many(
+    targets: &[u32],
+    charset: &[u8],
+    max_len: usize,
+    prefix: &str,
+    suffix: &str,
+) -> HashMap<u32, Vec<String>> {
+    let space = SearchSpace {
+        charset,
+        max_len,
+        prefix,
+        suffix,
+    };
+    search(&space, targets, None, |_| true).matches
+}
+
+/// Enumerate a search space with a cap on the number of candidates and a cancellation callback
+///
+/// # Arguments
+/// - `space` - The candidates to enumerate
+/// - `targets` - The hashes to recover names for
+/// - `max_iterations` - Stop after hashing this many candidates
+/// - `should_continue` - Called periodically with the number of candidates hashed so far, return `false` to stop
+///
+/// # Returns
+/// The matches found so far, and whether the enumeration completed
+pub fn search<F: FnMut(u64) -> bool>(
+    space: &SearchSpace<'_>,
+    targets: &[u32],
+    max_iterations: Option<u64>,
+    mut should_continue: F,
+) -> SearchResult {
+    let targets: HashSet<u32> = targets.iter().copied().collect();
+    let mut result = SearchResult::default();
+
+    let prefix_hash = calc_hash(space.prefix);
+    let suffix_hash = calc_hash(space.suffix);
+    let suffix_factor = (0..space.suffix.len()).fold(1u32, |acc, _| acc.wrapping_mul(31));
+    let finish = |partial: u32| partial.wrapping_mul(suffix_factor).wrapping_add(suffix_hash);
+
+    let record = |result: &mut SearchResult, hash: u32, body: &[u8]| {
+        if targets.contains(&hash) {
+            let mut name = String::with_capacity(space.prefix.len() + body.len() + space.suffix.len());
+            name.push_str(space.prefix);
+            name.extend(body.iter().map(|&b| b as char));
+            name.push_str(space.suffix);
+            result.matches.entry(hash).or_default().push(name);
+        }
+    };
+
+    if !should_continue(0) {
+        return result;
+    }
+
+    // The empty body
+    result.iterations += 1;
+    record(&mut result, finish(prefix_hash), &[]);
+
+    if space.charset.is_empty() {
+        result.completed = true;
+        return result;
+    }
+
+    let base = space.charset.len();
+    let mut indices: Vec<usize> = Vec::with_capacity(space.max_len);
+    let mut body: Vec<u8> = Vec::with_capacity(space.max_len);
+    // partials[i] is the hash of prefix + body[..i]
+    let mut partials: Vec<u32> = Vec::with_capacity(space.max_len + 1);
+
+    for len in 1..=space.max_len {
+        indices.clear();
+        indices.resize(len, 0);
+        body.clear();
+        body.resize(len, space.charset[0]);
+        partials.clear();
+        partials.push(prefix_hash);
+        for i in 0..len {
+            let next = partials[i].wrapping_mul(31).wrapping_add(byte_value(body[i]));
+            partials.push(next);
+        }
+
+        loop {
+            if max_iterations.is_some_and(|max| result.iterations >= max) {
+                return result;
+            }
+            if result.iterations.is_multiple_of(CHECK_INTERVAL) && !should_continue(result.iterations) {
+                return result;
+            }
+
+            result.iterations += 1;
+            record(&mut result, finish(partials[len]), &body);
+
+            // Advance the odometer, rightmost position first
+            let mut pos = len;
+            let advanced = loop {
+                if pos == 0 {
+                    break false;
+                }
+                pos -= 1;
+                indices[pos] += 1;
+                if indices[pos] < base {
+                    break true;
+                }
+                indices[pos] = 0;
+                body[pos] = space.charset[0];
+            };
+            if !advanced {
+                break;
+            }
+
+            body[pos] = space.charset[indices[pos]];
+            for i in pos..len {
+                partials[i + 1] = partials[i].wrapping_mul(31).wrapping_add(byte_value(body[i]));
+            }
+        }
+    }
+
+    result.completed = true;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_names() {
+        let lowercase = b"abcdefghijklmnopqrstuvwxyz";
+        assert!(find_names(calc_hash("no"), lowercase, 4, "", "").contains(&"no".to_string()));
+
+        let found = find_names_many(&[calc_hash("name"), calc_hash("no")], lowercase, 4, "", "");
+        assert!(found[&calc_hash("name")].contains(&"name".to_string()));
+        assert!(found[&calc_hash("no")].contains(&"no".to_string()));
+
+        let found = find_names(calc_hash("Obj_arg3"), LOWERCASE_DIGITS_UNDERSCORE, 1, "Obj_arg", "");
+        assert_eq!(found, ["Obj_arg3"]);
+        let found = find_names(calc_hash("ScenarioNo"), ALPHANUMERIC, 2, "Scenario", "");
+        assert!(found.contains(&"ScenarioNo".to_string()));
+        let found = find_names(calc_hash("PosX"), b"XYZ", 1, "Pos", "");
+        assert_eq!(found, ["PosX"]);
+        let found = find_names(calc_hash("pos_x_offset"), b"xyz", 1, "pos_", "_offset");
+        assert_eq!(found, ["pos_x_offset"]);
+    }
+
+    #[test]
+    fn test_search_limits() {
+        let space = SearchSpace {
+            charset: b"ab",
+            max_len: 3,
+            prefix: "",
+            suffix: "",
+        };
+        let result = search(&space, &[], None, |_| true);
+        assert!(result.completed);
+        // "" + 2 + 4 + 8 candidates
+        assert_eq!(result.iterations, 15);
+
+        let result = search(&space, &[], Some(5), |_| true);
+        assert!(!result.completed);
+        assert_eq!(result.iterations, 5);
+
+        let result = search(&space, &[calc_hash("bbb")], None, |_| false);
+        assert!(!result.completed);
+        assert!(result.matches.is_empty());
+    }
+}
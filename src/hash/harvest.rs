@@ -0,0 +1,134 @@
+//! Collect the field hashes used by a tree of BCSV files
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::io::{read_field_table, IoOptions};
+
+/// Field hashes found by [`harvest_dir`]
+#[derive(Debug, Clone, Default)]
+pub struct HarvestReport {
+    /// The files each hash appears in, in walk order
+    pub files: BTreeMap<u32, Vec<PathBuf>>,
+    /// Number of field definitions using each hash, across all files
+    pub occurrences: BTreeMap<u32, usize>,
+    /// Number of files whose field table was read
+    pub files_scanned: usize,
+    /// Files and directories that could not be read, with the reason
+    pub warnings: Vec<(PathBuf, String)>,
+}
+
+/// Walk a directory tree and tally the field hashes of every `.bcsv` file
+///
+/// Only the header and field table of each file are parsed. Files that cannot be read or parsed
+/// are recorded in [`HarvestReport::warnings`] rather than aborting the walk
+///
+/// # Arguments
+/// - `root` - The directory to walk
+/// - `options` - Options for endianness
+///
+/// # Errors
+/// - `JMapError::IoError` if `root` itself cannot be read
+///
+/// # Returns
+/// The hashes found, with the files they appear in
+pub fn harvest_dir<P: AsRef<Path>>(root: P, options: &IoOptions) -> Result<HarvestReport> {
+    let mut report = HarvestReport::default();
+    let mut pending = vec![root.as_ref().to_path_buf()];
+    let mut is_root = true;
+
+    while let Some(dir) = pending.pop() {
+        let mut paths = match list_dir(&dir) {
+            Ok(paths) => paths,
+            Err(err) if !is_root => {
+                report.warnings.push((dir, err.to_string()));
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        is_root = false;
+
+        paths.sort_unstable();
+        let mut subdirs = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                subdirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("bcsv")) {
+                harvest_file(&mut report, path, options);
+            }
+        }
+
+        // Reversed so that popping from the stack walks subdirectories in name order
+        pending.extend(subdirs.into_iter().rev());
+    }
+
+    Ok(report)
+}
+
+fn list_dir(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect()
+}
+
+fn harvest_file(report: &mut HarvestReport, path: PathBuf, options: &IoOptions) {
+    let fields = match fs::read(&path) {
+        Ok(data) => read_field_table(&data, options).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    let fields = match fields {
+        Ok(fields) => fields,
+        Err(reason) => {
+            report.warnings.push((path, reason));
+            return;
+        }
+    };
+
+    report.files_scanned += 1;
+    for field in &fields {
+        *report.occurrences.entry(field.hash).or_default() += 1;
+        let files = report.files.entry(field.hash).or_default();
+        if files.last() != Some(&path) {
+            files.push(path.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{FieldType, FieldValue};
+    use crate::hash::{calc_hash, no_lookup};
+    use crate::io::to_buffer;
+    use crate::jmap::JMapInfo;
+
+    fn write_fixture(path: &Path, names: &[&str]) {
+        let mut jmap = JMapInfo::new(no_lookup());
+        for name in names {
+            jmap.create_field(name, FieldType::Long, FieldValue::Int(0)).unwrap();
+        }
+        fs::write(path, to_buffer(&jmap, &IoOptions::default()).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_harvest_dir() {
+        let root = std::env::temp_dir().join("bcsv_jmap_test_harvest");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("StageData/Zone")).unwrap();
+        write_fixture(&root.join("StageData/ScenarioData.bcsv"), &["ScenarioNo", "ZoneName"]);
+        write_fixture(&root.join("StageData/Zone/ZoneList.bcsv"), &["ZoneName"]);
+        write_fixture(&root.join("StageData/Zone/Empty.BCSV"), &[]);
+        fs::write(root.join("StageData/Zone/Broken.bcsv"), [0u8; 4]).unwrap();
+        fs::write(root.join("StageData/readme.txt"), "not a bcsv").unwrap();
+
+        let report = harvest_dir(&root, &IoOptions::default()).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(report.files_scanned, 3);
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].0.ends_with("Broken.bcsv"));
+        assert_eq!(report.occurrences[&calc_hash("ZoneName")], 2);
+        assert_eq!(report.files[&calc_hash("ScenarioNo")], [root.join("StageData/ScenarioData.bcsv")]);
+        assert_eq!(report.files[&calc_hash("ZoneName")].len(), 2);
+    }
+}
@@ -1,14 +1,27 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
+use std::sync::Arc;
 
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 
-use crate::entry::Entry;
+use crate::entry::{Entry, FieldKey, SlotLayout};
 use crate::error::{JMapError, Result};
-use crate::field::{Field, FieldType, FieldValue};
-use crate::hash::HashTable;
+use crate::field::{Field, FieldType, FieldValue, DEFAULT_INLINE_STRING_SIZE};
+use crate::hash::{calc_hash, no_lookup, parse_hash_name, HashTable};
 use crate::jmap::JMapInfo;
+use crate::layout::{layout_conflicts, LayoutContext, LayoutStrategy};
+use crate::strictness::{Category, Level, Report, Strictness, Warning};
+
+mod dir;
+mod roundtrip;
+pub use dir::{load_dir, load_dir_filtered, JMapSet};
+pub use roundtrip::{
+    verify_roundtrip, verify_roundtrip_dir, ByteDifference, CellDifference, RoundtripOutcome, RoundtripReport, RoundtripSummary,
+    MAX_CELL_DIFFERENCES,
+};
 
 /// Options for reading/writing BCSV files
 #[derive(Debug, Clone)]
@@ -17,10 +30,27 @@ pub struct IoOptions {
     pub big_endian: bool,
     /// String encoding: "shift_jis" (for japanese language) or "utf-8"
     pub encoding: Encoding,
+    /// Order in which fields are laid out when writing
+    pub layout: LayoutStrategy,
+    /// Whether multi-byte fields are aligned to their natural boundary when writing
+    pub align_fields: bool,
+    /// Whether the entry size is rounded up to a multiple of 4 bytes when writing. With
+    /// `LayoutStrategy::Preserve`, the entry size read from a file is kept either way
+    pub align_entry_size: bool,
+    /// Whether the raw words read before masking are kept when reading, see [`JMapInfo::raw_at`]
+    pub keep_raw_values: bool,
+    /// What to do with malformed data when reading, see [`Strictness`]
+    pub strictness: Strictness,
+    /// Size of inline `String` fields in bytes, 32 in Nintendo's files but 16 or 64 in some others
+    pub inline_string_size: usize,
+    /// Encoding of the strings of some fields by hash, overriding `encoding`, see [`IoOptions::field_encoding`]
+    pub field_encodings: HashMap<u32, Encoding>,
+    /// What to do with a hash defined twice in the field table of a file, see [`DuplicateFieldPolicy`]
+    pub duplicate_fields: DuplicateFieldPolicy,
 }
 
 /// String encoding options
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Encoding {
     /// Shift-JIS encoding
     ShiftJis,
@@ -28,21 +58,79 @@ pub enum Encoding {
     Utf8,
 }
 
+/// Which definition of a field survives when the field table of a file defines its hash twice
+///
+/// The values of the dropped definition are not read. With `KeepFirst` and `KeepLast`, each duplicate is
+/// recorded as a `Structure` warning unless that category is set to `Level::Ignore`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateFieldPolicy {
+    /// Fail with `JMapError::DuplicateField`
+    #[default]
+    Error,
+    /// Keep the first definition in the field table
+    KeepFirst,
+    /// Keep the last definition in the field table, at the position of the first one
+    KeepLast,
+}
+
 impl Default for IoOptions {
     fn default() -> Self {
         Self {
             big_endian: true,
             encoding: Encoding::ShiftJis,
+            layout: LayoutStrategy::TypeOrder,
+            align_fields: false,
+            align_entry_size: true,
+            keep_raw_values: false,
+            strictness: Strictness::strict(),
+            inline_string_size: DEFAULT_INLINE_STRING_SIZE,
+            field_encodings: HashMap::new(),
+            duplicate_fields: DuplicateFieldPolicy::Error,
         }
     }
 }
 
 impl IoOptions {
     /// Options for Super Mario Galaxy (Wii)
+    ///
+    /// Field names are hashed with [`HashAlgorithm::SMG`](crate::hash::HashAlgorithm::SMG)
     pub fn super_mario_galaxy() -> Self {
-        Self {
-            big_endian: true,
-            encoding: Encoding::ShiftJis,
+        Self::default()
+    }
+
+    /// Options for Luigi's Mansion and Super Mario Sunshine (GameCube)
+    ///
+    /// Field names are hashed with [`HashAlgorithm::GameCube`](crate::hash::HashAlgorithm::GameCube),
+    /// so read these files with a hash table created from that algorithm
+    pub fn gamecube() -> Self {
+        Self::default()
+    }
+
+    /// Use another encoding for the strings of a field
+    ///
+    /// The name is hashed with [`calc_hash`], the SMG algorithm, unless it is `[XXXXXXXX]`. Fields hashed
+    /// with another algorithm are added to `field_encodings` by hash instead
+    ///
+    /// # Arguments
+    /// - `name` - The field name, or its hash as `[XXXXXXXX]`
+    /// - `encoding` - The encoding its strings are read and written with
+    pub fn field_encoding(mut self, name: &str, encoding: Encoding) -> Self {
+        let hash = parse_hash_name(name).unwrap_or_else(|| calc_hash(name));
+        self.field_encodings.insert(hash, encoding);
+        self
+    }
+
+    /// Get the encoding of the strings of a field, its override or `encoding`
+    pub fn encoding_for(&self, hash: u32) -> Encoding {
+        self.field_encodings.get(&hash).copied().unwrap_or(self.encoding)
+    }
+
+    /// The alignments and the size of inline strings used to lay out fields when writing
+    pub fn layout_context(&self) -> LayoutContext {
+        LayoutContext {
+            align_fields: self.align_fields,
+            inline_string_size: self.inline_string_size,
+            align_entry_size: self.align_entry_size,
         }
     }
 }
@@ -52,11 +140,24 @@ impl IoOptions {
 /// # Arguments
 /// - `hash_table` - The hash table to use for field name lookups
 /// - `data` - The byte buffer containing the BCSV data
-/// - `options` - Options for endianness and string encoding
+/// - `options` - Options for endianness, string encoding and strictness
 ///
 /// # TYpe
 /// - `H` - The type of hash table to use, which must implement the `HashTable` trait
 ///
+/// # Errors
+/// - `JMapError::Parse` with the offset, entry and field of a value that cannot be read, such as a string
+///   that cannot be decoded or a string offset past the end of the buffer
+/// - `JMapError::InvalidFieldType`, `JMapError::InvalidField` or `JMapError::BufferTooSmall` for a malformed
+///   field table or entries cut off by the end of the buffer
+///
+/// Each of these problems can be tolerated instead with `options.strictness`
+/// - `JMapError::DuplicateField` if the field table defines a hash twice, unless `options.duplicate_fields`
+///   keeps one of the definitions
+///
+/// With the `rayon` feature, large tables have their entries decoded in parallel. The result is the same as
+/// when decoding them in order, down to the error returned and the order of the warnings
+///
 /// # Returns
 /// A `JMapInfo` instance populated with the data from the buffer, or an error if parsing fails
 pub fn from_buffer<H: HashTable>(
@@ -64,8 +165,222 @@ pub fn from_buffer<H: HashTable>(
     data: &[u8],
     options: &IoOptions,
 ) -> Result<JMapInfo<H>> {
+    from_buffer_with_report(hash_table, data, options).map(|(jmap, _)| jmap)
+}
+
+/// Read a JMapInfo from a byte buffer, also returning the problems tolerated by `options.strictness`
+///
+/// See [`from_buffer`]
+///
+/// # Returns
+/// The JMapInfo and the report of the tolerated problems
+pub fn from_buffer_with_report<H: HashTable>(
+    hash_table: H,
+    data: &[u8],
+    options: &IoOptions,
+) -> Result<(JMapInfo<H>, Report)> {
     let mut jmap = JMapInfo::new(hash_table);
+    let header = read_header(data, options)?;
+    let (off_data, entry_size) = (header.off_data, header.entry_size);
+
+    jmap.entry_size = entry_size;
+
+    // Calculate string table offset
+    // string table starts immediately after the entries, which start at off_data and each entry is entry_size bytes
+    // So the string table is at off_data + (num_entries * entry_size)
+    let off_strings = to_u32("string table offset", header.off_strings())? as usize;
+
+    let mut values = ValueReader {
+        data,
+        off_strings,
+        options,
+        report: Report::default(),
+    };
+    debug!(
+        entries = header.num_entries,
+        fields = header.num_fields,
+        off_data,
+        entry_size,
+        off_strings,
+        "read BCSV header"
+    );
+    for field in read_unique_fields(data, &header, options, jmap.hash_table(), &mut values.report)? {
+        debug!(
+            hash = field.hash,
+            name = %jmap.hash_table().find(field.hash),
+            field_type = ?field.field_type,
+            offset = field.offset,
+            mask = field.mask,
+            shift = field.shift,
+            "read field"
+        );
+        if !jmap.hash_table().contains(field.hash) {
+            warn!(hash = field.hash, name = %jmap.hash_table().find(field.hash), "unresolved field hash");
+        }
+        jmap.fields_map_mut().insert(field.hash, field);
+    }
+    check_field_conflicts(jmap.fields(), options, jmap.hash_table(), &mut values.report)?;
+    let num_entries = readable_entries(&header, data.len(), options, &mut values.report)?;
+
+    // Read entries
+    let layout = jmap.slot_layout();
+    let fields: Vec<FieldReader> = jmap
+        .fields()
+        .map(|field| FieldReader {
+            field,
+            decoder: Decoder::for_field(field),
+            name: jmap.hash_table().find(field.hash),
+        })
+        .collect();
+    let entries = read_entries(&mut values, &fields, &layout, off_data as usize, entry_size as usize, num_entries)?;
+    *jmap.entries_vec_mut() = entries;
+
+    if options.keep_raw_values {
+        let raw_values = (0..num_entries)
+            .map(|i| read_raw_values(data, off_data as usize + i * entry_size as usize, &jmap, options))
+            .collect();
+        jmap.raw_values = Some(raw_values);
+    }
+
+    Ok((jmap, values.report))
+}
+
+/// Reads the values of a BCSV buffer, recovering from the problems tolerated by `options.strictness`
+pub(crate) struct ValueReader<'a> {
+    /// The BCSV data
+    pub(crate) data: &'a [u8],
+    /// Offset of the string table
+    pub(crate) off_strings: usize,
+    /// Options for endianness, string encoding and strictness
+    pub(crate) options: &'a IoOptions,
+    /// The problems tolerated so far
+    pub(crate) report: Report,
+}
+
+impl ValueReader<'_> {
+    /// Read the value of a field at `offset`, which belongs to entry `entry`
+    ///
+    /// `field_name` is the name of the field in warnings and errors, or its `[XXXXXXXX]` form
+    ///
+    /// # Errors
+    /// - `JMapError::Parse` if the value cannot be read and its problem is not tolerated
+    pub(crate) fn read(
+        &mut self,
+        decoder: Decoder,
+        field: &Field,
+        offset: usize,
+        entry: usize,
+        field_name: &str,
+    ) -> Result<FieldValue> {
+        let (data, off_strings, options) = (self.data, self.off_strings, self.options);
+        let source = match decoder.read(data, offset, off_strings, field, options) {
+            Ok(value) => return Ok(value),
+            Err(source) => source,
+        };
+
+        let (category, recovery) = match source {
+            JMapError::EncodingError(_) => (Category::Encoding, "decoded with replacement characters"),
+            _ => (Category::Values, "read as an empty string"),
+        };
+        let warning = Warning::bcsv(
+            category,
+            offset,
+            Some(entry),
+            Some(field_name.to_string()),
+            format!("{}, {}", source, recovery),
+        );
+        options.strictness.check(&mut self.report, warning, || JMapError::Parse {
+            offset,
+            entry: Some(entry),
+            field: Some(field_name.to_string()),
+            source: Box::new(source),
+        })?;
+        read_field_value(data, offset, off_strings, field, options, true)
+    }
+}
+
+/// Count the entries that fit in a buffer of `len` bytes
+///
+/// Entries cut off by the end of the buffer are a `Structure` problem, and are dropped when it is tolerated
+///
+/// # Errors
+/// - `JMapError::BufferTooSmall` if the buffer ends before the last entry and the problem is not tolerated
+pub(crate) fn readable_entries(header: &Header, len: usize, options: &IoOptions, report: &mut Report) -> Result<usize> {
+    let declared = header.num_entries as usize;
+    let needed = header.off_strings();
+    if needed <= len as u64 {
+        return Ok(declared);
+    }
+
+    let available = match header.entry_size as usize {
+        0 => 0,
+        entry_size => len.saturating_sub(header.off_data as usize) / entry_size,
+    };
+    let warning = Warning::bcsv(
+        Category::Structure,
+        len,
+        Some(available),
+        None,
+        format!("the buffer ends before entry {} of {}, dropping the last entries", available, declared),
+    );
+    options.strictness.check(report, warning, || JMapError::BufferTooSmall {
+        expected: needed as usize,
+        got: len,
+    })?;
+    Ok(available)
+}
 
+/// Read only the field table of a BCSV buffer, without decoding the entries
+///
+/// # Arguments
+/// - `data` - The byte buffer containing the BCSV data
+/// - `options` - Options for endianness
+///
+/// # Errors
+/// - `JMapError::BufferTooSmall` if the buffer ends before the field table does
+/// - `JMapError::InvalidFieldType` if a field has an unknown type
+///
+/// # Returns
+/// The fields in file order
+pub fn read_field_table(data: &[u8], options: &IoOptions) -> Result<Vec<Field>> {
+    let header = read_header(data, options)?;
+    read_fields(data, &header, options, &no_lookup(), &mut Report::default())
+}
+
+/// The four words at the start of a BCSV file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Number of entries
+    pub num_entries: u32,
+    /// Number of fields
+    pub num_fields: u32,
+    /// Offset of the first entry, right after the field table
+    pub off_data: u32,
+    /// Size of a single entry in bytes
+    pub entry_size: u32,
+}
+
+impl Header {
+    /// Offset of the string table, right after the entries
+    pub fn off_strings(&self) -> u64 {
+        self.off_data as u64 + self.num_entries as u64 * self.entry_size as u64
+    }
+
+    /// Whether the field table and the entries fit in a buffer of `len` bytes
+    fn is_consistent(&self, len: usize) -> bool {
+        self.off_data as u64 == 0x10 + self.num_fields as u64 * 0x0C && self.off_strings() <= len as u64
+    }
+}
+
+/// Read the header of a BCSV buffer
+///
+/// # Arguments
+/// - `data` - The BCSV data
+/// - `options` - Options for endianness
+///
+/// # Errors
+/// - `JMapError::BufferTooSmall` if the buffer is shorter than the 16-byte header
+pub fn read_header(data: &[u8], options: &IoOptions) -> Result<Header> {
     // The header size is 16 bytes, so we need at least that much to read the header
     if data.len() < 0x10 {
         return Err(JMapError::BufferTooSmall {
@@ -74,47 +389,364 @@ pub fn from_buffer<H: HashTable>(
         });
     }
 
-    // Read header
-    let (num_entries, num_fields, off_data, entry_size) = if options.big_endian {
-        (
-            BigEndian::read_u32(&data[0x00..0x04]),
-            BigEndian::read_u32(&data[0x04..0x08]),
-            BigEndian::read_u32(&data[0x08..0x0C]),
-            BigEndian::read_u32(&data[0x0C..0x10]),
-        )
+    let read_u32 = |offset: usize| {
+        if options.big_endian {
+            BigEndian::read_u32(&data[offset..offset + 0x04])
+        } else {
+            LittleEndian::read_u32(&data[offset..offset + 0x04])
+        }
+    };
+
+    Ok(Header {
+        num_entries: read_u32(0x00),
+        num_fields: read_u32(0x04),
+        off_data: read_u32(0x08),
+        entry_size: read_u32(0x0C),
+    })
+}
+
+/// Guess the endianness of a BCSV buffer from its header
+///
+/// A header is consistent when the entries start right after the field table and fit in the buffer.
+/// Big-endian wins when both readings are consistent, which only happens for nearly empty files
+///
+/// # Returns
+/// `Some(true)` for big-endian, `Some(false)` for little-endian, or `None` if neither reading is consistent
+pub fn detect_big_endian(data: &[u8]) -> Option<bool> {
+    [true, false].into_iter().find(|big_endian| {
+        let options = IoOptions {
+            big_endian: *big_endian,
+            ..IoOptions::default()
+        };
+        read_header(data, &options).is_ok_and(|header| header.is_consistent(data.len()))
+    })
+}
+
+/// The encoding of the strings of a BCSV buffer, as guessed by [`detect_encoding`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncodingGuess {
+    /// The non-ASCII strings read better in `encoding`
+    Likely {
+        /// The guessed encoding
+        encoding: Encoding,
+        /// Between 0 and 1. For UTF-8 it grows with the number of multibyte sequences, since a few can be
+        /// valid by chance, otherwise it is the share of non-ASCII bytes valid in `encoding`
+        confidence: f32,
+    },
+    /// The strings are pure ASCII, or there are none, so they read the same in every encoding
+    Ambiguous,
+}
+
+impl EncodingGuess {
+    /// Get the guessed encoding, or `hint` if the guess is ambiguous
+    pub fn encoding_or(&self, hint: Encoding) -> Encoding {
+        match self {
+            EncodingGuess::Likely { encoding, .. } => *encoding,
+            EncodingGuess::Ambiguous => hint,
+        }
+    }
+}
+
+/// Guess the string encoding of a BCSV buffer from its string table
+///
+/// The non-ASCII bytes are scored as UTF-8 multibyte sequences and as Shift-JIS characters, lead and trail
+/// pairs or half-width katakana. UTF-8 wins when all of them are valid UTF-8, as Shift-JIS text rarely is,
+/// otherwise the encoding with the larger share of valid bytes does. Inline strings are not examined
+///
+/// # Arguments
+/// - `data` - The BCSV data
+/// - `options_hint` - Options for endianness, used to find the string table
+///
+/// # Returns
+/// The guess, `Ambiguous` if the string table is pure ASCII or cannot be found
+pub fn detect_encoding(data: &[u8], options_hint: &IoOptions) -> EncodingGuess {
+    let Ok(header) = read_header(data, options_hint) else {
+        return EncodingGuess::Ambiguous;
+    };
+    let Some(strings) = usize::try_from(header.off_strings()).ok().and_then(|off| data.get(off..)) else {
+        return EncodingGuess::Ambiguous;
+    };
+
+    let non_ascii = strings.iter().filter(|byte| !byte.is_ascii()).count();
+    if non_ascii == 0 {
+        return EncodingGuess::Ambiguous;
+    }
+
+    let (mut utf8_sequences, mut utf8_invalid) = (0, 0);
+    for chunk in strings.utf8_chunks() {
+        utf8_sequences += chunk.valid().chars().filter(|c| !c.is_ascii()).count();
+        utf8_invalid += chunk.invalid().len();
+    }
+    if utf8_invalid == 0 {
+        return EncodingGuess::Likely {
+            encoding: Encoding::Utf8,
+            confidence: 1.0 - 0.5f32.powi(utf8_sequences.min(64) as i32),
+        };
+    }
+
+    let utf8_share = (non_ascii - utf8_invalid.min(non_ascii)) as f32 / non_ascii as f32;
+    let sjis_share = (non_ascii - shift_jis_invalid(strings).min(non_ascii)) as f32 / non_ascii as f32;
+    let (encoding, confidence) = if sjis_share >= utf8_share {
+        (Encoding::ShiftJis, sjis_share)
     } else {
-        (
-            LittleEndian::read_u32(&data[0x00..0x04]),
-            LittleEndian::read_u32(&data[0x04..0x08]),
-            LittleEndian::read_u32(&data[0x08..0x0C]),
-            LittleEndian::read_u32(&data[0x0C..0x10]),
-        )
+        (Encoding::Utf8, utf8_share)
     };
+    EncodingGuess::Likely { encoding, confidence }
+}
 
-    jmap.entry_size = entry_size;
+/// Count the bytes that are not part of a valid Shift-JIS character
+fn shift_jis_invalid(bytes: &[u8]) -> usize {
+    let mut invalid = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            0x00..=0x7F | 0xA1..=0xDF => i += 1,
+            0x81..=0x9F | 0xE0..=0xFC if matches!(bytes.get(i + 1), Some(0x40..=0x7E | 0x80..=0xFC)) => i += 2,
+            _ => {
+                invalid += 1;
+                i += 1;
+            }
+        }
+    }
+    invalid
+}
 
-    // Calculate string table offset
-    // string table starts immediately after the entries, which start at off_data and each entry is entry_size bytes
-    // So the string table is at off_data + (num_entries * entry_size)
-    let off_strings = off_data as usize + (num_entries as usize * entry_size as usize);
+/// Read a JMapInfo from a byte buffer, with the string encoding guessed by [`detect_encoding`]
+///
+/// See [`from_buffer`]
+///
+/// # Arguments
+/// - `options` - Options for endianness and strictness. `encoding` is only used when the guess is ambiguous
+///
+/// # Returns
+/// The JMapInfo and the guess it was read with
+pub fn from_buffer_auto_encoding<H: HashTable>(
+    hash_table: H,
+    data: &[u8],
+    options: &IoOptions,
+) -> Result<(JMapInfo<H>, EncodingGuess)> {
+    let guess = detect_encoding(data, options);
+    let options = IoOptions {
+        encoding: guess.encoding_or(options.encoding),
+        ..options.clone()
+    };
+    from_buffer(hash_table, data, &options).map(|jmap| (jmap, guess))
+}
 
-    // Read fields (each field is 0xC bytes)
-    let mut off = 0x10_usize;
-    for _ in 0..num_fields {
-        let field = read_field(data, off, options.big_endian)?;
-        jmap.fields_map_mut().insert(field.hash, field);
-        off += 0x0C;
+/// Read the field definitions that follow the header (each field is 0xC bytes)
+///
+/// Fields with an unknown type are a `Structure` problem, fields extending past the entry or shifted by
+/// as many bits as their type has or more are a `Layout` problem. Either way the field is dropped when the
+/// problem is tolerated. Numeric fields whose mask keeps no bits are a `Layout` problem too, but are kept
+/// when it is tolerated since they read as 0
+pub(crate) fn read_fields<H: HashTable + ?Sized>(
+    data: &[u8],
+    header: &Header,
+    options: &IoOptions,
+    hash_table: &H,
+    report: &mut Report,
+) -> Result<Vec<Field>> {
+    let records = read_field_records(data, header, options, hash_table, report)?;
+    Ok(records.into_iter().map(|(_, field)| field).collect())
+}
+
+/// Read the field definitions like [`read_fields`], keeping a single definition of each hash
+///
+/// # Errors
+/// - `JMapError::DuplicateField` for the first hash defined twice, with `DuplicateFieldPolicy::Error`
+pub(crate) fn read_unique_fields<H: HashTable + ?Sized>(
+    data: &[u8],
+    header: &Header,
+    options: &IoOptions,
+    hash_table: &H,
+    report: &mut Report,
+) -> Result<Vec<Field>> {
+    let records = read_field_records(data, header, options, hash_table, report)?;
+    resolve_duplicates(records, options, hash_table, report)
+}
+
+/// Read the field definitions like [`read_fields`], with the index of each one in the field table
+fn read_field_records<H: HashTable + ?Sized>(
+    data: &[u8],
+    header: &Header,
+    options: &IoOptions,
+    hash_table: &H,
+    report: &mut Report,
+) -> Result<Vec<(usize, Field)>> {
+    let end = (header.num_fields as usize)
+        .checked_mul(0x0C)
+        .and_then(|size| size.checked_add(0x10))
+        .unwrap_or(usize::MAX);
+    if data.len() < end {
+        return Err(JMapError::BufferTooSmall {
+            expected: end,
+            got: data.len(),
+        });
     }
 
-    // Read entries
-    off = off_data as usize;
-    for _ in 0..num_entries {
-        let entry = read_entry(data, off, off_strings, &jmap, options)?;
-        jmap.entries_vec_mut().push(entry);
-        off += entry_size as usize;
+    let mut fields = Vec::with_capacity(header.num_fields as usize);
+    for (index, offset) in (0..header.num_fields as usize).map(|i| (i, 0x10 + i * 0x0C)) {
+        let (warning, error) = match read_field(data, offset, options.big_endian) {
+            Ok(field) => {
+                let (problem, keep) = match field_layout_problem(&field, header.entry_size, options.inline_string_size) {
+                    Some(problem) => (problem, false),
+                    None => match field_mask_problem(&field) {
+                        Some(problem) => (problem, true),
+                        None => {
+                            fields.push((index, field));
+                            continue;
+                        }
+                    },
+                };
+                let outcome = if keep { "reading it as 0" } else { "dropping the field" };
+                let warning = Warning::bcsv(
+                    Category::Layout,
+                    offset,
+                    None,
+                    Some(hash_table.find(field.hash)),
+                    format!("field {}: {}, {}", index, problem, outcome),
+                );
+                let error = JMapError::InvalidField(format!("field {} [{:08X}]: {}", index, field.hash, problem));
+                if keep {
+                    fields.push((index, field));
+                }
+                (warning, error)
+            }
+            Err(err) => {
+                let hash = if options.big_endian {
+                    BigEndian::read_u32(&data[offset..offset + 0x04])
+                } else {
+                    LittleEndian::read_u32(&data[offset..offset + 0x04])
+                };
+                (
+                    Warning::bcsv(Category::Structure, offset, None, Some(hash_table.find(hash)), format!("{}, dropping the field", err)),
+                    err,
+                )
+            }
+        };
+        options.strictness.check(report, warning, || error)?;
     }
+    Ok(fields)
+}
 
-    Ok(jmap)
+/// Keep a single definition of each hash of a field table, as chosen by `options.duplicate_fields`
+///
+/// # Arguments
+/// - `records` - The fields in file order, with their index in the field table
+///
+/// # Errors
+/// - `JMapError::DuplicateField` for the first hash defined twice, with `DuplicateFieldPolicy::Error`
+fn resolve_duplicates<H: HashTable + ?Sized>(
+    records: Vec<(usize, Field)>,
+    options: &IoOptions,
+    hash_table: &H,
+    report: &mut Report,
+) -> Result<Vec<Field>> {
+    let mut resolved: Vec<Field> = Vec::with_capacity(records.len());
+    // Index in the field table and position in `resolved` of the first definition of each hash
+    let mut first: HashMap<u32, (usize, usize)> = HashMap::new();
+    for (index, field) in records {
+        let Some(&(first_index, position)) = first.get(&field.hash) else {
+            first.insert(field.hash, (index, resolved.len()));
+            resolved.push(field);
+            continue;
+        };
+        let outcome = match options.duplicate_fields {
+            DuplicateFieldPolicy::Error => {
+                return Err(JMapError::DuplicateField {
+                    hash: field.hash,
+                    first_index,
+                    second_index: index,
+                });
+            }
+            DuplicateFieldPolicy::KeepFirst => "keeping the first one",
+            DuplicateFieldPolicy::KeepLast => "keeping this one",
+        };
+        if options.strictness.level(Category::Structure) != Level::Ignore {
+            report.warnings.push(Warning::bcsv(
+                Category::Structure,
+                0x10 + index * 0x0C,
+                None,
+                Some(hash_table.find(field.hash)),
+                format!("field {} defines the same hash as field {}, {}", index, first_index, outcome),
+            ));
+        }
+        if options.duplicate_fields == DuplicateFieldPolicy::KeepLast {
+            resolved[position] = field;
+        }
+    }
+    Ok(resolved)
+}
+
+/// Check that no two fields read from a file write the same bits of an entry
+///
+/// Fields packing disjoint bits of the same bytes are fine, the others are kept and the last one written wins
+///
+/// # Errors
+/// - `JMapError::InvalidField` for a pair of fields with overlapping masks, if it is not tolerated
+pub(crate) fn check_field_conflicts<'a, H: HashTable + ?Sized>(
+    fields: impl IntoIterator<Item = &'a Field>,
+    options: &IoOptions,
+    hash_table: &H,
+    report: &mut Report,
+) -> Result<()> {
+    let fields = fields.into_iter().map(|field| (field, field.offset));
+    for conflict in layout_conflicts(fields, options.big_endian, options.inline_string_size) {
+        if conflict.is_benign() {
+            continue;
+        }
+        let warning = Warning::bcsv(
+            Category::Layout,
+            0x10,
+            None,
+            Some(hash_table.find(conflict.second)),
+            format!("{}, the last one written wins", conflict),
+        );
+        options.strictness.check(report, warning, || JMapError::InvalidField(conflict.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Describe why a field read from a file cannot be decoded within entries of `entry_size` bytes
+fn field_layout_problem(field: &Field, entry_size: u32, inline_string_size: usize) -> Option<String> {
+    let bits = match field.field_type {
+        FieldType::Short | FieldType::Char => field.size() * 8,
+        _ => 32,
+    };
+    if field.shift as usize >= bits {
+        return Some(format!("shift {} must be less than {} for {} fields", field.shift, bits, field.field_type));
+    }
+    let size = field.size_with(inline_string_size);
+    let end = field.offset as u32 + size as u32;
+    if end > entry_size {
+        return Some(format!(
+            "{} bytes at offset 0x{:X} extend past the {}-byte entry",
+            size,
+            field.offset,
+            entry_size
+        ));
+    }
+    None
+}
+
+/// Describe why a numeric field read from a file always reads as 0, with a mask keeping no bits
+fn field_mask_problem(field: &Field) -> Option<String> {
+    if !matches!(
+        field.field_type,
+        FieldType::Long | FieldType::UnsignedLong | FieldType::Short | FieldType::Char
+    ) {
+        return None;
+    }
+    let width = field.field_type.default_mask();
+    if field.mask == 0 {
+        Some("mask is zero".to_string())
+    } else if field.mask & width == 0 {
+        Some(format!("mask 0x{:08X} has no bits in {}-byte {} fields", field.mask, field.size(), field.field_type))
+    } else if (field.mask & width) >> field.shift == 0 {
+        Some(format!("mask 0x{:08X} has no bits left after a shift of {}", field.mask, field.shift))
+    } else {
+        None
+    }
 }
 
 /// Read a JMapInfo from a file
@@ -151,35 +783,40 @@ pub fn from_file<H: HashTable, P: AsRef<Path>>(
 /// # Type
 /// - `H` - The type of hash table used by the `JMapInfo`, which must implement the `HashTable` trait
 ///
+/// # Errors
+/// - `JMapError::Parse` with the offset, entry and field of a value that cannot be written, such as a value
+///   that does not match its field type
+/// - `JMapError::InvalidField` if two fields write the same bits of an entry and `options.strictness` does
+///   not tolerate layout problems
+///
 /// # Returns
 /// A `Result` containing the serialized byte buffer if successful, or an error if serialization fails
 ///
 /// TODO: This function is pretty complex and could use some refactoring to break it down into smaller functions
 pub fn to_buffer<H: HashTable>(jmap: &JMapInfo<H>, options: &IoOptions) -> Result<Vec<u8>> {
-    let num_entries = jmap.len() as u32;
-    let num_fields = jmap.num_fields() as u32;
-    let off_data = 0x10 + num_fields * 0x0C; // Header (16 bytes) + field definitions (12 bytes each)
-
-    // Calculate entry size and field offsets
-    let mut fields_with_offsets: Vec<(u32, Field)> = jmap
-        .fields()
-        .map(|f| (f.hash, f.clone()))
-        .collect();
-
-    // Sort by type order and assign offsets
-    fields_with_offsets.sort_by_key(|(_, f)| f.field_type.order());
+    let num_entries = to_u32("entry count", jmap.len() as u64)?;
+    let num_fields = to_u32("field count", jmap.num_fields() as u64)?;
 
-    let mut current_offset: u16 = 0;
-    for (_, field) in &mut fields_with_offsets {
-        field.offset = current_offset;
-        current_offset += field.field_type.size() as u16;
+    // Calculate entry size and field offsets, indexed like the fields of the JMapInfo
+    let fields = jmap.fields_map();
+    let (placement, entry_size) = entry_placement(jmap, options);
+    let mut offsets = vec![0u16; fields.len()];
+    for &(index, offset) in &placement {
+        offsets[index] = offset;
     }
+    if options.strictness.layout == Level::Error {
+        let placed = fields.values().zip(offsets.iter().copied());
+        let conflicts = layout_conflicts(placed, options.big_endian, options.inline_string_size);
+        if let Some(conflict) = conflicts.into_iter().find(|conflict| !conflict.is_benign()) {
+            return Err(JMapError::InvalidField(conflict.to_string()));
+        }
+    }
+    let (off_data, off_strings) = section_offsets(num_fields as u64, num_entries as u64, entry_size)?;
+    let off_strings = off_strings as usize;
 
-    // Align entry size to 4 bytes
-    let entry_size = ((current_offset as u32 + 3) & !3) as u32;
-
-    // Create buffer
-    let mut buffer = vec![0u8; (off_data + num_entries * entry_size) as usize];
+    // Create buffer, with room for the string table and the final padding
+    let mut buffer = Vec::with_capacity(off_strings + string_table_estimate(jmap) + 31);
+    buffer.resize(off_strings, 0);
 
     // Write header
     if options.big_endian {
@@ -194,49 +831,154 @@ pub fn to_buffer<H: HashTable>(jmap: &JMapInfo<H>, options: &IoOptions) -> Resul
         LittleEndian::write_u32(&mut buffer[0x0C..0x10], entry_size);
     }
 
-    // Build a map of hash -> offset for quick lookup
-    let field_offsets: std::collections::HashMap<u32, &Field> = fields_with_offsets
-        .iter()
-        .map(|(hash, field)| (*hash, field))
-        .collect();
-
     // Write fields
     let mut off = 0x10_usize;
-    for (hash, field) in &fields_with_offsets {
-        write_field(&mut buffer, off, *hash, field, options.big_endian);
+    for &(index, offset) in &placement {
+        let (hash, field) = fields.get_index(index).expect("placement indices come from the fields");
+        write_field(&mut buffer, off, *hash, field, offset, options.big_endian);
         off += 12;
     }
 
-    // Prepare string table for StringOffset fields
-    let mut string_table: Vec<u8> = Vec::new();
-    let mut string_offsets: std::collections::HashMap<String, u32> = std::collections::HashMap::new();
+    // Strings of StringOffset fields are appended after the entries, each distinct encoded string once
+    let mut string_offsets: HashMap<Cow<[u8]>, u32> = HashMap::new();
 
-    // Write entries
+    // Write entries, skipping values of hashes that are not fields
     off = off_data as usize;
-    for entry in jmap.entries() {
-        write_entry(
-            &mut buffer,
-            off,
-            entry,
-            &field_offsets,
-            &mut string_table,
-            &mut string_offsets,
-            options,
-        )?;
+    for (entry_index, entry) in jmap.entries().iter().enumerate() {
+        for (hash, value) in entry.iter() {
+            if let Some((index, _, field)) = fields.get_full(hash) {
+                let val_offset = off + offsets[index] as usize;
+                write_field_value(&mut buffer, val_offset, off_strings, value, field, &mut string_offsets, options)
+                    .map_err(|source| value_error(source, val_offset, entry_index, *hash, jmap.hash_table()))?;
+            }
+        }
         off += entry_size as usize;
     }
 
-    // Append string table
-    buffer.extend_from_slice(&string_table);
-
     // Align to 32 bytes with 0x40 padding
     let len = buffer.len();
-    let aligned_len = (len + 31) & !31;
+    let aligned_len = to_u32("output length", (len as u64).div_ceil(32) * 32)? as usize;
     buffer.resize(aligned_len, 0x40);
+    debug!(
+        entries = num_entries,
+        fields = num_fields,
+        off_data,
+        entry_size,
+        strings = len - off_strings,
+        len = aligned_len,
+        "wrote BCSV"
+    );
 
     Ok(buffer)
 }
 
+/// Compute the offsets of the entries and of the string table of a BCSV file, which follow a 16-byte header
+/// and 12 bytes per field
+///
+/// # Errors
+/// - `JMapError::SizeOverflow` if an offset does not fit in 32 bits
+///
+/// # Returns
+/// The offset of the first entry and the offset of the string table
+fn section_offsets(num_fields: u64, num_entries: u64, entry_size: u32) -> Result<(u32, u32)> {
+    let overflow = |what| JMapError::SizeOverflow { what, value: u64::MAX };
+    let off_data = num_fields
+        .checked_mul(0x0C)
+        .and_then(|size| size.checked_add(0x10))
+        .ok_or_else(|| overflow("entry offset"))?;
+    let off_strings = num_entries
+        .checked_mul(entry_size as u64)
+        .and_then(|size| size.checked_add(off_data))
+        .ok_or_else(|| overflow("string table offset"))?;
+    Ok((to_u32("entry offset", off_data)?, to_u32("string table offset", off_strings)?))
+}
+
+/// Convert an offset, a count or a length to the 32 bits of the format
+///
+/// # Errors
+/// - `JMapError::SizeOverflow` naming `what` if the value does not fit
+fn to_u32(what: &'static str, value: u64) -> Result<u32> {
+    u32::try_from(value).map_err(|_| JMapError::SizeOverflow { what, value })
+}
+
+/// Compute the offset of every field and the entry size written by [`to_buffer`]
+///
+/// With `LayoutStrategy::Preserve`, the entry size of the table is kept when its fields fit in it,
+/// so entries read with trailing bytes keep their size
+fn entry_placement<H: HashTable>(jmap: &JMapInfo<H>, options: &IoOptions) -> (Vec<(usize, u16)>, u32) {
+    let (placement, entry_size) = options.layout.placement(jmap.fields(), &options.layout_context());
+    if let LayoutStrategy::Preserve = options.layout {
+        let size = |field: &Field| field.size_with(options.inline_string_size) as u32;
+        let end = jmap.fields().map(|field| field.offset as u32 + size(field)).max().unwrap_or(0);
+        if jmap.entry_size >= end {
+            return (placement, jmap.entry_size);
+        }
+    }
+    (placement, entry_size)
+}
+
+/// Wrap an error raised by a value of BCSV data with its offset, entry and field name
+fn value_error<H: HashTable + ?Sized>(source: JMapError, offset: usize, entry: usize, hash: u32, hash_table: &H) -> JMapError {
+    JMapError::Parse {
+        offset,
+        entry: Some(entry),
+        field: Some(hash_table.find(hash)),
+        source: Box::new(source),
+    }
+}
+
+/// Compute the length of the output of [`to_buffer`], see [`JMapInfo::estimated_serialized_size`]
+pub(crate) fn serialized_size<H: HashTable>(jmap: &JMapInfo<H>, options: &IoOptions) -> usize {
+    let (_, entry_size) = entry_placement(jmap, options);
+    let Ok((_, off_strings)) = section_offsets(jmap.num_fields() as u64, jmap.len() as u64, entry_size) else {
+        return usize::MAX;
+    };
+    let off_strings = off_strings as usize;
+
+    let hashes: Vec<(u32, Encoding)> = jmap
+        .fields()
+        .filter(|field| field.field_type == FieldType::StringOffset)
+        .map(|field| (field.hash, options.encoding_for(field.hash)))
+        .collect();
+    let mut strings: HashSet<Cow<[u8]>> = HashSet::new();
+    let mut string_table = 0;
+    for entry in jmap.entries() {
+        for (value, encoding) in hashes.iter().filter_map(|&(hash, encoding)| Some((entry.get_by_hash(hash)?, encoding))) {
+            if let FieldValue::String(s) = value
+                && let Ok(bytes) = encode_string(s, encoding)
+            {
+                let len = bytes.len();
+                if strings.insert(bytes) {
+                    string_table += len + 1;
+                }
+            }
+        }
+    }
+
+    (off_strings + string_table + 31) & !31
+}
+
+/// Estimate the size of the string table, counting every StringOffset value with its terminator
+///
+/// Repeated strings are counted each time and the UTF-8 length stands in for the encoded one, so this is
+/// usually an overestimate. It only sizes the output buffer
+fn string_table_estimate<H: HashTable>(jmap: &JMapInfo<H>) -> usize {
+    let hashes: Vec<u32> = jmap
+        .fields()
+        .filter(|field| field.field_type == FieldType::StringOffset)
+        .map(|field| field.hash)
+        .collect();
+    if hashes.is_empty() {
+        return 0;
+    }
+
+    jmap.entries()
+        .iter()
+        .flat_map(|entry| hashes.iter().filter_map(|&hash| entry.get_by_hash(hash)))
+        .map(|value| value.as_str().map_or(0, |s| s.len() + 1))
+        .sum()
+}
+
 /// Write a JMapInfo to a file
 ///
 /// # Arguments
@@ -262,8 +1004,158 @@ pub fn to_file<H: HashTable, P: AsRef<Path>>(
     Ok(())
 }
 
+/// A value to write over one cell of an existing BCSV buffer, see [`patch_buffer`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellEdit {
+    /// Index of the entry holding the cell
+    pub entry_index: usize,
+    /// Hash of the field of the cell
+    pub field_hash: u32,
+    /// The new value
+    pub value: FieldValue,
+}
+
+/// What [`patch_buffer`] changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchOutcome {
+    /// Number of cells written
+    pub cells_written: usize,
+    /// Number of StringOffset values pointing at a string already in the string table
+    pub strings_reused: usize,
+    /// Number of strings appended to the string table
+    pub strings_appended: usize,
+}
+
+/// Write values into an existing BCSV buffer without serializing the whole table again
+///
+/// Numeric values are written in place, keeping the bits outside the field's mask. A StringOffset value
+/// points at an identical string already in the string table when there is one, otherwise the string is
+/// appended to the table and the 0x40 padding is adjusted. Every other byte stays as it was
+///
+/// The edits are checked before anything is written, so the buffer is left untouched when one is refused
+///
+/// # Arguments
+/// - `data` - The BCSV data to patch
+/// - `options` - Options for endianness, string encoding and strictness of the field table
+/// - `edits` - The cells to write, in order
+///
+/// # Errors
+/// - `JMapError::EntryIndexOutOfBounds` if an edit targets an entry past the end of the table
+/// - `JMapError::Parse` with the offset, entry and field of an edit that cannot be written in place:
+///   `FieldNotFound` for an unknown field, `TypeMismatch` for a value that does not match its field type,
+///   or `InvalidField` for an inline string longer than `options.inline_string_size`
+/// - `JMapError::BufferTooSmall` if the field table or the entries are cut off by the end of the buffer
+///
+/// # Returns
+/// The number of cells written and of strings reused or appended
+pub fn patch_buffer(data: &mut Vec<u8>, options: &IoOptions, edits: &[CellEdit]) -> Result<PatchOutcome> {
+    let header = read_header(data, options)?;
+    let fields = read_fields(data, &header, options, &no_lookup(), &mut Report::default())?;
+    let off_strings = header.off_strings();
+    if off_strings > data.len() as u64 {
+        return Err(JMapError::BufferTooSmall {
+            expected: off_strings as usize,
+            got: data.len(),
+        });
+    }
+    let off_strings = off_strings as usize;
+
+    // Check every edit first, writing it to a copy of its cell
+    let mut cells = Vec::with_capacity(edits.len());
+    for edit in edits {
+        if edit.entry_index >= header.num_entries as usize {
+            return Err(JMapError::EntryIndexOutOfBounds {
+                index: edit.entry_index,
+                len: header.num_entries as usize,
+            });
+        }
+        let entry_offset = header.off_data as usize + edit.entry_index * header.entry_size as usize;
+        let error = |offset, source| value_error(source, offset, edit.entry_index, edit.field_hash, &no_lookup());
+
+        let field = fields
+            .iter()
+            .find(|field| field.hash == edit.field_hash)
+            .ok_or_else(|| error(entry_offset, JMapError::FieldNotFound(no_lookup().find(edit.field_hash))))?;
+        let offset = entry_offset + field.offset as usize;
+        if let (FieldType::String, FieldValue::String(s)) = (field.field_type, &edit.value) {
+            let len = encode_string(s, options.encoding_for(field.hash))?.len();
+            if len > options.inline_string_size {
+                let problem =
+                    format!("inline string of {} bytes does not fit in {} bytes", len, options.inline_string_size);
+                return Err(error(offset, JMapError::InvalidField(problem)));
+            }
+        }
+        let size = field.size_with(options.inline_string_size);
+        let mut cell = data[offset..offset + size].to_vec();
+        write_field_value(&mut cell, 0, size, &edit.value, field, &mut HashMap::new(), options)
+            .map_err(|source| error(offset, source))?;
+        cells.push((offset, field));
+    }
+
+    // Strings are appended where the table ends, before its padding
+    let mut strings_end = string_table_end(data, off_strings);
+    if data[strings_end..].iter().any(|&b| b != 0x40) {
+        strings_end = data.len();
+    }
+
+    let mut outcome = PatchOutcome::default();
+    for (edit, (offset, field)) in edits.iter().zip(cells) {
+        match (field.field_type, &edit.value) {
+            (FieldType::String, _) => {
+                data[offset..offset + options.inline_string_size].fill(0);
+                write_field_value(data, offset, off_strings, &edit.value, field, &mut HashMap::new(), options)?;
+            }
+            (FieldType::StringOffset, FieldValue::String(s)) => {
+                let mut bytes = encode_string(s, options.encoding_for(field.hash))?.into_owned();
+                bytes.push(0);
+                let existing = data[off_strings..strings_end]
+                    .windows(bytes.len())
+                    .position(|window| window == bytes.as_slice());
+                let str_offset = match existing {
+                    Some(str_offset) => {
+                        outcome.strings_reused += 1;
+                        str_offset
+                    }
+                    None => {
+                        data.truncate(strings_end);
+                        data.extend_from_slice(&bytes);
+                        strings_end = data.len();
+                        outcome.strings_appended += 1;
+                        strings_end - bytes.len() - off_strings
+                    }
+                };
+                if options.big_endian {
+                    BigEndian::write_u32(&mut data[offset..offset + 4], str_offset as u32);
+                } else {
+                    LittleEndian::write_u32(&mut data[offset..offset + 4], str_offset as u32);
+                }
+            }
+            _ => write_field_value(data, offset, off_strings, &edit.value, field, &mut HashMap::new(), options)?,
+        }
+        outcome.cells_written += 1;
+    }
+
+    // Align to 32 bytes with 0x40 padding again if the table grew
+    if outcome.strings_appended > 0 {
+        let aligned_len = (data.len() + 31) & !31;
+        data.resize(aligned_len, 0x40);
+    }
+
+    Ok(outcome)
+}
+
 // Helper functions
 
+/// Find the end of the strings of the string table starting at `off_strings`, right after the last terminator
+///
+/// Bytes past this end are the padding of the file
+fn string_table_end(data: &[u8], off_strings: usize) -> usize {
+    data[off_strings..]
+        .iter()
+        .rposition(|&b| b == 0)
+        .map_or(off_strings, |end| off_strings + end + 1)
+}
+
 /// Read a field definition from the buffer at the given offset
 ///
 /// # Arguments
@@ -315,48 +1207,194 @@ fn read_field(data: &[u8], offset: usize, big_endian: bool) -> Result<Field> {
 /// - `offset` - The offset in the buffer where the field definition should start
 /// - `hash` - The hash of the field name
 /// - `field` - The `Field` instance containing the field definition to write
+/// - `field_offset` - The offset of the field within an entry, used instead of `field.offset`
 /// - `big_endian` - Whether the data should be written in big-endian or little-endian format
-fn write_field(buffer: &mut [u8], offset: usize, hash: u32, field: &Field, big_endian: bool) {
+fn write_field(buffer: &mut [u8], offset: usize, hash: u32, field: &Field, field_offset: u16, big_endian: bool) {
     if big_endian {
         BigEndian::write_u32(&mut buffer[offset..offset + 0x04], hash);
         BigEndian::write_u32(&mut buffer[offset + 0x04..offset + 0x08], field.mask);
-        BigEndian::write_u16(&mut buffer[offset + 0x08..offset + 0x0A], field.offset);
+        BigEndian::write_u16(&mut buffer[offset + 0x08..offset + 0x0A], field_offset);
     } else {
         LittleEndian::write_u32(&mut buffer[offset..offset + 0x04], hash);
         LittleEndian::write_u32(&mut buffer[offset + 0x04..offset + 0x08], field.mask);
-        LittleEndian::write_u16(&mut buffer[offset + 0x08..offset + 0x0A], field.offset);
+        LittleEndian::write_u16(&mut buffer[offset + 0x08..offset + 0x0A], field_offset);
     }
     buffer[offset + 0x0A] = field.shift;
     buffer[offset + 0x0B] = field.field_type as u8;
 }
 
-/// Read an entry from the buffer at the given offset, using the field definitions from the JMapInfo
+/// Tables with fewer entries are decoded on the calling thread, the work not paying for the thread overhead
+#[cfg(feature = "rayon")]
+const PARALLEL_MIN_ENTRIES: usize = 4096;
+
+/// A field with what is needed to decode its values
+struct FieldReader<'a> {
+    field: &'a Field,
+    decoder: Decoder,
+    /// Name of the field for errors and warnings, resolved once
+    name: String,
+}
+
+/// Decode `count` entries of `entry_size` bytes, the first one starting at `off_data`
 ///
-/// # Arguments
-/// - `data` - The byte buffer containing the entry data
-/// - `entry_offset` - The offset in the buffer where the entry starts
-/// - `string_table_offset` - The offset in the buffer where the string table starts (for StringOffset fields)
-/// - `jmap` - The `JMapInfo` instance containing the field definitions to use for parsing the entry
-/// - `options` - Options for endianness and string encoding
+/// With the `rayon` feature, tables of at least `PARALLEL_MIN_ENTRIES` entries are decoded in parallel.
+/// The entries and warnings keep their order, and the error of the first failing entry is returned
+fn read_entries(
+    values: &mut ValueReader,
+    fields: &[FieldReader],
+    layout: &Arc<SlotLayout>,
+    off_data: usize,
+    entry_size: usize,
+    count: usize,
+) -> Result<Vec<Entry>> {
+    let read_entry = |values: &mut ValueReader, index: usize| -> Result<Entry> {
+        let off = off_data + index * entry_size;
+        let mut entry = Entry::with_layout(Arc::clone(layout));
+        for FieldReader { field, decoder, name } in fields {
+            let value = values.read(*decoder, field, off + field.offset as usize, index, name)?;
+            entry.set_by_hash(field.hash, value);
+        }
+        Ok(entry)
+    };
+
+    #[cfg(feature = "rayon")]
+    if count >= PARALLEL_MIN_ENTRIES {
+        use rayon::prelude::*;
+
+        // Each entry gets its own report, merged in entry order. Results are collected before looking for
+        // errors, rayon returning whichever error it meets first
+        let (data, off_strings, options) = (values.data, values.off_strings, values.options);
+        let results: Vec<Result<(Entry, Report)>> = (0..count)
+            .into_par_iter()
+            .map(|index| {
+                let mut values = ValueReader { data, off_strings, options, report: Report::default() };
+                let entry = read_entry(&mut values, index)?;
+                Ok((entry, values.report))
+            })
+            .collect();
+
+        let mut entries = Vec::with_capacity(count);
+        for result in results {
+            let (entry, report) = result?;
+            values.report.warnings.extend(report.warnings);
+            entries.push(entry);
+        }
+        return Ok(entries);
+    }
+
+    (0..count).map(|index| read_entry(values, index)).collect()
+}
+
+/// Read the raw words of an entry before masking, keyed by field offset
 ///
-/// # Returns
-/// An `Entry` instance representing the parsed entry, or an error if parsing fails
-fn read_entry<H: HashTable>(
+/// Inline strings have no meaningful raw word and are skipped
+fn read_raw_values<H: HashTable>(
     data: &[u8],
     entry_offset: usize,
-    string_table_offset: usize,
     jmap: &JMapInfo<H>,
     options: &IoOptions,
-) -> Result<Entry> {
-    let mut entry = Entry::with_capacity(jmap.num_fields());
+) -> HashMap<u16, u32> {
+    let mut raw_values = HashMap::new();
 
     for field in jmap.fields() {
-        let val_offset = entry_offset + field.offset as usize;
-        let value = read_field_value(data, val_offset, string_table_offset, field, options)?;
-        entry.set_by_hash(field.hash, value);
+        let offset = entry_offset + field.offset as usize;
+        let raw = match field.field_type {
+            FieldType::Long | FieldType::UnsignedLong | FieldType::Float | FieldType::StringOffset => {
+                if options.big_endian {
+                    BigEndian::read_u32(&data[offset..offset + 4])
+                } else {
+                    LittleEndian::read_u32(&data[offset..offset + 4])
+                }
+            }
+            FieldType::Short => {
+                let raw = if options.big_endian {
+                    BigEndian::read_u16(&data[offset..offset + 2])
+                } else {
+                    LittleEndian::read_u16(&data[offset..offset + 2])
+                };
+                raw as u32
+            }
+            FieldType::Char => data[offset] as u32,
+            FieldType::String => continue,
+        };
+        raw_values.insert(field.offset, raw);
     }
 
-    Ok(entry)
+    raw_values
+}
+
+/// How the values of a field are read, resolved once per field rather than once per value
+///
+/// Most fields use the full mask of their type and no shift, so their values are the raw integers
+/// and need neither the mask arithmetic nor the sign extension of [`read_field_value`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decoder {
+    /// Full-width Long, read as is
+    Long,
+    /// Full-width UnsignedLong, read as is
+    UnsignedLong,
+    /// Full-width Short, sign-extended by the cast
+    Short,
+    /// Full-width Char, sign-extended by the cast
+    Char,
+    /// Any other field, read with [`read_field_value`]
+    Generic,
+}
+
+impl Decoder {
+    /// Find the decoder for a field
+    pub(crate) fn for_field(field: &Field) -> Self {
+        if field.shift != 0 || field.mask != field.field_type.default_mask() {
+            return Decoder::Generic;
+        }
+        match field.field_type {
+            FieldType::Long => Decoder::Long,
+            FieldType::UnsignedLong => Decoder::UnsignedLong,
+            FieldType::Short => Decoder::Short,
+            FieldType::Char => Decoder::Char,
+            FieldType::Float | FieldType::String | FieldType::StringOffset => Decoder::Generic,
+        }
+    }
+
+    /// Read a field value, see [`read_field_value`] for the arguments
+    ///
+    /// # Returns
+    /// The same value as `read_field_value`, which `field` must have been resolved for with [`Decoder::for_field`]
+    #[inline]
+    pub(crate) fn read(
+        self,
+        data: &[u8],
+        offset: usize,
+        string_table_offset: usize,
+        field: &Field,
+        options: &IoOptions,
+    ) -> Result<FieldValue> {
+        let value = match self {
+            Decoder::Long | Decoder::UnsignedLong => {
+                let raw = if options.big_endian {
+                    BigEndian::read_u32(&data[offset..offset + 4])
+                } else {
+                    LittleEndian::read_u32(&data[offset..offset + 4])
+                };
+                if self == Decoder::Long {
+                    FieldValue::Int(raw as i32)
+                } else {
+                    FieldValue::UInt(raw)
+                }
+            }
+            Decoder::Short => {
+                let raw = if options.big_endian {
+                    BigEndian::read_i16(&data[offset..offset + 2])
+                } else {
+                    LittleEndian::read_i16(&data[offset..offset + 2])
+                };
+                FieldValue::Int(raw as i32)
+            }
+            Decoder::Char => FieldValue::Int(data[offset] as i8 as i32),
+            Decoder::Generic => return read_field_value(data, offset, string_table_offset, field, options, false),
+        };
+        Ok(value)
+    }
 }
 
 /// Read a field value from the buffer at the given offset, applying the field's mask and shift, and using the string table for StringOffset fields
@@ -367,6 +1405,8 @@ fn read_entry<H: HashTable>(
 /// - `string_table_offset` - The offset in the buffer where the string table starts (for StringOffset fields)
 /// - `field` - The `Field` instance containing the field definition to use for parsing the value
 /// - `options` - Options for endianness and string encoding
+/// - `lossy` - Whether strings that cannot be decoded get replacement characters and string offsets past the end
+///   of the buffer read as empty strings, instead of failing
 ///
 /// # Returns
 /// A `FieldValue` instance representing the parsed field value, or an error if parsing fails
@@ -378,6 +1418,7 @@ fn read_field_value(
     string_table_offset: usize,
     field: &Field,
     options: &IoOptions,
+    lossy: bool,
 ) -> Result<FieldValue> {
     let value = match field.field_type {
         FieldType::Long | FieldType::UnsignedLong => {
@@ -386,14 +1427,13 @@ fn read_field_value(
             } else {
                 LittleEndian::read_u32(&data[offset..offset + 4])
             };
-            let masked = (raw & field.mask) >> field.shift;
-            // Sign extend for signed types
-            let signed = if masked & 0x80000000 != 0 {
-                masked as i32
+            let masked = (raw & field.mask).checked_shr(field.shift as u32).unwrap_or(0);
+            if field.field_type == FieldType::UnsignedLong {
+                FieldValue::UInt(masked)
             } else {
-                masked as i32
-            };
-            FieldValue::Int(signed)
+                // Full 32-bit values need no sign extension, the bits are reinterpreted as-is
+                FieldValue::Int(masked as i32)
+            }
         }
 
         FieldType::Float => {
@@ -411,7 +1451,7 @@ fn read_field_value(
             } else {
                 LittleEndian::read_u16(&data[offset..offset + 2])
             };
-            let masked = ((raw as u32) & field.mask) >> field.shift;
+            let masked = ((raw as u32) & field.mask).checked_shr(field.shift as u32).unwrap_or(0);
             let signed = if masked & 0x8000 != 0 {
                 (masked | 0xFFFF0000) as i32
             } else {
@@ -422,7 +1462,7 @@ fn read_field_value(
 
         FieldType::Char => {
             let raw = data[offset];
-            let masked = ((raw as u32) & field.mask) >> field.shift;
+            let masked = ((raw as u32) & field.mask).checked_shr(field.shift as u32).unwrap_or(0);
             let signed = if masked & 0x80 != 0 {
                 (masked | 0xFFFFFF00) as i32
             } else {
@@ -432,13 +1472,14 @@ fn read_field_value(
         }
 
         FieldType::String => {
-            // Read up to 32 bytes until null terminator
-            let end = data[offset..offset + 32]
+            // Read up to the size of the slot until null terminator
+            let size = options.inline_string_size;
+            let end = data[offset..offset + size]
                 .iter()
                 .position(|&b| b == 0)
-                .unwrap_or(32);
+                .unwrap_or(size);
             let bytes = &data[offset..offset + end];
-            let s = decode_string(bytes, options.encoding)?;
+            let s = decode_string(bytes, options.encoding_for(field.hash), lossy)?;
             FieldValue::String(s)
         }
 
@@ -448,13 +1489,22 @@ fn read_field_value(
             } else {
                 LittleEndian::read_u32(&data[offset..offset + 4])
             };
-            let str_start = string_table_offset + str_offset as usize;
+            let str_start = string_table_offset.saturating_add(str_offset as usize);
+            if str_start > data.len() {
+                if lossy {
+                    return Ok(FieldValue::String(String::new()));
+                }
+                return Err(JMapError::BufferTooSmall {
+                    expected: str_start.saturating_add(1),
+                    got: data.len(),
+                });
+            }
             let end = data[str_start..]
                 .iter()
                 .position(|&b| b == 0)
                 .unwrap_or(0);
             let bytes = &data[str_start..str_start + end];
-            let s = decode_string(bytes, options.encoding)?;
+            let s = decode_string(bytes, options.encoding_for(field.hash), lossy)?;
             FieldValue::String(s)
         }
     };
@@ -462,75 +1512,39 @@ fn read_field_value(
     Ok(value)
 }
 
-/// Write an entry to the buffer at the given offset, using the field definitions from the JMapInfo, and updating the string table for StringOffset fields
+/// Write a field value to the buffer at the given offset, applying the field's mask and shift, and appending the string of StringOffset fields to the buffer
 ///
 /// # Arguments
-/// - `buffer` - The byte buffer where the entry should be written
-/// - `entry_offset` - The offset in the buffer where the entry should start
-/// - `entry` - The `Entry` instance containing the field values to write
-/// - `field_offsets` - A map of field hash to `Field` instance, used for looking up field definitions when writing values
-/// - `string_table` - A mutable byte vector representing the string table, which will be updated with new strings for StringOffset fields
-/// - `string_offsets` - A mutable map of string to offset in the string table, used for reusing existing strings and avoiding duplicates in the string table
-/// - `options` - Options for endianness and string encoding
-///
-/// # Returns
-/// Ok(()) if the entry was successfully written to the buffer, or an error if writing fails (e.g. due to type mismatch or encoding errors)
-fn write_entry(
-    buffer: &mut [u8],
-    entry_offset: usize,
-    entry: &Entry,
-    field_offsets: &std::collections::HashMap<u32, &Field>,
-    string_table: &mut Vec<u8>,
-    string_offsets: &mut std::collections::HashMap<String, u32>,
-    options: &IoOptions,
-) -> Result<()> {
-    for (hash, value) in entry.iter() {
-        if let Some(field) = field_offsets.get(hash) {
-            let val_offset = entry_offset + field.offset as usize;
-            write_field_value(
-                buffer,
-                val_offset,
-                value,
-                field,
-                string_table,
-                string_offsets,
-                options,
-            )?;
-        }
-    }
-    Ok(())
-}
-
-/// Write a field value to the buffer at the given offset, applying the field's mask and shift, and updating the string table for StringOffset fields
-///
-/// # Arguments
-/// - `buffer` - The byte buffer where the field value should be written
+/// - `buffer` - The byte buffer where the field value should be written, ending with the string table written so far
 /// - `offset` - The offset in the buffer where the field value should start
+/// - `off_strings` - The offset in the buffer where the string table starts
 /// - `value` - The `FieldValue` instance representing the value to write
 /// - `field` - The `Field` instance containing the field definition to use for writing the value
-/// - `string_table` - A mutable byte vector representing the string table, which will be updated with new strings for StringOffset fields
-/// - `string_offsets` - A mutable map of string to offset in the string table, used for reusing existing strings and avoiding duplicates in the string table
+/// - `string_offsets` - A mutable map of encoded string to offset in the string table, used for reusing existing strings and avoiding duplicates in the string table
 /// - `options` - Options for endianness and string encoding
 ///
 /// # Returns
-/// Ok(()) if the field value was successfully written to the buffer, or an error if writing fails (e.g. due to type mismatch or encoding errors)
-fn write_field_value(
-    buffer: &mut [u8],
+/// Ok(()) if the field value was successfully written to the buffer, or an error if writing fails (e.g. due to type mismatch, a value out of the range of a Short or Char field, or encoding errors)
+fn write_field_value<'a>(
+    buffer: &mut Vec<u8>,
     offset: usize,
-    value: &FieldValue,
+    off_strings: usize,
+    value: &'a FieldValue,
     field: &Field,
-    string_table: &mut Vec<u8>,
-    string_offsets: &mut std::collections::HashMap<String, u32>,
+    string_offsets: &mut HashMap<Cow<'a, [u8]>, u32>,
     options: &IoOptions,
 ) -> Result<()> {
     match (field.field_type, value) {
-        (FieldType::Long | FieldType::UnsignedLong, FieldValue::Int(v)) => {
+        (FieldType::Long | FieldType::UnsignedLong, FieldValue::Int(_))
+        | (FieldType::UnsignedLong, FieldValue::UInt(_)) => {
+            // Int values written to UnsignedLong fields keep their bit pattern
+            let v = value.as_uint().unwrap_or_default();
             let existing = if options.big_endian {
                 BigEndian::read_u32(&buffer[offset..offset + 4])
             } else {
                 LittleEndian::read_u32(&buffer[offset..offset + 4])
             };
-            let masked = (existing & !field.mask) | (((*v as u32) << field.shift) & field.mask);
+            let masked = (existing & !field.mask) | (v.checked_shl(field.shift as u32).unwrap_or(0) & field.mask);
             if options.big_endian {
                 BigEndian::write_u32(&mut buffer[offset..offset + 4], masked);
             } else {
@@ -546,13 +1560,15 @@ fn write_field_value(
             }
         }
 
-        (FieldType::Short, FieldValue::Int(v)) => {
+        (FieldType::Short, FieldValue::Int(_)) => {
+            let v = value.to_i16()? as u16;
             let existing = if options.big_endian {
                 BigEndian::read_u16(&buffer[offset..offset + 2])
             } else {
                 LittleEndian::read_u16(&buffer[offset..offset + 2])
             };
-            let masked = ((existing as u32 & !field.mask) | (((*v as u32) << field.shift) & field.mask)) as u16;
+            let shifted = (v as u32).checked_shl(field.shift as u32).unwrap_or(0);
+            let masked = ((existing as u32 & !field.mask) | (shifted & field.mask)) as u16;
             if options.big_endian {
                 BigEndian::write_u16(&mut buffer[offset..offset + 2], masked);
             } else {
@@ -560,27 +1576,29 @@ fn write_field_value(
             }
         }
 
-        (FieldType::Char, FieldValue::Int(v)) => {
+        (FieldType::Char, FieldValue::Int(_)) => {
+            let v = value.to_i8()? as u8;
             let existing = buffer[offset] as u32;
-            let masked = ((existing & !field.mask) | (((*v as u32) << field.shift) & field.mask)) as u8;
+            let shifted = (v as u32).checked_shl(field.shift as u32).unwrap_or(0);
+            let masked = ((existing & !field.mask) | (shifted & field.mask)) as u8;
             buffer[offset] = masked;
         }
 
         (FieldType::String, FieldValue::String(s)) => {
-            let bytes = encode_string(s, options.encoding)?;
-            let len = bytes.len().min(32);
-            buffer[offset..offset + len].copy_from_slice(&bytes[..len]);
+            let bytes = encode_field_string(s, field, options).bytes;
+            buffer[offset..offset + bytes.len()].copy_from_slice(&bytes);
         }
 
         (FieldType::StringOffset, FieldValue::String(s)) => {
-            let str_offset = if let Some(&existing_offset) = string_offsets.get(s) {
+            // Strings are shared by their encoded bytes, the same text in two encodings is stored twice
+            let bytes = encode_field_string(s, field, options).bytes;
+            let str_offset = if let Some(&existing_offset) = string_offsets.get(&bytes) {
                 existing_offset
             } else {
-                let offset = string_table.len() as u32;
-                let bytes = encode_string(s, options.encoding)?;
-                string_table.extend_from_slice(&bytes);
-                string_table.push(0); // Null terminator
-                string_offsets.insert(s.clone(), offset);
+                let offset = to_u32("string table offset", (buffer.len() - off_strings) as u64)?;
+                buffer.extend_from_slice(&bytes);
+                buffer.push(0); // Null terminator
+                string_offsets.insert(bytes, offset);
                 offset
             };
 
@@ -593,6 +1611,7 @@ fn write_field_value(
 
         _ => {
             return Err(JMapError::TypeMismatch {
+                field: FieldKey::Hash(field.hash).to_string(),
                 expected: field.field_type.csv_name(),
                 got: value.type_name(),
             });
@@ -607,26 +1626,87 @@ fn write_field_value(
 /// # Arguments
 /// - `bytes` - The byte slice to decode
 /// - `encoding` - The encoding to use for decoding the bytes (e.g. Shift-JIS or UTF-8)
+/// - `lossy` - Whether invalid sequences are replaced with U+FFFD instead of failing
 ///
 /// # Errors
 /// - `JMapError::EncodingError` if the bytes cannot be decoded using the specified encoding
 ///
 /// # Returns
 /// A `String` containing the decoded text, or an error if decoding fails
-fn decode_string(bytes: &[u8], encoding: Encoding) -> Result<String> {
+pub(crate) fn decode_string(bytes: &[u8], encoding: Encoding, lossy: bool) -> Result<String> {
     match encoding {
+        Encoding::Utf8 if lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
         Encoding::Utf8 => String::from_utf8(bytes.to_vec())
             .map_err(|e| JMapError::EncodingError(e.to_string())),
         Encoding::ShiftJis => {
             let (decoded, _, had_errors) = encoding_rs::SHIFT_JIS.decode(bytes);
-            if had_errors {
-                // Try to decode anyway, some bytes might be valid
+            if had_errors && !lossy {
+                return Err(JMapError::EncodingError("invalid Shift-JIS sequence".to_string()));
             }
             Ok(decoded.into_owned())
         }
     }
 }
 
+/// The bytes written for a string value, and how they differ from the string
+pub(crate) struct EncodedString<'a> {
+    /// The bytes written, without the terminator
+    pub(crate) bytes: Cow<'a, [u8]>,
+    /// The length of the encoded string before it was cut to the inline string size
+    pub(crate) full_len: usize,
+    /// Whether characters the encoding cannot represent were replaced
+    pub(crate) unmappable: bool,
+    /// Whether cutting the string split a multibyte character
+    pub(crate) split_char: bool,
+}
+
+impl EncodedString<'_> {
+    /// Whether the string was cut to the inline string size
+    pub(crate) fn truncated(&self) -> bool {
+        self.full_len > self.bytes.len()
+    }
+}
+
+/// Encode a string value as the writer does, in the encoding of its field and cut to the inline string size
+/// for `String` fields
+///
+/// This is the only place deciding what is written for a string, so that [`JMapInfo::audit_strings`]
+/// reports exactly what the writer does
+pub(crate) fn encode_field_string<'a>(s: &'a str, field: &Field, options: &IoOptions) -> EncodedString<'a> {
+    let encoding = options.encoding_for(field.hash);
+    let (bytes, unmappable) = match encoding {
+        Encoding::Utf8 => (Cow::Borrowed(s.as_bytes()), false),
+        Encoding::ShiftJis => {
+            let (encoded, _, unmappable) = encoding_rs::SHIFT_JIS.encode(s);
+            (encoded, unmappable)
+        }
+    };
+    let full_len = bytes.len();
+    if field.field_type != FieldType::String || full_len <= options.inline_string_size {
+        return EncodedString {
+            bytes,
+            full_len,
+            unmappable,
+            split_char: false,
+        };
+    }
+
+    let bytes = match bytes {
+        Cow::Borrowed(bytes) => Cow::Borrowed(&bytes[..options.inline_string_size]),
+        Cow::Owned(mut bytes) => {
+            bytes.truncate(options.inline_string_size);
+            Cow::Owned(bytes)
+        }
+    };
+    let split_char = decode_string(&bytes, encoding, false).is_err();
+    EncodedString {
+        bytes,
+        full_len,
+        unmappable,
+        split_char,
+    }
+}
+
 /// Encode a string into a byte vector using the specified encoding
 ///
 /// # Arguments
@@ -634,13 +1714,880 @@ fn decode_string(bytes: &[u8], encoding: Encoding) -> Result<String> {
 /// - `encoding` - The encoding to use for encoding the string (e.g. Shift-JIS or UTF-8)
 ///
 /// # Returns
-/// A `Vec<u8>` containing the encoded bytes of the string, or an error if encoding fails
-fn encode_string(s: &str, encoding: Encoding) -> Result<Vec<u8>> {
+/// The encoded bytes of the string, borrowed when no conversion is needed, or an error if encoding fails
+fn encode_string(s: &str, encoding: Encoding) -> Result<Cow<'_, [u8]>> {
     match encoding {
-        Encoding::Utf8 => Ok(s.as_bytes().to_vec()),
+        Encoding::Utf8 => Ok(Cow::Borrowed(s.as_bytes())),
         Encoding::ShiftJis => {
             let (encoded, _, _) = encoding_rs::SHIFT_JIS.encode(s);
-            Ok(encoded.into_owned())
+            Ok(encoded)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{calc_hash, smg_hash_table};
+    use crate::layout::LayoutConflict;
+
+    #[test]
+    fn test_unsigned_round_trip() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Switch", FieldType::UnsignedLong, FieldValue::UInt(0)).unwrap();
+        jmap.create_field("Legacy", FieldType::UnsignedLong, FieldValue::UInt(0)).unwrap();
+        let entry = jmap.create_entry();
+        entry.set(&smg_hash_table(), "Switch", FieldValue::UInt(4294967295));
+        entry.set(&smg_hash_table(), "Legacy", FieldValue::Int(-1));
+
+        let buffer = to_buffer(&jmap, &IoOptions::default()).unwrap();
+        let read = from_buffer(smg_hash_table(), &buffer, &IoOptions::default()).unwrap();
+        let entry = &read.entries()[0];
+        assert_eq!(entry.get(read.hash_table(), "Switch"), Some(&FieldValue::UInt(4294967295)));
+        assert_eq!(entry.get(read.hash_table(), "Legacy"), Some(&FieldValue::UInt(4294967295)));
+        assert_eq!(entry.get_uint(read.hash_table(), "Switch"), Some(4294967295));
+    }
+
+    #[test]
+    fn test_short_char_range() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Id", FieldType::Short, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Flag", FieldType::Char, FieldValue::Int(0)).unwrap();
+        let entry = jmap.create_entry();
+        entry.set(&smg_hash_table(), "Id", FieldValue::Int(-32768));
+        entry.set(&smg_hash_table(), "Flag", FieldValue::Int(127));
+        let buffer = to_buffer(&jmap, &IoOptions::default()).unwrap();
+        let read = from_buffer(smg_hash_table(), &buffer, &IoOptions::default()).unwrap();
+        assert_eq!(read.entries()[0].get_int(read.hash_table(), "Id"), Some(-32768));
+        assert_eq!(read.entries()[0].get_int(read.hash_table(), "Flag"), Some(127));
+
+        let out_of_range = [(FieldType::Short, 65535), (FieldType::Short, -32769)];
+        for (field_type, value) in out_of_range.into_iter().chain([(FieldType::Char, 255), (FieldType::Char, -129)]) {
+            let mut jmap = JMapInfo::new(smg_hash_table());
+            jmap.create_field("Value", field_type, FieldValue::Int(0)).unwrap();
+            jmap.create_entry().set(&smg_hash_table(), "Value", FieldValue::Int(value));
+            let err = to_buffer(&jmap, &IoOptions::default()).unwrap_err();
+            let JMapError::Parse { source, .. } = &err else {
+                panic!("{err}");
+            };
+            assert!(matches!(**source, JMapError::Conversion(_)), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_bit_packed_round_trip() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        let field = Field::with_mask_shift(0, FieldType::Long, 0x7FF << 5, 5);
+        jmap.add_field("Packed", field).unwrap();
+        jmap.create_entry().set(&smg_hash_table(), "Packed", FieldValue::Int(0x5A5));
+        jmap.create_entry().set(&smg_hash_table(), "Packed", FieldValue::Int(0xFFF));
+
+        let buffer = to_buffer(&jmap, &IoOptions::default()).unwrap();
+        let read = from_buffer(smg_hash_table(), &buffer, &IoOptions::default()).unwrap();
+
+        let field = read.get_field("Packed").unwrap();
+        assert_eq!((field.mask, field.shift), (0xFFE0, 5));
+        assert_eq!(read.entries()[0].get_int(read.hash_table(), "Packed"), Some(0x5A5));
+        // Bits above the 11-bit mask are dropped
+        assert_eq!(read.entries()[1].get_int(read.hash_table(), "Packed"), Some(0x7FF));
+    }
+
+    #[test]
+    fn test_field_validation() {
+        assert!(Field::with_mask_shift(0, FieldType::Long, 0xFFE0, 5).validate().is_ok());
+        assert!(Field::with_mask_shift(0, FieldType::Long, 0xFF, 32).validate().is_err());
+        assert!(Field::with_mask_shift(0, FieldType::Short, 0, 0).validate().is_err());
+        assert!(Field::with_mask_shift(0, FieldType::Short, 0x1FFFF, 0).validate().is_err());
+        assert!(Field::with_mask_shift(0, FieldType::Char, 0xF0, 4).validate().is_ok());
+        assert!(Field::with_mask_shift(0, FieldType::StringOffset, 0xFF, 0).validate().is_err());
+
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        let err = jmap.add_field("Bad", Field::with_mask_shift(0, FieldType::Char, 0x100, 0));
+        assert!(matches!(err, Err(JMapError::InvalidField(_))));
+        assert_eq!(jmap.num_fields(), 0);
+    }
+
+    #[test]
+    fn test_layout_strategies() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Flag", FieldType::Char, FieldValue::Int(1)).unwrap();
+        jmap.create_field("Count", FieldType::Long, FieldValue::Int(2)).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(3.0)).unwrap();
+        jmap.create_entry();
+
+        let offsets = |options: &IoOptions| {
+            let buffer = to_buffer(&jmap, options).unwrap();
+            let read = from_buffer(smg_hash_table(), &buffer, options).unwrap();
+            let entry = &read.entries()[0];
+            assert_eq!(entry.get_int(read.hash_table(), "Flag"), Some(1));
+            assert_eq!(entry.get_int(read.hash_table(), "Count"), Some(2));
+            assert_eq!(entry.get_float(read.hash_table(), "Scale"), Some(3.0));
+            let offsets: Vec<u16> = ["Flag", "Count", "Scale"]
+                .iter()
+                .map(|name| read.get_field(name).unwrap().offset)
+                .collect();
+            (offsets, read.entry_size)
+        };
+
+        let mut options = IoOptions::default();
+        assert_eq!(offsets(&options), (vec![8, 4, 0], 12));
+
+        options.layout = LayoutStrategy::DeclarationOrder;
+        assert_eq!(offsets(&options), (vec![0, 1, 5], 12));
+        options.align_fields = true;
+        assert_eq!(offsets(&options), (vec![0, 4, 8], 12));
+
+        options.layout = LayoutStrategy::SizeDescending;
+        assert_eq!(offsets(&options), (vec![8, 0, 4], 12));
+
+        options.layout = LayoutStrategy::Custom(|a, b| b.field_type.order().cmp(&a.field_type.order()));
+        assert_eq!(offsets(&options), (vec![0, 4, 8], 12));
+    }
+
+    #[test]
+    fn test_reproduce_declaration_layout() {
+        // A file whose fields were laid out in declaration order rather than type order:
+        // Id (Short) at 0x0, Name (StringOffset) aligned to 0x4, Scale (Float) at 0x8
+        let mut original = Vec::new();
+        for word in [1u32, 3, 0x34, 0x0C] {
+            original.extend_from_slice(&word.to_be_bytes());
+        }
+        let fields = [("Id", 0xFFFFu32, 0x0u16, FieldType::Short), ("Name", !0, 0x4, FieldType::StringOffset)];
+        for (name, mask, offset, field_type) in fields.into_iter().chain([("Scale", !0, 0x8, FieldType::Float)]) {
+            original.extend_from_slice(&calc_hash(name).to_be_bytes());
+            original.extend_from_slice(&mask.to_be_bytes());
+            original.extend_from_slice(&offset.to_be_bytes());
+            original.extend_from_slice(&[0, field_type as u8]);
+        }
+        original.extend_from_slice(&[0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3F, 0x80, 0x00, 0x00]);
+        original.extend_from_slice(b"a\0");
+        original.resize(0x60, 0x40);
+        let options = IoOptions {
+            layout: LayoutStrategy::DeclarationOrder,
+            align_fields: true,
+            ..IoOptions::default()
+        };
+
+        let read = from_buffer(smg_hash_table(), &original, &options).unwrap();
+        assert_eq!(read.entries()[0].get_by_hash(calc_hash("Id")), Some(&FieldValue::Int(7)));
+        assert_eq!(read.entries()[0].get_by_hash(calc_hash("Name")), Some(&FieldValue::from("a")));
+        assert_eq!(to_buffer(&read, &options).unwrap(), original);
+        assert_ne!(to_buffer(&read, &IoOptions::default()).unwrap(), original);
+    }
+
+    #[test]
+    fn test_keep_raw_values() {
+        // Two fields packed in the same word at offset 0
+        let mut data = vec![0u8; 0x10 + 2 * 0x0C + 4];
+        BigEndian::write_u32(&mut data[0x00..0x04], 1);
+        BigEndian::write_u32(&mut data[0x04..0x08], 2);
+        BigEndian::write_u32(&mut data[0x08..0x0C], 0x10 + 2 * 0x0C);
+        BigEndian::write_u32(&mut data[0x0C..0x10], 4);
+        let low = Field::with_mask_shift(calc_hash("Low"), FieldType::Long, 0x0000FFFF, 0);
+        let high = Field::with_mask_shift(calc_hash("High"), FieldType::Long, 0xFFFF0000, 16);
+        write_field(&mut data, 0x10, low.hash, &low, low.offset, true);
+        write_field(&mut data, 0x1C, high.hash, &high, high.offset, true);
+        BigEndian::write_u32(&mut data[0x28..0x2C], 0x12345678);
+
+        let read = from_buffer(smg_hash_table(), &data, &IoOptions::default()).unwrap();
+        assert_eq!(read.raw_at(0, 0), None);
+
+        let options = IoOptions {
+            keep_raw_values: true,
+            ..IoOptions::default()
+        };
+        let read = from_buffer(smg_hash_table(), &data, &options).unwrap();
+        assert_eq!(read.entries()[0].get_int_by_hash(low.hash), Some(0x5678));
+        assert_eq!(read.entries()[0].get_int_by_hash(high.hash), Some(0x1234));
+        for field in read.fields() {
+            assert_eq!(read.raw_at(0, field.offset), Some(0x12345678));
+        }
+        assert_eq!(read.raw_at(1, 0), None);
+    }
+
+    /// A table with every field type, a bit-packed field and repeated and non-ASCII strings
+    fn mixed_table() -> JMapInfo<crate::hash::FileHashTable> {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("name", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("Comment", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("l_id", FieldType::Long, FieldValue::Int(-1)).unwrap();
+        jmap.create_field("ShapeModelNo", FieldType::Short, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Flag", FieldType::Char, FieldValue::Int(0)).unwrap();
+        jmap.create_field("pos_x", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+        jmap.create_field("SW_APPEAR", FieldType::UnsignedLong, FieldValue::UInt(u32::MAX)).unwrap();
+        jmap.create_field("Label", FieldType::String, FieldValue::from("")).unwrap();
+        jmap.add_field("Packed", Field::with_mask_shift(0, FieldType::Long, 0x7FF << 5, 5)).unwrap();
+        let names = ["Kuribo", "ドカン", "Kuribo", "", "スター"];
+        for index in 0..20 {
+            jmap.entry_builder()
+                .set("name", FieldValue::String(names[index % names.len()].to_string()))
+                .set("Comment", FieldValue::String(format!("メモ{}", index % 3)))
+                .set("l_id", FieldValue::Int(index as i32 * 7 - 40))
+                .set("ShapeModelNo", FieldValue::Int(-(index as i32)))
+                .set("Flag", FieldValue::Int(index as i32 % 2))
+                .set("pos_x", FieldValue::Float(index as f32 * 1.5))
+                .set("SW_APPEAR", FieldValue::UInt(index as u32 * 0x0101_0101))
+                .set("Label", FieldValue::String(format!("Label{}", index)))
+                .set("Packed", FieldValue::Int(index as i32 * 97))
+                .insert()
+                .unwrap();
+        }
+        jmap
+    }
+
+    #[test]
+    fn test_to_buffer_reference_output() {
+        // Files written by to_buffer before it stopped cloning fields and strings, which must not change its output
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples");
+        let data = std::fs::read(format!("{}/scenariodata.bcsv", root)).unwrap();
+        let fixture = from_buffer(smg_hash_table(), &data, &IoOptions::default()).unwrap();
+        let little = IoOptions {
+            big_endian: false,
+            layout: LayoutStrategy::DeclarationOrder,
+            align_fields: true,
+            ..IoOptions::default()
+        };
+        let mixed = mixed_table();
+        let cases = [
+            ("scenariodata_be", to_buffer(&fixture, &IoOptions::default())),
+            ("scenariodata_le", to_buffer(&fixture, &little)),
+            ("mixed_be", to_buffer(&mixed, &IoOptions::default())),
+            ("mixed_le", to_buffer(&mixed, &little)),
+        ];
+        for (name, written) in cases {
+            let expected = std::fs::read(format!("{}/written/{}.bcsv", root, name)).unwrap();
+            assert!(written.unwrap() == expected, "{name} differs from its reference");
+        }
+    }
+
+    #[test]
+    fn test_large_table_order() {
+        // Enough entries for the parallel path of the rayon feature
+        let count: usize = 10_000;
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Index", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Name", FieldType::String, FieldValue::from("")).unwrap();
+        for index in 0..count {
+            jmap.entry_builder()
+                .set("Index", FieldValue::Int(index as i32))
+                .set("Name", FieldValue::String(format!("Obj{}", index % 100)))
+                .insert()
+                .unwrap();
+        }
+        let options = IoOptions {
+            encoding: Encoding::Utf8,
+            ..IoOptions::default()
+        };
+        let mut data = to_buffer(&jmap, &options).unwrap();
+        let read = from_buffer(smg_hash_table(), &data, &options).unwrap();
+        assert_eq!(read.entries(), jmap.entries());
+
+        // Break the names of three entries
+        let header = read_header(&data, &options).unwrap();
+        let fields = read_field_table(&data, &options).unwrap();
+        let name = fields.iter().find(|field| field.hash == calc_hash("Name")).unwrap();
+        for (index, byte) in [(9_000, 3), (5_000, 1), (7_000, 2)] {
+            data[(header.off_data + index * header.entry_size) as usize + name.offset as usize + byte] = 0xFF;
+        }
+        for _ in 0..5 {
+            let err = from_buffer(smg_hash_table(), &data, &options).unwrap_err();
+            assert!(matches!(err, JMapError::Parse { entry: Some(5_000), .. }), "{err}");
+        }
+
+        let permissive = IoOptions {
+            strictness: Strictness::permissive(),
+            ..options
+        };
+        let (read, report) = from_buffer_with_report(smg_hash_table(), &data, &permissive).unwrap();
+        let entries: Vec<Option<usize>> = report.warnings.iter().map(|warning| warning.entry).collect();
+        assert_eq!(entries, [Some(5_000), Some(7_000), Some(9_000)]);
+        let indices =
+            read.entries().iter().map(|entry| entry.get_by_hash(calc_hash("Index")).and_then(FieldValue::as_int));
+        assert!(indices.enumerate().all(|(index, value)| value == Some(index as i32)));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_events() {
+        use std::sync::{Arc, Mutex};
+
+        use tracing::field::{Field as EventField, Visit};
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+        use tracing_subscriber::Layer;
+
+        use crate::hash::{smg_hash_table_with_lookup, FileHashTable, HashAlgorithm};
+
+        /// The level of an event and its fields, as `name=value`
+        type Event = (tracing::Level, Vec<String>);
+
+        /// Records every event
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<Event>>>);
+
+        impl<S: tracing::Subscriber> Layer<S> for Capture {
+            fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+                struct Fields(Vec<String>);
+                impl Visit for Fields {
+                    fn record_debug(&mut self, field: &EventField, value: &dyn std::fmt::Debug) {
+                        self.0.push(format!("{}={:?}", field.name(), value));
+                    }
+                }
+                let mut fields = Fields(Vec::new());
+                event.record(&mut fields);
+                self.0.lock().unwrap().push((*event.metadata().level(), fields.0));
+            }
+        }
+
+        let root = env!("CARGO_MANIFEST_DIR");
+        let data = std::fs::read(format!("{}/assets/examples/scenariodata.bcsv", root)).unwrap();
+        let names = smg_hash_table_with_lookup(format!("{}/assets/strings_SMG.txt", root)).unwrap();
+        let known = from_buffer(names, &data, &IoOptions::default()).unwrap();
+
+        // Every name of the fixture but the first one
+        let mut hash_table = FileHashTable::new(HashAlgorithm::SMG);
+        let unresolved = *known.field_hashes().next().unwrap();
+        hash_table.extend_from_names(known.fields().skip(1).map(|field| known.field_name(field.hash)));
+
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::registry().with(capture.clone());
+        tracing::subscriber::with_default(subscriber, || from_buffer(hash_table, &data, &IoOptions::default()).unwrap());
+
+        let events = capture.0.lock().unwrap();
+        let warnings: Vec<&Vec<String>> =
+            events.iter().filter(|(level, _)| *level == tracing::Level::WARN).map(|(_, fields)| fields).collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains(&"message=unresolved field hash".to_string()));
+        assert!(warnings[0].contains(&format!("hash={}", unresolved)));
+        assert!(warnings[0].contains(&format!("name=[{:08X}]", unresolved)));
+
+        let header = events.iter().find(|(_, fields)| fields.contains(&"message=read BCSV header".to_string()));
+        assert!(header.unwrap().1.contains(&format!("fields={}", known.num_fields())));
+    }
+
+    #[test]
+    fn test_decoder_matches_generic() {
+        // xorshift, so the test is deterministic without a random number crate
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let data: Vec<u8> = (0..256).map(|_| next() as u8).collect();
+        let types = [FieldType::Long, FieldType::UnsignedLong, FieldType::Short, FieldType::Char];
+        let mut fast = 0;
+        for _ in 0..2000 {
+            let field_type = types[next() as usize % types.len()];
+            let width = field_type.size() as u32 * 8;
+            let field = if next() % 2 == 0 {
+                Field::new(0, field_type)
+            } else {
+                let shift = (next() % width as u64) as u8;
+                let mask = (next() as u32 & field_type.default_mask()) << shift;
+                Field::with_mask_shift(0, field_type, mask, shift)
+            };
+            let decoder = Decoder::for_field(&field);
+            fast += (decoder != Decoder::Generic) as usize;
+
+            let offset = next() as usize % (data.len() - 4);
+            for big_endian in [true, false] {
+                let options = IoOptions {
+                    big_endian,
+                    ..IoOptions::default()
+                };
+                let expected = read_field_value(&data, offset, 0, &field, &options, false).unwrap();
+                assert_eq!(decoder.read(&data, offset, 0, &field, &options).unwrap(), expected, "{field:?}");
+            }
+        }
+        assert!(fast > 500);
+    }
+
+    #[test]
+    fn test_inline_string_size() {
+        for size in [16, 64] {
+            let options = IoOptions {
+                inline_string_size: size,
+                ..IoOptions::default()
+            };
+            let mut jmap = JMapInfo::new(smg_hash_table());
+            jmap.create_field("Label", FieldType::String, FieldValue::from("")).unwrap();
+            jmap.create_field("Id", FieldType::Long, FieldValue::Int(0)).unwrap();
+            let labels = ["a".repeat(size), "b".repeat(size - 1), String::new()];
+            for (id, label) in labels.iter().enumerate() {
+                jmap.entry_builder()
+                    .set("Label", FieldValue::from(label.as_str()))
+                    .set("Id", FieldValue::Int(id as i32))
+                    .insert()
+                    .unwrap();
+            }
+            assert!(jmap.validate(&options).is_empty());
+
+            let data = to_buffer(&jmap, &options).unwrap();
+            let header = read_header(&data, &options).unwrap();
+            assert_eq!(header.entry_size as usize, size + 4);
+            let read = from_buffer(smg_hash_table(), &data, &options).unwrap();
+            for (id, label) in labels.iter().enumerate() {
+                assert_eq!(read.entries()[id].get_by_hash(calc_hash("Label")), Some(&FieldValue::from(label.as_str())));
+                assert_eq!(read.entries()[id].get_by_hash(calc_hash("Id")), Some(&FieldValue::Int(id as i32)));
+            }
+            assert_eq!(to_buffer(&read, &options).unwrap(), data);
+
+            jmap.recalculate_offsets_in(LayoutStrategy::TypeOrder, &options.layout_context());
+            assert_eq!(jmap.get_field("Id").unwrap().offset as usize, size);
+        }
+
+        // Longer strings are cut to the slot, which extends past the entry when read with the default 32 bytes
+        let small = IoOptions {
+            inline_string_size: 16,
+            ..IoOptions::default()
+        };
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Label", FieldType::String, FieldValue::from("")).unwrap();
+        jmap.entry_builder().set("Label", FieldValue::from("x".repeat(17))).insert().unwrap();
+        assert_eq!(jmap.validate(&small).len(), 1);
+        let data = to_buffer(&jmap, &small).unwrap();
+        assert!(from_buffer(smg_hash_table(), &data, &IoOptions::default()).is_err());
+        let read = from_buffer(smg_hash_table(), &data, &small).unwrap();
+        assert_eq!(read.entries()[0].get_by_hash(calc_hash("Label")), Some(&FieldValue::from("x".repeat(16))));
+    }
+
+    #[test]
+    fn test_field_encodings() {
+        let options = IoOptions::default().field_encoding("Label", Encoding::Utf8).field_encoding("Tag", Encoding::Utf8);
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Name", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("Label", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("Tag", FieldType::String, FieldValue::from("")).unwrap();
+        for text in ["マリオ", "Mario"] {
+            jmap.entry_builder()
+                .set("Name", FieldValue::from(text))
+                .set("Label", FieldValue::from(text))
+                .set("Tag", FieldValue::from(text))
+                .insert()
+                .unwrap();
         }
+
+        let data = to_buffer(&jmap, &options).unwrap();
+        assert_eq!(data.len(), jmap.estimated_serialized_size(&options));
+        let header = read_header(&data, &options).unwrap();
+        let strings = &data[header.off_strings() as usize..];
+        let find = |needle: &[u8]| strings.windows(needle.len()).position(|window| window == needle);
+        let shift_jis = encode_string("マリオ", Encoding::ShiftJis).unwrap();
+        assert!(find(&shift_jis).is_some());
+        assert!(find("マリオ".as_bytes()).is_some());
+        // The same string in two encodings is stored twice, the same bytes once
+        assert_eq!(strings.iter().filter(|&&b| b == 0).count(), 3);
+
+        let read = from_buffer(smg_hash_table(), &data, &options).unwrap();
+        assert_eq!(read.entries(), jmap.entries());
+        assert_eq!(to_buffer(&read, &options).unwrap(), data);
+
+        // Read with a single encoding, the UTF-8 columns are garbled
+        let garbled = from_buffer(smg_hash_table(), &data, &IoOptions::default()).unwrap();
+        assert_eq!(garbled.entries()[0].get_by_hash(calc_hash("Name")), Some(&FieldValue::from("マリオ")));
+        assert_ne!(garbled.entries()[0].get_by_hash(calc_hash("Label")), Some(&FieldValue::from("マリオ")));
+        assert_eq!(garbled.entries()[1].get_by_hash(calc_hash("Label")), Some(&FieldValue::from("Mario")));
+    }
+
+    #[test]
+    fn test_field_record_validation() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Wide", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Half", FieldType::Short, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Byte", FieldType::Char, FieldValue::Int(0)).unwrap();
+        jmap.entry_builder()
+            .set("Wide", FieldValue::Int(-1))
+            .set("Half", FieldValue::Int(-1))
+            .set("Byte", FieldValue::Int(-1))
+            .insert()
+            .unwrap();
+        let data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+        let permissive = IoOptions {
+            strictness: Strictness::permissive(),
+            ..IoOptions::default()
+        };
+
+        // (field index, mask, shift, whether the field is kept when tolerated, expected problem)
+        let cases = [
+            (0, 0xFFFFFFFF, 32, false, "shift 32 must be less than 32 for Int fields"),
+            (1, 0x0000FFFF, 16, false, "shift 16 must be less than 16 for Short fields"),
+            (2, 0x000000FF, 255, false, "shift 255 must be less than 8 for Char fields"),
+            (0, 0x00000000, 0, true, "mask is zero"),
+            (1, 0xFFFF0000, 0, true, "mask 0xFFFF0000 has no bits in 2-byte Short fields"),
+            (0, 0x000000FF, 8, true, "mask 0x000000FF has no bits left after a shift of 8"),
+        ];
+        for (index, mask, shift, kept, problem) in cases {
+            let mut data = data.clone();
+            let record = 0x10 + index * 0x0C;
+            BigEndian::write_u32(&mut data[record + 4..], mask);
+            data[record + 0x0A] = shift;
+            let hash = BigEndian::read_u32(&data[record..]);
+
+            let err = from_buffer(smg_hash_table(), &data, &IoOptions::default()).unwrap_err();
+            assert_eq!(err.to_string(), format!("Invalid field: field {} [{:08X}]: {}", index, hash, problem));
+
+            let (read, report) = from_buffer_with_report(smg_hash_table(), &data, &permissive).unwrap();
+            assert_eq!(report.warnings.len(), 1);
+            assert_eq!(report.warnings[0].category, Category::Layout);
+            assert!(report.warnings[0].message.starts_with(&format!("field {}: {}", index, problem)));
+            assert_eq!(read.num_fields(), if kept { 3 } else { 2 });
+            if kept {
+                assert_eq!(read.entries()[0].get_by_hash(hash).and_then(FieldValue::as_int), Some(0));
+            }
+        }
+    }
+
+    #[test]
+    fn test_layout_conflicts() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Wide", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Half", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Byte", FieldType::Char, FieldValue::Int(0)).unwrap();
+        // Wide and Half pack the two halves of the first word
+        let layout = [(0, 0x0000FFFF, 0), (0, 0xFFFF0000, 16), (4, 0x000000FF, 0)];
+        for (field, (offset, mask, shift)) in jmap.fields_map_mut().values_mut().zip(layout) {
+            (field.offset, field.mask, field.shift) = (offset, mask, shift);
+        }
+        jmap.entry_builder()
+            .set("Wide", FieldValue::Int(0x1234))
+            .set("Half", FieldValue::Int(0x5678))
+            .set("Byte", FieldValue::Int(1))
+            .insert()
+            .unwrap();
+        let hashes: Vec<u32> = jmap.fields().map(|field| field.hash).collect();
+        let options = IoOptions {
+            layout: LayoutStrategy::Preserve,
+            ..IoOptions::default()
+        };
+        let permissive = IoOptions {
+            strictness: Strictness::permissive(),
+            ..options.clone()
+        };
+
+        let packed = LayoutConflict {
+            first: hashes[0],
+            second: hashes[1],
+            offset: 0,
+            overlapping_bits: 0,
+        };
+        assert_eq!(jmap.check_layout_conflicts(), [packed]);
+        assert!(packed.is_benign());
+        let data = to_buffer(&jmap, &options).unwrap();
+        let (read, report) = from_buffer_with_report(smg_hash_table(), &data, &options).unwrap();
+        assert!(report.warnings.is_empty());
+        assert_eq!(read.entries()[0].get_by_hash(hashes[0]).and_then(FieldValue::as_int), Some(0x1234));
+        assert_eq!(read.entries()[0].get_by_hash(hashes[1]).and_then(FieldValue::as_int), Some(0x5678));
+
+        // Byte moved into the third byte of the word, which Wide writes too
+        jmap.fields_map_mut()[2].offset = 2;
+        let conflict = LayoutConflict {
+            first: hashes[0],
+            second: hashes[2],
+            offset: 2,
+            overlapping_bits: 0x0000FF00,
+        };
+        let conflicts = jmap.check_layout_conflicts();
+        assert_eq!(conflicts.len(), 3);
+        assert!(conflicts.contains(&conflict));
+        assert_eq!(conflicts.iter().filter(|conflict| !conflict.is_benign()).count(), 1);
+        let message = format!(
+            "Invalid field: fields [{:08X}] and [{:08X}] both write bits 0x0000FF00 at offset 0x2",
+            hashes[0], hashes[2]
+        );
+        assert_eq!(to_buffer(&jmap, &options).unwrap_err().to_string(), message);
+
+        let data = to_buffer(&jmap, &permissive).unwrap();
+        assert_eq!(from_buffer(smg_hash_table(), &data, &options).unwrap_err().to_string(), message);
+        let (read, report) = from_buffer_with_report(smg_hash_table(), &data, &permissive).unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].category, Category::Layout);
+        assert_eq!(read.num_fields(), 3);
+    }
+
+    #[test]
+    fn test_duplicate_fields() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Wide", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Half", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.entry_builder()
+            .set("Wide", FieldValue::Int(1))
+            .set("Half", FieldValue::Int(2))
+            .insert()
+            .unwrap();
+        let mut data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+        // The second record defines the hash of the first one
+        let hash = BigEndian::read_u32(&data[0x10..]);
+        BigEndian::write_u32(&mut data[0x1C..], hash);
+
+        match from_buffer(smg_hash_table(), &data, &IoOptions::default()) {
+            Err(JMapError::DuplicateField { hash: duplicate, first_index, second_index }) => {
+                assert_eq!((duplicate, first_index, second_index), (hash, 0, 1));
+            }
+            other => panic!("expected a duplicate field, got {:?}", other),
+        }
+
+        for (policy, value, outcome) in [
+            (DuplicateFieldPolicy::KeepFirst, 1, "keeping the first one"),
+            (DuplicateFieldPolicy::KeepLast, 2, "keeping this one"),
+        ] {
+            let options = IoOptions {
+                duplicate_fields: policy,
+                ..IoOptions::default()
+            };
+            let (read, report) = from_buffer_with_report(smg_hash_table(), &data, &options).unwrap();
+            assert_eq!(read.num_fields(), 1);
+            assert_eq!(read.entries()[0].get_by_hash(hash).and_then(FieldValue::as_int), Some(value));
+            assert_eq!(report.warnings.len(), 1);
+            assert_eq!(report.warnings[0].category, Category::Structure);
+            assert_eq!(report.warnings[0].offset, Some(0x1C));
+            assert_eq!(report.warnings[0].message, format!("field 1 defines the same hash as field 0, {}", outcome));
+
+            let quiet = IoOptions {
+                strictness: Strictness::all(Level::Ignore),
+                ..options
+            };
+            let (_, report) = from_buffer_with_report(smg_hash_table(), &data, &quiet).unwrap();
+            assert!(report.warnings.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        let strings = |jmap: &JMapInfo<_>| {
+            let mut strings = Vec::new();
+            jmap.for_each_string(|_, _, s| strings.push(s.to_string()));
+            strings
+        };
+        let utf8 = IoOptions {
+            encoding: Encoding::Utf8,
+            ..IoOptions::default()
+        };
+
+        // Japanese names in Shift-JIS
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples/scenariodata.bcsv");
+        let data = std::fs::read(path).unwrap();
+        let expected = strings(&from_buffer(smg_hash_table(), &data, &IoOptions::default()).unwrap());
+        assert!(expected.iter().any(|s| !s.is_ascii()));
+        assert_eq!(
+            detect_encoding(&data, &utf8),
+            EncodingGuess::Likely {
+                encoding: Encoding::ShiftJis,
+                confidence: 1.0
+            }
+        );
+        let (jmap, _) = from_buffer_auto_encoding(smg_hash_table(), &data, &utf8).unwrap();
+        assert_eq!(strings(&jmap), expected);
+
+        // The same names in UTF-8
+        let data = to_buffer(&jmap, &utf8).unwrap();
+        match detect_encoding(&data, &IoOptions::default()) {
+            EncodingGuess::Likely { encoding, confidence } => {
+                assert_eq!(encoding, Encoding::Utf8);
+                assert!(confidence > 0.99);
+            }
+            EncodingGuess::Ambiguous => panic!("expected UTF-8"),
+        }
+        let (jmap, _) = from_buffer_auto_encoding(smg_hash_table(), &data, &IoOptions::default()).unwrap();
+        assert_eq!(strings(&jmap), expected);
+
+        // Only ASCII names, read with the hint
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples/odd_entry_size.bcsv");
+        let data = std::fs::read(path).unwrap();
+        assert_eq!(detect_encoding(&data, &IoOptions::default()), EncodingGuess::Ambiguous);
+        assert_eq!(EncodingGuess::Ambiguous.encoding_or(Encoding::Utf8), Encoding::Utf8);
+        let (jmap, guess) = from_buffer_auto_encoding(smg_hash_table(), &data, &utf8).unwrap();
+        assert_eq!(guess, EncodingGuess::Ambiguous);
+        assert!(strings(&jmap).contains(&"RedBlueExGalaxy".to_string()));
+
+        assert_eq!(detect_encoding(b"not a bcsv", &IoOptions::default()), EncodingGuess::Ambiguous);
+    }
+
+    #[test]
+    fn test_size_overflow() {
+        assert_eq!(section_offsets(2, 3, 8).unwrap(), (0x28, 0x40));
+        let max_entries = (u32::MAX as u64 - 0x1C) / 4;
+        assert_eq!(section_offsets(1, max_entries, 4).unwrap().1 as u64, 0x1C + max_entries * 4);
+
+        let overflow = |result: Result<(u32, u32)>| match result {
+            Err(JMapError::SizeOverflow { what, value }) => (what, value),
+            other => panic!("expected an overflow, got {:?}", other),
+        };
+        assert_eq!(overflow(section_offsets(1, max_entries + 1, 4)), ("string table offset", 0x20 + max_entries * 4));
+        assert_eq!(overflow(section_offsets(0, u32::MAX as u64, u32::MAX)).0, "string table offset");
+        assert_eq!(overflow(section_offsets(u64::MAX, 0, 4)), ("entry offset", u64::MAX));
+        assert_eq!(overflow(section_offsets(0, u64::MAX, u32::MAX)), ("string table offset", u64::MAX));
+        assert_eq!(overflow(section_offsets(0x1555_5555, 0, 4)), ("entry offset", 0x1_0000_0000 - 4 + 0x10));
+
+        let err = to_u32("output length", 1 << 32).unwrap_err();
+        assert_eq!(err.to_string(), "output length of 4294967296 does not fit in 32 bits");
+    }
+
+    #[test]
+    fn test_odd_entry_size() {
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples/odd_entry_size.bcsv")).unwrap();
+        let header = read_header(&data, &IoOptions::default()).unwrap();
+        assert_eq!((header.entry_size, header.off_strings()), (0x1A, 0xB2));
+
+        let mut names = smg_hash_table();
+        names.add("Comet");
+        let jmap = from_buffer(names, &data, &IoOptions::default()).unwrap();
+        assert_eq!(jmap.entry_size, 0x1A);
+        assert_eq!(jmap.get_value(2, "ZoneName"), Some(&FieldValue::from("TamakoroExLv2Galaxy")));
+        assert_eq!(jmap.get_value(1, "Comet"), Some(&FieldValue::from("Red")));
+        assert_eq!(jmap.get_value(2, "AppearPowerStarObj"), Some(&FieldValue::Int(1)));
+
+        let preserve = IoOptions {
+            layout: LayoutStrategy::Preserve,
+            ..IoOptions::default()
+        };
+        assert_eq!(to_buffer(&jmap, &preserve).unwrap(), data);
+        let unaligned = IoOptions {
+            align_entry_size: false,
+            ..IoOptions::default()
+        };
+        assert_eq!(to_buffer(&jmap, &unaligned).unwrap(), data);
+
+        let aligned = to_buffer(&jmap, &IoOptions::default()).unwrap();
+        assert_eq!(read_header(&aligned, &IoOptions::default()).unwrap().entry_size, 0x1C);
+        assert_eq!(from_buffer(smg_hash_table(), &aligned, &IoOptions::default()).unwrap().entries(), jmap.entries());
+    }
+
+    #[test]
+    fn test_error_context() {
+        let mut data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples/scenariodata.bcsv")).unwrap();
+        let options = IoOptions::default();
+        let names = || {
+            let mut names = smg_hash_table();
+            names.add("ScenarioName");
+            names
+        };
+        let jmap = from_buffer(names(), &data, &options).unwrap();
+        let header = read_header(&data, &options).unwrap();
+        let field = jmap.get_field("ScenarioName").unwrap().clone();
+        assert_eq!(field.field_type, FieldType::StringOffset);
+
+        // Point the name of entry 3 past the end of the file
+        let offset = (header.off_data + 3 * header.entry_size) as usize + field.offset as usize;
+        BigEndian::write_u32(&mut data[offset..offset + 4], 0xFFFF);
+        let err = from_buffer(names(), &data, &options).unwrap_err();
+        assert!(matches!(
+            &err,
+            JMapError::Parse { offset: o, entry: Some(3), field: Some(name), source }
+                if *o == offset && name == "ScenarioName" && matches!(**source, JMapError::BufferTooSmall { .. })
+        ));
+        assert!(err.to_string().starts_with(&format!("At offset 0x{:X}, entry 3, field ScenarioName: ", offset)));
+
+        let mut jmap = jmap;
+        jmap.entries_mut()[4].set_by_hash(field.hash, FieldValue::Int(1));
+        let err = to_buffer(&jmap, &options).unwrap_err();
+        let JMapError::Parse { entry, field: name, source, .. } = &err else {
+            panic!("{err}");
+        };
+        assert_eq!((*entry, name.as_deref()), (Some(4), Some("ScenarioName")));
+        assert!(matches!(**source, JMapError::TypeMismatch { .. }));
+    }
+
+    /// Offset of the cell of field `name` in entry `entry_index`
+    fn patch_cell(data: &[u8], entry_index: usize, name: &str) -> usize {
+        let header = read_header(data, &IoOptions::default()).unwrap();
+        let fields = read_field_table(data, &IoOptions::default()).unwrap();
+        let field = fields.iter().find(|field| field.hash == calc_hash(name)).unwrap();
+        (header.off_data + entry_index as u32 * header.entry_size) as usize + field.offset as usize
+    }
+
+    #[test]
+    fn test_patch_buffer_numbers() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Count", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+        jmap.create_field("Name", FieldType::StringOffset, FieldValue::from("a")).unwrap();
+        for _ in 0..3 {
+            jmap.create_entry();
+        }
+        let options = IoOptions::default();
+        let original = to_buffer(&jmap, &options).unwrap();
+
+        let mut data = original.clone();
+        let edits = [
+            CellEdit { entry_index: 1, field_hash: calc_hash("Count"), value: FieldValue::Int(0x01020304) },
+            CellEdit { entry_index: 2, field_hash: calc_hash("Scale"), value: FieldValue::Float(1.0) },
+        ];
+        let outcome = patch_buffer(&mut data, &options, &edits).unwrap();
+        assert_eq!(outcome, PatchOutcome { cells_written: 2, ..PatchOutcome::default() });
+
+        let (count, scale) = (patch_cell(&data, 1, "Count"), patch_cell(&data, 2, "Scale"));
+        let changed: Vec<usize> = (0..data.len()).filter(|&i| data[i] != original[i]).collect();
+        assert_eq!(changed, [count, count + 1, count + 2, count + 3, scale, scale + 1]);
+        let read = from_buffer(smg_hash_table(), &data, &options).unwrap();
+        assert_eq!(read.entries()[1].get_int(read.hash_table(), "Count"), Some(0x01020304));
+        assert_eq!(read.entries()[2].get_float(read.hash_table(), "Scale"), Some(1.0));
+
+        // Refused edits leave the buffer untouched
+        let edits = [
+            CellEdit { entry_index: 0, field_hash: calc_hash("Count"), value: FieldValue::Int(5) },
+            CellEdit { entry_index: 0, field_hash: calc_hash("Missing"), value: FieldValue::Int(5) },
+        ];
+        let err = patch_buffer(&mut data, &options, &edits).unwrap_err();
+        assert!(matches!(&err, JMapError::Parse { entry: Some(0), source, .. } if matches!(**source, JMapError::FieldNotFound(_))));
+        let edits = [CellEdit { entry_index: 0, field_hash: calc_hash("Scale"), value: FieldValue::Int(5) }];
+        let err = patch_buffer(&mut data, &options, &edits).unwrap_err();
+        assert!(matches!(&err, JMapError::Parse { source, .. } if matches!(**source, JMapError::TypeMismatch { .. })));
+        let edits = [CellEdit { entry_index: 3, field_hash: calc_hash("Count"), value: FieldValue::Int(5) }];
+        assert!(matches!(patch_buffer(&mut data, &options, &edits), Err(JMapError::EntryIndexOutOfBounds { index: 3, len: 3 })));
+        assert_eq!(from_buffer(smg_hash_table(), &data, &options).unwrap().entries()[0].get_int_by_hash(calc_hash("Count")), Some(0));
+    }
+
+    #[test]
+    fn test_patch_buffer_strings() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Name", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("Label", FieldType::String, FieldValue::from("")).unwrap();
+        jmap.create_entry().set(&smg_hash_table(), "Name", FieldValue::from("Galaxy"));
+        jmap.create_entry().set(&smg_hash_table(), "Name", FieldValue::from("Dome"));
+        let options = IoOptions::default();
+        let original = to_buffer(&jmap, &options).unwrap();
+        let off_strings = read_header(&original, &options).unwrap().off_strings() as usize;
+        assert_eq!(&original[off_strings..off_strings + 12], b"Galaxy\0Dome\0");
+
+        // An existing string, or the end of one, is reused
+        let mut data = original.clone();
+        let edits = [
+            CellEdit { entry_index: 1, field_hash: calc_hash("Name"), value: FieldValue::from("Galaxy") },
+            CellEdit { entry_index: 0, field_hash: calc_hash("Name"), value: FieldValue::from("ome") },
+        ];
+        let outcome = patch_buffer(&mut data, &options, &edits).unwrap();
+        assert_eq!((outcome.strings_reused, outcome.strings_appended), (2, 0));
+        assert_eq!(data.len(), original.len());
+        let read = from_buffer(smg_hash_table(), &data, &options).unwrap();
+        assert_eq!(read.entries()[0].get_string(read.hash_table(), "Name"), Some("ome"));
+        assert_eq!(read.entries()[1].get_string(read.hash_table(), "Name"), Some("Galaxy"));
+
+        // A new string is appended before the padding, which is adjusted
+        let mut data = original.clone();
+        let name = "A rather long galaxy name that needs more room";
+        let edits = [
+            CellEdit { entry_index: 1, field_hash: calc_hash("Name"), value: FieldValue::from(name) },
+            CellEdit { entry_index: 0, field_hash: calc_hash("Label"), value: FieldValue::from("Inline") },
+        ];
+        let outcome = patch_buffer(&mut data, &options, &edits).unwrap();
+        assert_eq!((outcome.strings_reused, outcome.strings_appended), (0, 1));
+        let end = off_strings + 12 + name.len() + 1;
+        let (name_cell, label_cell) = (patch_cell(&data, 1, "Name"), patch_cell(&data, 0, "Label"));
+        let untouched = |i: &usize| !(name_cell..name_cell + 4).contains(i) && !(label_cell..label_cell + 32).contains(i);
+        assert!((0..off_strings + 12).filter(untouched).all(|i| data[i] == original[i]));
+        assert_eq!(&data[off_strings + 12..end - 1], name.as_bytes());
+        assert_eq!(data[end - 1], 0);
+        assert_eq!(data.len(), (end + 31) & !31);
+        assert!(data[end..].iter().all(|&b| b == 0x40));
+        let read = from_buffer(smg_hash_table(), &data, &options).unwrap();
+        assert_eq!(read.entries()[0].get_string(read.hash_table(), "Name"), Some("Galaxy"));
+        assert_eq!(read.entries()[0].get_string(read.hash_table(), "Label"), Some("Inline"));
+        assert_eq!(read.entries()[1].get_string(read.hash_table(), "Name"), Some(name));
+
+        let edits = [CellEdit { entry_index: 0, field_hash: calc_hash("Label"), value: FieldValue::from("x".repeat(33)) }];
+        let err = patch_buffer(&mut data, &options, &edits).unwrap_err();
+        assert!(matches!(&err, JMapError::Parse { source, .. } if matches!(**source, JMapError::InvalidField(_))));
     }
 }
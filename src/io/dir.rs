@@ -0,0 +1,214 @@
+//! Load and save whole directory trees of BCSV files
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{JMapError, Result};
+use crate::hash::{FileHashTable, SharedHashTable};
+use crate::io::{from_file, to_file, IoOptions};
+use crate::jmap::JMapInfo;
+
+/// The tables of a directory tree, keyed by their path relative to its root, see [`load_dir`]
+///
+/// Every table refers to the same [`SharedHashTable`], so a name added through one of them resolves in all
+#[derive(Debug)]
+pub struct JMapSet {
+    tables: BTreeMap<PathBuf, JMapInfo<SharedHashTable>>,
+    hash_table: SharedHashTable,
+    /// Files and directories that could not be read, relative to the root, with the error
+    pub failures: Vec<(PathBuf, JMapError)>,
+}
+
+impl JMapSet {
+    /// Get the table of a file
+    ///
+    /// # Arguments
+    /// - `path` - The path of the file relative to the root, such as `StageData/Zone/ZoneList.bcsv`
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&JMapInfo<SharedHashTable>> {
+        self.tables.get(path.as_ref())
+    }
+
+    /// Get the table of a file for editing, see [`JMapSet::get`]
+    pub fn get_mut<P: AsRef<Path>>(&mut self, path: P) -> Option<&mut JMapInfo<SharedHashTable>> {
+        self.tables.get_mut(path.as_ref())
+    }
+
+    /// Iterate over the tables and their relative paths, sorted by path
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &JMapInfo<SharedHashTable>)> {
+        self.tables.iter().map(|(path, jmap)| (path.as_path(), jmap))
+    }
+
+    /// Get the number of tables loaded
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Check whether no table was loaded
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Get the hash table shared by every table
+    pub fn hash_table(&self) -> &SharedHashTable {
+        &self.hash_table
+    }
+
+    /// Write every table under `dest_root`, at its relative path, creating directories as needed
+    ///
+    /// # Arguments
+    /// - `dest_root` - The directory to write to, which can be the one the tables were loaded from
+    /// - `options` - Options for endianness, string encoding and layout
+    ///
+    /// # Errors
+    /// - The error of the first table that cannot be written, the tables before it being already written
+    pub fn save_all<P: AsRef<Path>>(&self, dest_root: P, options: &IoOptions) -> Result<()> {
+        let dest_root = dest_root.as_ref();
+        for (path, jmap) in &self.tables {
+            let dst = dest_root.join(path);
+            if let Some(parent) = dst.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            to_file(jmap, dst, options)?;
+        }
+        Ok(())
+    }
+}
+
+/// Load every `.bcsv` file under a directory, with one hash table shared by all of them
+///
+/// Other files are skipped. Files and directories that cannot be read are recorded in
+/// [`JMapSet::failures`] rather than aborting the walk
+///
+/// # Arguments
+/// - `root` - The directory to walk
+/// - `hash_table` - The hash table every table refers to
+/// - `options` - Options for endianness, string encoding and strictness
+///
+/// # Errors
+/// - `JMapError::IoError` if `root` itself cannot be read
+///
+/// # Returns
+/// The tables keyed by their path relative to `root`, and the failures
+pub fn load_dir<P: AsRef<Path>>(root: P, hash_table: FileHashTable, options: &IoOptions) -> Result<JMapSet> {
+    load_dir_filtered(root, hash_table, options, |_| true)
+}
+
+/// Like [`load_dir`], only loading the files for which `filter` returns true
+///
+/// `filter` is given each file's path relative to `root`
+pub fn load_dir_filtered<P, F>(root: P, hash_table: FileHashTable, options: &IoOptions, mut filter: F) -> Result<JMapSet>
+where
+    P: AsRef<Path>,
+    F: FnMut(&Path) -> bool,
+{
+    let root = root.as_ref();
+    let mut set = JMapSet {
+        tables: BTreeMap::new(),
+        hash_table: SharedHashTable::new(hash_table),
+        failures: Vec::new(),
+    };
+    let mut pending = vec![PathBuf::new()];
+    let mut is_root = true;
+
+    while let Some(dir) = pending.pop() {
+        let mut names = match list_dir(&root.join(&dir)) {
+            Ok(names) => names,
+            Err(err) if !is_root => {
+                set.failures.push((dir, err.into()));
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        is_root = false;
+
+        names.sort_unstable();
+        let mut subdirs = Vec::new();
+        for name in names {
+            let relative = dir.join(name);
+            let path = root.join(&relative);
+            if path.is_dir() {
+                subdirs.push(relative);
+                continue;
+            }
+            if !path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("bcsv")) || !filter(&relative) {
+                continue;
+            }
+
+            match from_file(set.hash_table.clone(), &path, options) {
+                Ok(jmap) => {
+                    set.tables.insert(relative, jmap);
+                }
+                Err(err) => set.failures.push((relative, err)),
+            }
+        }
+
+        // Reversed so that popping from the stack walks subdirectories in name order
+        pending.extend(subdirs.into_iter().rev());
+    }
+
+    Ok(set)
+}
+
+fn list_dir(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fs::read_dir(dir)?.map(|entry| entry.map(|entry| PathBuf::from(entry.file_name()))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{FieldType, FieldValue};
+    use crate::hash::{calc_hash, smg_hash_table, HashAlgorithm, HashTable};
+    use crate::io::to_buffer;
+
+    #[test]
+    fn test_load_dir() {
+        let root = std::env::temp_dir().join("bcsv_jmap_test_load_dir");
+        let _ = fs::remove_dir_all(&root);
+        let stage = root.join("stage");
+        fs::create_dir_all(stage.join("StageData/Zone")).unwrap();
+
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_entry().set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(3));
+        let data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+        fs::write(stage.join("StageData/ScenarioData.bcsv"), &data).unwrap();
+        fs::write(stage.join("StageData/Zone/ZoneList.bcsv"), &data).unwrap();
+        fs::write(stage.join("StageData/Zone/Broken.bcsv"), b"not a bcsv").unwrap();
+        fs::write(stage.join("StageData/readme.txt"), b"ignored").unwrap();
+
+        let mut names = FileHashTable::new(HashAlgorithm::SMG);
+        names.add("ScenarioNo");
+        let set = load_dir(&stage, names, &IoOptions::default()).unwrap();
+        let paths: Vec<&Path> = set.iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, [Path::new("StageData/ScenarioData.bcsv"), Path::new("StageData/Zone/ZoneList.bcsv")]);
+        assert_eq!(set.failures.len(), 1);
+        assert_eq!(set.failures[0].0, PathBuf::from("StageData/Zone/Broken.bcsv"));
+
+        let zone_list = set.get("StageData/Zone/ZoneList.bcsv").unwrap();
+        assert_eq!(zone_list.get_entry(0).unwrap().get_by_hash(calc_hash("ScenarioNo")).unwrap(), &FieldValue::Int(3));
+
+        // A name added through one table resolves in the others
+        let mut hash_table = set.get("StageData/ScenarioData.bcsv").unwrap().hash_table().clone();
+        hash_table.add("ZoneName");
+        assert!(zone_list.hash_table().contains(calc_hash("ZoneName")));
+        assert!(set.hash_table().contains(calc_hash("ZoneName")));
+
+        set.save_all(root.join("out"), &IoOptions::default()).unwrap();
+        assert_eq!(fs::read(root.join("out/StageData/Zone/ZoneList.bcsv")).unwrap(), data);
+        assert_eq!(fs::read(root.join("out/StageData/ScenarioData.bcsv")).unwrap(), data);
+        assert!(!root.join("out/StageData/Zone/Broken.bcsv").exists());
+
+        let set = load_dir_filtered(&stage, FileHashTable::new(HashAlgorithm::SMG), &IoOptions::default(), |path| {
+            path.starts_with("StageData/Zone")
+        })
+        .unwrap();
+        assert_eq!(set.len(), 1);
+        assert!(set.get("StageData/ScenarioData.bcsv").is_none());
+        assert_eq!(set.failures.len(), 1);
+
+        assert!(load_dir(root.join("missing"), FileHashTable::new(HashAlgorithm::SMG), &IoOptions::default()).is_err());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
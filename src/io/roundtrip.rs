@@ -0,0 +1,521 @@
+//! Check that BCSV files survive being read and written again
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::error::{JMapError, Result};
+use crate::field::{Field, FieldType, FieldValue};
+use crate::hash::no_lookup;
+use crate::io::{
+    from_buffer, read_field_value, read_fields, read_header, readable_entries, to_buffer, Header, IoOptions,
+};
+use crate::layout::LayoutStrategy;
+use crate::strictness::Report;
+
+/// Most cells listed in [`RoundtripReport::cells`]
+pub const MAX_CELL_DIFFERENCES: usize = 16;
+
+/// How BCSV data compares to itself once read and written again, see [`verify_roundtrip`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundtripReport {
+    /// The overall result
+    pub outcome: RoundtripOutcome,
+    /// Why the bytes differ, when the outcome is `Equivalent`
+    pub differences: Vec<ByteDifference>,
+    /// The first cells whose value changed, at most [`MAX_CELL_DIFFERENCES`]
+    pub cells: Vec<CellDifference>,
+    /// Number of cells whose value changed, including the ones not listed
+    pub cells_changed: usize,
+}
+
+/// Overall result of a round trip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundtripOutcome {
+    /// The written data is byte-identical to the original
+    Identical,
+    /// Every value is the same, but the bytes differ
+    Equivalent,
+    /// Some values changed
+    Divergent,
+    /// The data could not be read or written, with the reason
+    Failed(String),
+}
+
+/// A reason for the written bytes to differ from the original while holding the same values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteDifference {
+    /// The field table lists the same fields in another order
+    FieldOrder,
+    /// The header or the field definitions differ, the entries were not compared byte by byte
+    Layout,
+    /// Entry bytes outside of every field differ, such as unused bits or the bytes after an inline string
+    EntryPadding,
+    /// The strings are stored in another order, or repeated strings are shared differently
+    StringTableOrder,
+    /// The padding after the string table differs in length or contents
+    TrailingPadding,
+}
+
+/// A cell whose value changed in a round trip
+#[derive(Debug, Clone, PartialEq)]
+pub struct CellDifference {
+    /// Index of the entry
+    pub entry: usize,
+    /// Hash of the field
+    pub hash: u32,
+    /// The value in the original data, `None` if the entry is missing
+    pub original: Option<FieldValue>,
+    /// The value in the written data, `None` if the entry or the field is missing
+    pub rewritten: Option<FieldValue>,
+}
+
+impl RoundtripReport {
+    fn new(outcome: RoundtripOutcome) -> Self {
+        Self {
+            outcome,
+            differences: Vec::new(),
+            cells: Vec::new(),
+            cells_changed: 0,
+        }
+    }
+}
+
+/// Read BCSV data and write it again keeping its layout, then compare the result with the original
+///
+/// Field names are not needed, the data is read with [`no_lookup`]. The fields keep their offsets and
+/// order (see [`LayoutStrategy::Preserve`]), so the bytes can only differ where the writer makes choices
+/// of its own, listed by [`ByteDifference`]
+///
+/// # Arguments
+/// - `data` - The BCSV data
+/// - `options` - Options for endianness, string encoding and strictness. The layout is ignored
+///
+/// # Returns
+/// The comparison, with a `Failed` outcome if the data cannot be read or written
+pub fn verify_roundtrip(data: &[u8], options: &IoOptions) -> RoundtripReport {
+    let options = IoOptions {
+        layout: LayoutStrategy::Preserve,
+        ..options.clone()
+    };
+    let result = from_buffer(no_lookup(), data, &options)
+        .and_then(|jmap| to_buffer(&jmap, &options))
+        .and_then(|rewritten| compare(data, &rewritten, &options));
+    result.unwrap_or_else(|err| RoundtripReport::new(RoundtripOutcome::Failed(err.to_string())))
+}
+
+/// Round trip results of a tree of BCSV files, see [`verify_roundtrip_dir`]
+#[derive(Debug, Clone, Default)]
+pub struct RoundtripSummary {
+    /// The report of every `.bcsv` file, in walk order
+    pub files: Vec<(PathBuf, RoundtripReport)>,
+    /// Directories that could not be read, with the reason
+    pub warnings: Vec<(PathBuf, String)>,
+}
+
+impl RoundtripSummary {
+    /// Count the files with the given outcome, ignoring the reason of `Failed` outcomes
+    pub fn count(&self, outcome: &RoundtripOutcome) -> usize {
+        self.files
+            .iter()
+            .filter(|(_, report)| std::mem::discriminant(&report.outcome) == std::mem::discriminant(outcome))
+            .count()
+    }
+
+    /// Check whether every file is byte-identical once written again
+    pub fn all_identical(&self) -> bool {
+        self.files.iter().all(|(_, report)| report.outcome == RoundtripOutcome::Identical)
+    }
+}
+
+/// Walk a directory tree and run [`verify_roundtrip`] on every `.bcsv` file
+///
+/// Files that cannot be read get a `Failed` report, and directories that cannot be read are recorded in
+/// [`RoundtripSummary::warnings`] rather than aborting the walk
+///
+/// # Arguments
+/// - `root` - The directory to walk
+/// - `options` - Options for endianness, string encoding and strictness
+///
+/// # Errors
+/// - `JMapError::IoError` if `root` itself cannot be read
+///
+/// # Returns
+/// The report of every file
+pub fn verify_roundtrip_dir<P: AsRef<Path>>(root: P, options: &IoOptions) -> Result<RoundtripSummary> {
+    let mut summary = RoundtripSummary::default();
+    let mut pending = vec![root.as_ref().to_path_buf()];
+    let mut is_root = true;
+
+    while let Some(dir) = pending.pop() {
+        let mut paths = match list_dir(&dir) {
+            Ok(paths) => paths,
+            Err(err) if !is_root => {
+                summary.warnings.push((dir, err.to_string()));
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        };
+        is_root = false;
+
+        paths.sort_unstable();
+        let mut subdirs = Vec::new();
+        for path in paths {
+            if path.is_dir() {
+                subdirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("bcsv")) {
+                let report = match fs::read(&path) {
+                    Ok(data) => verify_roundtrip(&data, options),
+                    Err(err) => RoundtripReport::new(RoundtripOutcome::Failed(JMapError::from(err).to_string())),
+                };
+                summary.files.push((path, report));
+            }
+        }
+
+        // Reversed so that popping from the stack walks subdirectories in name order
+        pending.extend(subdirs.into_iter().rev());
+    }
+
+    Ok(summary)
+}
+
+fn list_dir(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect()
+}
+
+/// The parts of a BCSV buffer needed to compare it
+struct Parsed<'a> {
+    data: &'a [u8],
+    header: Header,
+    fields: Vec<Field>,
+    num_entries: usize,
+    off_strings: usize,
+}
+
+impl<'a> Parsed<'a> {
+    fn new(data: &'a [u8], options: &IoOptions) -> Result<Self> {
+        let header = read_header(data, options)?;
+        let fields = read_fields(data, &header, options, &no_lookup(), &mut Report::default())?;
+        let num_entries = readable_entries(&header, data.len(), options, &mut Report::default())?;
+        Ok(Self {
+            data,
+            header,
+            fields,
+            num_entries,
+            off_strings: (header.off_strings() as usize).min(data.len()),
+        })
+    }
+
+    /// Offset of an entry
+    fn entry_offset(&self, entry: usize) -> usize {
+        self.header.off_data as usize + entry * self.header.entry_size as usize
+    }
+
+    /// Read the value of `field` in an entry, `None` if the entry is missing
+    fn value(&self, entry: usize, field: &Field, options: &IoOptions) -> Option<FieldValue> {
+        if entry >= self.num_entries {
+            return None;
+        }
+        let offset = self.entry_offset(entry) + field.offset as usize;
+        read_field_value(self.data, offset, self.off_strings, field, options, true).ok()
+    }
+
+    /// Find the end of the strings referenced by the entries
+    ///
+    /// Unlike the last terminator, this tells the strings from padding made of zeros
+    fn strings_end(&self, options: &IoOptions) -> usize {
+        let mut end = self.off_strings;
+        for field in self.fields.iter().filter(|field| field.field_type == FieldType::StringOffset) {
+            for entry in 0..self.num_entries {
+                let offset = self.entry_offset(entry) + field.offset as usize;
+                let word = &self.data[offset..offset + 4];
+                let str_offset = if options.big_endian { BigEndian::read_u32(word) } else { LittleEndian::read_u32(word) };
+                let start = self.off_strings + str_offset as usize;
+                if let Some(len) = self.data.get(start..).and_then(|bytes| bytes.iter().position(|&b| b == 0)) {
+                    end = end.max(start + len + 1);
+                }
+            }
+        }
+        end
+    }
+
+    /// The field definitions, sorted so that tables listing the same fields in another order are equal
+    fn sorted_fields(&self) -> Vec<(u32, u32, u16, u8, u8)> {
+        let mut fields: Vec<_> = self
+            .fields
+            .iter()
+            .map(|field| (field.hash, field.mask, field.offset, field.shift, field.field_type as u8))
+            .collect();
+        fields.sort_unstable();
+        fields
+    }
+}
+
+/// Compare the original BCSV data with the data written from it
+fn compare(original: &[u8], rewritten: &[u8], options: &IoOptions) -> Result<RoundtripReport> {
+    if original == rewritten {
+        return Ok(RoundtripReport::new(RoundtripOutcome::Identical));
+    }
+    let old = Parsed::new(original, options)?;
+    let new = Parsed::new(rewritten, options)?;
+
+    let mut report = RoundtripReport::new(RoundtripOutcome::Equivalent);
+    for entry in 0..old.num_entries.max(new.num_entries) {
+        for field in &old.fields {
+            let original = old.value(entry, field, options);
+            let rewritten = new
+                .fields
+                .iter()
+                .find(|new_field| new_field.hash == field.hash)
+                .and_then(|new_field| new.value(entry, new_field, options));
+            let same = match (&original, &rewritten) {
+                (Some(a), Some(b)) => a.total_cmp(b).is_eq(),
+                (a, b) => a.is_none() && b.is_none(),
+            };
+            if same {
+                continue;
+            }
+            report.cells_changed += 1;
+            if report.cells.len() < MAX_CELL_DIFFERENCES {
+                report.cells.push(CellDifference {
+                    entry,
+                    hash: field.hash,
+                    original,
+                    rewritten,
+                });
+            }
+        }
+    }
+    if report.cells_changed > 0 {
+        report.outcome = RoundtripOutcome::Divergent;
+        return Ok(report);
+    }
+
+    report.differences = byte_differences(&old, &new, options);
+    Ok(report)
+}
+
+/// Find why the bytes of two buffers holding the same values differ
+fn byte_differences(old: &Parsed, new: &Parsed, options: &IoOptions) -> Vec<ByteDifference> {
+    let mut differences = Vec::new();
+    let field_table = 0x10..old.header.off_data as usize;
+    if old.header != new.header || old.sorted_fields() != new.sorted_fields() {
+        differences.push(ByteDifference::Layout);
+    } else if old.data[field_table.clone()] != new.data[field_table] {
+        differences.push(ByteDifference::FieldOrder);
+    }
+
+    // Entries can only be compared byte by byte when their fields are at the same offsets
+    if !differences.contains(&ByteDifference::Layout) {
+        let entry_size = old.header.entry_size as usize;
+        for entry in 0..old.num_entries {
+            let start = old.entry_offset(entry);
+            let (meaningful, offsets) = entry_bits(old, entry, options);
+            for i in 0..entry_size {
+                let changed = old.data[start + i] ^ new.data[start + i];
+                // The values being equal, the meaningful bits of the other fields are equal too
+                if changed & offsets[i] != 0 && !differences.contains(&ByteDifference::StringTableOrder) {
+                    differences.push(ByteDifference::StringTableOrder);
+                }
+                if changed & !meaningful[i] != 0 && !differences.contains(&ByteDifference::EntryPadding) {
+                    differences.push(ByteDifference::EntryPadding);
+                }
+            }
+        }
+    }
+
+    let (old_end, new_end) = (old.strings_end(options), new.strings_end(options));
+    if old.data[old.off_strings..old_end] != new.data[new.off_strings..new_end]
+        && !differences.contains(&ByteDifference::StringTableOrder)
+    {
+        differences.push(ByteDifference::StringTableOrder);
+    }
+    if old.data[old_end..] != new.data[new_end..] {
+        differences.push(ByteDifference::TrailingPadding);
+    }
+    differences
+}
+
+/// Find the bits of an entry that hold a value, and the bits that hold a string offset
+fn entry_bits(parsed: &Parsed, entry: usize, options: &IoOptions) -> (Vec<u8>, Vec<u8>) {
+    let entry_size = parsed.header.entry_size as usize;
+    let start = parsed.entry_offset(entry);
+    let mut meaningful = vec![0u8; entry_size];
+    let mut offsets = vec![0u8; entry_size];
+
+    for field in &parsed.fields {
+        let offset = field.offset as usize;
+        let size = field.size_with(options.inline_string_size);
+        match field.field_type {
+            FieldType::String => {
+                // Bytes after the terminator are not read
+                let bytes = &parsed.data[start + offset..start + offset + size];
+                let len = bytes.iter().position(|&b| b == 0).map_or(size, |end| end + 1);
+                meaningful[offset..offset + len].fill(0xFF);
+            }
+            FieldType::StringOffset => {
+                meaningful[offset..offset + size].fill(0xFF);
+                offsets[offset..offset + size].fill(0xFF);
+            }
+            field_type => {
+                // Floats are read whatever their mask
+                let mask = if field_type == FieldType::Float { u32::MAX } else { field.mask };
+                let mut bytes = [0u8; 4];
+                if options.big_endian {
+                    BigEndian::write_u32(&mut bytes, mask);
+                } else {
+                    LittleEndian::write_u32(&mut bytes, mask);
+                }
+                // Big-endian values narrower than a word keep the low bytes of the mask
+                let bytes = if options.big_endian { &bytes[4 - size..] } else { &bytes[..size] };
+                for (bits, mask) in meaningful[offset..offset + size].iter_mut().zip(bytes) {
+                    *bits |= mask;
+                }
+            }
+        }
+    }
+    (meaningful, offsets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{calc_hash, smg_hash_table};
+    use crate::jmap::JMapInfo;
+    use crate::io::DuplicateFieldPolicy;
+
+    fn scenario_data() -> Vec<u8> {
+        fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples/scenariodata.bcsv")).unwrap()
+    }
+
+    /// A table with a packed field in the low half of a word and a StringOffset field
+    fn packed_table() -> Vec<u8> {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.add_field("Low", Field::with_mask_shift(0, FieldType::Long, 0xFFFF, 0)).unwrap();
+        jmap.create_field("Name", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.recalculate_offsets_with(LayoutStrategy::DeclarationOrder, false);
+        for (low, name) in [(1, "B"), (2, "A")] {
+            jmap.entry_builder()
+                .set("Low", FieldValue::Int(low))
+                .set("Name", FieldValue::from(name))
+                .insert()
+                .unwrap();
+        }
+        let options = IoOptions {
+            layout: LayoutStrategy::Preserve,
+            ..IoOptions::default()
+        };
+        to_buffer(&jmap, &options).unwrap()
+    }
+
+    #[test]
+    fn test_identical() {
+        let report = verify_roundtrip(&scenario_data(), &IoOptions::default());
+        assert_eq!(report, RoundtripReport::new(RoundtripOutcome::Identical));
+        assert_eq!(verify_roundtrip(&packed_table(), &IoOptions::default()).outcome, RoundtripOutcome::Identical);
+    }
+
+    #[test]
+    fn test_equivalent() {
+        let options = IoOptions::default();
+        let original = packed_table();
+        let header = read_header(&original, &options).unwrap();
+        let off_data = header.off_data as usize;
+        let off_strings = header.off_strings() as usize;
+        assert_eq!(&original[off_strings..off_strings + 4], b"B\0A\0");
+
+        // Bits above the 16-bit mask are dropped when written again
+        let mut data = original.clone();
+        data[off_data] = 0xAB;
+        let report = verify_roundtrip(&data, &options);
+        assert_eq!(report.outcome, RoundtripOutcome::Equivalent);
+        assert_eq!(report.differences, [ByteDifference::EntryPadding]);
+
+        // The writer stores strings in the order it meets them
+        let mut data = original.clone();
+        data[off_strings..off_strings + 4].copy_from_slice(b"A\0B\0");
+        BigEndian::write_u32(&mut data[off_data + 4..off_data + 8], 2);
+        BigEndian::write_u32(&mut data[off_data + 12..off_data + 16], 0);
+        let report = verify_roundtrip(&data, &options);
+        assert_eq!(report.outcome, RoundtripOutcome::Equivalent);
+        assert_eq!(report.differences, [ByteDifference::StringTableOrder]);
+
+        // The writer pads with 0x40
+        let mut data = original.clone();
+        data[off_strings + 4..].fill(0);
+        let report = verify_roundtrip(&data, &options);
+        assert_eq!(report.differences, [ByteDifference::TrailingPadding]);
+        data.truncate(off_strings + 4);
+        let report = verify_roundtrip(&data, &options);
+        assert_eq!(report.differences, [ByteDifference::TrailingPadding]);
+    }
+
+    #[test]
+    fn test_field_order() {
+        let options = IoOptions::default();
+        let original = packed_table();
+        let mut swapped = original.clone();
+        swapped[0x10..0x1C].copy_from_slice(&original[0x1C..0x28]);
+        swapped[0x1C..0x28].copy_from_slice(&original[0x10..0x1C]);
+
+        let report = compare(&original, &swapped, &options).unwrap();
+        assert_eq!(report.outcome, RoundtripOutcome::Equivalent);
+        assert_eq!(report.differences, [ByteDifference::FieldOrder]);
+    }
+
+    #[test]
+    fn test_divergent() {
+        // Two definitions with the same hash: only the last one is kept when reading
+        let options = IoOptions {
+            duplicate_fields: DuplicateFieldPolicy::KeepLast,
+            ..IoOptions::default()
+        };
+        let mut data = packed_table();
+        BigEndian::write_u32(&mut data[0x1C..0x20], calc_hash("Low"));
+        data[0x27] = FieldType::Long as u8;
+
+        let report = verify_roundtrip(&data, &options);
+        assert_eq!(report.outcome, RoundtripOutcome::Divergent);
+        assert_eq!(report.cells_changed, 1);
+        assert_eq!(
+            report.cells[0],
+            CellDifference {
+                entry: 0,
+                hash: calc_hash("Low"),
+                original: Some(FieldValue::Int(1)),
+                rewritten: Some(FieldValue::Int(0)),
+            }
+        );
+        assert!(report.differences.is_empty());
+
+        let report = verify_roundtrip(b"not a bcsv", &options);
+        assert!(matches!(report.outcome, RoundtripOutcome::Failed(_)));
+    }
+
+    #[test]
+    fn test_verify_roundtrip_dir() {
+        let root = std::env::temp_dir().join("bcsv_jmap_test_roundtrip");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("StageData/Zone")).unwrap();
+        fs::write(root.join("StageData/ScenarioData.bcsv"), scenario_data()).unwrap();
+        let mut padded = packed_table();
+        padded.extend_from_slice(&[0x40; 32]);
+        fs::write(root.join("StageData/Zone/Padded.bcsv"), padded).unwrap();
+        fs::write(root.join("StageData/Zone/Broken.bcsv"), b"not a bcsv").unwrap();
+        fs::write(root.join("StageData/readme.txt"), b"ignored").unwrap();
+
+        let summary = verify_roundtrip_dir(&root, &IoOptions::default()).unwrap();
+        let files: Vec<_> = summary.files.iter().map(|(path, _)| path.strip_prefix(&root).unwrap()).collect();
+        assert_eq!(
+            files,
+            [Path::new("StageData/ScenarioData.bcsv"), Path::new("StageData/Zone/Broken.bcsv"), Path::new("StageData/Zone/Padded.bcsv")]
+        );
+        assert_eq!(summary.count(&RoundtripOutcome::Identical), 1);
+        assert_eq!(summary.count(&RoundtripOutcome::Equivalent), 1);
+        assert_eq!(summary.count(&RoundtripOutcome::Failed(String::new())), 1);
+        assert!(!summary.all_identical());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
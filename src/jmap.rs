@@ -1,9 +1,47 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
 use indexmap::IndexMap;
 
-use crate::entry::Entry;
-use crate::error::{JMapError, Result};
+use crate::entry::{Entry, EntryBuilder, EntryView, EntryViewMut, FieldKey, SlotLayout, ValueKey};
+use crate::error::{ConversionError, JMapError, Result};
 use crate::field::{Field, FieldType, FieldValue};
 use crate::hash::HashTable;
+use crate::io::{serialized_size, IoOptions};
+use crate::layout::{LayoutContext, LayoutStrategy};
+
+/// How many fields of a JMapInfo have a known name, see [`JMapInfo::resolution_stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResolutionStats {
+    /// Fields whose name is known by the hash table
+    pub resolved: usize,
+    /// Fields only known by their hash
+    pub unresolved: usize,
+}
+
+/// Estimated heap and inline memory used by a JMapInfo, in bytes, see [`JMapInfo::estimated_memory`]
+///
+/// Allocator overhead is not counted, and hash maps are estimated from their capacity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Field definitions, including their index and the text of string defaults
+    pub fields: usize,
+    /// Entry storage: the entry list, the value slots and the slot layouts shared by the entries, and raw values if kept
+    pub entries: usize,
+    /// Summed length of the string values of all entries
+    pub strings_len: usize,
+    /// Summed capacity of the string values of all entries, the memory actually used by their text
+    pub strings_capacity: usize,
+    /// Sum of `fields`, `entries` and `strings_capacity`
+    pub total: usize,
+}
+
+/// A JMapInfo whose hash table implementation is chosen at runtime
+///
+/// `Box<dyn HashTable>` is neither `Send` nor `Sync`. To move the table across threads, use
+/// `JMapInfo<Box<dyn HashTable + Send + Sync>>`, which works the same way
+pub type DynJMap = JMapInfo<Box<dyn HashTable>>;
 
 /// The main JMap that holds fields and entries. So basically the in-memory representation of a BCSV file
 ///
@@ -11,6 +49,11 @@ use crate::hash::HashTable;
 /// and each entry represents a row of data
 ///
 /// Basically implemented what i see on [this page](https://www.lumasworkshop.com/wiki/BCSV_(File_format))
+///
+/// Changes are tracked with a [`revision`](JMapInfo::revision) counter, bumped by every method that modifies
+/// the fields or the entries, and a set of [changed entries](JMapInfo::take_changed_entries). Methods handing
+/// out `&mut Entry` cannot see what is done with it, so they count as a change when called: `get_entry_mut`
+/// and `view_mut` mark their entry, `entries_mut` and `iter_mut` mark every entry
 #[derive(Debug)]
 pub struct JMapInfo<H: HashTable> {
     /// The hash table used for field name lookups
@@ -19,8 +62,16 @@ pub struct JMapInfo<H: HashTable> {
     fields: IndexMap<u32, Field>,
     /// List of entries
     entries: Vec<Entry>,
+    /// Slots of the values of entries, shared by the entries of the table
+    layout: Arc<SlotLayout>,
     /// Size of a single entry in bytes
     pub(crate) entry_size: u32,
+    /// Raw words of each entry as read from the file, keyed by field offset (see `IoOptions::keep_raw_values`)
+    pub(crate) raw_values: Option<Vec<HashMap<u16, u32>>>,
+    /// Number of changes since the table was created
+    revision: u64,
+    /// Indices of the entries changed since the last call to `take_changed_entries`
+    changed: BTreeSet<usize>,
 }
 
 impl<H: HashTable> JMapInfo<H> {
@@ -33,7 +84,11 @@ impl<H: HashTable> JMapInfo<H> {
             hash_table,
             fields: IndexMap::new(),
             entries: Vec::new(),
+            layout: Arc::default(),
             entry_size: 0,
+            raw_values: None,
+            revision: 0,
+            changed: BTreeSet::new(),
         }
     }
 
@@ -62,6 +117,29 @@ impl<H: HashTable> JMapInfo<H> {
         self.entries.is_empty()
     }
 
+    /// Get the revision of the table, increased by every change to its fields or entries
+    ///
+    /// A table fresh from a reader is at revision 0
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Check whether the table changed since it was at `revision`
+    pub fn is_modified_since(&self, revision: u64) -> bool {
+        self.revision > revision
+    }
+
+    /// Get the indices of the entries changed since the last call, and forget them
+    ///
+    /// The tracking is coarse: entries moved by a sort or a removal and every entry touched by a field
+    /// change count as changed, and removed entries are not reported
+    ///
+    /// # Returns
+    /// The indices in ascending order, as entries are placed now
+    pub fn take_changed_entries(&mut self) -> Vec<usize> {
+        std::mem::take(&mut self.changed).into_iter().collect()
+    }
+
     /// Get an iterator over all fields
     pub fn fields(&self) -> impl Iterator<Item = &Field> {
         self.fields.values()
@@ -96,7 +174,117 @@ impl<H: HashTable> JMapInfo<H> {
 
     /// Get the name of a field by its hash
     pub fn field_name(&self, hash: u32) -> String {
-        self.hash_table.find(hash)
+        match self.hash_table.find_opt(hash) {
+            Some(name) => name.into_owned(),
+            None => format!("[{:08X}]", hash),
+        }
+    }
+
+    /// Get the hashes of the fields whose name is unknown to the hash table
+    ///
+    /// # Returns
+    /// The unresolved hashes in field order
+    pub fn unresolved_hashes(&self) -> Vec<u32> {
+        self.fields
+            .keys()
+            .copied()
+            .filter(|&hash| !self.hash_table.contains(hash))
+            .collect()
+    }
+
+    /// Count the fields whose name is known or unknown to the hash table
+    pub fn resolution_stats(&self) -> ResolutionStats {
+        let unresolved = self.unresolved_hashes().len();
+        ResolutionStats {
+            resolved: self.fields.len() - unresolved,
+            unresolved,
+        }
+    }
+
+    /// Estimate the memory used by the fields and entries
+    ///
+    /// The hash table is not counted, as it is often shared between tables
+    pub fn estimated_memory(&self) -> MemoryStats {
+        let string_capacity = |value: &FieldValue| match value {
+            FieldValue::String(s) => s.capacity(),
+            _ => 0,
+        };
+
+        // IndexMap keeps the pairs with their hash in a vector, and an index table of `usize`
+        let fields = self.fields.capacity() * (size_of::<(u64, u32, Field)>() + size_of::<usize>() + 1)
+            + self.fields.values().map(|field| string_capacity(&field.default)).sum::<usize>();
+
+        let mut entries = self.entries.capacity() * size_of::<Entry>();
+        let mut layouts: Vec<*const SlotLayout> = Vec::new();
+        let (mut strings_len, mut strings_capacity) = (0, 0);
+        for entry in &self.entries {
+            entries += entry.slots_capacity() * size_of::<Option<FieldValue>>();
+            let layout = Arc::as_ptr(entry.layout());
+            if !layouts.contains(&layout) {
+                layouts.push(layout);
+                // The Arc allocation holds two reference counts before the layout
+                entries += 2 * size_of::<usize>() + size_of::<SlotLayout>() + entry.layout().heap_size();
+            }
+            for (_, value) in entry.iter() {
+                if let FieldValue::String(s) = value {
+                    strings_len += s.len();
+                    strings_capacity += s.capacity();
+                }
+            }
+        }
+        if let Some(raw_values) = &self.raw_values {
+            entries += raw_values.capacity() * size_of::<HashMap<u16, u32>>()
+                + raw_values
+                    .iter()
+                    .map(|raw| raw.capacity() * (size_of::<(u16, u32)>() + 1))
+                    .sum::<usize>();
+        }
+
+        MemoryStats {
+            fields,
+            entries,
+            strings_len,
+            strings_capacity,
+            total: fields + entries + strings_capacity,
+        }
+    }
+
+    /// Predict the length of the output of [`to_buffer`](crate::io::to_buffer) without serializing
+    ///
+    /// Strings of StringOffset fields are encoded to measure them, repeated strings being counted once like the writer does.
+    /// Values that `to_buffer` would reject are ignored
+    ///
+    /// # Arguments
+    /// - `options` - Options for layout and string encoding
+    ///
+    /// # Returns
+    /// The size of the BCSV data in bytes, including the final padding, or `usize::MAX` if the entries
+    /// do not fit in the 32-bit offsets of the format
+    pub fn estimated_serialized_size(&self, options: &IoOptions) -> usize {
+        serialized_size(self, options)
+    }
+
+    /// Describe all fields in field order, one per line, with aligned columns
+    ///
+    /// Each line has the format of [`Field::describe`]
+    pub fn describe_fields(&self) -> String {
+        let rows: Vec<(String, &Field)> = self
+            .fields
+            .values()
+            .map(|field| (self.field_name(field.hash), field))
+            .collect();
+
+        let mut widths = [0; 3];
+        for (name, field) in &rows {
+            widths[0] = widths[0].max(format!("+0x{:X}", field.offset).len());
+            widths[1] = widths[1].max(name.chars().count());
+            widths[2] = widths[2].max(field.field_type.csv_name().len());
+        }
+
+        rows.iter()
+            .map(|(name, field)| field.describe_columns(name, widths))
+            .collect::<Vec<_>>()
+            .join("\n")
     }
 
     /// Create a new field with the given name and type
@@ -118,10 +306,33 @@ impl<H: HashTable> JMapInfo<H> {
         field_type: FieldType,
         default: FieldValue,
     ) -> Result<()> {
-        if !default.is_compatible_with(field_type) {
+        let field = Field::with_default(0, field_type, default);
+        self.add_field(name, field)
+    }
+
+    /// Add a fully specified field, such as a bit-packed one created with [`Field::with_mask_shift`]
+    ///
+    /// The field's hash is replaced by the hash of `name`, and existing entries get the field's default value
+    ///
+    /// # Arguments
+    /// - `name` - The name of the field to add
+    /// - `field` - The field definition
+    ///
+    /// # Errors
+    /// - `JMapError::InvalidField` if the field fails [`Field::validate`]
+    /// - `JMapError::TypeMismatch` if the default value is not compatible with the field type
+    /// - `JMapError::FieldAlreadyExists` if a field with the same name already exists
+    ///
+    /// # Returns
+    /// Ok(()) if the field was added successfully
+    pub fn add_field(&mut self, name: &str, mut field: Field) -> Result<()> {
+        field.validate()?;
+
+        if !field.default.is_compatible_with(field.field_type) {
             return Err(JMapError::TypeMismatch {
-                expected: field_type.csv_name(),
-                got: default.type_name(),
+                field: name.to_string(),
+                expected: field.field_type.csv_name(),
+                got: field.default.type_name(),
             });
         }
 
@@ -131,13 +342,16 @@ impl<H: HashTable> JMapInfo<H> {
             return Err(JMapError::FieldAlreadyExists(name.to_string()));
         }
 
-        let field = Field::with_default(hash, field_type, default.clone());
+        field.hash = hash;
+        let default = field.default.clone();
         self.fields.insert(hash, field);
+        self.sync_entry_layout();
 
         // Add default value to all existing entries
         for entry in &mut self.entries {
             entry.set_by_hash(hash, default.clone());
         }
+        self.touch_from(0);
 
         Ok(())
     }
@@ -162,8 +376,52 @@ impl<H: HashTable> JMapInfo<H> {
         self.fields.swap_remove(&hash);
 
         for entry in &mut self.entries {
-            entry.data_mut().remove(&hash);
+            entry.remove_by_hash(hash);
+        }
+        self.touch_from(0);
+
+        Ok(())
+    }
+
+    /// Rename a field, keeping its position, definition and values
+    ///
+    /// # Arguments
+    /// - `old` - The current name of the field
+    /// - `new` - The new name, added to the hash table
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if there is no field named `old`
+    /// - `JMapError::FieldAlreadyExists` if a field named `new` already exists
+    ///
+    /// # Returns
+    /// Ok(()) if the field was renamed
+    pub fn rename_field(&mut self, old: &str, new: &str) -> Result<()> {
+        let old_hash = self.hash_table.calc(old);
+        let position = self
+            .fields
+            .get_index_of(&old_hash)
+            .ok_or_else(|| JMapError::FieldNotFound(old.to_string()))?;
+
+        let new_hash = self.hash_table.calc(new);
+        if new_hash == old_hash {
+            return Ok(());
+        }
+        if self.fields.contains_key(&new_hash) {
+            return Err(JMapError::FieldAlreadyExists(new.to_string()));
+        }
+        self.hash_table.add(new);
+
+        let mut field = self.fields.shift_remove(&old_hash).expect("field position was just found");
+        field.hash = new_hash;
+        self.fields.shift_insert(position, new_hash, field);
+        self.sync_entry_layout();
+
+        for entry in &mut self.entries {
+            if let Some(value) = entry.remove_by_hash(old_hash) {
+                entry.set_by_hash(new_hash, value);
+            }
         }
+        self.touch_from(0);
 
         Ok(())
     }
@@ -174,7 +432,10 @@ impl<H: HashTable> JMapInfo<H> {
     }
 
     /// Get a mutable slice of all entries
+    ///
+    /// Every entry is marked as changed
     pub fn entries_mut(&mut self) -> &mut [Entry] {
+        self.touch_from(0);
         &mut self.entries
     }
 
@@ -183,23 +444,147 @@ impl<H: HashTable> JMapInfo<H> {
         self.entries.get(index)
     }
 
+    /// Get the raw word stored at a field offset of an entry, before masking and shifting
+    ///
+    /// Only available when the table was read with `IoOptions::keep_raw_values`. The raw values describe
+    /// the file as loaded: `index` is the entry's position in the file, and later edits are not reflected
+    ///
+    /// # Arguments
+    /// - `index` - The index of the entry in the file
+    /// - `offset` - The field offset within the entry
+    ///
+    /// # Returns
+    /// The raw `u32`, `u16` or `u8` read at this offset, or `None` if unavailable
+    pub fn raw_at(&self, index: usize, offset: u16) -> Option<u32> {
+        self.raw_values.as_ref()?.get(index)?.get(&offset).copied()
+    }
+
+    /// Get a value of an entry by key
+    ///
+    /// # Arguments
+    /// - `index` - The index of the entry
+    /// - `key` - The field hash or name. Names are resolved through the hash table
+    ///
+    /// # Returns
+    /// The value, or `None` if the index is out of bounds or the entry has no value for the key
+    pub fn get_value(&self, index: usize, key: impl Into<FieldKey>) -> Option<&FieldValue> {
+        self.entries
+            .get(index)
+            .and_then(|entry| entry.get_key(key, &self.hash_table))
+    }
+
+    /// Set a value of an entry by key, checking it against the field's type
+    ///
+    /// # Arguments
+    /// - `index` - The index of the entry
+    /// - `key` - The field hash or name. Names are resolved through the hash table
+    /// - `value` - The value to store
+    ///
+    /// # Errors
+    /// - `JMapError::EntryIndexOutOfBounds` if the index is out of bounds
+    /// - `JMapError::FieldNotFound` if the field does not exist
+    /// - `JMapError::TypeMismatch` if the value is not compatible with the field type
+    ///
+    /// # Returns
+    /// Ok(()) if the value was stored
+    pub fn set_value(&mut self, index: usize, key: impl Into<FieldKey>, value: FieldValue) -> Result<()> {
+        let len = self.entries.len();
+        let key = key.into();
+        let hash = key.resolve(&self.hash_table);
+
+        let field = self
+            .fields
+            .get(&hash)
+            .ok_or_else(|| JMapError::FieldNotFound(key.to_string()))?;
+
+        if !value.is_compatible_with(field.field_type) {
+            return Err(JMapError::TypeMismatch {
+                field: key.to_string(),
+                expected: field.field_type.csv_name(),
+                got: value.type_name(),
+            });
+        }
+
+        let entry = self
+            .entries
+            .get_mut(index)
+            .ok_or(JMapError::EntryIndexOutOfBounds { index, len })?;
+        entry.set_by_hash(hash, value);
+        self.touch(index);
+        Ok(())
+    }
+
+    /// Get a borrowed view of an entry, with name-based access
+    ///
+    /// # Arguments
+    /// - `index` - The index of the entry
+    ///
+    /// # Returns
+    /// An `EntryView` over the entry, or `None` if the index is out of bounds
+    pub fn view(&self, index: usize) -> Option<EntryView<'_, H>> {
+        self.entries
+            .get(index)
+            .map(|entry| EntryView::new(self, entry))
+    }
+
+    /// Get a mutable view of an entry, with name-based and type-checked access
+    ///
+    /// # Arguments
+    /// - `index` - The index of the entry
+    ///
+    /// # Returns
+    /// An `EntryViewMut` over the entry, or `None` if the index is out of bounds. The entry is marked as changed
+    pub fn view_mut(&mut self, index: usize) -> Option<EntryViewMut<'_, H>> {
+        if index < self.entries.len() {
+            self.touch(index);
+        }
+        let entry = self.entries.get_mut(index)?;
+        Some(EntryViewMut::new(&self.hash_table, &self.fields, entry))
+    }
+
+    /// Get the values of an entry in field order
+    ///
+    /// # Arguments
+    /// - `index` - The index of the entry
+    ///
+    /// # Returns
+    /// An iterator over each field paired with its value (see [`Entry::iter_ordered`]), or `None` if the index is out of bounds
+    pub fn row(&self, index: usize) -> Option<impl Iterator<Item = (&Field, Option<&FieldValue>)>> {
+        self.entries
+            .get(index)
+            .map(|entry| entry.iter_ordered(self.fields.values()))
+    }
+
     /// Get a mutable entry by index
+    ///
+    /// The entry is marked as changed
     pub fn get_entry_mut(&mut self, index: usize) -> Option<&mut Entry> {
+        if index < self.entries.len() {
+            self.touch(index);
+        }
         self.entries.get_mut(index)
     }
 
     /// Create a new entry with default values for all fields
     pub fn create_entry(&mut self) -> &mut Entry {
-        let mut entry = Entry::with_capacity(self.fields.len());
+        let mut entry = self.new_entry();
 
         for field in self.fields.values() {
             entry.set_by_hash(field.hash, field.default.clone());
         }
 
         self.entries.push(entry);
+        self.touch(self.entries.len() - 1);
         self.entries.last_mut().unwrap()
     }
 
+    /// Start building a new entry with type-checked values
+    ///
+    /// Nothing is added to the table until [`EntryBuilder::insert`] or [`EntryBuilder::insert_at`] is called
+    pub fn entry_builder(&mut self) -> EntryBuilder<'_, H> {
+        EntryBuilder::new(self)
+    }
+
     /// Remove an entry by index
     ///
     /// # Arguments
@@ -218,12 +603,39 @@ impl<H: HashTable> JMapInfo<H> {
             });
         }
 
-        Ok(self.entries.remove(index))
+        let entry = self.entries.remove(index);
+        self.touch_from(index);
+        Ok(entry)
+    }
+
+    /// Append a copy of an entry
+    ///
+    /// # Arguments
+    /// - `index` - The index of the entry to copy
+    ///
+    /// # Errors
+    /// - `JMapError::EntryIndexOutOfBounds` if the index is out of bounds
+    ///
+    /// # Returns
+    /// The index of the copy, which is the last entry
+    pub fn duplicate_entry(&mut self, index: usize) -> Result<usize> {
+        let entry = self
+            .entries
+            .get(index)
+            .cloned()
+            .ok_or(JMapError::EntryIndexOutOfBounds {
+                index,
+                len: self.entries.len(),
+            })?;
+        self.entries.push(entry);
+        self.touch(self.entries.len() - 1);
+        Ok(self.entries.len() - 1)
     }
 
     /// Clear all entries but keep the field definitions
     pub fn clear_entries(&mut self) {
         self.entries.clear();
+        self.touch_from(0);
     }
 
     /// Sort entries by a custom key function
@@ -240,6 +652,133 @@ impl<H: HashTable> JMapInfo<H> {
         K: Ord,
     {
         self.entries.sort_by_key(f);
+        self.touch_from(0);
+    }
+
+    /// Sort entries by the values of several fields, compared with [`FieldValue::total_cmp`]
+    ///
+    /// The sort is stable: entries with equal keys keep their order, even when `descending`.
+    /// Missing values sort before every other value
+    ///
+    /// # Arguments
+    /// - `keys` - The names or hashes of the fields to sort by, the first one being the primary key
+    /// - `descending` - Whether to sort from the largest key to the smallest
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if a field does not exist, the entries are left unchanged
+    pub fn sort_by_fields<K: Into<FieldKey> + Clone>(&mut self, keys: &[K], descending: bool) -> Result<()> {
+        let hashes = keys
+            .iter()
+            .map(|key| {
+                let key: FieldKey = key.clone().into();
+                let hash = key.resolve(&self.hash_table);
+                if self.fields.contains_key(&hash) {
+                    Ok(hash)
+                } else {
+                    Err(JMapError::FieldNotFound(key.to_string()))
+                }
+            })
+            .collect::<Result<Vec<u32>>>()?;
+
+        self.entries.sort_by(|a, b| {
+            let ordering = hashes
+                .iter()
+                .map(|&hash| match (a.get_by_hash(hash), b.get_by_hash(hash)) {
+                    (Some(a), Some(b)) => a.total_cmp(b),
+                    (a, b) => a.is_some().cmp(&b.is_some()),
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal);
+            if descending { ordering.reverse() } else { ordering }
+        });
+        self.touch_from(0);
+        Ok(())
+    }
+
+    /// Keep only the entries for which `f` returns true, in their order
+    ///
+    /// # Returns
+    /// The number of removed entries
+    pub fn retain_entries<F>(&mut self, f: F) -> usize
+    where
+        F: FnMut(&Entry) -> bool,
+    {
+        let len = self.entries.len();
+        self.entries.retain(f);
+        let removed = len - self.entries.len();
+        if removed > 0 {
+            self.touch_from(0);
+        }
+        removed
+    }
+
+    /// Call `f` with every value of the `String` and `StringOffset` fields, in entry order then field order
+    ///
+    /// # Arguments
+    /// - `f` - Called with the entry index, the field hash and the string
+    pub fn for_each_string<F>(&self, mut f: F)
+    where
+        F: FnMut(usize, u32, &str),
+    {
+        let hashes = self.string_field_hashes();
+        for (index, entry) in self.entries.iter().enumerate() {
+            for &hash in &hashes {
+                if let Some(FieldValue::String(s)) = entry.get_by_hash(hash) {
+                    f(index, hash, s);
+                }
+            }
+        }
+    }
+
+    /// Replace the values of the `String` and `StringOffset` fields, leaving other fields untouched
+    ///
+    /// # Arguments
+    /// - `f` - Called with every string, returns its replacement or `None` to keep it
+    ///
+    /// # Returns
+    /// The number of values that changed, a replacement equal to the string does not count
+    pub fn map_strings<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let hashes = self.string_field_hashes();
+        let mut changed = 0;
+        for index in 0..self.entries.len() {
+            let mut entry_changed = false;
+            for &hash in &hashes {
+                if let Some(FieldValue::String(s)) = self.entries[index].get_mut_by_hash(hash)
+                    && let Some(replacement) = f(s)
+                    && replacement != *s
+                {
+                    *s = replacement;
+                    changed += 1;
+                    entry_changed = true;
+                }
+            }
+            if entry_changed {
+                self.touch(index);
+            }
+        }
+        changed
+    }
+
+    /// Convert full-width digits and Latin letters, such as `ＳＭＧ２`, to ASCII in every string value
+    ///
+    /// # Returns
+    /// The number of values that changed
+    pub fn normalize_widths(&mut self) -> usize {
+        self.map_strings(|s| {
+            s.chars().any(|c| half_width(c) != c).then(|| s.chars().map(half_width).collect())
+        })
+    }
+
+    /// Get the hashes of the `String` and `StringOffset` fields
+    fn string_field_hashes(&self) -> Vec<u32> {
+        self.fields
+            .values()
+            .filter(|field| matches!(field.field_type, FieldType::String | FieldType::StringOffset))
+            .map(|field| field.hash)
+            .collect()
     }
 
     /// Iterate over entries
@@ -248,11 +787,68 @@ impl<H: HashTable> JMapInfo<H> {
     }
 
     /// Iterate over entries mutably
+    ///
+    /// Every entry is marked as changed
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Entry> {
+        self.touch_from(0);
         self.entries.iter_mut()
     }
 
-    /// Get internal access to fields (for I/O operations)
+    /// Record a change of the entry at `index`
+    pub(crate) fn touch(&mut self, index: usize) {
+        self.revision += 1;
+        self.changed.insert(index);
+    }
+
+    /// Record a change of the entries from `start` to the end, forgetting the indices of removed entries
+    ///
+    /// With `start` at the end of the entries, only the revision is bumped
+    pub(crate) fn touch_from(&mut self, start: usize) {
+        self.revision += 1;
+        let len = self.entries.len();
+        self.changed.split_off(&len);
+        self.changed.extend(start..len);
+    }
+
+    /// Get the shared slot layout, with a slot for every field
+    ///
+    /// The layout is extended when fields were added since the last call, entries using the previous
+    /// layout stay valid
+    pub(crate) fn slot_layout(&mut self) -> Arc<SlotLayout> {
+        let missing = self.fields.keys().any(|&hash| self.layout.slot(hash).is_none());
+        if missing {
+            let layout = Arc::make_mut(&mut self.layout);
+            for &hash in self.fields.keys() {
+                layout.push(hash);
+            }
+        }
+        Arc::clone(&self.layout)
+    }
+
+    /// Create an empty entry, not added to the table, sharing the slot layout of the table
+    pub(crate) fn new_entry(&mut self) -> Entry {
+        Entry::with_layout(self.slot_layout())
+    }
+
+    /// Make every entry share the slot layout of the table, after fields were added
+    ///
+    /// Without it, setting a value for a new field gives each entry its own copy of the layout
+    pub(crate) fn sync_entry_layout(&mut self) {
+        let layout = self.slot_layout();
+        for entry in &mut self.entries {
+            entry.adopt_layout(&layout);
+        }
+    }
+
+    /// Split the table into its hash table, fields and entries
+    pub(crate) fn into_parts(self) -> (H, IndexMap<u32, Field>, Vec<Entry>) {
+        (self.hash_table, self.fields, self.entries)
+    }
+
+    /// Get internal access to fields, keyed by hash (for I/O operations)
+    ///
+    /// Unlike the public methods, the internal accessors below do not record changes, callers
+    /// modifying a loaded table record them with [`JMapInfo::touch`] and [`JMapInfo::touch_from`]
     pub(crate) fn fields_map(&self) -> &IndexMap<u32, Field> {
         &self.fields
     }
@@ -262,6 +858,14 @@ impl<H: HashTable> JMapInfo<H> {
         &mut self.fields
     }
 
+    /// Get the hash table together with a mutable entry, split-borrowing the table
+    ///
+    /// The entry is marked as changed
+    pub(crate) fn hash_table_and_entry_mut(&mut self, index: usize) -> (&H, &mut Entry) {
+        self.touch(index);
+        (&self.hash_table, &mut self.entries[index])
+    }
+
     /// Get mutable access to entries (for I/O operations)
     pub(crate) fn entries_vec_mut(&mut self) -> &mut Vec<Entry> {
         &mut self.entries
@@ -269,26 +873,243 @@ impl<H: HashTable> JMapInfo<H> {
 
     /// Recalculate field offsets and entry size based on field types.
     pub fn recalculate_offsets(&mut self) {
-        let mut fields_with_hashes: Vec<(u32, Field)> = self
-            .fields
-            .iter()
-            .map(|(h, f)| (*h, f.clone()))
-            .collect();
+        self.recalculate_offsets_with(LayoutStrategy::TypeOrder, false);
+    }
 
-        fields_with_hashes.sort_by_key(|(_, f)| f.field_type.order());
+    /// Recalculate field offsets and entry size with the given layout strategy
+    ///
+    /// # Arguments
+    /// - `strategy` - The order in which fields are laid out
+    /// - `align_fields` - Whether multi-byte fields are aligned to their natural boundary
+    pub fn recalculate_offsets_with(&mut self, strategy: LayoutStrategy, align_fields: bool) {
+        let context = LayoutContext {
+            align_fields,
+            ..LayoutContext::default()
+        };
+        self.recalculate_offsets_in(strategy, &context);
+    }
 
-        let mut current_offset: u16 = 0;
-        for (hash, field) in &mut fields_with_hashes {
-            field.offset = current_offset;
-            current_offset += field.field_type.size() as u16;
+    /// Recalculate field offsets and entry size with the given layout strategy and context,
+    /// such as [`IoOptions::layout_context`](crate::io::IoOptions::layout_context) for files with inline
+    /// strings of another size
+    ///
+    /// # Arguments
+    /// - `strategy` - The order in which fields are laid out
+    /// - `context` - The alignment and the size of inline strings
+    pub fn recalculate_offsets_in(&mut self, strategy: LayoutStrategy, context: &LayoutContext) {
+        let (laid_out, entry_size) = strategy.layout_in(self.fields.values(), context);
 
-            if let Some(f) = self.fields.get_mut(hash) {
+        for field in laid_out {
+            if let Some(f) = self.fields.get_mut(&field.hash) {
                 f.offset = field.offset;
             }
         }
 
-        self.entry_size = ((current_offset as u32 + 3) & !3) as u32;
+        self.entry_size = entry_size;
+        let len = self.entries.len();
+        self.touch_from(len);
+    }
+}
+
+/// Convert a full-width digit or Latin letter to ASCII, leaving other characters unchanged
+fn half_width(c: char) -> char {
+    match c {
+        '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+impl<H: HashTable + Clone> JMapInfo<H> {
+    /// Split the table into one table per value of a field, such as one per zone or per layer
+    ///
+    /// Each table has every field of this one and the entries of its group, in their order here. Groups come
+    /// in the order their value is first seen. Entries without a value for the field are grouped under the
+    /// default value of the field, which is what they are written with
+    ///
+    /// # Arguments
+    /// - `field` - The name of the field to group by
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` if there is no field named `field`
+    ///
+    /// # Returns
+    /// The table of each group keyed by the value of `field`, floats being grouped by bit pattern
+    pub fn split_by(&self, field: &str) -> Result<IndexMap<ValueKey, JMapInfo<H>>> {
+        self.split(field, false)
+    }
+
+    /// Split the table like [`JMapInfo::split_by`], leaving `field` out of the resulting tables
+    pub fn split_by_dropping_key(&self, field: &str) -> Result<IndexMap<ValueKey, JMapInfo<H>>> {
+        self.split(field, true)
+    }
+
+    fn split(&self, name: &str, drop_key: bool) -> Result<IndexMap<ValueKey, JMapInfo<H>>> {
+        let hash = self.hash_table.calc(name);
+        let key_field = self.fields.get(&hash).ok_or_else(|| JMapError::FieldNotFound(name.to_string()))?;
+        let mut fields = self.fields.clone();
+        if drop_key {
+            fields.shift_remove(&hash);
+        }
+
+        let mut tables: IndexMap<ValueKey, JMapInfo<H>> = IndexMap::new();
+        for entry in &self.entries {
+            let key = entry.get_by_hash(hash).unwrap_or(&key_field.default);
+            let table = tables.entry(ValueKey(key.clone())).or_insert_with(|| {
+                let mut table = JMapInfo::new(self.hash_table.clone());
+                table.fields = fields.clone();
+                table.entry_size = self.entry_size;
+                table
+            });
+            let mut entry = entry.clone();
+            if drop_key {
+                entry.remove_by_hash(hash);
+            }
+            table.entries.push(entry);
+        }
+
+        for table in tables.values_mut() {
+            table.sync_entry_layout();
+        }
+        Ok(tables)
+    }
+}
+
+/// How [`concat`] combines tables whose fields differ
+#[derive(Debug, Clone, Default)]
+pub struct ConcatPolicy {
+    /// Whether the values of a field defined with another type by an earlier table are converted to that type
+    /// with the checked conversions, such as [`FieldValue::to_i32`], instead of failing
+    pub coerce_types: bool,
+    /// The column recording which table each entry comes from
+    pub source: SourceColumn,
+}
+
+/// A column added by [`concat`] to record which table each entry comes from
+#[derive(Debug, Clone, Default)]
+pub enum SourceColumn {
+    /// No column
+    #[default]
+    None,
+    /// A `Long` column with the given name, holding the index of the table in the list
+    Index(String),
+    /// A `StringOffset` column with the given name, holding the label of the table, one label per table
+    Labels(String, Vec<String>),
+}
+
+/// Append tables one after the other, such as the tables of the layers of a zone
+///
+/// The result has the union of the fields, in the order they are first seen, and the entries of every table
+/// in list order. Entries of tables without a field get its default value. The definition of a field, with
+/// its type, default value and mask, comes from the first table defining it
+///
+/// # Arguments
+/// - `tables` - The tables to append, the hash table of the first one being used for the result
+/// - `policy` - How type conflicts are handled, and the column recording where entries come from
+///
+/// # Errors
+/// - `JMapError::InvalidArgument` if `tables` is empty, or if the number of labels does not match the number
+///   of tables
+/// - `JMapError::InvalidField` if a field has another type than in an earlier table and `policy.coerce_types`
+///   is false
+/// - `JMapError::InEntry` with a `JMapError::Conversion` if a value cannot be converted to the type of its field
+/// - `JMapError::FieldAlreadyExists` if a table already has a field named like the source column
+///
+/// # Returns
+/// The combined table
+pub fn concat<H: HashTable + Clone>(tables: Vec<JMapInfo<H>>, policy: ConcatPolicy) -> Result<JMapInfo<H>> {
+    if let SourceColumn::Labels(_, labels) = &policy.source
+        && labels.len() != tables.len()
+    {
+        return Err(JMapError::InvalidArgument(format!("{} labels for {} tables", labels.len(), tables.len())));
+    }
+    let Some(first) = tables.first() else {
+        return Err(JMapError::InvalidArgument("no tables to concatenate".to_string()));
+    };
+
+    let mut combined = JMapInfo::new(first.hash_table.clone());
+    // Index of the table defining each field first
+    let mut defined_by: HashMap<u32, usize> = HashMap::new();
+    let mut sources = Vec::new();
+    for (index, table) in tables.iter().enumerate() {
+        let mut converted = Vec::new();
+        for field in table.fields() {
+            let Some(existing) = combined.fields.get(&field.hash) else {
+                combined.fields.insert(field.hash, field.clone());
+                defined_by.insert(field.hash, index);
+                continue;
+            };
+            if existing.field_type == field.field_type {
+                continue;
+            }
+            if !policy.coerce_types {
+                return Err(JMapError::InvalidField(format!(
+                    "field {} is {} in table {} but {} in table {}",
+                    combined.hash_table.find(field.hash),
+                    existing.field_type,
+                    defined_by[&field.hash],
+                    field.field_type,
+                    index
+                )));
+            }
+            converted.push((field.hash, existing.field_type));
+        }
+
+        for entry in table.entries() {
+            let mut entry = entry.clone();
+            for &(hash, field_type) in &converted {
+                if let Some(value) = entry.get_by_hash(hash) {
+                    let value = convert_value(value, field_type).map_err(|err| JMapError::InEntry {
+                        index: combined.entries.len(),
+                        source: Box::new(err.into()),
+                    })?;
+                    entry.set_by_hash(hash, value);
+                }
+            }
+            combined.entries.push(entry);
+            sources.push(index);
+        }
+    }
+
+    // Fill in the fields some tables do not have
+    for entry in &mut combined.entries {
+        for field in combined.fields.values() {
+            if entry.get_by_hash(field.hash).is_none() {
+                entry.set_by_hash(field.hash, field.default.clone());
+            }
+        }
+    }
+    combined.sync_entry_layout();
+
+    match &policy.source {
+        SourceColumn::None => {}
+        SourceColumn::Index(name) => {
+            combined.create_field(name, FieldType::Long, FieldValue::Int(0))?;
+            let hash = combined.hash_table.calc(name);
+            for (entry, &index) in combined.entries.iter_mut().zip(&sources) {
+                entry.set_by_hash(hash, FieldValue::Int(index as i32));
+            }
+        }
+        SourceColumn::Labels(name, labels) => {
+            combined.create_field(name, FieldType::StringOffset, FieldValue::String(String::new()))?;
+            let hash = combined.hash_table.calc(name);
+            for (entry, &index) in combined.entries.iter_mut().zip(&sources) {
+                entry.set_by_hash(hash, FieldValue::String(labels[index].clone()));
+            }
+        }
     }
+    Ok(combined)
+}
+
+/// Convert a value to a field type with the checked conversions
+fn convert_value(value: &FieldValue, field_type: FieldType) -> std::result::Result<FieldValue, ConversionError> {
+    Ok(match field_type {
+        FieldType::Long => FieldValue::Int(value.to_i32()?),
+        FieldType::Short => FieldValue::Int(value.to_i16()? as i32),
+        FieldType::Char => FieldValue::Int(value.to_i8()? as i32),
+        FieldType::UnsignedLong => FieldValue::UInt(value.to_u32()?),
+        FieldType::Float => FieldValue::Float(value.to_f32()?),
+        FieldType::String | FieldType::StringOffset => FieldValue::String(value.to_string()),
+    })
 }
 
 /// Implement IntoIterator for JMapInfo to allow iterating over entries directly
@@ -323,6 +1144,412 @@ impl<'a, H: HashTable> IntoIterator for &'a mut JMapInfo<H> {
     type IntoIter = std::slice::IterMut<'a, Entry>;
 
     fn into_iter(self) -> Self::IntoIter {
+        self.touch_from(0);
         self.entries.iter_mut()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{calc_hash, smg_hash_table, FileHashTable, HashAlgorithm};
+    use crate::io::{from_buffer, to_buffer, IoOptions};
+
+    #[test]
+    fn test_describe_fields() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("No", FieldType::Char, FieldValue::Int(1)).unwrap();
+        jmap.recalculate_offsets();
+
+        assert_eq!(
+            jmap.describe_fields(),
+            "+0x1 ScenarioName String mask=0xFFFFFFFF shift=0 default=\n\
+             +0x0 No           Char   mask=0x000000FF shift=0 default=1"
+        );
+    }
+
+    #[test]
+    fn test_unresolved_hashes() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.fields_map_mut().insert(0x12345678, Field::new(0x12345678, FieldType::Long));
+        jmap.recalculate_offsets();
+        let data = to_buffer(&jmap, &IoOptions::default()).unwrap();
+
+        let mut table = FileHashTable::new(HashAlgorithm::SMG);
+        table.add("ZoneName");
+        let read = from_buffer(table, &data, &IoOptions::default()).unwrap();
+        assert_eq!(read.unresolved_hashes(), [0x12345678]);
+        assert_eq!(read.resolution_stats(), ResolutionStats { resolved: 1, unresolved: 1 });
+    }
+
+    #[test]
+    fn test_rename_field() {
+        let mut jmap = JMapInfo::new(FileHashTable::new(HashAlgorithm::SMG));
+        jmap.create_field("First", FieldType::Long, FieldValue::Int(1)).unwrap();
+        jmap.create_field("Second", FieldType::Long, FieldValue::Int(2)).unwrap();
+        jmap.create_entry();
+
+        jmap.rename_field("First", "Renamed").unwrap();
+        assert_eq!(jmap.fields().map(|field| jmap.field_name(field.hash)).collect::<Vec<_>>(), ["Renamed", "Second"]);
+        assert_eq!(jmap.get_value(0, "Renamed"), Some(&FieldValue::Int(1)));
+        assert!(matches!(jmap.rename_field("First", "Other"), Err(JMapError::FieldNotFound(_))));
+        assert!(matches!(jmap.rename_field("Renamed", "Second"), Err(JMapError::FieldAlreadyExists(_))));
+    }
+
+    #[test]
+    fn test_duplicate_entry() {
+        let mut jmap = JMapInfo::new(FileHashTable::new(HashAlgorithm::SMG));
+        jmap.create_field("Second", FieldType::Long, FieldValue::Int(2)).unwrap();
+        jmap.create_entry();
+        jmap.set_value(0, "Second", FieldValue::Int(5)).unwrap();
+        assert_eq!(jmap.duplicate_entry(0).unwrap(), 1);
+        assert_eq!(jmap.entries()[1], jmap.entries()[0]);
+        assert!(matches!(jmap.duplicate_entry(2), Err(JMapError::EntryIndexOutOfBounds { index: 2, len: 2 })));
+    }
+
+    #[test]
+    fn test_sort_by_fields() {
+        let mut jmap = JMapInfo::new(FileHashTable::new(HashAlgorithm::SMG));
+        jmap.create_field("Group", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+        for (group, scale) in [(2, 0.5), (1, 2.0), (2, -1.0), (1, 2.0)] {
+            jmap.entry_builder()
+                .set("Group", FieldValue::Int(group))
+                .set("Scale", FieldValue::Float(scale))
+                .insert()
+                .unwrap();
+        }
+        let original = jmap.entries().to_vec();
+
+        jmap.sort_by_fields(&["Group", "Scale"], false).unwrap();
+        let order: Vec<&Entry> = jmap.entries().iter().collect();
+        assert_eq!(order, [&original[1], &original[3], &original[2], &original[0]]);
+
+        jmap.sort_by_fields(&["Scale"], true).unwrap();
+        let scales: Vec<Option<f32>> = (0..jmap.len())
+            .map(|index| jmap.get_value(index, "Scale").and_then(FieldValue::as_float))
+            .collect();
+        assert_eq!(scales, [Some(2.0), Some(2.0), Some(0.5), Some(-1.0)]);
+
+        assert!(matches!(jmap.sort_by_fields(&["Missing"], false), Err(JMapError::FieldNotFound(_))));
+    }
+
+    #[test]
+    fn test_retain_entries() {
+        let mut jmap = JMapInfo::new(FileHashTable::new(HashAlgorithm::SMG));
+        jmap.create_field("Id", FieldType::Long, FieldValue::Int(0)).unwrap();
+        for id in 0..5 {
+            jmap.entry_builder().set("Id", FieldValue::Int(id)).insert().unwrap();
+        }
+        let hash = jmap.get_field("Id").unwrap().hash;
+        let removed = jmap.retain_entries(|entry| entry.get_by_hash(hash) != Some(&FieldValue::Int(2)));
+        assert_eq!(removed, 1);
+        assert_eq!(jmap.len(), 4);
+    }
+
+    #[test]
+    fn test_revision() {
+        use crate::csv::from_csv;
+
+        let assets = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/examples");
+        let data = std::fs::read(assets.join("scenariodata.bcsv")).unwrap();
+        let mut jmap = from_buffer(smg_hash_table(), &data, &IoOptions::default()).unwrap();
+        assert_eq!(jmap.revision(), 0);
+        assert!(jmap.take_changed_entries().is_empty());
+        assert_eq!(from_csv(smg_hash_table(), assets.join("scenariodata.csv"), None).unwrap().revision(), 0);
+
+        // Read-only access changes nothing
+        let _ = (jmap.entries(), jmap.get_entry(0), jmap.view(1), jmap.row(2).map(Iterator::count));
+        let _ = (jmap.iter().count(), (&jmap).into_iter().count(), jmap.get_value(0, "ScenarioNo"), jmap.describe_fields());
+        let _ = (jmap.estimated_memory(), jmap.estimated_serialized_size(&IoOptions::default()), jmap.hash_table_mut());
+        let len = jmap.len();
+        assert_eq!(jmap.revision(), 0);
+
+        let mut revision = jmap.revision();
+        let mut changed = |jmap: &mut JMapInfo<_>, expected: Vec<usize>| {
+            assert!(jmap.is_modified_since(revision));
+            assert!(!jmap.is_modified_since(jmap.revision()));
+            revision = jmap.revision();
+            assert_eq!(jmap.take_changed_entries(), expected);
+        };
+        jmap.set_value(1, "ScenarioNo", FieldValue::Int(9)).unwrap();
+        changed(&mut jmap, vec![1]);
+        jmap.create_entry();
+        changed(&mut jmap, vec![len]);
+        jmap.duplicate_entry(0).unwrap();
+        changed(&mut jmap, vec![len + 1]);
+        jmap.entry_builder().insert_at(len).unwrap();
+        changed(&mut jmap, (len..len + 3).collect());
+        jmap.remove_entry(len).unwrap();
+        changed(&mut jmap, (len..len + 2).collect());
+        jmap.get_entry_mut(2).unwrap();
+        changed(&mut jmap, vec![2]);
+        jmap.view_mut(3).unwrap();
+        changed(&mut jmap, vec![3]);
+        let all: Vec<usize> = (0..len + 2).collect();
+        jmap.entries_mut();
+        changed(&mut jmap, all.clone());
+        jmap.iter_mut().for_each(drop);
+        changed(&mut jmap, all.clone());
+        jmap.sort_by_fields(&["ScenarioNo"], false).unwrap();
+        changed(&mut jmap, all.clone());
+        jmap.sort_entries_by(|entry| entry.get_int_by_hash(0xED08B591));
+        changed(&mut jmap, all.clone());
+        jmap.create_field("Label", FieldType::Long, FieldValue::Int(0)).unwrap();
+        changed(&mut jmap, all.clone());
+        jmap.rename_field("Label", "Other").unwrap();
+        changed(&mut jmap, all.clone());
+        jmap.drop_field("Other").unwrap();
+        changed(&mut jmap, all.clone());
+        jmap.recalculate_offsets();
+        changed(&mut jmap, vec![]);
+        jmap.retain_entries(|entry| entry.get_int_by_hash(0xED08B591) != Some(9));
+        changed(&mut jmap, (0..len + 1).collect());
+        jmap.clear_entries();
+        changed(&mut jmap, vec![]);
+
+        // Failed and empty operations change nothing
+        assert!(jmap.set_value(0, "ScenarioNo", FieldValue::Int(1)).is_err());
+        assert!(jmap.drop_field("Missing").is_err());
+        assert!(jmap.get_entry_mut(0).is_none());
+        assert_eq!(jmap.retain_entries(|_| false), 0);
+        assert!(!jmap.is_modified_since(revision));
+
+        // Removed entries are forgotten
+        jmap.create_entry();
+        jmap.create_entry();
+        jmap.remove_entry(1).unwrap();
+        assert_eq!(jmap.take_changed_entries(), [0]);
+    }
+
+    #[test]
+    fn test_estimates() {
+        use crate::io::Encoding;
+        use crate::layout::LayoutStrategy;
+
+        let data = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples/scenariodata.bcsv")).unwrap();
+        let mut jmap = from_buffer(smg_hash_table(), &data, &IoOptions::default()).unwrap();
+        jmap.create_field("Label", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        for index in 0..jmap.len() {
+            let label = if index % 2 == 0 { "同じ" } else { "other" };
+            jmap.set_value(index, "Label", FieldValue::from(label)).unwrap();
+        }
+
+        let options = [
+            IoOptions::default(),
+            IoOptions {
+                big_endian: false,
+                encoding: Encoding::Utf8,
+                layout: LayoutStrategy::SizeDescending,
+                align_fields: true,
+                ..IoOptions::default()
+            },
+        ];
+        for options in &options {
+            assert_eq!(jmap.estimated_serialized_size(options), to_buffer(&jmap, options).unwrap().len());
+        }
+
+        let stats = jmap.estimated_memory();
+        assert_eq!(stats.total, stats.fields + stats.entries + stats.strings_capacity);
+        assert!(stats.strings_len <= stats.strings_capacity);
+        let labels = 3 * "同じ".len() + 3 * "other".len();
+        let names: usize = jmap.entries().iter().filter_map(|entry| entry.get(jmap.hash_table(), "ScenarioName")?.as_str()).map(str::len).sum();
+        assert!(stats.strings_len >= labels + names);
+    }
+
+    #[test]
+    fn test_dyn_jmap() {
+        use crate::csv::from_csv;
+        use crate::hash::no_lookup;
+        use crate::io::from_file;
+
+        let assets = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/examples");
+        let tables: [Box<dyn HashTable>; 2] = [Box::new(smg_hash_table()), Box::new(no_lookup())];
+        for table in tables {
+            let jmap: DynJMap = from_file(table, assets.join("scenariodata.bcsv"), &IoOptions::default()).unwrap();
+            assert!(!jmap.is_empty());
+            assert!(jmap.contains_field_hash(0xED08B591));
+        }
+
+        let table: Box<dyn HashTable> = Box::new(smg_hash_table());
+        let jmap: DynJMap = from_csv(table, assets.join("scenariodata.csv"), None).unwrap();
+        assert_eq!(jmap.field_name(0xED08B591), "ScenarioNo");
+        assert_eq!(jmap.entries()[0].get(jmap.hash_table(), "ScenarioNo"), Some(&FieldValue::Int(4)));
+
+        let shared: JMapInfo<Box<dyn HashTable + Send + Sync>> = JMapInfo::new(Box::new(smg_hash_table()));
+        std::thread::spawn(move || shared.len()).join().unwrap();
+    }
+
+    #[test]
+    fn test_split_by() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples/scenariodata.bcsv");
+        let mut jmap = crate::io::from_file(smg_hash_table(), path, &IoOptions::default()).unwrap();
+        let comet = calc_hash("Comet");
+        for (index, value) in ["Red", "Dark", "Red", "", "Dark", "Red"].into_iter().enumerate() {
+            jmap.get_entry_mut(index).unwrap().set_by_hash(comet, FieldValue::from(value));
+        }
+        // Without a value, the entry goes with the default value of the field
+        jmap.get_entry_mut(3).unwrap().remove_by_hash(comet);
+
+        let tables = jmap.split_by("Comet").unwrap();
+        let keys: Vec<&FieldValue> = tables.keys().map(|key| &key.0).collect();
+        assert_eq!(keys, [&FieldValue::from("Red"), &FieldValue::from("Dark"), &FieldValue::from("")]);
+        let scenarios = |value: &str| -> Vec<i32> {
+            let table = &tables[&ValueKey(FieldValue::from(value))];
+            table.entries().iter().map(|entry| entry.get_by_hash(calc_hash("ScenarioNo")).unwrap().as_int().unwrap()).collect()
+        };
+        assert_eq!(scenarios("Red"), [1, 3, 6]);
+        assert_eq!(scenarios("Dark"), [2, 5]);
+        assert_eq!(scenarios(""), [4]);
+        assert!(tables.iter().all(|(_, table)| table.num_fields() == jmap.num_fields()));
+
+        // Merging the tables back gives the same entries
+        let mut counts: HashMap<&Entry, i32> = HashMap::new();
+        for entry in tables.iter().flat_map(|(_, table)| table.entries()) {
+            *counts.entry(entry).or_default() += 1;
+        }
+        for entry in jmap.entries() {
+            *counts.entry(entry).or_default() -= 1;
+        }
+        assert!(counts.values().all(|&count| count == 0));
+        let data = to_buffer(&tables[0], &IoOptions::default()).unwrap();
+        assert_eq!(from_buffer(smg_hash_table(), &data, &IoOptions::default()).unwrap().len(), 3);
+
+        let tables = jmap.split_by_dropping_key("Comet").unwrap();
+        assert_eq!(tables.len(), 3);
+        assert!(tables.iter().all(|(_, table)| table.num_fields() == jmap.num_fields() - 1));
+        assert!(tables.iter().flat_map(|(_, table)| table.entries()).all(|entry| entry.get_by_hash(comet).is_none()));
+
+        assert!(matches!(jmap.split_by("Missing"), Err(JMapError::FieldNotFound(_))));
+
+        // Floats are grouped by bit pattern
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Speed", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+        for value in [0.0, -0.0, f32::NAN, f32::NAN, 0.0] {
+            jmap.create_entry().set_by_hash(calc_hash("Speed"), FieldValue::Float(value));
+        }
+        let sizes: Vec<usize> = jmap.split_by("Speed").unwrap().values().map(JMapInfo::len).collect();
+        assert_eq!(sizes, [2, 1, 2]);
+    }
+
+    #[test]
+    fn test_concat() {
+        let layer = |rows: &[(i32, &str)]| {
+            let mut jmap = JMapInfo::new(smg_hash_table());
+            jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+            jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+            for &(no, zone) in rows {
+                jmap.entry_builder()
+                    .set("ScenarioNo", FieldValue::Int(no))
+                    .set("ZoneName", FieldValue::from(zone))
+                    .insert()
+                    .unwrap();
+            }
+            jmap
+        };
+        let common = || layer(&[(1, "RedBlueExGalaxy"), (2, "HeavensDoorGalaxy")]);
+        let layer_a = || {
+            let mut jmap = layer(&[(3, "TamakoroExLv2Galaxy")]);
+            jmap.create_field("Scale", FieldType::Float, FieldValue::Float(1.0)).unwrap();
+            jmap.get_entry_mut(0).unwrap().set_by_hash(calc_hash("Scale"), FieldValue::Float(2.5));
+            jmap
+        };
+
+        // The union of the fields, with the default value where a table lacks one
+        let combined = concat(vec![common(), layer_a()], ConcatPolicy::default()).unwrap();
+        assert_eq!(combined.num_fields(), 3);
+        assert_eq!(combined.len(), 3);
+        let column = |jmap: &JMapInfo<_>, name: &str| -> Vec<FieldValue> {
+            jmap.entries().iter().map(|entry| entry.get_by_hash(calc_hash(name)).unwrap().clone()).collect()
+        };
+        assert_eq!(column(&combined, "Scale"), [1.0, 1.0, 2.5].map(FieldValue::Float));
+        assert!(to_buffer(&combined, &IoOptions::default()).is_ok());
+
+        // Where each entry comes from
+        let policy = ConcatPolicy {
+            source: SourceColumn::Labels("Layer".to_string(), vec!["Common".to_string(), "LayerA".to_string()]),
+            ..ConcatPolicy::default()
+        };
+        let combined = concat(vec![common(), layer_a()], policy).unwrap();
+        assert_eq!(column(&combined, "Layer"), ["Common", "Common", "LayerA"].map(FieldValue::from));
+        let policy = ConcatPolicy {
+            source: SourceColumn::Index("Layer".to_string()),
+            ..ConcatPolicy::default()
+        };
+        let combined = concat(vec![common(), layer_a()], policy).unwrap();
+        assert_eq!(column(&combined, "Layer"), [0, 0, 1].map(FieldValue::Int));
+        let policy = ConcatPolicy {
+            source: SourceColumn::Labels("Layer".to_string(), vec!["Common".to_string()]),
+            ..ConcatPolicy::default()
+        };
+        let err = concat(vec![common(), layer_a()], policy).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid argument: 1 labels for 2 tables");
+        let err = concat(Vec::<JMapInfo<FileHashTable>>::new(), ConcatPolicy::default()).unwrap_err();
+        assert!(matches!(err, JMapError::InvalidArgument(_)));
+
+        // ScenarioNo as a float in the second table
+        let layer_b = |no: f32| {
+            let mut jmap = JMapInfo::new(smg_hash_table());
+            jmap.create_field("ScenarioNo", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+            jmap.entry_builder().set("ScenarioNo", FieldValue::Float(no)).insert().unwrap();
+            jmap
+        };
+        let err = concat(vec![common(), layer_b(4.0)], ConcatPolicy::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid field: field ScenarioNo is Int in table 0 but Float in table 1");
+
+        let coerce = ConcatPolicy {
+            coerce_types: true,
+            ..ConcatPolicy::default()
+        };
+        let combined = concat(vec![common(), layer_b(4.0)], coerce.clone()).unwrap();
+        assert_eq!(combined.fields().next().unwrap().field_type, FieldType::Long);
+        assert_eq!(combined.get_entry(2).unwrap().get_by_hash(calc_hash("ScenarioNo")), Some(&FieldValue::Int(4)));
+        let err = concat(vec![common(), layer_b(4.5)], coerce).unwrap_err();
+        assert!(matches!(err, JMapError::InEntry { index: 2, .. }));
+    }
+
+    #[test]
+    fn test_map_strings() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("Label", FieldType::String, FieldValue::from("")).unwrap();
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        for (zone, label, no) in [
+            ("ＲｅｄＢｌｕｅＥｘＧａｌａｘｙ ", "ステージ１", 1),
+            ("HeavensDoorGalaxy", "ステージ２ ", 2),
+            ("ヨッシー", "ＳＭＧ２", 3),
+        ] {
+            jmap.entry_builder()
+                .set("ZoneName", FieldValue::from(zone))
+                .set("Label", FieldValue::from(label))
+                .set("ScenarioNo", FieldValue::Int(no))
+                .insert()
+                .unwrap();
+        }
+
+        let mut seen = Vec::new();
+        jmap.for_each_string(|index, hash, s| seen.push((index, jmap.field_name(hash), s.to_string())));
+        assert_eq!(seen.len(), 6);
+        assert_eq!(seen[1], (0, "Label".to_string(), "ステージ１".to_string()));
+
+        // Only trailing spaces are trimmed, and unchanged replacements are not counted
+        let revision = jmap.revision();
+        jmap.take_changed_entries();
+        assert_eq!(jmap.map_strings(|s| Some(s.trim_end().to_string())), 2);
+        assert_eq!(jmap.take_changed_entries(), [0, 1]);
+        assert!(jmap.is_modified_since(revision));
+        assert_eq!(jmap.map_strings(|_| None), 0);
+
+        assert_eq!(jmap.normalize_widths(), 4);
+        let strings: Vec<&str> = jmap
+            .iter()
+            .flat_map(|entry| [calc_hash("ZoneName"), calc_hash("Label")].map(|hash| entry.get_string_by_hash(hash)))
+            .flatten()
+            .collect();
+        assert_eq!(strings, ["RedBlueExGalaxy", "ステージ1", "HeavensDoorGalaxy", "ステージ2", "ヨッシー", "SMG2"]);
+        assert_eq!(jmap.get_value(2, "ScenarioNo"), Some(&FieldValue::Int(3)));
+        assert_eq!(jmap.normalize_widths(), 0);
+    }
+}
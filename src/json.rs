@@ -0,0 +1,374 @@
+//! JSON import/export, with explicit field types
+//!
+//! A table is written as its [`JMapDocument`]:
+//!
+//! ```json
+//! {
+//!   "fields": [
+//!     { "name": "ScenarioNo", "type": "Long", "default": 0 },
+//!     { "name": "[DEADBEEF]", "type": "Long", "default": 0, "mask": 65280, "shift": 8 }
+//!   ],
+//!   "entries": [
+//!     { "ScenarioNo": 1, "[DEADBEEF]": 3 }
+//!   ]
+//! }
+//! ```
+//!
+//! Floats that are not finite cannot be represented in JSON numbers and are written as their bit pattern in a string,
+//! such as `"0x7FC00000"` for NaN, which reads back to the same bits
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+use crate::document::{DocumentColumns, DocumentField, JMapDocument};
+use crate::error::{JMapError, Result};
+use crate::field::{FieldType, FieldValue};
+use crate::hash::HashTable;
+use crate::jmap::JMapInfo;
+
+/// Write a JMapInfo to a JSON string
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to export
+/// - `pretty` - Whether the JSON is indented over several lines
+///
+/// # Returns
+/// The JSON document
+pub fn to_json<H: HashTable>(jmap: &JMapInfo<H>, pretty: bool) -> Result<String> {
+    let document = json_document(jmap);
+    let json = if pretty {
+        serde_json::to_string_pretty(&document)?
+    } else {
+        serde_json::to_string(&document)?
+    };
+    Ok(json)
+}
+
+/// Write a JMapInfo to a JSON file
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to export
+/// - `path` - The path to the JSON file to write
+/// - `pretty` - Whether the JSON is indented over several lines
+///
+/// # Returns
+/// Ok(()) if the export was successful, or an error if the file could not be written
+pub fn to_json_file<H: HashTable, P: AsRef<Path>>(jmap: &JMapInfo<H>, path: P, pretty: bool) -> Result<()> {
+    let document = json_document(jmap);
+    let mut writer = BufWriter::new(File::create(path)?);
+    if pretty {
+        serde_json::to_writer_pretty(&mut writer, &document)?;
+    } else {
+        serde_json::to_writer(&mut writer, &document)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Convert a JMapInfo to a document, floats that are not finite being replaced by their bit pattern
+fn json_document<H: HashTable>(jmap: &JMapInfo<H>) -> JMapDocument {
+    let mut document = jmap.to_document();
+    let defaults = document.fields.iter_mut().map(|field| &mut field.default);
+    for value in defaults.chain(document.entries.iter_mut().flat_map(|values| values.values_mut())) {
+        if let FieldValue::Float(v) = value
+            && !v.is_finite()
+        {
+            *value = FieldValue::String(format!("0x{:08X}", v.to_bits()));
+        }
+    }
+    document
+}
+
+/// Read a JMapInfo from a JSON string
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups. Field names from the JSON will be added to this hash table
+/// - `s` - The JSON document
+///
+/// # Errors
+/// - `JMapError::JsonError` if the document is not valid JSON or does not have the expected layout
+/// - `JMapError::InEntry` with the entry index and field name if a value does not match its field type
+///
+/// # Returns
+/// A JMapInfo populated with the fields and entries of the document
+pub fn from_json<H: HashTable>(hash_table: H, s: &str) -> Result<JMapInfo<H>> {
+    let document: JMapDocument = serde_json::from_str(s)?;
+    JMapInfo::from_document(hash_table, document)
+}
+
+/// Read a JMapInfo from a JSON file, see [`from_json`]
+pub fn from_json_file<H: HashTable, P: AsRef<Path>>(hash_table: H, path: P) -> Result<JMapInfo<H>> {
+    let reader = BufReader::new(File::open(path)?);
+    let document: JMapDocument = serde_json::from_reader(reader)?;
+    JMapInfo::from_document(hash_table, document)
+}
+
+/// Options for [`to_jsonl_with_options`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonlOptions {
+    /// Whether the first line holds the fields, as `{"fields":[...]}`, followed by entries keyed by field name.
+    /// Otherwise every value is written with its type, as `{"ScenarioNo":{"type":"Long","value":1}}`,
+    /// which cannot hold default values, masks or shifts
+    pub schema_line: bool,
+}
+
+impl Default for JsonlOptions {
+    fn default() -> Self {
+        Self { schema_line: true }
+    }
+}
+
+/// The first line of a JSON Lines file with a schema line
+#[derive(Serialize, Deserialize)]
+struct SchemaLine {
+    fields: Vec<DocumentField>,
+}
+
+/// A value written with its type, in JSON Lines files without a schema line
+#[derive(Serialize, Deserialize)]
+struct TypedValue {
+    #[serde(rename = "type")]
+    field_type: FieldType,
+    value: FieldValue,
+}
+
+/// Write a JMapInfo as JSON Lines, one line of fields followed by one line per entry
+///
+/// See [`to_jsonl_with_options`]
+pub fn to_jsonl<H: HashTable, W: Write>(jmap: &JMapInfo<H>, writer: W) -> Result<()> {
+    to_jsonl_with_options(jmap, writer, &JsonlOptions::default())
+}
+
+/// Write a JMapInfo as JSON Lines, each line being a complete JSON document
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to export
+/// - `writer` - Where to write the lines
+/// - `options` - Whether the fields are written on a line of their own
+///
+/// # Returns
+/// Ok(()) if the export was successful, or an error if writing failed
+pub fn to_jsonl_with_options<H: HashTable, W: Write>(jmap: &JMapInfo<H>, writer: W, options: &JsonlOptions) -> Result<()> {
+    let mut writer = BufWriter::new(writer);
+    let JMapDocument { fields, entries } = json_document(jmap);
+
+    if options.schema_line {
+        serde_json::to_writer(&mut writer, &SchemaLine { fields })?;
+        writeln!(writer)?;
+        for values in entries {
+            serde_json::to_writer(&mut writer, &values)?;
+            writeln!(writer)?;
+        }
+    } else {
+        let types: IndexMap<&str, FieldType> =
+            fields.iter().map(|field| (field.name.as_str(), field.field_type)).collect();
+        for values in entries {
+            let typed: IndexMap<&str, TypedValue> = values
+                .into_iter()
+                .filter_map(|(name, value)| {
+                    let (name, field_type) = types.get_key_value(name.as_str())?;
+                    Some((*name, TypedValue { field_type: *field_type, value }))
+                })
+                .collect();
+            serde_json::to_writer(&mut writer, &typed)?;
+            writeln!(writer)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read a JMapInfo from JSON Lines, with or without a schema line
+///
+/// Without a schema line, fields are created in the order they are first seen, with the type's default value.
+/// Entries without a value for a field get its default. Blank lines are skipped
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups. Field names will be added to this hash table
+/// - `reader` - The lines to read
+///
+/// # Errors
+/// - `JMapError::JsonError` with the 1-based line number if a line is not valid JSON, has the wrong layout,
+///   or holds a value that does not match its field type
+///
+/// # Returns
+/// A JMapInfo populated with the fields and entries of the lines
+pub fn from_jsonl<H: HashTable, R: BufRead>(hash_table: H, reader: R) -> Result<JMapInfo<H>> {
+    let mut jmap = JMapInfo::new(hash_table);
+    let mut columns = DocumentColumns::default();
+    let mut typed = None;
+
+    for (line_number, line) in reader.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let at_line = |err: JMapError| {
+            let message = match err {
+                JMapError::JsonError(message) => message,
+                other => other.to_string(),
+            };
+            JMapError::JsonError(format!("Line {}: {}", line_number, message))
+        };
+
+        let typed = match typed {
+            Some(typed) => typed,
+            None => {
+                let first: serde_json::Map<String, serde_json::Value> =
+                    serde_json::from_str(&line).map_err(|err| at_line(err.into()))?;
+                let has_schema = first.get("fields").is_some_and(serde_json::Value::is_array);
+                typed = Some(!has_schema);
+                if has_schema {
+                    let schema: SchemaLine = serde_json::from_value(serde_json::Value::Object(first))
+                        .map_err(|err| at_line(err.into()))?;
+                    columns.add_fields(&mut jmap, schema.fields).map_err(at_line)?;
+                    continue;
+                }
+                true
+            }
+        };
+
+        let values = if typed {
+            let values: IndexMap<String, TypedValue> =
+                serde_json::from_str(&line).map_err(|err| at_line(err.into()))?;
+            let mut untyped = IndexMap::with_capacity(values.len());
+            for (name, TypedValue { field_type, value }) in values {
+                match columns.get(&name) {
+                    Some((_, existing)) if existing != field_type => {
+                        return Err(at_line(JMapError::InvalidField(format!(
+                            "{}: expected {}, got {}",
+                            name,
+                            existing.csv_name(),
+                            field_type.csv_name()
+                        ))));
+                    }
+                    Some(_) => {}
+                    None => {
+                        let field = DocumentField {
+                            name: name.clone(),
+                            field_type,
+                            default: FieldValue::default_for(field_type),
+                            mask: None,
+                            shift: None,
+                        };
+                        columns.add_fields(&mut jmap, [field]).map_err(at_line)?;
+                    }
+                }
+                untyped.insert(name, value);
+            }
+            untyped
+        } else {
+            serde_json::from_str(&line).map_err(|err| at_line(err.into()))?
+        };
+
+        let entry = columns.entry(jmap.new_entry(), values).map_err(at_line)?;
+        jmap.entries_vec_mut().push(entry);
+    }
+
+    Ok(jmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::JMapError;
+    use crate::field::{Field, FieldType, FieldValue};
+    use crate::hash::{calc_hash, smg_hash_table};
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.create_field("Id", FieldType::UnsignedLong, FieldValue::UInt(0)).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+        jmap.fields_map_mut().insert(0xDEADBEEF, Field::new(0xDEADBEEF, FieldType::Short));
+
+        let entry = jmap.create_entry();
+        entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String("ギャラクシー | \"Dôme\"\n".into()));
+        entry.set_by_hash(calc_hash("Id"), FieldValue::UInt(0xFFFFFFFF));
+        entry.set_by_hash(calc_hash("Scale"), FieldValue::Float(1.0));
+        entry.set_by_hash(0xDEADBEEF, FieldValue::Int(-2));
+
+        for pretty in [false, true] {
+            let json = to_json(&jmap, pretty).unwrap();
+            assert!(json.contains("4294967295"));
+            let back = from_json(smg_hash_table(), &json).unwrap();
+            assert_eq!(back.entries(), jmap.entries());
+            assert_eq!(back.field_name(0xDEADBEEF), "[DEADBEEF]");
+        }
+
+        let path = std::env::temp_dir().join("bcsv_jmap_test_round_trip.json");
+        to_json_file(&jmap, &path, true).unwrap();
+        assert_eq!(from_json_file(smg_hash_table(), &path).unwrap().entries(), jmap.entries());
+
+        let json = r#"{"fields":[{"name":"Scale","type":"Float","default":0}],"entries":[{"Scale":2},{"Scale":"big"}]}"#;
+        let err = from_json(smg_hash_table(), json).unwrap_err();
+        assert!(matches!(err, JMapError::InEntry { index: 1, .. }));
+        assert!(err.to_string().contains("Scale: expected Float"));
+        assert!(matches!(from_json(smg_hash_table(), "{"), Err(JMapError::JsonError(_))));
+    }
+
+    #[test]
+    fn test_non_finite_floats() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(f32::NAN)).unwrap();
+        for v in [f32::from_bits(0x7FC00001), f32::INFINITY, f32::NEG_INFINITY, 1.5] {
+            jmap.create_entry().set_by_hash(calc_hash("Scale"), FieldValue::Float(v));
+        }
+
+        let json = to_json(&jmap, false).unwrap();
+        assert!(json.contains(r#"[{"Scale":"0x7FC00001"},{"Scale":"0x7F800000"},{"Scale":"0xFF800000"},{"Scale":1.5}]"#));
+        let back = from_json(smg_hash_table(), &json).unwrap();
+        assert_eq!(back.entries(), jmap.entries());
+        let default = &back.get_field("Scale").unwrap().default;
+        assert!(matches!(default, FieldValue::Float(v) if v.to_bits() == f32::NAN.to_bits()));
+
+        let mut lines = Vec::new();
+        to_jsonl(&jmap, &mut lines).unwrap();
+        assert_eq!(from_jsonl(smg_hash_table(), lines.as_slice()).unwrap().entries(), jmap.entries());
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+        jmap.fields_map_mut().insert(0xDEADBEEF, Field::new(0xDEADBEEF, FieldType::UnsignedLong));
+        for i in 0..1000 {
+            let entry = jmap.create_entry();
+            entry.set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(i - 500));
+            entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String(format!("Zone\n{}", i)));
+            entry.set_by_hash(calc_hash("Scale"), FieldValue::Float(i as f32 / 8.0));
+            entry.set_by_hash(0xDEADBEEF, FieldValue::UInt(u32::MAX - i as u32));
+        }
+
+        for schema_line in [true, false] {
+            let mut buffer = Vec::new();
+            to_jsonl_with_options(&jmap, &mut buffer, &JsonlOptions { schema_line }).unwrap();
+            let text = String::from_utf8(buffer).unwrap();
+            let lines: Vec<&str> = text.lines().collect();
+            assert_eq!(lines.len(), if schema_line { 1001 } else { 1000 });
+            for line in &lines {
+                serde_json::from_str::<serde_json::Value>(line).unwrap();
+            }
+
+            let back = from_jsonl(smg_hash_table(), text.as_bytes()).unwrap();
+            assert_eq!(back.entries(), jmap.entries());
+            assert_eq!(back.fields().count(), 4);
+        }
+
+        let text = "{\"fields\":[{\"name\":\"ScenarioNo\",\"type\":\"Long\",\"default\":0}]}\n{\"ScenarioNo\":1}\n\n{\"ScenarioNo\":\n";
+        let err = from_jsonl(smg_hash_table(), text.as_bytes()).unwrap_err();
+        assert!(err.to_string().starts_with("JSON error: Line 4: "), "{}", err);
+
+        let text = "{\"ScenarioNo\":{\"type\":\"Long\",\"value\":1}}\n{\"ScenarioNo\":{\"type\":\"Float\",\"value\":1.5}}\n";
+        let err = from_jsonl(smg_hash_table(), text.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "JSON error: Line 2: Invalid field: ScenarioNo: expected Int, got Float");
+    }
+}
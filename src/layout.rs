@@ -0,0 +1,506 @@
+//! Strategies for assigning field offsets within an entry
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::error::{JMapError, Result};
+use crate::field::{Field, FieldType, DEFAULT_INLINE_STRING_SIZE};
+use crate::hash::HashTable;
+use crate::jmap::JMapInfo;
+use crate::strictness::{Category, Level, Report, Strictness, Warning};
+
+/// Order in which fields are laid out within an entry
+#[derive(Debug, Clone, Copy, Default)]
+pub enum LayoutStrategy {
+    /// Sort by [`FieldType::order`](crate::field::FieldType::order), as done by Nintendo's tools
+    #[default]
+    TypeOrder,
+    /// Keep the order in which fields were declared
+    DeclarationOrder,
+    /// Largest fields first, keeping declaration order for fields of the same size
+    SizeDescending,
+    /// Sort with a custom comparison, keeping declaration order for equal fields
+    Custom(fn(&Field, &Field) -> Ordering),
+    /// Keep the offset each field already has, such as the one read from a file, in declaration order
+    ///
+    /// Fields sharing a word stay packed together, and `align_fields` is ignored
+    Preserve,
+}
+
+/// What the offsets of a layout depend on besides the fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutContext {
+    /// Whether multi-byte fields are aligned to their natural boundary (at most 4 bytes)
+    pub align_fields: bool,
+    /// Size of inline `String` fields in bytes
+    pub inline_string_size: usize,
+    /// Whether the entry size is rounded up to a multiple of 4 bytes
+    pub align_entry_size: bool,
+}
+
+impl Default for LayoutContext {
+    fn default() -> Self {
+        Self {
+            align_fields: false,
+            inline_string_size: DEFAULT_INLINE_STRING_SIZE,
+            align_entry_size: true,
+        }
+    }
+}
+
+impl LayoutStrategy {
+    /// Compute the offset of every field and the resulting entry size
+    ///
+    /// # Arguments
+    /// - `fields` - The fields to lay out, in declaration order
+    /// - `align_fields` - Whether multi-byte fields are aligned to their natural boundary (at most 4 bytes)
+    ///
+    /// # Returns
+    /// The fields in layout order with their offsets set, and the entry size aligned to 4 bytes
+    pub fn layout<'a>(&self, fields: impl Iterator<Item = &'a Field>, align_fields: bool) -> (Vec<Field>, u32) {
+        let context = LayoutContext {
+            align_fields,
+            ..LayoutContext::default()
+        };
+        self.layout_in(fields, &context)
+    }
+
+    /// Compute the offset of every field and the resulting entry size, see [`LayoutStrategy::layout`]
+    ///
+    /// # Arguments
+    /// - `fields` - The fields to lay out, in declaration order
+    /// - `context` - The alignment and the size of inline strings
+    pub fn layout_in<'a>(&self, fields: impl Iterator<Item = &'a Field>, context: &LayoutContext) -> (Vec<Field>, u32) {
+        let fields: Vec<&Field> = fields.collect();
+        let (placement, entry_size) = self.placement(fields.iter().copied(), context);
+        let laid_out = placement
+            .into_iter()
+            .map(|(index, offset)| Field {
+                offset,
+                ..fields[index].clone()
+            })
+            .collect();
+        (laid_out, entry_size)
+    }
+
+    /// Compute the offset of every field without copying them, see [`LayoutStrategy::layout_in`]
+    ///
+    /// # Returns
+    /// The declaration index and offset of every field in layout order, and the entry size, aligned to 4 bytes
+    /// unless `context.align_entry_size` is false
+    pub(crate) fn placement<'a>(&self, fields: impl Iterator<Item = &'a Field>, context: &LayoutContext) -> (Vec<(usize, u16)>, u32) {
+        let mut fields: Vec<(usize, &Field)> = fields.enumerate().collect();
+        let size = |field: &Field| field.size_with(context.inline_string_size);
+        let entry_size = |end: u32| if context.align_entry_size { (end + 3) & !3 } else { end };
+
+        // `sort_by` is stable, so ties keep declaration order
+        match self {
+            LayoutStrategy::TypeOrder => fields.sort_by_key(|(_, f)| f.field_type.order()),
+            LayoutStrategy::DeclarationOrder => {}
+            LayoutStrategy::SizeDescending => fields.sort_by_key(|(_, f)| Reverse(size(f))),
+            LayoutStrategy::Custom(compare) => fields.sort_by(|(_, a), (_, b)| compare(a, b)),
+            LayoutStrategy::Preserve => {
+                let end = fields.iter().map(|(_, f)| f.offset as u32 + size(f) as u32).max().unwrap_or(0);
+                let placement = fields.into_iter().map(|(index, f)| (index, f.offset)).collect();
+                return (placement, entry_size(end));
+            }
+        }
+
+        let mut current_offset: u32 = 0;
+        let placement = fields
+            .into_iter()
+            .map(|(index, field)| {
+                if context.align_fields {
+                    let alignment = size(field).min(4) as u32;
+                    current_offset = current_offset.div_ceil(alignment) * alignment;
+                }
+                let offset = current_offset as u16;
+                current_offset += size(field) as u32;
+                (index, offset)
+            })
+            .collect();
+
+        (placement, entry_size(current_offset))
+    }
+}
+
+/// Two fields occupying some of the same bytes of an entry, found by [`JMapInfo::check_layout_conflicts`]
+///
+/// Fields packing disjoint bits of a word are benign, the game stores flags that way. Fields whose masks
+/// overlap are conflicting: the one written last overwrites the shared bits of the other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutConflict {
+    /// Hash of the field coming first in the field table
+    pub first: u32,
+    /// Hash of the other field
+    pub second: u32,
+    /// Offset within an entry of the first byte both fields occupy
+    pub offset: u16,
+    /// Bits of the value of the first field that the second one writes too, zero for a benign overlap
+    ///
+    /// Inline strings are longer than 32 bits, so all the bits are set when one of them conflicts
+    pub overlapping_bits: u32,
+}
+
+impl LayoutConflict {
+    /// Whether the fields only share bytes, not bits
+    pub fn is_benign(&self) -> bool {
+        self.overlapping_bits == 0
+    }
+}
+
+impl fmt::Display for LayoutConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_benign() {
+            write!(f, "fields [{:08X}] and [{:08X}] pack disjoint bits at offset 0x{:X}", self.first, self.second, self.offset)
+        } else {
+            write!(
+                f,
+                "fields [{:08X}] and [{:08X}] both write bits 0x{:08X} at offset 0x{:X}",
+                self.first, self.second, self.overlapping_bits, self.offset
+            )
+        }
+    }
+}
+
+/// Get the bits a field writes in each of its bytes, from its first byte to its last
+fn written_bits(field: &Field, big_endian: bool, inline_string_size: usize) -> Vec<u8> {
+    let size = field.size_with(inline_string_size);
+    match field.field_type {
+        FieldType::Long | FieldType::UnsignedLong | FieldType::Short | FieldType::Char => (0..size)
+            .map(|i| (field.mask >> byte_shift(i, size, big_endian)) as u8)
+            .collect(),
+        // Floats and strings are written whole, whatever their mask
+        _ => vec![0xFF; size],
+    }
+}
+
+/// Get the shift of byte `index` within a value of `size` bytes
+fn byte_shift(index: usize, size: usize, big_endian: bool) -> usize {
+    if big_endian { 8 * (size - 1 - index) } else { 8 * index }
+}
+
+/// Find the fields sharing bytes of an entry
+///
+/// # Arguments
+/// - `fields` - The fields in field table order, with the offset each one is written at
+/// - `big_endian` - The byte order, which tells the bytes the mask bits fall in
+/// - `inline_string_size` - Size of inline `String` fields in bytes
+pub(crate) fn layout_conflicts<'a>(
+    fields: impl IntoIterator<Item = (&'a Field, u16)>,
+    big_endian: bool,
+    inline_string_size: usize,
+) -> Vec<LayoutConflict> {
+    let fields: Vec<(&Field, usize, Vec<u8>)> = fields
+        .into_iter()
+        .map(|(field, offset)| (field, offset as usize, written_bits(field, big_endian, inline_string_size)))
+        .collect();
+
+    let mut conflicts = Vec::new();
+    for (i, (first, first_offset, first_bits)) in fields.iter().enumerate() {
+        for (second, second_offset, second_bits) in &fields[i + 1..] {
+            let start = *first_offset.max(second_offset);
+            let end = (first_offset + first_bits.len()).min(second_offset + second_bits.len());
+            if start >= end {
+                continue;
+            }
+            let mut overlapping_bits = 0;
+            for byte in start..end {
+                let shared = first_bits[byte - first_offset] & second_bits[byte - second_offset];
+                if shared == 0 {
+                    continue;
+                }
+                overlapping_bits |= match first_bits.len() {
+                    size @ ..=4 => (shared as u32) << byte_shift(byte - first_offset, size, big_endian),
+                    _ => u32::MAX,
+                };
+            }
+            conflicts.push(LayoutConflict {
+                first: first.hash,
+                second: second.hash,
+                offset: start as u16,
+                overlapping_bits,
+            });
+        }
+    }
+    conflicts
+}
+
+impl<H: HashTable> JMapInfo<H> {
+    /// Find the fields sharing bytes of an entry at their current offsets
+    ///
+    /// Masks are placed in bytes as in big-endian files, and inline strings are
+    /// [`DEFAULT_INLINE_STRING_SIZE`] bytes long
+    ///
+    /// # Returns
+    /// Every pair of overlapping fields, benign or not, in field table order
+    pub fn check_layout_conflicts(&self) -> Vec<LayoutConflict> {
+        layout_conflicts(self.fields().map(|field| (field, field.offset)), true, DEFAULT_INLINE_STRING_SIZE)
+    }
+}
+
+/// The binary layout of a table, saved next to a CSV export so that the BCSV file can be rebuilt identically
+///
+/// The text format has an `entry_size` line, then a `field` line per field in field table order with the
+/// hash, type, offset, mask and shift. Everything after a `#` is a comment:
+///
+/// ```text
+/// entry_size 0x8
+/// field ED08B591 Int 0x0 0xFFFFFFFF 0 # ScenarioNo
+/// field 3666C077 String 0x4 0xFFFFFFFF 0 # ZoneName
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LayoutSidecar {
+    /// Size of an entry in bytes, including its padding
+    pub entry_size: u32,
+    /// The fields in field table order
+    pub fields: Vec<SidecarField>,
+}
+
+/// The layout of a field in a [`LayoutSidecar`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SidecarField {
+    /// Hash of the field name
+    pub hash: u32,
+    /// Data type of the field
+    pub field_type: FieldType,
+    /// Offset within an entry
+    pub offset: u16,
+    /// Bitmask for the field value
+    pub mask: u32,
+    /// Bit shift amount
+    pub shift: u8,
+}
+
+impl LayoutSidecar {
+    /// Get the layout of a table, such as one read from a BCSV file
+    pub fn from_jmap<H: HashTable>(jmap: &JMapInfo<H>) -> Self {
+        Self {
+            entry_size: jmap.entry_size,
+            fields: jmap
+                .fields()
+                .map(|field| SidecarField {
+                    hash: field.hash,
+                    field_type: field.field_type,
+                    offset: field.offset,
+                    mask: field.mask,
+                    shift: field.shift,
+                })
+                .collect(),
+        }
+    }
+
+    /// Parse a layout in the format described in [`LayoutSidecar`]
+    ///
+    /// # Errors
+    /// - `JMapError::InvalidField` with the line number of a malformed line, an unknown type or a field listed twice
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut sidecar = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error = |reason: String| JMapError::InvalidField(format!("line {}: {}", index + 1, reason));
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                ["entry_size", size] => {
+                    sidecar.entry_size = parse_number(size).ok_or_else(|| error(format!("invalid entry size {:?}", size)))?;
+                }
+                ["field", hash, type_name, offset, mask, shift] => {
+                    let field = SidecarField {
+                        hash: u32::from_str_radix(hash, 16).map_err(|_| error(format!("invalid hash {:?}", hash)))?,
+                        field_type: FieldType::from_csv_name(type_name)
+                            .ok_or_else(|| error(format!("unknown type {:?}", type_name)))?,
+                        offset: parse_number(offset)
+                            .and_then(|offset| u16::try_from(offset).ok())
+                            .ok_or_else(|| error(format!("invalid offset {:?}", offset)))?,
+                        mask: parse_number(mask).ok_or_else(|| error(format!("invalid mask {:?}", mask)))?,
+                        shift: shift.parse().map_err(|_| error(format!("invalid shift {:?}", shift)))?,
+                    };
+                    if sidecar.fields.iter().any(|f| f.hash == field.hash) {
+                        return Err(error(format!("field {:08X} is listed twice", field.hash)));
+                    }
+                    sidecar.fields.push(field);
+                }
+                _ => return Err(error(format!("expected entry_size or field, got {:?}", line))),
+            }
+        }
+        Ok(sidecar)
+    }
+
+    /// Give a table this layout: its fields are put in sidecar order with the sidecar offset, mask and
+    /// shift, and the entry size is kept when the table is written with [`LayoutStrategy::Preserve`]
+    ///
+    /// Columns of the table missing from the sidecar are appended after the entry, which grows to fit them.
+    /// Sidecar fields missing from the table are a `Structure` problem handled according to `strictness`,
+    /// their bytes are left as padding
+    ///
+    /// # Arguments
+    /// - `jmap` - The table, usually just read from a CSV file
+    /// - `strictness` - What to do with sidecar fields missing from the table
+    /// - `report` - The report warnings are added to, columns missing from the sidecar are recorded
+    ///   unless the `Structure` level is `Level::Ignore`
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` for a sidecar field missing from the table, with `Level::Error`
+    /// - `JMapError::InvalidCsvFieldDescriptor` if a field has another type in the table than in the sidecar
+    /// - `JMapError::InvalidField` if a sidecar mask or shift does not suit the field type
+    pub fn apply<H: HashTable>(&self, jmap: &mut JMapInfo<H>, strictness: &Strictness, report: &mut Report) -> Result<()> {
+        self.apply_unrecorded(jmap, strictness, report)?;
+        let len = jmap.len();
+        jmap.touch_from(len);
+        Ok(())
+    }
+
+    /// Apply the layout without bumping the revision of the table, for loaders
+    pub(crate) fn apply_unrecorded<H: HashTable>(
+        &self,
+        jmap: &mut JMapInfo<H>,
+        strictness: &Strictness,
+        report: &mut Report,
+    ) -> Result<()> {
+        let mut order: HashMap<u32, usize> = HashMap::with_capacity(self.fields.len());
+        for layout in &self.fields {
+            let Some(field) = jmap.get_field_by_hash(layout.hash) else {
+                let name = jmap.hash_table().find(layout.hash);
+                let message = format!("'{}' is in the layout but not in the table", name);
+                let warning = Warning::csv(Category::Structure, None, None, format!("{}, leaving its bytes as padding", message));
+                strictness.check(report, warning, || JMapError::FieldNotFound(message))?;
+                continue;
+            };
+            if field.field_type != layout.field_type {
+                return Err(JMapError::InvalidCsvFieldDescriptor(format!(
+                    "'{}' is {} in the table but {} in the layout",
+                    jmap.hash_table().find(layout.hash),
+                    field.field_type.csv_name(),
+                    layout.field_type.csv_name()
+                )));
+            }
+            let field = Field {
+                offset: layout.offset,
+                mask: layout.mask,
+                shift: layout.shift,
+                ..field.clone()
+            };
+            field.validate()?;
+            order.insert(layout.hash, order.len());
+            jmap.fields_map_mut().insert(layout.hash, field);
+        }
+
+        // Columns unknown to the sidecar go after the laid out fields, in table order
+        let mut end = self.entry_size;
+        let extra: Vec<u32> = jmap.field_hashes().copied().filter(|hash| !order.contains_key(hash)).collect();
+        for hash in extra {
+            let name = jmap.hash_table().find(hash);
+            let field = &mut jmap.fields_map_mut()[&hash];
+            let alignment = field.size().min(4) as u32;
+            end = end.div_ceil(alignment) * alignment;
+            field.offset = end as u16;
+            end += field.size() as u32;
+            order.insert(hash, order.len());
+            if strictness.structure != Level::Ignore {
+                report.warnings.push(Warning::csv(
+                    Category::Structure,
+                    None,
+                    None,
+                    format!("'{}' is not in the layout, appending it at offset 0x{:X}", name, end - field.size() as u32),
+                ));
+            }
+        }
+
+        jmap.fields_map_mut().sort_by(|a, _, b, _| order[a].cmp(&order[b]));
+        // An entry size that is not a multiple of 4 is kept as is, unless columns were appended
+        jmap.entry_size = if end == self.entry_size { end } else { (end + 3) & !3 };
+        Ok(())
+    }
+}
+
+impl fmt::Display for LayoutSidecar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "entry_size 0x{:X}", self.entry_size)?;
+        for field in &self.fields {
+            writeln!(
+                f,
+                "field {:08X} {} 0x{:X} 0x{:08X} {}",
+                field.hash,
+                field.field_type.csv_name(),
+                field.offset,
+                field.mask,
+                field.shift
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a decimal or `0x` hexadecimal number
+fn parse_number(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Write the layout of a table next to its CSV export, see [`LayoutSidecar`]
+///
+/// Field lines end with the field name as a comment when the hash table knows it
+///
+/// # Arguments
+/// - `jmap` - The table, usually just read from a BCSV file
+/// - `path` - The path of the sidecar file, such as `ScenarioData.layout` next to `ScenarioData.csv`
+pub fn save_sidecar<H: HashTable, P: AsRef<Path>>(jmap: &JMapInfo<H>, path: P) -> Result<()> {
+    let sidecar = LayoutSidecar::from_jmap(jmap);
+    let display = sidecar.to_string();
+    let mut lines = display.lines();
+    let mut text = format!("{}\n", lines.next().unwrap_or_default());
+    for (line, field) in lines.zip(&sidecar.fields) {
+        text.push_str(line);
+        if let Some(name) = jmap.hash_table().find_opt(field.hash) {
+            text.push_str(" # ");
+            text.push_str(&name);
+        }
+        text.push('\n');
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+/// Read a layout written by [`save_sidecar`]
+///
+/// # Errors
+/// - `JMapError::InvalidField` if the file is malformed, see [`LayoutSidecar::parse`]
+pub fn load_sidecar<P: AsRef<Path>>(path: P) -> Result<LayoutSidecar> {
+    LayoutSidecar::parse(&std::fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sidecar() {
+        let text = "# ScenarioData\nentry_size 0xC\nfield ED08B591 Int 0x8 0x0000FF00 8 # ScenarioNo\nfield 3666C077 String 0 0xFFFFFFFF 0\n";
+        let sidecar = LayoutSidecar::parse(text).unwrap();
+        assert_eq!(sidecar.entry_size, 0xC);
+        assert_eq!(
+            sidecar.fields[0],
+            SidecarField {
+                hash: 0xED08B591,
+                field_type: FieldType::Long,
+                offset: 8,
+                mask: 0xFF00,
+                shift: 8,
+            }
+        );
+        assert_eq!(LayoutSidecar::parse(&sidecar.to_string()).unwrap(), sidecar);
+
+        let err = LayoutSidecar::parse("entry_size 4\nfield ED08B591 Bogus 0 0xFFFFFFFF 0").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid field: line 2: unknown type \"Bogus\"");
+        assert!(LayoutSidecar::parse("field ED08B591 Int 0 0xFFFFFFFF 0\nfield ED08B591 Int 4 0xFFFFFFFF 0").is_err());
+        assert!(LayoutSidecar::parse("padding 4").is_err());
+    }
+}
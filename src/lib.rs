@@ -16,8 +16,9 @@
 //!
 //! // Print some data
 //! println!("Entries: {}", jmap.len());
-//! for entry in jmap.entries() {
-//!     if let Some(name) = entry.get_string(jmap.hash_table(), "ZoneName") {
+//! for i in 0..jmap.len() {
+//!     let view = jmap.view(i).unwrap();
+//!     if let Some(name) = view.get_str("ZoneName") {
 //!         println!("Zone: {}", name);
 //!     }
 //! }
@@ -31,26 +32,103 @@
 //! - Read and write BCSV files with full format support
 //! - big-endian and little-endian support
 //! - Shift-JIS and UTF-8 string encoding
-//! - CSV import/export
+//! - CSV import/export, with layout sidecars to rebuild the original binary layout
+//! - `ColumnarJMap`: column-oriented storage for scanning a few fields over many entries
+//! - `derive` feature: `#[derive(FromEntry, ToEntry)]` to map entries to structs
+//! - `bundled-names` feature: `smg_hash_table()` knows the common SMG field names without a lookup file
+//! - `schemas` feature: `schemas::for_filename()` knows the columns of common SMG tables
+//! - `json` feature: JSON import/export with explicit field types
+//! - `yaml` feature: YAML import/export, readable in diffs
+//! - `serde` feature: `Serialize`/`Deserialize` for values, fields and entries, and `JMapDocument` for whole tables
+//! - `sqlite` feature: SQLite export/import, to query tables with SQL
+//! - `wasm` feature: WebAssembly bindings, the `JsJMap` class
+//! - `tracing` feature: `tracing` events describing what the readers, writers and hash table loaders decided
+//! - `rayon` feature: parallel decoding of the entries of large BCSV files
+//! - `ffi` feature: a C interface, declared in `include/bcsv_jmap.h`
+//! - `cli` feature: the `bcsv-jmap` command line tool
 
+/// `tracing::debug!`, compiled out without the `tracing` feature
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// `tracing::warn!`, compiled out without the `tracing` feature
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+    };
+}
+
+pub mod columnar;
 pub mod csv;
+#[cfg(feature = "serde")]
+pub mod document;
+pub mod dump;
 pub mod entry;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod field;
 pub mod hash;
 pub mod io;
 pub mod jmap;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod layout;
+pub mod markdown;
+pub mod patch;
+pub mod row;
+pub mod schema;
+#[cfg(feature = "schemas")]
+pub mod schemas;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod strictness;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "yaml")]
+pub mod yaml;
 #[cfg(feature = "python")]
 pub mod python;
 
 
-pub use crate::csv::{from_csv, to_csv};
-pub use crate::entry::{Entry, FieldKey};
-pub use crate::error::{JMapError, Result};
-pub use crate::field::{Field, FieldType, FieldValue};
+pub use crate::columnar::{from_buffer_columnar, Column, ColumnarJMap};
+pub use crate::csv::{
+    convert_dir_bcsv_to_csv, convert_dir_bcsv_to_csv_filtered, convert_dir_csv_to_bcsv, convert_dir_csv_to_bcsv_filtered,
+    from_csv, from_csv_reader, merge_csv_into, merge_csv_reader_into, from_csv_reader_with_report, from_csv_reader_with_schema, from_csv_str, from_csv_with_layout, from_csv_with_options, from_csv_with_schema, to_csv, to_csv_string, to_csv_with_options,
+    to_csv_writer, BatchReport, ColumnOrder, CsvBanner, CsvDialect, CsvOptions, CsvQuoting, FloatFormat, MergeOptions, MergeReport, SchemaOptions,
+};
+#[cfg(feature = "serde")]
+pub use crate::document::{DocumentField, JMapDocument};
+pub use crate::dump::DumpOptions;
+#[cfg(feature = "serde")]
+pub use crate::entry::NamedEntry;
+pub use crate::entry::{Entry, EntryBuilder, EntryView, EntryViewMut, FieldChange, FieldKey, ValueKey};
+pub use crate::error::{ConversionError, JMapError, Result};
+pub use crate::field::{Field, FieldType, FieldValue, FloatToInt, DEFAULT_INLINE_STRING_SIZE};
 pub use crate::hash::{
-    calc_hash, FileHashTable, HashAlgorithm, HashTable,
-    smg_hash_table, smg_hash_table_with_lookup,
+    calc_hash, calc_hash_crc32_lowercase, calc_hash_gamecube, harvest_dir, hash_table_with_lookup, no_lookup, parse_hash_name,
+    smg_hash_table, smg_hash_table_with_lookup, CachedHashTable, CollisionPolicy, CoverageReport, FileHashTable, HarvestReport, MergeStats, HexHashTable, SharedHashTable, HashAlgorithm, HashTable,
+};
+pub use crate::io::{
+    detect_big_endian, detect_encoding, from_buffer, from_buffer_auto_encoding, from_buffer_with_report, from_file, load_dir, load_dir_filtered, patch_buffer, read_field_table, read_header, to_buffer, to_file,
+    verify_roundtrip, verify_roundtrip_dir, ByteDifference, CellDifference, CellEdit, DuplicateFieldPolicy, Encoding, EncodingGuess, Header, IoOptions, JMapSet, PatchOutcome,
+    RoundtripOutcome, RoundtripReport, RoundtripSummary,
 };
-pub use crate::io::{from_buffer, from_file, to_buffer, to_file, Encoding, IoOptions};
-pub use crate::jmap::JMapInfo;
+pub use crate::jmap::{concat, ConcatPolicy, DynJMap, JMapInfo, MemoryStats, ResolutionStats, SourceColumn};
+pub use crate::layout::{load_sidecar, save_sidecar, LayoutConflict, LayoutContext, LayoutSidecar, LayoutStrategy, SidecarField};
+pub use crate::markdown::{to_markdown, MarkdownOptions};
+pub use crate::patch::{apply_patch, create_patch, ApplyReport, ConflictPolicy, Patch, RowChange, RowKey, ValueConflict};
+#[cfg(feature = "serde")]
+pub use crate::patch::{DiffChange, DiffRow, JMapDiff};
+pub use crate::row::{FromEntry, RowValue, ToEntry};
+pub use crate::schema::{Schema, SchemaField};
+pub use crate::strictness::{Category, Level, Report, Strictness, Warning};
+pub use crate::validate::{FieldTypeMismatch, IssueKind, StringIssue, StringIssueKind, ValidationIssue, ValidationReport};
+#[cfg(feature = "derive")]
+pub use lib_bcsv_jmap_derive::{FromEntry, ToEntry};
@@ -0,0 +1,160 @@
+//! Markdown table export, for documentation and issue reports
+
+use crate::field::{Field, FieldType};
+use crate::hash::{parse_hash_name, HashTable};
+use crate::jmap::JMapInfo;
+
+/// Options for [`to_markdown`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownOptions {
+    /// Maximum number of entries written, the others being summarized in a note below the table
+    pub max_rows: Option<usize>,
+    /// Names of the fields to write, in order, or `None` for every field.
+    /// Names can also be given in the `[XXXXXXXX]` form, names that are not fields are skipped
+    pub columns: Option<Vec<String>>,
+    /// Whether the first row of the table holds the field types
+    pub show_types: bool,
+    /// Whether numeric columns are right-aligned
+    pub align_numbers: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            max_rows: None,
+            columns: None,
+            show_types: false,
+            align_numbers: true,
+        }
+    }
+}
+
+/// Write a JMapInfo as a GitHub-flavored Markdown table
+///
+/// Headers are the field names, or `[XXXXXXXX]` for unknown hashes. Pipes in cells are escaped
+/// and line breaks become `<br>`
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to export
+/// - `options` - Which rows and columns are written, and how
+///
+/// # Returns
+/// The Markdown table, followed by a note if rows were left out
+pub fn to_markdown<H: HashTable>(jmap: &JMapInfo<H>, options: &MarkdownOptions) -> String {
+    let columns: Vec<&Field> = match &options.columns {
+        Some(names) => names
+            .iter()
+            .filter_map(|name| {
+                let hash = parse_hash_name(name).unwrap_or_else(|| jmap.hash_table().calc(name));
+                jmap.get_field_by_hash(hash)
+            })
+            .collect(),
+        None => jmap.fields().collect(),
+    };
+
+    let mut out = String::new();
+    push_row(&mut out, columns.iter().map(|field| jmap.field_name(field.hash)));
+    push_row(
+        &mut out,
+        columns.iter().map(|field| {
+            if options.align_numbers && is_numeric(field.field_type) {
+                "---:".to_string()
+            } else {
+                "---".to_string()
+            }
+        }),
+    );
+    if options.show_types {
+        push_row(&mut out, columns.iter().map(|field| format!("*{}*", field.field_type.csv_name())));
+    }
+
+    let shown = options.max_rows.unwrap_or(usize::MAX).min(jmap.len());
+    for entry in &jmap.entries()[..shown] {
+        push_row(
+            &mut out,
+            columns.iter().map(|field| {
+                entry
+                    .get_by_hash(field.hash)
+                    .map(|value| value.to_string())
+                    .unwrap_or_default()
+            }),
+        );
+    }
+
+    let hidden = jmap.len() - shown;
+    if hidden > 0 {
+        out.push_str(&format!(
+            "\n*{} more {} not shown*\n",
+            hidden,
+            if hidden == 1 { "row" } else { "rows" }
+        ));
+    }
+    out
+}
+
+fn is_numeric(field_type: FieldType) -> bool {
+    !matches!(field_type, FieldType::String | FieldType::StringOffset)
+}
+
+fn push_row(out: &mut String, cells: impl Iterator<Item = String>) {
+    out.push('|');
+    for cell in cells {
+        out.push(' ');
+        out.push_str(&escape_cell(&cell));
+        out.push_str(" |");
+    }
+    out.push('\n');
+}
+
+fn escape_cell(cell: &str) -> String {
+    cell.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::FieldValue;
+    use crate::hash::{calc_hash, smg_hash_table};
+
+    #[test]
+    fn test_to_markdown() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.fields_map_mut().insert(0xDEADBEEF, Field::new(0xDEADBEEF, FieldType::Float));
+        for (no, zone) in [(1, "Sky | Garden"), (2, "Two\nLines"), (3, "Dome")] {
+            let entry = jmap.create_entry();
+            entry.set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(no));
+            entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String(zone.into()));
+            entry.set_by_hash(0xDEADBEEF, FieldValue::Float(no as f32 / 2.0));
+        }
+
+        assert_eq!(
+            to_markdown(&jmap, &MarkdownOptions::default()),
+            "| ScenarioNo | ZoneName | [DEADBEEF] |\n\
+             | ---: | --- | ---: |\n\
+             | 1 | Sky \\| Garden | 0.5 |\n\
+             | 2 | Two<br>Lines | 1 |\n\
+             | 3 | Dome | 1.5 |\n"
+        );
+
+        let options = MarkdownOptions {
+            max_rows: Some(1),
+            columns: Some(vec!["ZoneName".to_string(), "[DEADBEEF]".to_string(), "Missing".to_string()]),
+            show_types: true,
+            align_numbers: false,
+        };
+        assert_eq!(
+            to_markdown(&jmap, &options),
+            "| ZoneName | [DEADBEEF] |\n\
+             | --- | --- |\n\
+             | *String* | *Float* |\n\
+             | Sky \\| Garden | 0.5 |\n\
+             \n\
+             *2 more rows not shown*\n"
+        );
+    }
+}
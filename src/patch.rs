@@ -0,0 +1,631 @@
+//! Record the edits between two versions of a table and apply them to other files
+//!
+//! A [`Patch`] addresses rows by the values of key fields, so that it applies to a table whose rows
+//! were reordered, added or removed by someone else. With the `serde` feature, patches can be serialized,
+//! and [`Patch::to_diff`] gives a [`JMapDiff`] with field names written out, for reports
+
+use std::collections::HashMap;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use indexmap::IndexMap;
+
+#[cfg(feature = "serde")]
+use crate::document::DocumentField;
+use crate::entry::{Entry, FieldChange, ValueKey};
+use crate::error::{JMapError, Result};
+use crate::field::{Field, FieldValue};
+use crate::hash::HashTable;
+use crate::jmap::JMapInfo;
+
+/// How a patch row is found in a table
+///
+/// Serialized as the bare index, or the list of key values
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum RowKey {
+    /// By position, when the patch has no key fields
+    Index(usize),
+    /// By the values of the key fields, in [`Patch::key_fields`] order
+    Values(Vec<FieldValue>),
+}
+
+impl fmt::Display for RowKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowKey::Index(index) => write!(f, "#{}", index),
+            RowKey::Values(values) => {
+                let values: Vec<String> = values.iter().map(|value| format!("'{}'", value)).collect();
+                write!(f, "({})", values.join(", "))
+            }
+        }
+    }
+}
+
+/// The changed values of an existing row
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RowChange {
+    /// The row the changes apply to
+    pub key: RowKey,
+    /// The values that differ, `before` being the value in the base table
+    pub changes: Vec<FieldChange>,
+}
+
+/// The edits turning one table into another, see [`create_patch`]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Patch {
+    /// Hashes of the fields identifying rows, empty if rows are identified by index
+    pub key_fields: Vec<u32>,
+    /// Fields of the modified table that the base table does not have
+    pub added_fields: Vec<Field>,
+    /// Hashes of the fields of the base table that the modified table does not have
+    pub removed_fields: Vec<u32>,
+    /// Rows of both tables whose values differ
+    pub changed_rows: Vec<RowChange>,
+    /// Rows of the modified table only
+    pub added_rows: Vec<Entry>,
+    /// Rows of the base table only
+    pub removed_rows: Vec<RowKey>,
+}
+
+impl Patch {
+    /// Check whether the patch has no edits
+    pub fn is_empty(&self) -> bool {
+        self.added_fields.is_empty()
+            && self.removed_fields.is_empty()
+            && self.changed_rows.is_empty()
+            && self.added_rows.is_empty()
+            && self.removed_rows.is_empty()
+    }
+
+    /// Describe the patch with field names, such as for a JSON report
+    ///
+    /// # Arguments
+    /// - `base` - The table the patch was made against, naming the key and removed fields
+    /// - `modified` - The edited table, naming the added and changed fields
+    ///
+    /// # Returns
+    /// The edits, fields being named through the hash tables or written as `[XXXXXXXX]`
+    #[cfg(feature = "serde")]
+    pub fn to_diff<H: HashTable, H2: HashTable>(&self, base: &JMapInfo<H>, modified: &JMapInfo<H2>) -> JMapDiff {
+        JMapDiff {
+            key_fields: self.key_fields.iter().map(|hash| base.field_name(*hash)).collect(),
+            added_fields: self
+                .added_fields
+                .iter()
+                .map(|field| DocumentField {
+                    name: modified.field_name(field.hash),
+                    field_type: field.field_type,
+                    default: field.default.clone(),
+                    mask: (field.mask != field.field_type.default_mask()).then_some(field.mask),
+                    shift: (field.shift != 0).then_some(field.shift),
+                })
+                .collect(),
+            removed_fields: self.removed_fields.iter().map(|hash| base.field_name(*hash)).collect(),
+            added_rows: self
+                .added_rows
+                .iter()
+                .map(|entry| {
+                    let value = |hash| entry.get_by_hash(hash).map(|value| (modified.field_name(hash), value.clone()));
+                    modified.field_hashes().filter_map(|hash| value(*hash)).collect()
+                })
+                .collect(),
+            removed_rows: self.removed_rows.clone(),
+            changed_rows: self
+                .changed_rows
+                .iter()
+                .map(|row| DiffRow {
+                    key: row.key.clone(),
+                    changes: row
+                        .changes
+                        .iter()
+                        .map(|change| DiffChange {
+                            field: modified.field_name(change.hash),
+                            before: change.before.clone(),
+                            after: change.after.clone(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The edits of a [`Patch`] with field names written out, see [`Patch::to_diff`]
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct JMapDiff {
+    /// Names of the fields identifying rows, empty if rows are identified by index
+    pub key_fields: Vec<String>,
+    /// Fields of the modified table that the base table does not have
+    pub added_fields: Vec<DocumentField>,
+    /// Names of the fields of the base table that the modified table does not have
+    pub removed_fields: Vec<String>,
+    /// Rows of the modified table only, mapping field names to values
+    pub added_rows: Vec<IndexMap<String, FieldValue>>,
+    /// Rows of the base table only
+    pub removed_rows: Vec<RowKey>,
+    /// Rows of both tables whose values differ
+    pub changed_rows: Vec<DiffRow>,
+}
+
+/// A row of a [`JMapDiff`] whose values differ
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DiffRow {
+    /// The row the changes apply to
+    pub key: RowKey,
+    /// The values that differ
+    pub changes: Vec<DiffChange>,
+}
+
+/// A value of a [`DiffRow`] that differs
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DiffChange {
+    /// Name of the field
+    pub field: String,
+    /// Value in the base table, `None` if it has none
+    pub before: Option<FieldValue>,
+    /// Value in the modified table, `None` if it has none
+    pub after: Option<FieldValue>,
+}
+
+/// What [`apply_patch`] does when the target does not hold the value the patch expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Keep the target's value
+    #[default]
+    KeepTarget,
+    /// Write the patch's value anyway
+    Overwrite,
+    /// Fail with `JMapError::PatchConflict` without changing the target
+    Fail,
+}
+
+/// A value of the target that differs from the one the patch was made against
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueConflict {
+    /// The row of the value
+    pub key: RowKey,
+    /// Hash of the field
+    pub hash: u32,
+    /// Value in the table the patch was made against, `None` for an added row
+    pub expected: Option<FieldValue>,
+    /// Value in the target
+    pub found: Option<FieldValue>,
+    /// Value the patch writes
+    pub wanted: Option<FieldValue>,
+}
+
+/// Outcome of [`apply_patch`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ApplyReport {
+    /// Values written
+    pub changed_values: usize,
+    /// Rows appended
+    pub added_rows: usize,
+    /// Rows removed
+    pub removed_rows: usize,
+    /// Rows of the patch that the target does not have
+    pub rows_not_found: Vec<RowKey>,
+    /// Values of the target that differ from the ones the patch was made against
+    pub conflicts: Vec<ValueConflict>,
+}
+
+/// Record the edits turning `base` into `modified`
+///
+/// Rows are matched by the values of `key_fields`, a row without a value for a key field using the field's
+/// default. If no key field is given, or a key field is missing from either table, rows are matched by index.
+/// When several rows have the same key, the first one is used
+///
+/// # Arguments
+/// - `base` - The original table, such as the vanilla file
+/// - `modified` - The edited table
+/// - `key_fields` - Names of the fields identifying rows, such as `["ZoneName", "ScenarioNo"]`
+///
+/// # Returns
+/// The field and row edits
+pub fn create_patch<H: HashTable, H2: HashTable>(
+    base: &JMapInfo<H>,
+    modified: &JMapInfo<H2>,
+    key_fields: &[&str],
+) -> Patch {
+    let key_hashes: Vec<u32> = key_fields.iter().map(|name| base.hash_table().calc(name)).collect();
+    let (key_hashes, base_defaults, modified_defaults) =
+        match key_defaults(base, &key_hashes).zip(key_defaults(modified, &key_hashes)) {
+            Some((base_defaults, modified_defaults)) => (key_hashes, base_defaults, modified_defaults),
+            None => (Vec::new(), Vec::new(), Vec::new()),
+        };
+
+    let mut patch = Patch {
+        key_fields: key_hashes,
+        added_fields: modified.fields().filter(|field| !base.contains_field_hash(field.hash)).cloned().collect(),
+        removed_fields: base.field_hashes().filter(|hash| !modified.contains_field_hash(**hash)).copied().collect(),
+        ..Patch::default()
+    };
+
+    let base_rows = RowIndex::new(base.entries(), &patch.key_fields, Some(&base_defaults));
+    let mut matched = vec![false; base.len()];
+    for (index, entry) in modified.entries().iter().enumerate() {
+        let key = row_key(entry, index, &patch.key_fields, &modified_defaults);
+        let Some(position) = base_rows.find(&key).filter(|position| !matched[*position]) else {
+            patch.added_rows.push(entry.clone());
+            continue;
+        };
+        matched[position] = true;
+
+        let changes: Vec<FieldChange> = base.entries()[position]
+            .diff(entry)
+            .into_iter()
+            .filter(|change| !patch.removed_fields.contains(&change.hash))
+            .filter(|change| {
+                // Added fields are filled with their default when applied
+                change.before.is_some()
+                    || !patch
+                        .added_fields
+                        .iter()
+                        .any(|field| field.hash == change.hash && change.after.as_ref() == Some(&field.default))
+            })
+            .collect();
+        if !changes.is_empty() {
+            patch.changed_rows.push(RowChange { key, changes });
+        }
+    }
+
+    for (index, entry) in base.entries().iter().enumerate() {
+        if !matched[index] {
+            patch.removed_rows.push(row_key(entry, index, &patch.key_fields, &base_defaults));
+        }
+    }
+
+    patch
+}
+
+/// Apply a patch to a table, which may differ from the one the patch was made against
+///
+/// Fields are added and removed first, then rows are changed and added, and finally removed.
+/// A value is a conflict when the target holds neither the value the patch expects nor the one it writes
+///
+/// # Arguments
+/// - `target` - The table to patch
+/// - `patch` - The edits to apply
+/// - `policy` - What to do with conflicting values
+///
+/// # Errors
+/// - `JMapError::PatchConflict` if there is a conflict and the policy is `ConflictPolicy::Fail`,
+///   the target being unchanged
+/// - `JMapError::FieldAlreadyExists` if an added field exists in the target with another type
+///
+/// # Returns
+/// What was applied, the rows that were not found and the conflicts
+pub fn apply_patch<H: HashTable>(
+    target: &mut JMapInfo<H>,
+    patch: &Patch,
+    policy: ConflictPolicy,
+) -> Result<ApplyReport> {
+    let mut report = ApplyReport::default();
+
+    for field in &patch.added_fields {
+        if let Some(existing) = target.get_field_by_hash(field.hash)
+            && existing.field_type != field.field_type
+        {
+            return Err(JMapError::FieldAlreadyExists(format!(
+                "{} is {} in the target but {} in the patch",
+                target.field_name(field.hash),
+                existing.field_type,
+                field.field_type
+            )));
+        }
+    }
+
+    // Work out every write before changing anything, so that a failing policy leaves the target untouched
+    let target_defaults = key_defaults(target, &patch.key_fields);
+    let rows = RowIndex::new(target.entries(), &patch.key_fields, target_defaults.as_deref());
+    let mut writes: Vec<(usize, u32, FieldValue)> = Vec::new();
+    for row in &patch.changed_rows {
+        let Some(position) = rows.find(&row.key) else {
+            report.rows_not_found.push(row.key.clone());
+            continue;
+        };
+        let entry = &target.entries()[position];
+        for change in &row.changes {
+            let found = entry.get_by_hash(change.hash);
+            if found == change.after.as_ref() {
+                continue;
+            }
+            if found != change.before.as_ref() {
+                report.conflicts.push(ValueConflict {
+                    key: row.key.clone(),
+                    hash: change.hash,
+                    expected: change.before.clone(),
+                    found: found.cloned(),
+                    wanted: change.after.clone(),
+                });
+                if policy != ConflictPolicy::Overwrite {
+                    continue;
+                }
+            }
+            if let Some(after) = &change.after {
+                writes.push((position, change.hash, after.clone()));
+            }
+        }
+    }
+
+    let mut additions: Vec<&Entry> = Vec::new();
+    for entry in &patch.added_rows {
+        let index = target.len() + additions.len();
+        let existing = target_defaults.as_deref().filter(|_| !patch.key_fields.is_empty()).and_then(|defaults| {
+            let key = row_key(entry, index, &patch.key_fields, defaults);
+            rows.find(&key).map(|position| (key, position))
+        });
+        let Some((key, position)) = existing else {
+            additions.push(entry);
+            continue;
+        };
+        // The row already exists, from an earlier application or another edit
+        for (hash, value) in entry.iter() {
+            let found = target.entries()[position].get_by_hash(*hash);
+            if found == Some(value) {
+                continue;
+            }
+            report.conflicts.push(ValueConflict {
+                key: key.clone(),
+                hash: *hash,
+                expected: None,
+                found: found.cloned(),
+                wanted: Some(value.clone()),
+            });
+            if policy == ConflictPolicy::Overwrite {
+                writes.push((position, *hash, value.clone()));
+            }
+        }
+    }
+
+    let mut removals: Vec<usize> = Vec::new();
+    for key in &patch.removed_rows {
+        match rows.find(key) {
+            Some(position) => removals.push(position),
+            None => report.rows_not_found.push(key.clone()),
+        }
+    }
+
+    if policy == ConflictPolicy::Fail && !report.conflicts.is_empty() {
+        let first = &report.conflicts[0];
+        return Err(JMapError::PatchConflict(format!(
+            "{} conflicting values, first in row {} field {}: expected {}, found {}",
+            report.conflicts.len(),
+            first.key,
+            target.field_name(first.hash),
+            describe_value(first.expected.as_ref()),
+            describe_value(first.found.as_ref())
+        )));
+    }
+
+    for field in &patch.added_fields {
+        if target.contains_field_hash(field.hash) {
+            continue;
+        }
+        target.fields_map_mut().insert(field.hash, field.clone());
+        target.sync_entry_layout();
+        for entry in target.entries_mut() {
+            entry.set_by_hash(field.hash, field.default.clone());
+        }
+    }
+    for hash in &patch.removed_fields {
+        if target.fields_map_mut().shift_remove(hash).is_some() {
+            for entry in target.entries_mut() {
+                entry.remove_by_hash(*hash);
+            }
+        }
+    }
+
+    report.changed_values = writes.len();
+    for (position, hash, value) in writes {
+        target.entries_vec_mut()[position].set_by_hash(hash, value);
+        target.touch(position);
+    }
+
+    report.added_rows = additions.len();
+    for values in additions {
+        let entry = target.create_entry();
+        for (hash, value) in values.iter() {
+            entry.set_by_hash(*hash, value.clone());
+        }
+    }
+
+    removals.sort_unstable();
+    removals.dedup();
+    report.removed_rows = removals.len();
+    if let Some(&first) = removals.first() {
+        for &position in removals.iter().rev() {
+            target.entries_vec_mut().remove(position);
+        }
+        target.touch_from(first);
+    }
+
+    Ok(report)
+}
+
+/// Format a value of a conflict for an error message
+fn describe_value(value: Option<&FieldValue>) -> String {
+    value.map_or_else(|| "no value".to_string(), |value| format!("'{}'", value))
+}
+
+/// Get the default values of the key fields of a table, `None` if the table lacks one of them
+fn key_defaults<H: HashTable>(jmap: &JMapInfo<H>, key_fields: &[u32]) -> Option<Vec<FieldValue>> {
+    key_fields.iter().map(|hash| jmap.get_field_by_hash(*hash).map(|field| field.default.clone())).collect()
+}
+
+/// Get the key of a row, key fields without a value getting their default from `defaults`
+fn row_key(entry: &Entry, index: usize, key_fields: &[u32], defaults: &[FieldValue]) -> RowKey {
+    if key_fields.is_empty() {
+        return RowKey::Index(index);
+    }
+    RowKey::Values(
+        key_fields
+            .iter()
+            .zip(defaults)
+            .map(|(hash, default)| entry.get_by_hash(*hash).unwrap_or(default).clone())
+            .collect(),
+    )
+}
+
+/// Convert the values of a key to map keys, floats being compared by bit pattern
+fn value_keys(values: &[FieldValue]) -> Vec<ValueKey> {
+    values.iter().cloned().map(ValueKey).collect()
+}
+
+/// Positions of rows by key, the first row winning when several have the same key
+struct RowIndex {
+    len: usize,
+    positions: HashMap<Vec<ValueKey>, usize>,
+}
+
+impl RowIndex {
+    /// Index rows by key, `defaults` being `None` when the table lacks a key field so that no key matches
+    fn new(entries: &[Entry], key_fields: &[u32], defaults: Option<&[FieldValue]>) -> Self {
+        let mut positions = HashMap::new();
+        if let Some(defaults) = defaults.filter(|_| !key_fields.is_empty()) {
+            for (index, entry) in entries.iter().enumerate() {
+                if let RowKey::Values(values) = row_key(entry, index, key_fields, defaults) {
+                    positions.entry(value_keys(&values)).or_insert(index);
+                }
+            }
+        }
+        Self { len: entries.len(), positions }
+    }
+
+    fn find(&self, key: &RowKey) -> Option<usize> {
+        match key {
+            RowKey::Index(index) => (*index < self.len).then_some(*index),
+            RowKey::Values(values) => self.positions.get(&value_keys(values)).copied(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::FieldType;
+    use crate::hash::{calc_hash, smg_hash_table, FileHashTable};
+
+    fn vanilla() -> JMapInfo<FileHashTable> {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.create_field("StarId", FieldType::Long, FieldValue::Int(0)).unwrap();
+        for (zone, star) in [("Beach", 1), ("Dome", 2), ("Garden", 3)] {
+            let entry = jmap.create_entry();
+            entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String(zone.into()));
+            entry.set_by_hash(calc_hash("StarId"), FieldValue::Int(star));
+        }
+        jmap
+    }
+
+    fn star(jmap: &JMapInfo<FileHashTable>, zone: &str) -> Option<i32> {
+        let ht = jmap.hash_table();
+        jmap.iter()
+            .find(|entry| entry.get_string(ht, "ZoneName") == Some(zone))
+            .and_then(|entry| entry.get_int(ht, "StarId"))
+    }
+
+    #[test]
+    fn test_patch() {
+        let base = vanilla();
+        let mut modified = vanilla();
+        modified.entries_mut()[1].set_by_hash(calc_hash("StarId"), FieldValue::Int(20));
+        modified.remove_entry(2).unwrap();
+        let entry = modified.create_entry();
+        entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String("Tower".into()));
+        entry.set_by_hash(calc_hash("StarId"), FieldValue::Int(4));
+        modified.create_field("Comet", FieldType::Long, FieldValue::Int(0)).unwrap();
+        modified.entries_mut()[0].set_by_hash(calc_hash("Comet"), FieldValue::Int(1));
+
+        let patch = create_patch(&base, &modified, &["ZoneName"]);
+        assert_eq!(patch.added_fields.len(), 1);
+        assert_eq!(patch.changed_rows.len(), 2);
+        assert_eq!(patch.added_rows.len(), 1);
+        assert_eq!(patch.removed_rows, [RowKey::Values(vec![FieldValue::String("Garden".into())])]);
+        #[cfg(feature = "json")]
+        {
+            let json = serde_json::to_string(&patch).unwrap();
+            let parsed: Patch = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.changed_rows, patch.changed_rows);
+            assert_eq!(parsed.removed_rows, patch.removed_rows);
+
+            let diff = serde_json::to_value(patch.to_diff(&base, &modified)).unwrap();
+            assert_eq!(diff["key_fields"], serde_json::json!(["ZoneName"]));
+            assert_eq!(diff["added_fields"], serde_json::json!([{"name": "Comet", "type": "Long", "default": 0}]));
+            assert_eq!(diff["added_rows"], serde_json::json!([{"ZoneName": "Tower", "StarId": 4, "Comet": 0}]));
+            assert_eq!(diff["removed_rows"], serde_json::json!([["Garden"]]));
+            let changes = serde_json::json!([{"field": "StarId", "before": 2, "after": 20}]);
+            let changed = serde_json::json!({"key": ["Dome"], "changes": changes});
+            assert!(diff["changed_rows"].as_array().unwrap().contains(&changed));
+        }
+
+        // Clean application to vanilla, with the rows in another order
+        let mut target = vanilla();
+        target.entries_vec_mut().reverse();
+        let report = apply_patch(&mut target, &patch, ConflictPolicy::Fail).unwrap();
+        assert_eq!((report.changed_values, report.added_rows, report.removed_rows), (2, 1, 1));
+        assert!(report.conflicts.is_empty() && report.rows_not_found.is_empty());
+        assert_eq!((star(&target, "Dome"), star(&target, "Tower"), star(&target, "Garden")), (Some(20), Some(4), None));
+
+        // Applying again changes nothing, the removed row being gone
+        let report = apply_patch(&mut target, &patch, ConflictPolicy::Fail).unwrap();
+        assert_eq!((report.changed_values, report.added_rows, report.removed_rows), (0, 0, 0));
+        assert!(report.conflicts.is_empty());
+
+        // Another mod changed the same value
+        let mut other = vanilla();
+        other.entries_mut()[1].set_by_hash(calc_hash("StarId"), FieldValue::Int(99));
+        let err = apply_patch(&mut other, &patch, ConflictPolicy::Fail).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Patch conflict: 1 conflicting values, first in row ('Dome') field StarId: expected '2', found '99'"
+        );
+        assert_eq!(other.len(), 3);
+        let report = apply_patch(&mut other, &patch, ConflictPolicy::KeepTarget).unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].found, Some(FieldValue::Int(99)));
+        assert_eq!(star(&other, "Dome"), Some(99));
+
+        // A file missing a keyed row
+        let mut missing = vanilla();
+        missing.remove_entry(1).unwrap();
+        let report = apply_patch(&mut missing, &patch, ConflictPolicy::Overwrite).unwrap();
+        assert_eq!(report.rows_not_found, [RowKey::Values(vec![FieldValue::String("Dome".into())])]);
+        assert_eq!(star(&missing, "Tower"), Some(4));
+    }
+
+    #[test]
+    fn test_missing_key_value() {
+        let mut base = JMapInfo::new(smg_hash_table());
+        base.create_field("Id", FieldType::Long, FieldValue::Int(5)).unwrap();
+        base.create_field("StarId", FieldType::Long, FieldValue::Int(0)).unwrap();
+        base.create_entry().remove_by_hash(calc_hash("Id"));
+        base.create_entry().set_by_hash(calc_hash("Id"), FieldValue::Int(0));
+
+        // The row without an id is keyed by the default, and does not hide the row with id 0
+        let mut modified = JMapInfo::new(smg_hash_table());
+        modified.create_field("Id", FieldType::Long, FieldValue::Int(5)).unwrap();
+        modified.create_field("StarId", FieldType::Long, FieldValue::Int(0)).unwrap();
+        modified.create_entry().remove_by_hash(calc_hash("Id"));
+        let entry = modified.create_entry();
+        entry.set_by_hash(calc_hash("Id"), FieldValue::Int(0));
+        entry.set_by_hash(calc_hash("StarId"), FieldValue::Int(3));
+
+        let patch = create_patch(&base, &modified, &["Id"]);
+        assert_eq!(patch.changed_rows.len(), 1);
+        assert_eq!(patch.changed_rows[0].key, RowKey::Values(vec![FieldValue::Int(0)]));
+        assert_eq!(patch.changed_rows[0].key.to_string(), "('0')");
+        assert!(patch.added_rows.is_empty() && patch.removed_rows.is_empty());
+
+        apply_patch(&mut base, &patch, ConflictPolicy::Fail).unwrap();
+        assert_eq!(base.entries()[1].get_by_hash(calc_hash("StarId")), Some(&FieldValue::Int(3)));
+        assert_eq!(base.entries()[0].get_by_hash(calc_hash("StarId")), Some(&FieldValue::Int(0)));
+    }
+}
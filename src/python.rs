@@ -1,9 +1,17 @@
+// The code generated by the pyo3 0.21 macros predates edition 2024
+#![allow(unsafe_op_in_unsafe_fn)]
+
 use std::path::Path;
 
+use pyo3::buffer::PyBuffer;
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIndexError, PyKeyError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyFloat, PyList, PyLong, PySlice, PyString};
 use crate::{
-    from_csv, from_file, smg_hash_table_with_lookup, to_csv, to_file, FileHashTable, IoOptions,
-    JMapInfo as RustJMapInfo,
+    from_buffer, from_csv, from_csv_str, from_file, parse_hash_name, smg_hash_table, smg_hash_table_with_lookup,
+    to_buffer, to_csv, to_csv_string, to_file, CsvOptions, Encoding, Field, FieldType, FieldValue, FileHashTable,
+    HashTable, IoOptions, JMapError, JMapInfo as RustJMapInfo,
 };
 
 /// A Python wrapper for JMapInfo.
@@ -12,16 +20,323 @@ pub struct PyJMap {
     inner: RustJMapInfo<FileHashTable>,
 }
 
+/// A view of one entry of a JMap, reading and writing through to it.
+///
+/// Values are accessed as `entry["ScenarioNo"]` or `entry.ScenarioNo`,
+/// and `dict(entry)` copies the entry.
+#[pyclass(name = "Entry")]
+pub struct PyEntry {
+    jmap: Py<PyJMap>,
+    /// Index of the entry in the JMap
+    #[pyo3(get)]
+    index: usize,
+}
+
+#[pymethods]
+impl PyEntry {
+    fn __getitem__(&self, py: Python<'_>, field: &str) -> PyResult<PyObject> {
+        self.jmap.borrow(py).get_value(py, self.index, field)
+    }
+
+    fn __setitem__(&self, py: Python<'_>, field: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.jmap.borrow_mut(py).set_value(self.index, field, value)
+    }
+
+    fn __getattr__(&self, py: Python<'_>, field: &str) -> PyResult<PyObject> {
+        self.__getitem__(py, field)
+            .map_err(|_| pyo3::exceptions::PyAttributeError::new_err(field.to_string()))
+    }
+
+    fn __setattr__(&self, py: Python<'_>, field: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.__setitem__(py, field, value)
+    }
+
+    fn __contains__(&self, py: Python<'_>, field: &str) -> bool {
+        self.jmap.borrow(py).field(field).is_ok()
+    }
+
+    /// Get the field names, in field order.
+    fn keys(&self, py: Python<'_>) -> Vec<String> {
+        let jmap = self.jmap.borrow(py);
+        jmap.inner.fields().map(|field| jmap.inner.field_name(field.hash)).collect()
+    }
+
+    /// Copy the entry to a dict, see `JMap.get_row`.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        self.jmap.borrow(py).get_row(py, self.index)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!("Entry({}, {})", self.index, self.to_dict(py)?.repr()?))
+    }
+}
+
+/// Build the SMG hash table from an optional lookup file and an optional list of names
+fn load_hash_table(hash_table_path: Option<&str>, names: Option<Vec<String>>) -> PyResult<FileHashTable> {
+    let mut hash_table = match hash_table_path {
+        Some(path) => smg_hash_table_with_lookup(Path::new(path))
+            .map_err(bcsv_error)?,
+        None => smg_hash_table(),
+    };
+    if let Some(names) = names {
+        hash_table.extend_from_names(names);
+    }
+    Ok(hash_table)
+}
+
+/// Build BCSV options from the endianness and an encoding name
+fn io_options(big_endian: bool, encoding: &str) -> PyResult<IoOptions> {
+    let encoding = match encoding.to_ascii_lowercase().as_str() {
+        "shift_jis" | "shift-jis" | "sjis" => Encoding::ShiftJis,
+        "utf-8" | "utf8" => Encoding::Utf8,
+        _ => {
+            return Err(PyValueError::new_err(format!(
+                "unknown encoding {}, expected shift_jis or utf-8",
+                encoding
+            )))
+        }
+    };
+    Ok(IoOptions {
+        big_endian,
+        encoding,
+        ..IoOptions::default()
+    })
+}
+
+create_exception!(lib_bcsv_jmap, BcsvError, PyException, "Base class of the errors raised by the library.");
+create_exception!(lib_bcsv_jmap, BcsvFormatError, BcsvError, "Malformed BCSV, CSV or JSON data, or an invalid field.");
+create_exception!(lib_bcsv_jmap, BcsvTypeError, BcsvError, "A value does not match the type of its field.");
+create_exception!(lib_bcsv_jmap, BcsvEncodingError, BcsvError, "A string cannot be encoded or decoded.");
+create_exception!(lib_bcsv_jmap, BcsvLookupError, BcsvError, "A field, entry or name could not be found.");
+
+/// Convert a library error to the matching exception class, keeping its message.
+///
+/// Errors in an entry or at an offset get the class of the underlying error. I/O errors become the matching
+/// OSError subclass, such as FileNotFoundError.
+fn bcsv_error(err: JMapError) -> PyErr {
+    let message = err.to_string();
+    let mut kind = &err;
+    while let JMapError::InEntry { source, .. } | JMapError::Parse { source, .. } = kind {
+        kind = source;
+    }
+    match kind {
+        JMapError::InvalidFieldType(_)
+        | JMapError::InvalidField(_)
+        | JMapError::BufferTooSmall { .. }
+        | JMapError::SizeOverflow { .. }
+        | JMapError::DuplicateField { .. }
+        | JMapError::InvalidHeader
+        | JMapError::CsvError(_)
+        | JMapError::JsonError(_)
+        | JMapError::YamlError(_)
+        | JMapError::InvalidCsvFieldDescriptor(_) => BcsvFormatError::new_err(message),
+        JMapError::TypeMismatch { .. } | JMapError::Conversion(_) => BcsvTypeError::new_err(message),
+        JMapError::EncodingError(_) => BcsvEncodingError::new_err(message),
+        JMapError::FieldNotFound(_)
+        | JMapError::EntryIndexOutOfBounds { .. }
+        | JMapError::LookupFileNotFound(_)
+        | JMapError::HashCollision { .. }
+        | JMapError::HashAlgorithmMismatch(..) => BcsvLookupError::new_err(message),
+        JMapError::IoError(e) => std::io::Error::new(e.kind(), message).into(),
+        JMapError::FieldAlreadyExists(_)
+        | JMapError::PatchConflict(_)
+        | JMapError::SqliteError(_)
+        | JMapError::InvalidArgument(_)
+        | JMapError::InEntry { .. }
+        | JMapError::Parse { .. } => BcsvError::new_err(message),
+    }
+}
+
+/// Convert an error of a JSON import, giving the path of the rejected values of entries
+fn json_error(err: JMapError) -> PyErr {
+    match err {
+        JMapError::InEntry { index, source } => match *source {
+            // The message starts with the field name
+            JMapError::InvalidField(message) => BcsvTypeError::new_err(format!("entries[{}].{}", index, message)),
+            JMapError::FieldNotFound(name) => {
+                BcsvLookupError::new_err(format!("entries[{}].{}: no field with this name", index, name))
+            }
+            source => bcsv_error(JMapError::InEntry { index, source: Box::new(source) }),
+        },
+        err => bcsv_error(err),
+    }
+}
+
+/// Convert a value to the matching Python int, float or str
+fn value_to_py(py: Python<'_>, value: &FieldValue) -> PyObject {
+    match value {
+        FieldValue::Int(v) => v.into_py(py),
+        FieldValue::UInt(v) => v.into_py(py),
+        FieldValue::Float(v) => v.into_py(py),
+        FieldValue::String(v) => v.into_py(py),
+    }
+}
+
+/// Convert a Python value to a value of the field's type
+///
+/// Raises TypeError if the Python type does not match the field type, ValueError if an int does not fit
+fn value_from_py(field: &Field, name: &str, value: &Bound<'_, PyAny>) -> PyResult<FieldValue> {
+    let type_error = || {
+        let got = value.get_type().name().map(|n| n.to_string()).unwrap_or_default();
+        PyTypeError::new_err(format!("{} expects {}, got {}", name, field.field_type, got))
+    };
+    let int = |min: i64, max: i64| -> PyResult<i64> {
+        if !value.is_instance_of::<PyLong>() {
+            return Err(type_error());
+        }
+        let v: i64 = value
+            .extract()
+            .map_err(|_| PyValueError::new_err(format!("{} is out of range for {}", value, name)))?;
+        if v < min || v > max {
+            return Err(PyValueError::new_err(format!(
+                "{} is out of range for {} ({}, {} to {})",
+                v, name, field.field_type, min, max
+            )));
+        }
+        Ok(v)
+    };
+
+    match field.field_type {
+        FieldType::Long => Ok(FieldValue::Int(int(i32::MIN as i64, i32::MAX as i64)? as i32)),
+        FieldType::Short => Ok(FieldValue::Int(int(i16::MIN as i64, i16::MAX as i64)? as i32)),
+        FieldType::Char => Ok(FieldValue::Int(int(i8::MIN as i64, i8::MAX as i64)? as i32)),
+        FieldType::UnsignedLong => Ok(FieldValue::UInt(int(0, u32::MAX as i64)? as u32)),
+        FieldType::Float => {
+            if !value.is_instance_of::<PyFloat>() && !value.is_instance_of::<PyLong>() {
+                return Err(type_error());
+            }
+            Ok(FieldValue::Float(value.extract::<f64>()? as f32))
+        }
+        FieldType::String | FieldType::StringOffset => {
+            if !value.is_instance_of::<PyString>() {
+                return Err(type_error());
+            }
+            Ok(FieldValue::String(value.extract()?))
+        }
+    }
+}
+
+impl PyJMap {
+    /// Call `predicate` with the row dict of every entry, in order
+    fn matching(&self, py: Python<'_>, predicate: &Bound<'_, PyAny>) -> PyResult<Vec<bool>> {
+        (0..self.inner.len())
+            .map(|index| predicate.call1((self.get_row(py, index)?,))?.is_truthy())
+            .collect()
+    }
+
+    /// Resolve a field name, or a `[XXXXXXXX]` hash, raising KeyError if the field does not exist
+    fn field(&self, name: &str) -> PyResult<&Field> {
+        let hash = parse_hash_name(name).unwrap_or_else(|| self.inner.hash_table().calc(name));
+        self.inner
+            .get_field_by_hash(hash)
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+
+    /// Resolve a Python index, negative ones counting from the end
+    fn normalize_index(&self, index: isize) -> PyResult<usize> {
+        let len = self.inner.len() as isize;
+        let resolved = if index < 0 { index + len } else { index };
+        if (0..len).contains(&resolved) {
+            Ok(resolved as usize)
+        } else {
+            Err(PyIndexError::new_err(format!("entry index {} out of range (len: {})", index, len)))
+        }
+    }
+
+    /// Raise IndexError if there is no entry at `index`
+    fn check_index(&self, index: usize) -> PyResult<()> {
+        if index < self.inner.len() {
+            Ok(())
+        } else {
+            Err(PyIndexError::new_err(format!(
+                "entry index {} out of range (len: {})",
+                index,
+                self.inner.len()
+            )))
+        }
+    }
+
+    /// Convert a dict of field names to values, checking every value before anything is written
+    fn values_from_dict(&self, values: &Bound<'_, PyDict>) -> PyResult<Vec<(u32, FieldValue)>> {
+        values
+            .iter()
+            .map(|(key, value)| {
+                let name: String = key.extract()?;
+                let field = self.field(&name)?;
+                Ok((field.hash, value_from_py(field, &name, &value)?))
+            })
+            .collect()
+    }
+}
+
 #[pymethods]
 impl PyJMap {
+    /// Create an empty JMap, with field names from a lookup file and from a list of names if given.
+    #[new]
+    #[pyo3(signature = (hash_table_path=None, names=None))]
+    pub fn new(hash_table_path: Option<&str>, names: Option<Vec<String>>) -> PyResult<Self> {
+        Ok(PyJMap {
+            inner: RustJMapInfo::new(load_hash_table(hash_table_path, names)?),
+        })
+    }
+
+    /// Create a JMap from BCSV data in a bytes, bytearray or memoryview.
+    ///
+    /// Field names come from the lookup file and from `names`, either of which may be None.
+    #[staticmethod]
+    #[pyo3(signature = (hash_table_path, data, big_endian=true, encoding="shift_jis", names=None))]
+    pub fn from_bytes(
+        py: Python<'_>,
+        hash_table_path: Option<&str>,
+        data: &Bound<'_, PyAny>,
+        big_endian: bool,
+        encoding: &str,
+        names: Option<Vec<String>>,
+    ) -> PyResult<Self> {
+        let data = PyBuffer::<u8>::get_bound(data)?.to_vec(py)?;
+        let options = io_options(big_endian, encoding)?;
+        let jmap = from_buffer(load_hash_table(hash_table_path, names)?, &data, &options)
+            .map_err(bcsv_error)?;
+        Ok(PyJMap { inner: jmap })
+    }
+
+    /// Create a JMap from CSV text, in the format written by `to_csv_string`.
+    #[staticmethod]
+    #[pyo3(signature = (hash_table_path, text, names=None))]
+    pub fn from_csv_string(hash_table_path: Option<&str>, text: &str, names: Option<Vec<String>>) -> PyResult<Self> {
+        let jmap = from_csv_str(load_hash_table(hash_table_path, names)?, text, &CsvOptions::default())
+            .map_err(bcsv_error)?;
+        Ok(PyJMap { inner: jmap })
+    }
+
+    /// Create a JMap from a JSON document, in the format written by `to_json`.
+    ///
+    /// A value that does not match its field type raises BcsvTypeError, with its path such as
+    /// `entries[2].ScenarioNo` in the message.
+    #[staticmethod]
+    #[pyo3(signature = (hash_table_path, json_str, names=None))]
+    pub fn from_json(hash_table_path: Option<&str>, json_str: &str, names: Option<Vec<String>>) -> PyResult<Self> {
+        let jmap = crate::json::from_json(load_hash_table(hash_table_path, names)?, json_str).map_err(json_error)?;
+        Ok(PyJMap { inner: jmap })
+    }
+
+    /// Create a JMap from a JSON file, see `from_json`.
+    #[staticmethod]
+    #[pyo3(signature = (hash_table_path, json_path, names=None))]
+    pub fn from_json_file(hash_table_path: Option<&str>, json_path: &str, names: Option<Vec<String>>) -> PyResult<Self> {
+        let jmap = crate::json::from_json_file(load_hash_table(hash_table_path, names)?, Path::new(json_path))
+            .map_err(json_error)?;
+        Ok(PyJMap { inner: jmap })
+    }
+
     /// Create a JMap from a BCSV file.
     #[staticmethod]
     pub fn from_file(hash_table_path: &str, bcsv_path: &str) -> PyResult<Self> {
         let hash_table = smg_hash_table_with_lookup(Path::new(hash_table_path))
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            .map_err(bcsv_error)?;
         
         let jmap = from_file(hash_table, Path::new(bcsv_path), &IoOptions::default())
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            .map_err(bcsv_error)?;
 
         Ok(PyJMap { inner: jmap })
     }
@@ -30,11 +345,11 @@ impl PyJMap {
     #[staticmethod]
     pub fn from_csv(hash_table_path: &str, csv_path: &str) -> PyResult<Self> {
         let hash_table = smg_hash_table_with_lookup(Path::new(hash_table_path))
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            .map_err(bcsv_error)?;
         
         // Use default delimiter
         let jmap = from_csv(hash_table, Path::new(csv_path), None)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            .map_err(bcsv_error)?;
 
         Ok(PyJMap { inner: jmap })
     }
@@ -42,17 +357,43 @@ impl PyJMap {
     /// Write the JMap to a BCSV file.
     pub fn to_file(&self, path: &str) -> PyResult<()> {
         to_file(&self.inner, Path::new(path), &IoOptions::default())
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            .map_err(bcsv_error)?;
         Ok(())
     }
 
     /// Write the JMap to a CSV file.
     pub fn to_csv(&self, path: &str) -> PyResult<()> {
         to_csv(&self.inner, Path::new(path), None)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyIOError, _>(e.to_string()))?;
+            .map_err(bcsv_error)?;
         Ok(())
     }
 
+    /// Write the JMap as BCSV data.
+    #[pyo3(signature = (big_endian=true, encoding="shift_jis"))]
+    pub fn to_bytes<'py>(&self, py: Python<'py>, big_endian: bool, encoding: &str) -> PyResult<Bound<'py, PyBytes>> {
+        let data = to_buffer(&self.inner, &io_options(big_endian, encoding)?)
+            .map_err(bcsv_error)?;
+        Ok(PyBytes::new_bound(py, &data))
+    }
+
+    /// Write the JMap as CSV text.
+    pub fn to_csv_string(&self) -> PyResult<String> {
+        to_csv_string(&self.inner, &CsvOptions::default())
+            .map_err(bcsv_error)
+    }
+
+    /// Write the JMap as a JSON document with its field types, indented if `pretty`.
+    #[pyo3(signature = (pretty=false))]
+    pub fn to_json(&self, pretty: bool) -> PyResult<String> {
+        crate::json::to_json(&self.inner, pretty).map_err(bcsv_error)
+    }
+
+    /// Write the JMap to a JSON file, see `to_json`.
+    #[pyo3(signature = (path, pretty=false))]
+    pub fn to_json_file(&self, path: &str, pretty: bool) -> PyResult<()> {
+        crate::json::to_json_file(&self.inner, Path::new(path), pretty).map_err(bcsv_error)
+    }
+
     /// Get the number of entries.
     pub fn len(&self) -> usize {
         self.inner.len()
@@ -62,16 +403,333 @@ impl PyJMap {
     pub fn num_fields(&self) -> usize {
         self.inner.num_fields()
     }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Get an `Entry` view by index, negative indices counting from the end, or a list of them for a slice.
+    fn __getitem__(slf: &Bound<'_, Self>, key: &Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let py = slf.py();
+        if let Ok(slice) = key.downcast::<PySlice>() {
+            let indices = slice.indices(slf.borrow().inner.len() as std::ffi::c_long)?;
+            let entries = (0..indices.slicelength)
+                .map(|i| {
+                    let index = (indices.start + i * indices.step) as usize;
+                    Py::new(py, PyEntry { jmap: slf.clone().unbind(), index })
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            return Ok(PyList::new_bound(py, entries).into_py(py));
+        }
+
+        let index = slf.borrow().normalize_index(key.extract()?)?;
+        Ok(Py::new(py, PyEntry { jmap: slf.clone().unbind(), index })?.into_py(py))
+    }
+
+    /// Iterate over `Entry` views of every entry.
+    fn __iter__(slf: &Bound<'_, Self>) -> PyResult<PyObject> {
+        let len = slf.borrow().inner.len() as isize;
+        let entries = PySlice::new_bound(slf.py(), 0, len, 1);
+        let list = Self::__getitem__(slf, entries.as_any())?;
+        Ok(list.bind(slf.py()).iter()?.into_py(slf.py()))
+    }
+
+    /// Check whether a field exists, by name or `[XXXXXXXX]` hash.
+    fn __contains__(&self, field: &str) -> bool {
+        self.field(field).is_ok()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("JMap(entries={}, fields={})", self.inner.len(), self.inner.num_fields())
+    }
     
+    /// Get a value as an int, float or str, or None if the entry has no value for the field.
+    ///
+    /// Raises IndexError if there is no entry at `index` and KeyError if the field does not exist.
+    pub fn get_value(&self, py: Python<'_>, index: usize, field: &str) -> PyResult<PyObject> {
+        self.check_index(index)?;
+        let hash = self.field(field)?.hash;
+        Ok(self.inner.entries()[index]
+            .get_by_hash(hash)
+            .map_or_else(|| py.None(), |value| value_to_py(py, value)))
+    }
+
+    /// Set a value, checked against the field's type.
+    ///
+    /// Raises IndexError, KeyError, TypeError if the value has the wrong type
+    /// and ValueError if an int does not fit in the field.
+    pub fn set_value(&mut self, index: usize, field: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.check_index(index)?;
+        let field_def = self.field(field)?;
+        let (hash, value) = (field_def.hash, value_from_py(field_def, field, value)?);
+        self.inner.entries_mut()[index].set_by_hash(hash, value);
+        Ok(())
+    }
+
+    /// Get an entry as a dict of field names to values, in field order.
+    ///
+    /// Unknown names are given as `[XXXXXXXX]`, which all methods accept in place of a name.
+    pub fn get_row<'py>(&self, py: Python<'py>, index: usize) -> PyResult<Bound<'py, PyDict>> {
+        self.check_index(index)?;
+        let entry = &self.inner.entries()[index];
+        let row = PyDict::new_bound(py);
+        for field in self.inner.fields() {
+            let value = entry
+                .get_by_hash(field.hash)
+                .map_or_else(|| py.None(), |value| value_to_py(py, value));
+            row.set_item(self.inner.field_name(field.hash), value)?;
+        }
+        Ok(row)
+    }
+
+    /// Set the values of an entry from a dict of field names to values.
+    ///
+    /// Fields missing from the dict keep their value. Every value is checked before any is written,
+    /// so the entry is unchanged if an error is raised.
+    pub fn set_row(&mut self, index: usize, values: &Bound<'_, PyDict>) -> PyResult<()> {
+        self.check_index(index)?;
+        let values = self.values_from_dict(values)?;
+        let entry = &mut self.inner.entries_mut()[index];
+        for (hash, value) in values {
+            entry.set_by_hash(hash, value);
+        }
+        Ok(())
+    }
+
+    /// Convert every entry to a dict, in field order, for `pandas.DataFrame(jmap.to_records())`.
+    ///
+    /// Unresolved fields are keyed by their `[XXXXXXXX]` hash, and missing values are None.
+    pub fn to_records<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let records = PyList::empty_bound(py);
+        for index in 0..self.inner.len() {
+            records.append(self.get_row(py, index)?)?;
+        }
+        Ok(records)
+    }
+
+    /// Replace the entries with a list of dicts, as produced by `to_records`.
+    ///
+    /// Record `i` updates entry `i`: extra records are added as new entries and extra entries are
+    /// removed. Keys missing from a record, or set to None, keep the existing value (or the field
+    /// default for new entries). Unknown keys raise KeyError if `strict`, and are skipped otherwise.
+    /// Every record is checked before anything is written, so the JMap is unchanged on error.
+    #[pyo3(signature = (records, strict=true))]
+    pub fn update_from_records(&mut self, records: Vec<Bound<'_, PyDict>>, strict: bool) -> PyResult<()> {
+        let mut rows = Vec::with_capacity(records.len());
+        for record in &records {
+            let mut values = Vec::with_capacity(record.len());
+            for (key, value) in record.iter() {
+                let name: String = key.extract()?;
+                let field = match self.field(&name) {
+                    Ok(field) => field,
+                    Err(_) if !strict => continue,
+                    Err(err) => return Err(err),
+                };
+                if !value.is_none() {
+                    values.push((field.hash, value_from_py(field, &name, &value)?));
+                }
+            }
+            rows.push(values);
+        }
+
+        let existing = self.inner.len().min(rows.len());
+        self.inner.entries_vec_mut().truncate(existing);
+        self.inner.touch_from(existing);
+        for (index, values) in rows.into_iter().enumerate() {
+            if index < existing {
+                let entry = &mut self.inner.entries_mut()[index];
+                for (hash, value) in values {
+                    entry.set_by_hash(hash, value);
+                }
+            } else {
+                let builder = values
+                    .into_iter()
+                    .fold(self.inner.entry_builder(), |builder, (hash, value)| builder.set_hash(hash, value));
+                builder.insert().map_err(bcsv_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a field, every entry getting its default.
+    ///
+    /// `field_type` is a CSV type name: Int, UnsignedInt, Short, Char, Float, String or EmbeddedString.
+    /// The default is converted to the field type, or is the type's zero value if None.
+    #[pyo3(signature = (name, field_type, default=None))]
+    pub fn create_field(&mut self, name: &str, field_type: &str, default: Option<&Bound<'_, PyAny>>) -> PyResult<()> {
+        let field_type = FieldType::from_csv_name(field_type).ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "unknown field type {}, expected Int, UnsignedInt, Short, Char, Float, String or EmbeddedString",
+                field_type
+            ))
+        })?;
+        let default = match default {
+            Some(value) if !value.is_none() => value_from_py(&Field::new(0, field_type), name, value)?,
+            _ => FieldValue::default_for(field_type),
+        };
+        self.inner.create_field(name, field_type, default).map_err(bcsv_error)
+    }
+
+    /// Remove a field and its values.
+    pub fn drop_field(&mut self, name: &str) -> PyResult<()> {
+        let hash = self.field(name)?.hash;
+        let name = self.inner.field_name(hash);
+        self.inner.drop_field(&name).map_err(bcsv_error)
+    }
+
+    /// Rename a field, keeping its position and values.
+    pub fn rename_field(&mut self, old: &str, new: &str) -> PyResult<()> {
+        self.field(old)?;
+        self.inner.rename_field(old, new).map_err(bcsv_error)
+    }
+
+    /// Describe the fields, in field order, as dicts with name, hash, type, offset, mask, shift and default.
+    ///
+    /// Hashes are u32 values.
+    pub fn fields<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.inner
+            .fields()
+            .map(|field| {
+                let info = PyDict::new_bound(py);
+                info.set_item("name", self.inner.field_name(field.hash))?;
+                info.set_item("hash", field.hash)?;
+                info.set_item("type", field.field_type.csv_name())?;
+                info.set_item("offset", field.offset)?;
+                info.set_item("mask", field.mask)?;
+                info.set_item("shift", field.shift)?;
+                info.set_item("default", value_to_py(py, &field.default))?;
+                Ok(info)
+            })
+            .collect()
+    }
+
+    /// Append an entry, filled from the field defaults and then from `values`.
+    ///
+    /// Returns the index of the new entry. Every value is checked first, so nothing is added on error.
+    #[pyo3(signature = (values=None))]
+    pub fn add_entry(&mut self, values: Option<&Bound<'_, PyDict>>) -> PyResult<usize> {
+        let values = values.map(|values| self.values_from_dict(values)).transpose()?.unwrap_or_default();
+        let builder = values
+            .into_iter()
+            .fold(self.inner.entry_builder(), |builder, (hash, value)| builder.set_hash(hash, value));
+        builder.insert().map_err(bcsv_error)
+    }
+
+    /// Append a copy of an entry, with the values of `overrides` replaced.
+    ///
+    /// Returns the index of the copy. Every value is checked first, so nothing is added on error.
+    #[pyo3(signature = (index, overrides=None))]
+    pub fn duplicate_entry(&mut self, index: usize, overrides: Option<&Bound<'_, PyDict>>) -> PyResult<usize> {
+        self.check_index(index)?;
+        let overrides = overrides.map(|values| self.values_from_dict(values)).transpose()?.unwrap_or_default();
+        let copy = self.inner.duplicate_entry(index).map_err(bcsv_error)?;
+        let entry = &mut self.inner.entries_mut()[copy];
+        for (hash, value) in overrides {
+            entry.set_by_hash(hash, value);
+        }
+        Ok(copy)
+    }
+
+    /// Remove an entry, shifting the later ones.
+    pub fn remove_entry(&mut self, index: usize) -> PyResult<()> {
+        self.check_index(index)?;
+        self.inner.remove_entry(index).map_err(bcsv_error)?;
+        Ok(())
+    }
+
+    /// Remove every entry, keeping the fields.
+    pub fn clear_entries(&mut self) {
+        self.inner.clear_entries();
+    }
+
+    /// Sort the entries by one or more fields, the first being the primary key.
+    ///
+    /// The sort is stable, and missing values sort first.
+    #[pyo3(signature = (*fields, descending=false))]
+    pub fn sort(&mut self, fields: Vec<String>, descending: bool) -> PyResult<()> {
+        let hashes = fields
+            .iter()
+            .map(|name| self.field(name).map(|field| field.hash))
+            .collect::<PyResult<Vec<u32>>>()?;
+        self.inner.sort_by_fields(&hashes, descending).map_err(bcsv_error)
+    }
+
+    /// Keep only the entries for which `predicate(row)` is true, `row` being the dict of `get_row`.
+    ///
+    /// Returns the number of removed entries. The predicate is called on every entry first,
+    /// so the JMap is unchanged if it raises.
+    pub fn retain(&mut self, py: Python<'_>, predicate: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let keep = self.matching(py, predicate)?;
+        let mut keep = keep.into_iter();
+        Ok(self.inner.retain_entries(|_| keep.next().unwrap_or(true)))
+    }
+
+    /// Remove the entries for which `predicate(row)` is true, the opposite of `retain`.
+    pub fn remove_where(&mut self, py: Python<'_>, predicate: &Bound<'_, PyAny>) -> PyResult<usize> {
+        let remove = self.matching(py, predicate)?;
+        let mut remove = remove.into_iter();
+        Ok(self.inner.retain_entries(|_| !remove.next().unwrap_or(false)))
+    }
+
+    /// Get the indices of the entries whose field holds `value`.
+    pub fn find(&self, field: &str, value: &Bound<'_, PyAny>) -> PyResult<Vec<usize>> {
+        let field = self.field(field)?;
+        let (hash, value) = (field.hash, value_from_py(field, &self.inner.field_name(field.hash), value)?);
+        Ok(self
+            .inner
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.get_by_hash(hash) == Some(&value))
+            .map(|(index, _)| index)
+            .collect())
+    }
+
+    /// Get the name of a field hash, or its `[XXXXXXXX]` form if the name is unknown.
+    ///
+    /// Hashes are unsigned 32-bit ints.
+    pub fn resolve_hash(&self, hash: u32) -> String {
+        self.inner.hash_table().find(hash)
+    }
+
+    /// Add a name to the name table and return its hash.
+    ///
+    /// Raises BcsvLookupError if the hash already belongs to a different name.
+    pub fn add_name(&mut self, name: &str) -> PyResult<u32> {
+        self.inner.hash_table_mut().try_add(name).map_err(bcsv_error)
+    }
+
+    /// Get the hashes of the fields whose name is unknown, in field order.
+    pub fn unresolved_hashes(&self) -> Vec<u32> {
+        self.inner.unresolved_hashes()
+    }
+
+    /// Write every known name to a lookup file, one per line, which `from_file` can load back.
+    pub fn save_names(&self, path: &str) -> PyResult<()> {
+        self.inner.hash_table().to_file(path).map_err(bcsv_error)
+    }
+
     /// Recalculate offsets in memory (useful for debugging).
     pub fn recalculate_offsets(&mut self) {
         self.inner.recalculate_offsets();
     }
 }
 
+/// Calculate the SMG hash of a field name, as an unsigned 32-bit int.
+#[pyfunction]
+fn calc_hash(name: &str) -> u32 {
+    crate::calc_hash(name)
+}
+
 /// A Python module implemented in Rust.
 #[pymodule]
 fn lib_bcsv_jmap(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyJMap>()?;
+    m.add_class::<PyEntry>()?;
+    m.add_function(wrap_pyfunction!(calc_hash, m)?)?;
+    m.add("BcsvError", m.py().get_type_bound::<BcsvError>())?;
+    m.add("BcsvFormatError", m.py().get_type_bound::<BcsvFormatError>())?;
+    m.add("BcsvTypeError", m.py().get_type_bound::<BcsvTypeError>())?;
+    m.add("BcsvEncodingError", m.py().get_type_bound::<BcsvEncodingError>())?;
+    m.add("BcsvLookupError", m.py().get_type_bound::<BcsvLookupError>())?;
     Ok(())
 }
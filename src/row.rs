@@ -0,0 +1,231 @@
+//! Mapping between entries and user-defined structs
+
+use crate::entry::Entry;
+use crate::error::{JMapError, Result};
+use crate::field::FieldValue;
+use crate::hash::HashTable;
+use crate::jmap::JMapInfo;
+
+/// A Rust type that can be stored in a single field, used by the `FromEntry`/`ToEntry` derives
+pub trait RowValue: Sized {
+    /// Name of the expected value type, for error messages
+    const TYPE_NAME: &'static str;
+
+    /// Convert a stored value, returning `None` if it has the wrong type
+    fn from_value(value: &FieldValue) -> Option<Self>;
+
+    /// Convert to a value that can be stored in an entry
+    fn to_value(&self) -> FieldValue;
+}
+
+impl RowValue for i32 {
+    const TYPE_NAME: &'static str = "Int";
+
+    fn from_value(value: &FieldValue) -> Option<Self> {
+        value.as_int()
+    }
+
+    fn to_value(&self) -> FieldValue {
+        FieldValue::Int(*self)
+    }
+}
+
+impl RowValue for u32 {
+    const TYPE_NAME: &'static str = "Int";
+
+    fn from_value(value: &FieldValue) -> Option<Self> {
+        value.as_uint()
+    }
+
+    fn to_value(&self) -> FieldValue {
+        FieldValue::UInt(*self)
+    }
+}
+
+impl RowValue for f32 {
+    const TYPE_NAME: &'static str = "Float";
+
+    fn from_value(value: &FieldValue) -> Option<Self> {
+        value.as_float()
+    }
+
+    fn to_value(&self) -> FieldValue {
+        FieldValue::Float(*self)
+    }
+}
+
+impl RowValue for String {
+    const TYPE_NAME: &'static str = "String";
+
+    fn from_value(value: &FieldValue) -> Option<Self> {
+        value.as_str().map(str::to_string)
+    }
+
+    fn to_value(&self) -> FieldValue {
+        FieldValue::String(self.clone())
+    }
+}
+
+impl RowValue for bool {
+    const TYPE_NAME: &'static str = "Int";
+
+    fn from_value(value: &FieldValue) -> Option<Self> {
+        value.as_bool()
+    }
+
+    fn to_value(&self) -> FieldValue {
+        FieldValue::from(*self)
+    }
+}
+
+/// Convert an optional stored value, `None` if absent (used by the derives)
+///
+/// # Errors
+/// - `JMapError::TypeMismatch` naming `name` if the value is present but has the wrong type
+#[doc(hidden)]
+pub fn read_optional<T: RowValue>(value: Option<&FieldValue>, name: &str) -> Result<Option<T>> {
+    value
+        .map(|value| {
+            T::from_value(value).ok_or_else(|| JMapError::TypeMismatch {
+                field: name.to_string(),
+                expected: T::TYPE_NAME,
+                got: value.type_name(),
+            })
+        })
+        .transpose()
+}
+
+/// Convert a required stored value (used by the derives)
+///
+/// # Errors
+/// - `JMapError::FieldNotFound` naming `name` if the value is absent
+/// - `JMapError::TypeMismatch` if the value has the wrong type
+#[doc(hidden)]
+pub fn read_required<T: RowValue>(value: Option<&FieldValue>, name: &str) -> Result<T> {
+    read_optional(value, name)?.ok_or_else(|| JMapError::FieldNotFound(name.to_string()))
+}
+
+/// A type that can be read from an entry
+///
+/// The `Entry::require_*` accessors return descriptive errors for missing or mistyped fields
+/// and are the intended building blocks for implementations
+pub trait FromEntry: Sized {
+    /// Build a value from an entry
+    ///
+    /// # Arguments
+    /// - `entry` - The entry to read from
+    /// - `hash_table` - The hash table used to resolve field names
+    ///
+    /// # Errors
+    /// - `JMapError::FieldNotFound` or `JMapError::TypeMismatch` if a field is missing or has the wrong type
+    fn from_entry<H: HashTable>(entry: &Entry, hash_table: &H) -> Result<Self>;
+}
+
+/// A type that can be written into an entry
+pub trait ToEntry {
+    /// Write the values of `self` into an entry
+    ///
+    /// # Arguments
+    /// - `entry` - The entry to write to
+    /// - `hash_table` - The hash table used to resolve field names
+    fn apply_to<H: HashTable>(&self, entry: &mut Entry, hash_table: &H);
+}
+
+impl<H: HashTable> JMapInfo<H> {
+    /// Convert every entry to a user-defined type
+    ///
+    /// # Types
+    /// - `T` - The type to convert entries to, which must implement `FromEntry`
+    ///
+    /// # Errors
+    /// - `JMapError::InEntry` wrapping the conversion error of the first entry that fails
+    ///
+    /// # Returns
+    /// The converted rows in entry order
+    pub fn collect_rows<T: FromEntry>(&self) -> Result<Vec<T>> {
+        self.entries()
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                T::from_entry(entry, self.hash_table()).map_err(|source| JMapError::InEntry {
+                    index,
+                    source: Box::new(source),
+                })
+            })
+            .collect()
+    }
+
+    /// Append one entry per row, starting from the field defaults
+    ///
+    /// # Arguments
+    /// - `rows` - The rows to append
+    pub fn extend_from_rows(&mut self, rows: &[impl ToEntry]) {
+        for row in rows {
+            self.create_entry();
+            let index = self.len() - 1;
+            let (hash_table, entry) = self.hash_table_and_entry_mut(index);
+            row.apply_to(entry, hash_table);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{FieldType, FieldValue};
+    use crate::hash::{calc_hash, smg_hash_table};
+
+    #[derive(Debug, PartialEq)]
+    struct Scenario {
+        no: i32,
+        name: String,
+    }
+
+    impl FromEntry for Scenario {
+        fn from_entry<H: HashTable>(entry: &Entry, hash_table: &H) -> Result<Self> {
+            Ok(Self {
+                no: entry.require_int(hash_table, "ScenarioNo")?,
+                name: entry.require_string(hash_table, "ScenarioName")?.to_string(),
+            })
+        }
+    }
+
+    impl ToEntry for Scenario {
+        fn apply_to<H: HashTable>(&self, entry: &mut Entry, hash_table: &H) {
+            entry.set(hash_table, "ScenarioNo", FieldValue::Int(self.no));
+            entry.set(hash_table, "ScenarioName", FieldValue::from(self.name.as_str()));
+        }
+    }
+
+    #[test]
+    fn test_rows_round_trip() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("ScenarioName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+
+        let rows = vec![
+            Scenario { no: 1, name: "First".to_string() },
+            Scenario { no: 2, name: "Second".to_string() },
+        ];
+        jmap.extend_from_rows(&rows);
+        assert_eq!(jmap.collect_rows::<Scenario>().unwrap(), rows);
+    }
+
+    #[test]
+    fn test_missing_field_error() {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_entry();
+        jmap.create_entry();
+
+        let err = jmap.collect_rows::<Scenario>().unwrap_err();
+        assert_eq!(err.to_string(), "Entry 0: Field not found: ScenarioName");
+
+        jmap.create_field("ScenarioName", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.entries_mut()[1].set_by_hash(calc_hash("ScenarioName"), FieldValue::Int(3));
+        let err = jmap.collect_rows::<Scenario>().unwrap_err();
+        assert_eq!(err.to_string(), "Entry 1: Type mismatch for ScenarioName: expected String, got Int");
+        let err = read_optional::<i32>(Some(&FieldValue::from("x")), "[1176D409]").unwrap_err();
+        assert_eq!(err.to_string(), "Type mismatch for [1176D409]: expected Int, got String");
+    }
+}
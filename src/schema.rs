@@ -0,0 +1,105 @@
+//! Field definitions known ahead of time, independently of any file
+
+use crate::field::{Field, FieldType, FieldValue};
+use crate::hash::{parse_hash_name, HashTable};
+use crate::jmap::JMapInfo;
+
+/// A named field definition of a [`Schema`]
+#[derive(Debug, Clone)]
+pub struct SchemaField {
+    /// Field name, or `[XXXXXXXX]` for a field known only by its hash
+    pub name: String,
+    /// Data type of the field
+    pub field_type: FieldType,
+    /// Default value for new entries and empty cells
+    pub default: FieldValue,
+    /// Bitmask for the field value
+    pub mask: u32,
+    /// Bit shift amount
+    pub shift: u8,
+}
+
+impl SchemaField {
+    /// Create a field definition with the type's default value, mask and shift
+    pub fn new(name: impl Into<String>, field_type: FieldType) -> Self {
+        Self {
+            name: name.into(),
+            field_type,
+            default: FieldValue::default_for(field_type),
+            mask: field_type.default_mask(),
+            shift: 0,
+        }
+    }
+
+    /// Get the hash of the field name with the given hash table
+    pub fn hash<H: HashTable>(&self, hash_table: &H) -> u32 {
+        parse_hash_name(&self.name).unwrap_or_else(|| hash_table.calc(&self.name))
+    }
+
+    /// Create the field with the given hash
+    pub fn to_field(&self, hash: u32) -> Field {
+        let mut field = Field::with_default(hash, self.field_type, self.default.clone());
+        field.mask = self.mask;
+        field.shift = self.shift;
+        field
+    }
+}
+
+/// The field definitions expected in a table, in order
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    fields: Vec<SchemaField>,
+}
+
+impl Schema {
+    /// Create an empty schema
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a field with the type's default value, mask and shift
+    pub fn with_field(mut self, name: impl Into<String>, field_type: FieldType) -> Self {
+        self.fields.push(SchemaField::new(name, field_type));
+        self
+    }
+
+    /// Add a field definition
+    pub fn push(&mut self, field: SchemaField) {
+        self.fields.push(field);
+    }
+
+    /// Get the schema of an existing table, unknown names being written as `[XXXXXXXX]`
+    pub fn from_jmap<H: HashTable>(jmap: &JMapInfo<H>) -> Self {
+        let fields = jmap
+            .fields()
+            .map(|field| SchemaField {
+                name: jmap.field_name(field.hash),
+                field_type: field.field_type,
+                default: field.default.clone(),
+                mask: field.mask,
+                shift: field.shift,
+            })
+            .collect();
+        Self { fields }
+    }
+
+    /// Iterate over the field definitions in order
+    pub fn fields(&self) -> impl Iterator<Item = &SchemaField> {
+        self.fields.iter()
+    }
+
+    /// Get a field definition by name
+    pub fn get(&self, name: &str) -> Option<&SchemaField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Get the number of field definitions
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Check whether the schema has no field definitions
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
@@ -0,0 +1,163 @@
+//! Schemas of well-known Super Mario Galaxy tables, looked up by file name
+//!
+//! The bundled schemas cover `scenariodata`, `zonelist`, `objinfo` and `railinfo`. More can be added at
+//! runtime with [`register_schema`], for example from a file in the format of [`BUNDLED_SCHEMAS`]
+//! read with [`parse_schemas`]
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use crate::error::{JMapError, Result};
+use crate::field::FieldType;
+use crate::schema::Schema;
+
+/// The bundled schemas: a `[name]` line per table, then one `Name:Type` line per column
+pub const BUNDLED_SCHEMAS: &str = include_str!("../assets/schemas_SMG.txt");
+
+/// The known schemas by table name, starting with the bundled ones
+fn registry() -> &'static RwLock<HashMap<String, Schema>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, Schema>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let schemas = parse_schemas(BUNDLED_SCHEMAS).expect("the bundled schemas are valid");
+        RwLock::new(schemas.into_iter().collect())
+    })
+}
+
+/// Get the table name of a file name: its last path component, without extensions, in lowercase
+///
+/// For example `StageData/RedBlueExGalaxy/RedBlueExGalaxyScenario/ScenarioData.bcsv` gives `scenariodata`
+fn table_name(file_name: &str) -> String {
+    let base = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+    let stem = base.split('.').next().unwrap_or(base);
+    stem.to_lowercase()
+}
+
+/// Get the schema of a well-known table from its file name
+///
+/// The directories, the extensions and the case of the file name are ignored, so `ObjInfo`,
+/// `objinfo.bcsv` and `Placement/Scenario1/ObjInfo` find the same schema
+///
+/// # Returns
+/// A copy of the schema, or `None` if the table is unknown
+pub fn for_filename(name: &str) -> Option<Schema> {
+    let registry = registry().read().unwrap_or_else(|err| err.into_inner());
+    registry.get(&table_name(name)).cloned()
+}
+
+/// Add a schema for a table, replacing the one it may already have
+///
+/// # Arguments
+/// - `name` - The file name of the table, normalized like in [`for_filename`]
+/// - `schema` - The columns of the table
+pub fn register_schema(name: &str, schema: Schema) {
+    let mut registry = registry().write().unwrap_or_else(|err| err.into_inner());
+    registry.insert(table_name(name), schema);
+}
+
+/// Get the names of the tables with a known schema, sorted
+pub fn known_tables() -> Vec<String> {
+    let registry = registry().read().unwrap_or_else(|err| err.into_inner());
+    let mut names: Vec<String> = registry.keys().cloned().collect();
+    names.sort_unstable();
+    names
+}
+
+/// Parse schemas in the format of [`BUNDLED_SCHEMAS`]
+///
+/// Blank lines and lines starting with `#` are ignored
+///
+/// # Errors
+/// - `JMapError::InvalidField` with the line number of a column outside of a table, or with an unknown type
+///
+/// # Returns
+/// The table names, normalized like in [`for_filename`], with their schemas in file order
+pub fn parse_schemas(text: &str) -> Result<Vec<(String, Schema)>> {
+    let mut schemas: Vec<(String, Schema)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            schemas.push((table_name(name.trim()), Schema::new()));
+            continue;
+        }
+
+        let error = |reason: String| JMapError::InvalidField(format!("line {}: {}", index + 1, reason));
+        let Some((_, schema)) = schemas.last_mut() else {
+            return Err(error(format!("column {:?} is not in a table", line)));
+        };
+        let (name, type_name) = line
+            .split_once(':')
+            .ok_or_else(|| error(format!("expected Name:Type, got {:?}", line)))?;
+        let field_type =
+            FieldType::from_csv_name(type_name.trim()).ok_or_else(|| error(format!("unknown type {:?}", type_name)))?;
+        *schema = std::mem::take(schema).with_field(name.trim(), field_type);
+    }
+    Ok(schemas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::FieldValue;
+    use crate::hash::{smg_hash_table, smg_hash_table_with_lookup, HashTable};
+    use crate::io::{from_file, IoOptions};
+    use crate::validate::FieldTypeMismatch;
+
+    #[test]
+    fn test_bundled_schemas() {
+        let tables = known_tables();
+        for table in ["objinfo", "railinfo", "scenariodata", "zonelist"] {
+            assert!(tables.iter().any(|name| name == table), "{}", table);
+        }
+
+        // Every column has a name known to SMG
+        let names = smg_hash_table_with_lookup(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/strings_SMG.txt")).unwrap();
+        for (table, schema) in parse_schemas(BUNDLED_SCHEMAS).unwrap() {
+            assert!(!schema.is_empty());
+            for field in schema.fields() {
+                assert!(names.contains(field.hash(&names)), "{}: {}", table, field.name);
+            }
+        }
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/examples/scenariodata.bcsv");
+        let mut names = smg_hash_table();
+        for field in for_filename("ScenarioData.bcsv").unwrap().fields() {
+            names.add(&field.name);
+        }
+        let mut jmap = from_file(names, path, &IoOptions::default()).unwrap();
+        let schema = for_filename(path).unwrap();
+        assert!(jmap.validate_against(&schema).is_ok());
+
+        // A broken copy: a column dropped, one of another type and one unknown to the schema
+        jmap.drop_field("Comet").unwrap();
+        jmap.drop_field("AstroDome").unwrap();
+        jmap.create_field("AstroDome", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+        jmap.create_field("Extra", FieldType::Long, FieldValue::Int(0)).unwrap();
+        let report = jmap.validate_against(&schema);
+        assert!(!report.is_ok());
+        assert_eq!(report.missing, ["Comet"]);
+        assert_eq!(report.extra, ["Extra"]);
+        assert_eq!(
+            report.type_mismatches,
+            [FieldTypeMismatch {
+                field: "AstroDome".to_string(),
+                expected: FieldType::Long,
+                found: FieldType::Float,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_register_schema() {
+        assert!(for_filename("CustomTable.bcsv").is_none());
+        register_schema("CustomTable.bcsv", Schema::new().with_field("Id", FieldType::Short));
+        let schema = for_filename("Data/customtable").unwrap();
+        assert_eq!(schema.get("Id").unwrap().field_type, FieldType::Short);
+
+        let err = parse_schemas("[table]\nId:Bogus").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid field: line 2: unknown type \"Bogus\"");
+        assert!(parse_schemas("Id:Int").is_err());
+    }
+}
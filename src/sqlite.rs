@@ -0,0 +1,306 @@
+//! SQLite export/import, to query tables with SQL
+//!
+//! Each field becomes a column named after the field, or `h_XXXXXXXX` when its name is unknown,
+//! and each entry becomes a row. Column types follow the field types:
+//!
+//! | Field type                  | Column type |
+//! |-----------------------------|-------------|
+//! | Long, Short, Char           | INTEGER     |
+//! | UnsignedLong                | INTEGER     |
+//! | Float                       | REAL        |
+//! | String, StringOffset        | TEXT        |
+//!
+//! UnsignedLong values are written as their unsigned value, from 0 to 4294967295, even when held
+//! as a negative `FieldValue::Int` such as the `-1` sentinel. SQLite stores NaN as NULL, which reads
+//! back as the field default
+
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params_from_iter, Connection};
+
+use crate::error::{ConversionError, JMapError, Result};
+use crate::field::{FieldType, FieldValue};
+use crate::hash::{parse_hash_name, HashTable};
+use crate::jmap::JMapInfo;
+use crate::schema::Schema;
+
+/// Write a JMapInfo to a new SQLite table
+///
+/// The rows are inserted inside a single transaction
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to export
+/// - `conn` - The database to write to
+/// - `table_name` - The name of the table to create
+///
+/// # Errors
+/// - `JMapError::SqliteError` if the table already exists or cannot be written
+pub fn export<H: HashTable>(jmap: &JMapInfo<H>, conn: &Connection, table_name: &str) -> Result<()> {
+    let columns: Vec<String> = jmap
+        .fields()
+        .map(|field| {
+            let name = match jmap.hash_table().find_opt(field.hash) {
+                Some(name) => quote(&name),
+                None => quote(&hash_column(field.hash)),
+            };
+            format!("{} {}", name, column_type(field.field_type))
+        })
+        .collect();
+
+    let transaction = conn.unchecked_transaction()?;
+    transaction.execute(&format!("CREATE TABLE {} ({})", quote(table_name), columns.join(", ")), [])?;
+
+    if jmap.num_fields() > 0 {
+        let placeholders = vec!["?"; jmap.num_fields()].join(", ");
+        let mut statement =
+            transaction.prepare(&format!("INSERT INTO {} VALUES ({})", quote(table_name), placeholders))?;
+        for entry in jmap.entries() {
+            let values = jmap.fields().map(|field| match entry.get_by_hash(field.hash) {
+                Some(value) => to_sql(value, field.field_type),
+                None => Value::Null,
+            });
+            statement.execute(params_from_iter(values))?;
+        }
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Read a JMapInfo from a SQLite table, with field definitions from a schema
+///
+/// SQLite column types are too loose to tell the BCSV types apart, so they come from the schema.
+/// Each schema field is read from the column of the same name, `[XXXXXXXX]` names from the
+/// `h_XXXXXXXX` column written by [`export`]. Other columns are ignored and NULL reads as the field default
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups. Schema field names will be added to this hash table
+/// - `conn` - The database to read from
+/// - `table_name` - The name of the table to read
+/// - `schema` - The field definitions of the table
+///
+/// # Errors
+/// - `JMapError::SqliteError` if the table does not exist
+/// - `JMapError::FieldNotFound` if the table has no column for a schema field
+/// - `JMapError::TypeMismatch` if a value has a SQLite type that does not fit its field
+/// - `JMapError::Conversion` if an integer is out of range for its field
+///
+/// # Returns
+/// A JMapInfo with the fields of the schema and one entry per row, in the order SQLite returns them
+pub fn import<H: HashTable>(
+    hash_table: H,
+    conn: &Connection,
+    table_name: &str,
+    schema: &Schema,
+) -> Result<JMapInfo<H>> {
+    let mut jmap = JMapInfo::new(hash_table);
+    let mut columns = Vec::with_capacity(schema.len());
+    let mut fields = Vec::with_capacity(schema.len());
+    for schema_field in schema.fields() {
+        let (hash, column) = match parse_hash_name(&schema_field.name) {
+            Some(hash) => (hash, hash_column(hash)),
+            None => (jmap.hash_table_mut().try_add(&schema_field.name)?, schema_field.name.clone()),
+        };
+        let field = schema_field.to_field(hash);
+        field.validate()?;
+        columns.push(column);
+        fields.push((schema_field.name.as_str(), hash, field.field_type, field.default.clone()));
+        jmap.fields_map_mut().insert(hash, field);
+    }
+    if columns.is_empty() {
+        return Ok(jmap);
+    }
+
+    // Columns are looked up by name rather than selected, SQLite reading an unknown quoted column as a string
+    let mut statement = conn.prepare(&format!("SELECT * FROM {}", quote(table_name)))?;
+    let names = statement.column_names();
+    let indices = columns
+        .iter()
+        .map(|column| {
+            names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(column))
+                .ok_or_else(|| JMapError::FieldNotFound(format!("column {} of table {}", column, table_name)))
+        })
+        .collect::<Result<Vec<usize>>>()?;
+
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let mut entry = jmap.new_entry();
+        for (&index, (name, hash, field_type, default)) in indices.iter().zip(&fields) {
+            let value = match row.get_ref(index)? {
+                ValueRef::Null => default.clone(),
+                value => from_sql(value, *field_type, name)?,
+            };
+            entry.set_by_hash(*hash, value);
+        }
+        jmap.entries_vec_mut().push(entry);
+    }
+
+    Ok(jmap)
+}
+
+/// Name the column of a field whose name is unknown, like `h_DEADBEEF`
+fn hash_column(hash: u32) -> String {
+    format!("h_{:08X}", hash)
+}
+
+/// Quote an SQL identifier
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+fn column_type(field_type: FieldType) -> &'static str {
+    match field_type {
+        FieldType::Long | FieldType::UnsignedLong | FieldType::Short | FieldType::Char => "INTEGER",
+        FieldType::Float => "REAL",
+        FieldType::String | FieldType::StringOffset => "TEXT",
+    }
+}
+
+fn to_sql(value: &FieldValue, field_type: FieldType) -> Value {
+    match value {
+        FieldValue::Int(v) if field_type == FieldType::UnsignedLong => Value::Integer(*v as u32 as i64),
+        FieldValue::Int(v) => Value::Integer(*v as i64),
+        FieldValue::UInt(v) => Value::Integer(*v as i64),
+        FieldValue::Float(v) => Value::Real(*v as f64),
+        FieldValue::String(s) => Value::Text(s.clone()),
+    }
+}
+
+/// Convert a non-NULL SQLite value to a value of the given field type
+fn from_sql(value: ValueRef<'_>, field_type: FieldType, name: &str) -> Result<FieldValue> {
+    let out_of_range = |v: i64, to: &'static str| ConversionError { from: "INTEGER", to, value: v.to_string() };
+    let value = match (field_type, value) {
+        (FieldType::Long, ValueRef::Integer(v)) => {
+            FieldValue::Int(i32::try_from(v).map_err(|_| out_of_range(v, "i32"))?)
+        }
+        (FieldType::Short, ValueRef::Integer(v)) => {
+            FieldValue::Int(i16::try_from(v).map_err(|_| out_of_range(v, "i16"))? as i32)
+        }
+        (FieldType::Char, ValueRef::Integer(v)) => {
+            FieldValue::Int(i8::try_from(v).map_err(|_| out_of_range(v, "i8"))? as i32)
+        }
+        // Negative values are accepted as the bits of an i32, like the -1 sentinel
+        (FieldType::UnsignedLong, ValueRef::Integer(v)) => match u32::try_from(v) {
+            Ok(v) => FieldValue::UInt(v),
+            Err(_) => FieldValue::UInt(i32::try_from(v).map_err(|_| out_of_range(v, "u32"))? as u32),
+        },
+        (FieldType::Float, ValueRef::Real(v)) => FieldValue::Float(v as f32),
+        (FieldType::Float, ValueRef::Integer(v)) => FieldValue::Float(v as f32),
+        (FieldType::String | FieldType::StringOffset, ValueRef::Text(text)) => {
+            FieldValue::String(String::from_utf8_lossy(text).into_owned())
+        }
+        (_, value) => {
+            return Err(JMapError::TypeMismatch {
+                field: name.to_string(),
+                expected: field_type.csv_name(),
+                got: sql_type_name(value),
+            });
+        }
+    };
+    Ok(value)
+}
+
+fn sql_type_name(value: ValueRef<'_>) -> &'static str {
+    match value {
+        ValueRef::Null => "NULL",
+        ValueRef::Integer(_) => "INTEGER",
+        ValueRef::Real(_) => "REAL",
+        ValueRef::Text(_) => "TEXT",
+        ValueRef::Blob(_) => "BLOB",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Field;
+    use crate::hash::{calc_hash, smg_hash_table, FileHashTable, HashAlgorithm};
+
+    fn sample() -> JMapInfo<FileHashTable> {
+        let mut jmap = JMapInfo::new(FileHashTable::new(HashAlgorithm::SMG));
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(1.0)).unwrap();
+        jmap.create_field("Switch", FieldType::UnsignedLong, FieldValue::UInt(0)).unwrap();
+        jmap.create_field("Priority", FieldType::Short, FieldValue::Int(0)).unwrap();
+        jmap.fields_map_mut().insert(0xDEADBEEF, Field::new(0xDEADBEEF, FieldType::Char));
+
+        for (no, zone, scale, switch) in [(1, "RedBlueExGalaxy", 0.5, -1), (2, "HeavensDoorGalaxy", 2.0, 7)] {
+            let entry = jmap.create_entry();
+            entry.set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(no));
+            entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String(zone.to_string()));
+            entry.set_by_hash(calc_hash("Scale"), FieldValue::Float(scale));
+            entry.set_by_hash(calc_hash("Switch"), FieldValue::Int(switch));
+            entry.set_by_hash(calc_hash("Priority"), FieldValue::Int(-3));
+            entry.set_by_hash(0xDEADBEEF, FieldValue::Int(no * 10));
+        }
+        jmap
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let jmap = sample();
+        let conn = Connection::open_in_memory().unwrap();
+        export(&jmap, &conn, "scenario").unwrap();
+
+        let zone: String =
+            conn.query_row("SELECT ZoneName FROM scenario WHERE ScenarioNo = 2", [], |row| row.get(0)).unwrap();
+        assert_eq!(zone, "HeavensDoorGalaxy");
+        let switch: i64 =
+            conn.query_row("SELECT Switch FROM scenario WHERE ScenarioNo = 1", [], |row| row.get(0)).unwrap();
+        assert_eq!(switch, 4294967295);
+        let unknown: i64 = conn.query_row("SELECT SUM(h_DEADBEEF) FROM scenario", [], |row| row.get(0)).unwrap();
+        assert_eq!(unknown, 30);
+
+        let schema = Schema::from_jmap(&jmap);
+        let read = import(smg_hash_table(), &conn, "scenario", &schema).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(
+            read.fields().map(|field| field.hash).collect::<Vec<_>>(),
+            jmap.field_hashes().copied().collect::<Vec<_>>()
+        );
+        for (read_entry, entry) in read.entries().iter().zip(jmap.entries()) {
+            for field in jmap.fields() {
+                let expected = match entry.get_by_hash(field.hash).unwrap() {
+                    FieldValue::Int(v) if field.field_type == FieldType::UnsignedLong => FieldValue::UInt(*v as u32),
+                    value => value.clone(),
+                };
+                assert_eq!(read_entry.get_by_hash(field.hash), Some(&expected));
+            }
+        }
+
+        // A second export to the same table fails instead of appending
+        assert!(matches!(export(&jmap, &conn, "scenario"), Err(JMapError::SqliteError(_))));
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE t (Id INTEGER, Name TEXT, Small INTEGER);
+             INSERT INTO t VALUES (1, 'a', 300), (NULL, 'b', NULL);",
+        )
+        .unwrap();
+
+        let schema = Schema::new().with_field("Id", FieldType::Long).with_field("Name", FieldType::Long);
+        let err = import(smg_hash_table(), &conn, "t", &schema).unwrap_err();
+        assert_eq!(err.to_string(), "Type mismatch for Name: expected Int, got TEXT");
+
+        let schema = Schema::new().with_field("Small", FieldType::Char);
+        assert!(matches!(import(smg_hash_table(), &conn, "t", &schema), Err(JMapError::Conversion(_))));
+
+        let schema = Schema::new().with_field("Missing", FieldType::Long);
+        let err = import(smg_hash_table(), &conn, "t", &schema).unwrap_err();
+        assert_eq!(err.to_string(), "Field not found: column Missing of table t");
+        assert!(matches!(import(smg_hash_table(), &conn, "missing", &schema), Err(JMapError::SqliteError(_))));
+
+        // NULL reads as the default
+        conn.execute("DELETE FROM t WHERE Id = 1", []).unwrap();
+        let schema = Schema::new().with_field("Id", FieldType::Long).with_field("Small", FieldType::Short);
+        let read = import(smg_hash_table(), &conn, "t", &schema).unwrap();
+        assert_eq!(read.len(), 1);
+        assert_eq!(read.get_entry(0).unwrap().get_by_hash(calc_hash("Id")), Some(&FieldValue::Int(0)));
+        assert_eq!(read.get_entry(0).unwrap().get_by_hash(calc_hash("Small")), Some(&FieldValue::Int(0)));
+    }
+}
@@ -0,0 +1,338 @@
+//! How strictly malformed data is handled when reading, and the report of what was tolerated
+//!
+//! Problems are sorted into a few [`Category`] values, and a [`Strictness`] gives the [`Level`] of each:
+//!
+//! | Category | BCSV | CSV |
+//! |----------|------|-----|
+//! | `Encoding` | strings that are not valid in the chosen encoding are decoded with replacement characters | - |
+//! | `Values` | string offsets past the end of the buffer read as empty strings | cells that cannot be parsed get the column default |
+//! | `Layout` | fields extending past the entry, or shifted by as many bits as their type has, are dropped, numeric fields whose mask keeps no bits read as 0, fields writing the same bits are kept | - |
+//! | `Structure` | unknown field types are dropped, entries cut off by the end of the buffer are dropped, a field defined twice follows `IoOptions::duplicate_fields` | missing cells are padded with the column default and extra cells are dropped, a field defined twice keeps its last column |
+//!
+//! With `Level::Error` the problem fails the read, otherwise the data is recovered as described above and
+//! `Level::Warn` records a [`Warning`] in the [`Report`] returned by the `_with_report` functions
+
+use std::fmt;
+
+use crate::error::{JMapError, Result};
+
+/// What to do with a problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Level {
+    /// Fail with an error
+    #[default]
+    Error,
+    /// Recover and record a warning
+    Warn,
+    /// Recover silently
+    Ignore,
+}
+
+/// Kinds of problems found in malformed data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Strings that cannot be decoded
+    Encoding,
+    /// Values that are out of range or cannot be parsed
+    Values,
+    /// Fields that do not fit the entry layout
+    Layout,
+    /// Malformed headers, field tables and rows
+    Structure,
+}
+
+/// The level of each category of problems, see the [module documentation](self)
+///
+/// The default is [`Strictness::strict`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Strictness {
+    /// Strings that cannot be decoded
+    pub encoding: Level,
+    /// Values that are out of range or cannot be parsed
+    pub values: Level,
+    /// Fields that do not fit the entry layout
+    pub layout: Level,
+    /// Malformed headers, field tables and rows
+    pub structure: Level,
+}
+
+impl Strictness {
+    /// Fail on every problem
+    pub fn strict() -> Self {
+        Self::all(Level::Error)
+    }
+
+    /// Recover from every problem, recording a warning for each
+    pub fn permissive() -> Self {
+        Self::all(Level::Warn)
+    }
+
+    /// Use the same level for every category
+    pub fn all(level: Level) -> Self {
+        Self {
+            encoding: level,
+            values: level,
+            layout: level,
+            structure: level,
+        }
+    }
+
+    /// Get the level of a category
+    pub fn level(&self, category: Category) -> Level {
+        match category {
+            Category::Encoding => self.encoding,
+            Category::Values => self.values,
+            Category::Layout => self.layout,
+            Category::Structure => self.structure,
+        }
+    }
+
+    /// Handle a problem according to the level of its category
+    ///
+    /// # Arguments
+    /// - `report` - The report the warning is added to with `Level::Warn`
+    /// - `warning` - The problem, whose category gives the level
+    /// - `error` - The error returned with `Level::Error`
+    ///
+    /// # Errors
+    /// The result of `error` if the category is set to `Level::Error`
+    pub(crate) fn check(&self, report: &mut Report, warning: Warning, error: impl FnOnce() -> JMapError) -> Result<()> {
+        match self.level(warning.category) {
+            Level::Error => Err(error()),
+            Level::Warn => {
+                warn!(
+                    category = ?warning.category,
+                    row = warning.row,
+                    column = warning.column,
+                    offset = warning.offset,
+                    entry = warning.entry,
+                    field = warning.field.as_deref(),
+                    "{}",
+                    warning.message
+                );
+                report.warnings.push(warning);
+                Ok(())
+            }
+            Level::Ignore => Ok(()),
+        }
+    }
+}
+
+/// A problem that did not stop reading
+///
+/// CSV problems are located by `row` and `column`, BCSV problems by `offset`, `entry` and `field`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// Kind of problem
+    pub category: Category,
+    /// 1-based CSV record number, the header being record 1
+    pub row: Option<usize>,
+    /// 1-based CSV column number
+    pub column: Option<usize>,
+    /// Offset in the BCSV data
+    pub offset: Option<usize>,
+    /// Index of the BCSV entry
+    pub entry: Option<usize>,
+    /// Name of the BCSV field, or its `[XXXXXXXX]` form if the name is unknown
+    pub field: Option<String>,
+    /// What went wrong and how it was handled
+    pub message: String,
+}
+
+impl Warning {
+    /// Create a warning about a CSV record or column
+    pub(crate) fn csv(category: Category, row: Option<usize>, column: Option<usize>, message: String) -> Self {
+        Self {
+            category,
+            row,
+            column,
+            offset: None,
+            entry: None,
+            field: None,
+            message,
+        }
+    }
+
+    /// Create a warning about BCSV data
+    pub(crate) fn bcsv(category: Category, offset: usize, entry: Option<usize>, field: Option<String>, message: String) -> Self {
+        Self {
+            category,
+            row: None,
+            column: None,
+            offset: Some(offset),
+            entry,
+            field,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut location = Vec::new();
+        if let Some(row) = self.row {
+            location.push(format!("row {}", row));
+        }
+        if let Some(column) = self.column {
+            location.push(format!("column {}", column));
+        }
+        if let Some(offset) = self.offset {
+            location.push(format!("offset 0x{:X}", offset));
+        }
+        if let Some(entry) = self.entry {
+            location.push(format!("entry {}", entry));
+        }
+        if let Some(field) = &self.field {
+            location.push(format!("field {}", field));
+        }
+
+        if location.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", location.join(", "), self.message)
+        }
+    }
+}
+
+/// Everything tolerated while reading, see the `_with_report` functions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Problems that did not stop reading
+    pub warnings: Vec<Warning>,
+}
+
+impl Report {
+    /// Check whether nothing was recorded
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Iterate over the warnings of a category
+    pub fn of(&self, category: Category) -> impl Iterator<Item = &Warning> {
+        self.warnings.iter().filter(move |warning| warning.category == category)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, ByteOrder};
+
+    use super::*;
+    use crate::csv::{from_csv_reader_with_report, CsvOptions};
+    use crate::field::{FieldType, FieldValue};
+    use crate::hash::smg_hash_table;
+    use crate::io::{from_buffer_with_report, read_header, to_buffer, Encoding, IoOptions};
+    use crate::jmap::JMapInfo;
+
+    /// A two-entry table with a StringOffset `Name` and a Long `Id`, and the offset of the definition of each field
+    fn sample() -> (Vec<u8>, usize, usize) {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("Name", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        jmap.create_field("Id", FieldType::Long, FieldValue::Int(0)).unwrap();
+        for (name, id) in [("abc", 1), ("def", 2)] {
+            jmap.entry_builder().set("Name", name).set("Id", id).insert().unwrap();
+        }
+        let data = to_buffer(&jmap, &options(Strictness::strict())).unwrap();
+        let definition = |name: &str| {
+            let hash = jmap.get_field(name).unwrap().hash;
+            (0..2).map(|i| 0x10 + i * 0x0C).find(|&off| BigEndian::read_u32(&data[off..]) == hash).unwrap()
+        };
+        let (name, id) = (definition("Name"), definition("Id"));
+        (data, name, id)
+    }
+
+    fn options(strictness: Strictness) -> IoOptions {
+        IoOptions {
+            encoding: Encoding::Utf8,
+            strictness,
+            ..IoOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_levels() {
+        let (data, name, id) = sample();
+        let header = read_header(&data, &IoOptions::default()).unwrap();
+        let name_offset = header.off_data as usize + BigEndian::read_u16(&data[name + 8..]) as usize;
+
+        let mut bad_string = data.clone();
+        bad_string[header.off_strings() as usize] = 0xFF;
+        let mut bad_offset = data.clone();
+        BigEndian::write_u32(&mut bad_offset[name_offset + header.entry_size as usize..], 0xFFFF);
+        let mut bad_layout = data.clone();
+        BigEndian::write_u16(&mut bad_layout[id + 8..], header.entry_size as u16);
+        let mut bad_type = data.clone();
+        bad_type[name + 0x0B] = 0x7F;
+        let mut truncated = data.clone();
+        truncated.truncate(header.off_data as usize + header.entry_size as usize + 2);
+
+        let bcsv = [
+            (Category::Encoding, &bad_string),
+            (Category::Values, &bad_offset),
+            (Category::Layout, &bad_layout),
+            (Category::Structure, &bad_type),
+        ];
+        for level in [Level::Error, Level::Warn, Level::Ignore] {
+            for (category, data) in bcsv {
+                let mut strictness = Strictness::strict();
+                match category {
+                    Category::Encoding => strictness.encoding = level,
+                    Category::Values => strictness.values = level,
+                    Category::Layout => strictness.layout = level,
+                    Category::Structure => strictness.structure = level,
+                }
+                assert_eq!(strictness.level(category), level);
+
+                let result = from_buffer_with_report(smg_hash_table(), data, &options(strictness));
+                match level {
+                    Level::Error => assert!(result.is_err(), "{:?}", category),
+                    Level::Warn => {
+                        let (_, report) = result.unwrap();
+                        assert_eq!(report.warnings.len(), 1, "{:?}", category);
+                        assert_eq!(report.of(category).count(), 1);
+                    }
+                    Level::Ignore => assert!(result.unwrap().1.is_empty(), "{:?}", category),
+                }
+            }
+        }
+
+        let (jmap, report) = from_buffer_with_report(smg_hash_table(), &bad_string, &options(Strictness::permissive())).unwrap();
+        assert_eq!(jmap.get_value(0, "Name").unwrap(), &FieldValue::from("\u{FFFD}bc"));
+        assert_eq!(report.warnings[0].entry, Some(0));
+        let (jmap, _) = from_buffer_with_report(smg_hash_table(), &bad_layout, &options(Strictness::permissive())).unwrap();
+        assert_eq!(jmap.num_fields(), 1);
+
+        // Only the first entry fits, and its string is past the end too
+        assert!(from_buffer_with_report(smg_hash_table(), &truncated, &options(Strictness::strict())).is_err());
+        let (jmap, report) = from_buffer_with_report(smg_hash_table(), &truncated, &options(Strictness::permissive())).unwrap();
+        assert_eq!(jmap.len(), 1);
+        let categories: Vec<_> = report.warnings.iter().map(|warning| warning.category).collect();
+        assert_eq!(categories, [Category::Structure, Category::Values]);
+    }
+
+    #[test]
+    fn test_csv_levels() {
+        let cases = [
+            (Category::Values, "ScenarioNo:Int:-1\nx\n"),
+            (Category::Structure, "ScenarioNo:Int:-1\n1,2\n"),
+        ];
+        for level in [Level::Error, Level::Warn, Level::Ignore] {
+            for (category, text) in cases {
+                let strictness = match category {
+                    Category::Values => Strictness { values: level, ..Strictness::strict() },
+                    _ => Strictness { structure: level, ..Strictness::strict() },
+                };
+                let options = CsvOptions {
+                    strictness,
+                    ..CsvOptions::default()
+                };
+                let result = from_csv_reader_with_report(smg_hash_table(), text.as_bytes(), &options);
+                match level {
+                    Level::Error => assert!(result.is_err()),
+                    Level::Warn => assert_eq!(result.unwrap().1.of(category).count(), 1),
+                    Level::Ignore => assert!(result.unwrap().1.is_empty()),
+                }
+            }
+        }
+    }
+}
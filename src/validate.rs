@@ -0,0 +1,461 @@
+//! Checks that a table can be written without losing data
+
+use std::fmt;
+
+use crate::field::{Field, FieldType, FieldValue};
+use crate::hash::HashTable;
+use crate::io::{decode_string, encode_field_string, IoOptions};
+use crate::jmap::JMapInfo;
+use crate::schema::Schema;
+
+/// What is wrong, see [`ValidationIssue`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    /// The mask or shift of the field is inconsistent, see [`Field::validate`]
+    InvalidField(String),
+    /// The name of the field is unknown to the hash table
+    UnresolvedHash,
+    /// The entry has no value for the field
+    MissingValue,
+    /// The value does not have the type of the field
+    TypeMismatch {
+        expected: &'static str,
+        got: &'static str,
+    },
+    /// The value does not fit in the bits of the field, so it would be written truncated
+    OutOfRange(FieldValue),
+    /// The encoded inline string is longer than the field, so it would be written truncated
+    StringTooLong { len: usize, max: usize },
+    /// The string has characters the encoding cannot represent
+    Unencodable(String),
+}
+
+/// A problem found by [`JMapInfo::validate`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    /// Index of the entry, `None` for problems of the field itself
+    pub entry: Option<usize>,
+    /// Hash of the field
+    pub hash: u32,
+    /// Name of the field, or `[XXXXXXXX]` if unknown
+    pub field: String,
+    /// What is wrong
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.entry {
+            Some(entry) => write!(f, "entry {}, {}: ", entry, self.field)?,
+            None => write!(f, "field {}: ", self.field)?,
+        }
+        match &self.kind {
+            IssueKind::InvalidField(reason) => write!(f, "{}", reason),
+            IssueKind::UnresolvedHash => write!(f, "name not in the hash table"),
+            IssueKind::MissingValue => write!(f, "no value"),
+            IssueKind::TypeMismatch { expected, got } => write!(f, "expected {}, got {}", expected, got),
+            IssueKind::OutOfRange(value) => write!(f, "{} does not fit in the field", value),
+            IssueKind::StringTooLong { len, max } => {
+                write!(f, "string of {} bytes is longer than {} bytes", len, max)
+            }
+            IssueKind::Unencodable(value) => write!(f, "{:?} cannot be encoded", value),
+        }
+    }
+}
+
+/// What would happen to a string when written, see [`StringIssue`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StringIssueKind {
+    /// The encoded inline string is longer than the field and would be cut
+    Truncated {
+        /// Length of the encoded string in bytes
+        len: usize,
+        /// Size of the field in bytes
+        max: usize,
+        /// Whether the cut splits a multibyte character, whose remaining bytes would read as garbage
+        split_char: bool,
+    },
+    /// The string has characters the encoding cannot represent, which would be written as `&#NNNN;`
+    Unmappable,
+}
+
+/// A string that would not be written as is, found by [`JMapInfo::audit_strings`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StringIssue {
+    /// Index of the entry
+    pub entry: usize,
+    /// Hash of the field
+    pub hash: u32,
+    /// Name of the field, or `[XXXXXXXX]` if unknown
+    pub field: String,
+    /// The string in the table
+    pub value: String,
+    /// The string that would be read back after writing
+    pub written: String,
+    /// What would happen to the string
+    pub kind: StringIssueKind,
+}
+
+impl fmt::Display for StringIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "entry {}, {}: ", self.entry, self.field)?;
+        match self.kind {
+            StringIssueKind::Truncated { len, max, split_char } => {
+                write!(f, "string of {} bytes is cut to {} bytes", len, max)?;
+                if split_char {
+                    write!(f, " in the middle of a character")?;
+                }
+            }
+            StringIssueKind::Unmappable => write!(f, "string has characters the encoding cannot represent")?,
+        }
+        write!(f, ", {:?} would be written as {:?}", self.value, self.written)
+    }
+}
+
+/// How a table differs from a [`Schema`], see [`JMapInfo::validate_against`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Schema fields the table does not have, in schema order
+    pub missing: Vec<String>,
+    /// Table fields the schema does not have, in field order
+    pub extra: Vec<String>,
+    /// Fields whose type differs from the schema, in schema order
+    pub type_mismatches: Vec<FieldTypeMismatch>,
+}
+
+/// A field whose type differs from its schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldTypeMismatch {
+    /// Name of the field
+    pub field: String,
+    /// The type in the schema
+    pub expected: FieldType,
+    /// The type in the table
+    pub found: FieldType,
+}
+
+impl ValidationReport {
+    /// Check whether the table matches the schema
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.type_mismatches.is_empty()
+    }
+}
+
+impl<H: HashTable> JMapInfo<H> {
+    /// Compare the fields of this JMapInfo with the ones expected by a schema
+    ///
+    /// Fields are matched by hash, schema names being hashed with the table's hash table. The order of the
+    /// fields, their masks and their defaults are not compared
+    ///
+    /// # Returns
+    /// The missing, extra and mistyped fields
+    pub fn validate_against(&self, schema: &Schema) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let mut expected = Vec::with_capacity(schema.len());
+        for schema_field in schema.fields() {
+            let hash = schema_field.hash(self.hash_table());
+            expected.push(hash);
+            match self.get_field_by_hash(hash) {
+                None => report.missing.push(schema_field.name.clone()),
+                Some(field) if field.field_type != schema_field.field_type => report.type_mismatches.push(FieldTypeMismatch {
+                    field: schema_field.name.clone(),
+                    expected: schema_field.field_type,
+                    found: field.field_type,
+                }),
+                Some(_) => {}
+            }
+        }
+        report.extra = self
+            .field_hashes()
+            .filter(|hash| !expected.contains(hash))
+            .map(|&hash| self.field_name(hash))
+            .collect();
+        report
+    }
+
+    /// Find the values that would be lost or changed by writing this JMapInfo
+    ///
+    /// Fields are checked with [`Field::validate`] and for unresolved names, then every value of every entry
+    /// is checked against its field's type, mask and shift. Inline strings are measured in the encoding of their field
+    /// against `options.inline_string_size`
+    ///
+    /// # Arguments
+    /// - `options` - The options the table would be written with
+    ///
+    /// # Returns
+    /// The issues in field order, then in entry order, empty if the table is fine
+    pub fn validate(&self, options: &IoOptions) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let issue = |entry, field: &Field, kind| ValidationIssue {
+            entry,
+            hash: field.hash,
+            field: self.field_name(field.hash),
+            kind,
+        };
+
+        for field in self.fields() {
+            if let Err(err) = field.validate() {
+                issues.push(issue(None, field, IssueKind::InvalidField(err.to_string())));
+            }
+            if self.hash_table().find_opt(field.hash).is_none() {
+                issues.push(issue(None, field, IssueKind::UnresolvedHash));
+            }
+        }
+
+        for (index, entry) in self.entries().iter().enumerate() {
+            for field in self.fields() {
+                let kind = match entry.get_by_hash(field.hash) {
+                    None => Some(IssueKind::MissingValue),
+                    Some(value) => value_issue(field, value, options),
+                };
+                if let Some(kind) = kind {
+                    issues.push(issue(Some(index), field, kind));
+                }
+            }
+        }
+
+        issues
+    }
+}
+
+impl<H: HashTable> JMapInfo<H> {
+    /// Find the strings of `String` and `StringOffset` fields that would not be written as they are
+    ///
+    /// The strings are encoded with the same code as the writer, so a table whose audit is empty is written
+    /// without changing any string
+    ///
+    /// # Arguments
+    /// - `options` - The options the table would be written with, for the encodings and the inline string size
+    ///
+    /// # Returns
+    /// The issues in entry order, then in field order, with an issue for each problem of a string
+    pub fn audit_strings(&self, options: &IoOptions) -> Vec<StringIssue> {
+        let mut issues = Vec::new();
+        self.for_each_string(|entry, hash, s| {
+            let Some(field) = self.get_field_by_hash(hash) else {
+                return;
+            };
+            let encoded = encode_field_string(s, field, options);
+            let mut kinds = Vec::new();
+            if encoded.unmappable {
+                kinds.push(StringIssueKind::Unmappable);
+            }
+            if encoded.truncated() {
+                kinds.push(StringIssueKind::Truncated {
+                    len: encoded.full_len,
+                    max: options.inline_string_size,
+                    split_char: encoded.split_char,
+                });
+            }
+            if kinds.is_empty() {
+                return;
+            }
+
+            let written = decode_string(&encoded.bytes, options.encoding_for(hash), true).unwrap_or_default();
+            for kind in kinds {
+                issues.push(StringIssue {
+                    entry,
+                    hash,
+                    field: self.field_name(hash),
+                    value: s.to_string(),
+                    written: written.clone(),
+                    kind,
+                });
+            }
+        });
+        issues
+    }
+}
+
+fn value_issue(field: &Field, value: &FieldValue, options: &IoOptions) -> Option<IssueKind> {
+    if !value.is_compatible_with(field.field_type) {
+        return Some(IssueKind::TypeMismatch {
+            expected: field.field_type.csv_name(),
+            got: value.type_name(),
+        });
+    }
+
+    match value {
+        FieldValue::Int(_) | FieldValue::UInt(_) => (!fits(field, value)).then(|| IssueKind::OutOfRange(value.clone())),
+        FieldValue::String(s) => {
+            let encoded = encode_field_string(s, field, options);
+            if encoded.unmappable {
+                Some(IssueKind::Unencodable(s.clone()))
+            } else if encoded.truncated() {
+                Some(IssueKind::StringTooLong {
+                    len: encoded.full_len,
+                    max: options.inline_string_size,
+                })
+            } else {
+                None
+            }
+        }
+        FieldValue::Float(_) => None,
+    }
+}
+
+/// Whether an integer reads back unchanged once packed with the field's mask and shift
+fn fits(field: &Field, value: &FieldValue) -> bool {
+    let raw = match value {
+        FieldValue::Int(v) => *v as u32,
+        FieldValue::UInt(v) => *v,
+        _ => return true,
+    };
+    let canonical = field.mask == field.field_type.default_mask() && field.shift == 0;
+
+    match (field.field_type, value) {
+        // Full-width values are read back sign-extended
+        (FieldType::Short, FieldValue::Int(v)) if canonical => i16::try_from(*v).is_ok(),
+        (FieldType::Char, FieldValue::Int(v)) if canonical => i8::try_from(*v).is_ok(),
+        _ => match raw.checked_shl(field.shift as u32) {
+            Some(shifted) => (shifted & field.mask) >> field.shift == raw,
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::{calc_hash, FileHashTable, HashAlgorithm};
+    use crate::io::Encoding;
+
+    #[test]
+    fn test_validate() {
+        let mut ht = FileHashTable::new(HashAlgorithm::SMG);
+        for name in ["Flag", "Packed", "Label", "Name"] {
+            ht.add(name);
+        }
+        let mut jmap = JMapInfo::new(ht);
+        jmap.create_field("Flag", FieldType::Char, FieldValue::Int(0)).unwrap();
+        jmap.add_field("Packed", Field::with_mask_shift(0, FieldType::Long, 0x0000FF00, 8)).unwrap();
+        jmap.create_field("Label", FieldType::String, FieldValue::String(String::new())).unwrap();
+        jmap.create_field("Name", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.fields_map_mut().insert(0x1234ABCD, Field::new(0x1234ABCD, FieldType::Float));
+
+        jmap.create_entry();
+        let entry = jmap.create_entry();
+        entry.set_by_hash(calc_hash("Flag"), FieldValue::Int(200));
+        entry.set_by_hash(calc_hash("Packed"), FieldValue::Int(0x100));
+        entry.set_by_hash(calc_hash("Label"), FieldValue::String("x".repeat(33)));
+        entry.set_by_hash(calc_hash("Name"), FieldValue::String("🍄".to_string()));
+        entry.set_by_hash(0x1234ABCD, FieldValue::Int(1));
+
+        let issues = jmap.validate(&IoOptions::default());
+        let described: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(
+            described,
+            [
+                "field [1234ABCD]: name not in the hash table",
+                "entry 1, Flag: 200 does not fit in the field",
+                "entry 1, Packed: 256 does not fit in the field",
+                "entry 1, Label: string of 33 bytes is longer than 32 bytes",
+                "entry 1, Name: \"🍄\" cannot be encoded",
+                "entry 1, [1234ABCD]: expected Float, got Int",
+            ]
+        );
+        let utf8 = IoOptions {
+            encoding: Encoding::Utf8,
+            ..IoOptions::default()
+        };
+        assert_eq!(jmap.validate(&utf8).len(), 5);
+    }
+
+    #[test]
+    fn test_validate_against() {
+        let mut jmap = JMapInfo::new(FileHashTable::new(HashAlgorithm::SMG));
+        jmap.create_field("Id", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Scale", FieldType::Short, FieldValue::Int(0)).unwrap();
+        jmap.create_field("Extra", FieldType::Char, FieldValue::Int(0)).unwrap();
+
+        let schema = Schema::new()
+            .with_field("Id", FieldType::Long)
+            .with_field("Scale", FieldType::Float)
+            .with_field("Name", FieldType::StringOffset);
+        let report = jmap.validate_against(&schema);
+        assert_eq!(report.missing, ["Name"]);
+        assert_eq!(report.extra, ["Extra"]);
+        assert_eq!(report.type_mismatches.len(), 1);
+        assert_eq!((report.type_mismatches[0].expected, report.type_mismatches[0].found), (FieldType::Float, FieldType::Short));
+        assert!(jmap.validate_against(&Schema::from_jmap(&jmap)).is_ok());
+    }
+
+    #[test]
+    fn test_audit_strings() {
+        use crate::io::{from_buffer, to_buffer};
+        use crate::strictness::Strictness;
+
+        let mut ht = FileHashTable::new(HashAlgorithm::SMG);
+        for name in ["Label", "Name"] {
+            ht.add(name);
+        }
+        let mut jmap = JMapInfo::new(ht.clone());
+        jmap.create_field("Label", FieldType::String, FieldValue::from("")).unwrap();
+        jmap.create_field("Name", FieldType::StringOffset, FieldValue::from("")).unwrap();
+        // 1 + 16 * 2 bytes in Shift-JIS, cut in the middle of the last character
+        let label = format!("a{}", "ス".repeat(16));
+        jmap.entry_builder()
+            .set("Label", FieldValue::from("キノピオ"))
+            .set("Name", FieldValue::from("Kinopio"))
+            .insert()
+            .unwrap();
+        jmap.entry_builder()
+            .set("Label", FieldValue::from(label.as_str()))
+            .set("Name", FieldValue::from("🍄 Mushroom"))
+            .insert()
+            .unwrap();
+
+        let options = IoOptions::default();
+        let issues = jmap.audit_strings(&options);
+        assert_eq!(
+            issues,
+            [
+                StringIssue {
+                    entry: 1,
+                    hash: calc_hash("Label"),
+                    field: "Label".to_string(),
+                    value: label.clone(),
+                    written: format!("a{}\u{FFFD}", "ス".repeat(15)),
+                    kind: StringIssueKind::Truncated {
+                        len: 33,
+                        max: 32,
+                        split_char: true,
+                    },
+                },
+                StringIssue {
+                    entry: 1,
+                    hash: calc_hash("Name"),
+                    field: "Name".to_string(),
+                    value: "🍄 Mushroom".to_string(),
+                    written: "&#127812; Mushroom".to_string(),
+                    kind: StringIssueKind::Unmappable,
+                },
+            ]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            format!(
+                "entry 1, Label: string of 33 bytes is cut to 32 bytes in the middle of a character, {:?} would be written as {:?}",
+                label, issues[0].written
+            )
+        );
+
+        // The predictions match what is read back
+        let data = to_buffer(&jmap, &options).unwrap();
+        let lenient = IoOptions {
+            strictness: Strictness::permissive(),
+            ..IoOptions::default()
+        };
+        let read = from_buffer(ht, &data, &lenient).unwrap();
+        for issue in &issues {
+            assert_eq!(read.entries()[issue.entry].get_string_by_hash(issue.hash), Some(issue.written.as_str()));
+        }
+
+        let utf8 = IoOptions {
+            encoding: Encoding::Utf8,
+            inline_string_size: 64,
+            ..IoOptions::default()
+        };
+        assert!(jmap.audit_strings(&utf8).is_empty());
+    }
+}
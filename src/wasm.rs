@@ -0,0 +1,172 @@
+//! WebAssembly bindings, mirroring the Python ones
+//!
+//! Build with `wasm-pack build --features wasm`. Only the in-memory functions are wrapped, a browser
+//! having no files to read. Errors are thrown as JS `Error`s with the message of the `JMapError`
+
+use js_sys::Reflect;
+use wasm_bindgen::prelude::*;
+
+use crate::hash::lookup_line;
+use crate::{
+    from_buffer, parse_hash_name, smg_hash_table, to_buffer, to_csv_string, CsvOptions, Encoding, Field, FieldType,
+    FieldValue, FileHashTable, HashTable, IoOptions, JMapError, JMapInfo,
+};
+
+/// A JS wrapper for JMapInfo
+#[wasm_bindgen]
+pub struct JsJMap {
+    inner: JMapInfo<FileHashTable>,
+}
+
+/// Build the SMG hash table from a list of names, one per line like a lookup file
+fn load_hash_table(names: Option<String>) -> FileHashTable {
+    let mut hash_table = smg_hash_table();
+    if let Some(names) = names {
+        hash_table.extend_from_names(names.lines().filter_map(lookup_line));
+    }
+    hash_table
+}
+
+/// Build BCSV options from a `{ bigEndian, encoding }` object, missing properties keeping the SMG defaults
+fn io_options(options: &JsValue) -> Result<IoOptions, JsError> {
+    let mut io_options = IoOptions::default();
+    if options.is_undefined() || options.is_null() {
+        return Ok(io_options);
+    }
+    let property = |name: &str| {
+        Reflect::get(options, &JsValue::from_str(name)).map_err(|_| JsError::new("options must be an object"))
+    };
+
+    let big_endian = property("bigEndian")?;
+    if !big_endian.is_undefined() {
+        io_options.big_endian = big_endian.as_bool().ok_or_else(|| JsError::new("bigEndian must be a boolean"))?;
+    }
+    let encoding = property("encoding")?;
+    if !encoding.is_undefined() {
+        io_options.encoding = match encoding.as_string().map(|name| name.to_ascii_lowercase()).as_deref() {
+            Some("shift_jis" | "shift-jis" | "sjis") => Encoding::ShiftJis,
+            Some("utf-8" | "utf8") => Encoding::Utf8,
+            _ => return Err(JsError::new("unknown encoding, expected shift_jis or utf-8")),
+        };
+    }
+    Ok(io_options)
+}
+
+/// Convert a library error to a JS `Error` with its message
+fn js_error(err: JMapError) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// Convert a value to the matching JS number or string
+fn value_to_js(value: &FieldValue) -> JsValue {
+    match value {
+        FieldValue::Int(v) => JsValue::from(*v),
+        FieldValue::UInt(v) => JsValue::from(*v),
+        FieldValue::Float(v) => JsValue::from(*v),
+        FieldValue::String(v) => JsValue::from_str(v),
+    }
+}
+
+/// Convert a JS value to a value of the field's type
+///
+/// Integer fields take whole numbers that fit in the field, Float fields any number and string fields strings
+fn value_from_js(field: &Field, name: &str, value: &JsValue) -> Result<FieldValue, JsError> {
+    let type_error = || JsError::new(&format!("{} expects {}, got {:?}", name, field.field_type, value));
+    let int = |min: i64, max: i64| -> Result<i64, JsError> {
+        let v = value.as_f64().ok_or_else(type_error)?;
+        if v.fract() != 0.0 || v < min as f64 || v > max as f64 {
+            return Err(JsError::new(&format!(
+                "{} is out of range for {} ({}, {} to {})",
+                v, name, field.field_type, min, max
+            )));
+        }
+        Ok(v as i64)
+    };
+
+    match field.field_type {
+        FieldType::Long => Ok(FieldValue::Int(int(i32::MIN as i64, i32::MAX as i64)? as i32)),
+        FieldType::Short => Ok(FieldValue::Int(int(i16::MIN as i64, i16::MAX as i64)? as i32)),
+        FieldType::Char => Ok(FieldValue::Int(int(i8::MIN as i64, i8::MAX as i64)? as i32)),
+        FieldType::UnsignedLong => Ok(FieldValue::UInt(int(0, u32::MAX as i64)? as u32)),
+        FieldType::Float => Ok(FieldValue::Float(value.as_f64().ok_or_else(type_error)? as f32)),
+        FieldType::String | FieldType::StringOffset => {
+            Ok(FieldValue::String(value.as_string().ok_or_else(type_error)?))
+        }
+    }
+}
+
+impl JsJMap {
+    /// Resolve a field name, or a `[XXXXXXXX]` hash
+    fn field(&self, name: &str) -> Result<&Field, JsError> {
+        let hash = parse_hash_name(name).unwrap_or_else(|| self.inner.hash_table().calc(name));
+        self.inner.get_field_by_hash(hash).ok_or_else(|| js_error(JMapError::FieldNotFound(name.to_string())))
+    }
+
+    /// Fail if there is no entry at `row`
+    fn check_row(&self, row: usize) -> Result<(), JsError> {
+        if row < self.inner.len() {
+            Ok(())
+        } else {
+            Err(js_error(JMapError::EntryIndexOutOfBounds { index: row, len: self.inner.len() }))
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl JsJMap {
+    /// Read BCSV data from a `Uint8Array`
+    ///
+    /// `names` lists the known field names, one per line like a lookup file, and `options` is an optional
+    /// `{ bigEndian, encoding }` object, `encoding` being `"shift_jis"` or `"utf-8"`
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[u8], names: Option<String>, options: JsValue) -> Result<JsJMap, JsError> {
+        let jmap = from_buffer(load_hash_table(names), data, &io_options(&options)?).map_err(js_error)?;
+        Ok(JsJMap { inner: jmap })
+    }
+
+    /// Get the number of entries
+    #[wasm_bindgen(js_name = entryCount)]
+    pub fn entry_count(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Get the number of fields
+    #[wasm_bindgen(js_name = fieldCount)]
+    pub fn field_count(&self) -> usize {
+        self.inner.num_fields()
+    }
+
+    /// Get the field names in field order, unknown names being given as `[XXXXXXXX]`
+    #[wasm_bindgen(js_name = fieldNames)]
+    pub fn field_names(&self) -> Vec<String> {
+        self.inner.fields().map(|field| self.inner.field_name(field.hash)).collect()
+    }
+
+    /// Get a value as a number or a string, or `undefined` if the entry has no value for the field
+    pub fn get(&self, row: usize, field: &str) -> Result<JsValue, JsError> {
+        self.check_row(row)?;
+        let hash = self.field(field)?.hash;
+        Ok(self.inner.entries()[row].get_by_hash(hash).map_or(JsValue::UNDEFINED, value_to_js))
+    }
+
+    /// Set a value, checked against the field's type
+    pub fn set(&mut self, row: usize, field: &str, value: JsValue) -> Result<(), JsError> {
+        self.check_row(row)?;
+        let field_def = self.field(field)?;
+        let (hash, value) = (field_def.hash, value_from_js(field_def, field, &value)?);
+        self.inner.entries_mut()[row].set_by_hash(hash, value);
+        Ok(())
+    }
+
+    /// Write the table as CSV text
+    #[wasm_bindgen(js_name = toCsvString)]
+    pub fn to_csv_string(&self) -> Result<String, JsError> {
+        to_csv_string(&self.inner, &CsvOptions::default()).map_err(js_error)
+    }
+
+    /// Write the table as BCSV data, returned as a `Uint8Array`, with the same options as the constructor
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self, options: JsValue) -> Result<Vec<u8>, JsError> {
+        to_buffer(&self.inner, &io_options(&options)?).map_err(js_error)
+    }
+}
@@ -0,0 +1,698 @@
+//! YAML import/export, using the same document model as the JSON layer
+//!
+//! A table is written as its [`JMapDocument`], keys in a fixed order so that diffs stay minimal:
+//!
+//! ```yaml
+//! fields:
+//!   - name: ScenarioNo
+//!     type: Long
+//!     default: 0
+//!   - name: "[DEADBEEF]"
+//!     type: Long
+//!     default: 0
+//!     mask: 0x0000FF00
+//!     shift: 8
+//! entries:
+//!   - ScenarioNo: 1
+//!     "[DEADBEEF]": 3
+//! ```
+//!
+//! Multi-line strings are written as block scalars. Floats use the shortest form that reads back
+//! to the same value, with `.inf`, `-.inf` and `.nan` for non-finite values.
+//! Only the subset of YAML written by [`to_yaml`] is read back: block mappings and sequences, plain,
+//! quoted and block scalars, comments, and the empty `[]` and `{}`
+
+use indexmap::IndexMap;
+
+use crate::document::{DocumentField, JMapDocument};
+use crate::error::{JMapError, Result};
+use crate::field::{FieldType, FieldValue};
+use crate::hash::HashTable;
+use crate::jmap::JMapInfo;
+
+/// Write a JMapInfo to a YAML string
+///
+/// # Arguments
+/// - `jmap` - The JMapInfo to export
+///
+/// # Returns
+/// The YAML document
+pub fn to_yaml<H: HashTable>(jmap: &JMapInfo<H>) -> Result<String> {
+    let document = jmap.to_document();
+    let mut out = String::new();
+
+    if document.fields.is_empty() {
+        out.push_str("fields: []\n");
+    } else {
+        out.push_str("fields:\n");
+        for field in &document.fields {
+            push_key(&mut out, "  - ", "name");
+            push_string(&mut out, &field.name, 6);
+            push_key(&mut out, "    ", "type");
+            out.push_str(&format!(" {:?}\n", field.field_type));
+            push_key(&mut out, "    ", "default");
+            push_value(&mut out, &field.default, 6);
+            if let Some(mask) = field.mask {
+                push_key(&mut out, "    ", "mask");
+                out.push_str(&format!(" 0x{:08X}\n", mask));
+            }
+            if let Some(shift) = field.shift {
+                push_key(&mut out, "    ", "shift");
+                out.push_str(&format!(" {}\n", shift));
+            }
+        }
+    }
+
+    if document.entries.is_empty() {
+        out.push_str("entries: []\n");
+    } else {
+        out.push_str("entries:\n");
+        for values in &document.entries {
+            if values.is_empty() {
+                out.push_str("  - {}\n");
+            }
+            for (i, (name, value)) in values.iter().enumerate() {
+                push_key(&mut out, if i == 0 { "  - " } else { "    " }, name);
+                push_value(&mut out, value, 6);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Read a JMapInfo from a YAML string
+///
+/// # Arguments
+/// - `hash_table` - The hash table to use for field name lookups. Field names from the YAML will be added to this hash table
+/// - `s` - The YAML document
+///
+/// # Errors
+/// - `JMapError::YamlError` with the 1-based line number if the document is not valid or has the wrong layout
+/// - `JMapError::InEntry` with the entry index and field name if a value does not match its field type
+///
+/// # Returns
+/// A JMapInfo populated with the fields and entries of the document
+pub fn from_yaml<H: HashTable>(hash_table: H, s: &str) -> Result<JMapInfo<H>> {
+    let mut parser = Parser {
+        lines: s.lines().map(str::to_string).collect(),
+        pos: 0,
+    };
+    let root = parser.parse_block(0)?;
+    if let Some(line) = parser.peek() {
+        return Err(parser.error(line.0, "unexpected content after the document"));
+    }
+    JMapInfo::from_document(hash_table, document_from_node(root)?)
+}
+
+/// Write `key: ` after the given prefix
+fn push_key(out: &mut String, prefix: &str, key: &str) {
+    out.push_str(prefix);
+    if needs_quotes(key) || key.contains('\n') {
+        push_quoted(out, key);
+    } else {
+        out.push_str(key);
+    }
+    out.push(':');
+}
+
+/// Write a value after its key, block scalars being indented by `indent`
+fn push_value(out: &mut String, value: &FieldValue, indent: usize) {
+    match value {
+        FieldValue::Int(v) => out.push_str(&format!(" {}\n", v)),
+        FieldValue::UInt(v) => out.push_str(&format!(" {}\n", v)),
+        FieldValue::Float(_) => out.push_str(&format!(" {}\n", format_float(value))),
+        FieldValue::String(v) => push_string(out, v, indent),
+    }
+}
+
+/// Format a float with [`FieldValue::format_float`], in the YAML spelling of special values
+/// and with a decimal point so that whole numbers read back as floats
+fn format_float(value: &FieldValue) -> String {
+    match value {
+        FieldValue::Float(v) if v.is_nan() => ".nan".to_string(),
+        FieldValue::Float(v) if v.is_infinite() => if *v > 0.0 { ".inf" } else { "-.inf" }.to_string(),
+        _ => {
+            let mut text = value.format_float(None);
+            if !text.contains('.') {
+                text.push_str(".0");
+            }
+            text
+        }
+    }
+}
+
+/// Write a string value after its key, as a plain, quoted or block scalar
+fn push_string(out: &mut String, s: &str, indent: usize) {
+    if s.contains('\n') && is_block_safe(s) {
+        let trailing = s.len() - s.trim_end_matches('\n').len();
+        let (chomping, body) = match trailing {
+            0 => ("|-", s),
+            1 => ("|", &s[..s.len() - 1]),
+            _ => ("|+", &s[..s.len() - 1]),
+        };
+        out.push(' ');
+        out.push_str(chomping);
+        out.push('\n');
+        for line in body.split('\n') {
+            if !line.is_empty() {
+                out.push_str(&" ".repeat(indent));
+                out.push_str(line);
+            }
+            out.push('\n');
+        }
+    } else {
+        out.push(' ');
+        if needs_quotes(s) {
+            push_quoted(out, s);
+        } else {
+            out.push_str(s);
+        }
+        out.push('\n');
+    }
+}
+
+/// Whether a multi-line string can be written as a literal block scalar
+fn is_block_safe(s: &str) -> bool {
+    !s.starts_with([' ', '\t', '\n']) && !s.chars().any(|c| c.is_control() && c != '\n' && c != '\t')
+}
+
+/// Whether a string must be quoted to read back as the same string
+fn needs_quotes(s: &str) -> bool {
+    s.is_empty()
+        || s != s.trim()
+        || s.starts_with(['-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%', '@', '`'])
+        || s.contains(": ")
+        || s.contains(" #")
+        || s.ends_with(':')
+        || s.chars().any(char::is_control)
+        || !matches!(plain_scalar(s), Scalar::String(_))
+}
+
+fn push_quoted(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A parsed node of the YAML subset
+#[derive(Debug)]
+enum Node {
+    Scalar { value: Scalar, line: usize },
+    Sequence(Vec<Node>),
+    Mapping(Vec<(String, Node)>),
+}
+
+/// A scalar resolved to its type
+#[derive(Debug, PartialEq)]
+enum Scalar {
+    Null,
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+/// Resolve a plain (unquoted) scalar
+fn plain_scalar(s: &str) -> Scalar {
+    match s {
+        "" | "~" | "null" | "Null" | "NULL" => return Scalar::Null,
+        ".inf" | "+.inf" | ".Inf" | ".INF" => return Scalar::Float(f64::INFINITY),
+        "-.inf" | "-.Inf" | "-.INF" => return Scalar::Float(f64::NEG_INFINITY),
+        ".nan" | ".NaN" | ".NAN" => return Scalar::Float(f64::NAN),
+        _ => {}
+    }
+    if matches!(s.to_ascii_lowercase().as_str(), "true" | "false" | "yes" | "no" | "on" | "off") {
+        // Booleans have no field type, keep them strings but force quoting on export
+        return Scalar::Null;
+    }
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if let Some(hex) = digits.strip_prefix("0x")
+        && let Ok(v) = i64::from_str_radix(hex, 16)
+    {
+        return Scalar::Int(if negative { -v } else { v });
+    }
+    if !digits.is_empty()
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && let Ok(v) = s.parse::<i64>()
+    {
+        return Scalar::Int(v);
+    }
+    if digits.bytes().any(|b| b.is_ascii_digit())
+        && digits.bytes().all(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E' | b'+' | b'-'))
+        && let Ok(v) = s.parse::<f64>()
+    {
+        return Scalar::Float(v);
+    }
+    Scalar::String(s.to_string())
+}
+
+struct Parser {
+    lines: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, line: usize, message: impl std::fmt::Display) -> JMapError {
+        JMapError::YamlError(format!("Line {}: {}", line + 1, message))
+    }
+
+    /// Get the next line that is not blank or a comment, as its index and indentation, without consuming it
+    fn peek(&mut self) -> Option<(usize, usize)> {
+        while let Some(line) = self.lines.get(self.pos) {
+            let content = line.trim_start_matches(' ');
+            if content.is_empty() || content.starts_with('#') {
+                self.pos += 1;
+                continue;
+            }
+            return Some((self.pos, line.len() - content.len()));
+        }
+        None
+    }
+
+    fn content(&self, line: usize) -> &str {
+        self.lines[line].trim_start_matches(' ')
+    }
+
+    /// Parse the mapping or sequence starting at the next line, which must be indented by at least `indent`
+    fn parse_block(&mut self, indent: usize) -> Result<Node> {
+        let Some((line, actual)) = self.peek() else {
+            return Ok(Node::Mapping(Vec::new()));
+        };
+        if actual < indent {
+            return Err(self.error(line, "expected an indented block"));
+        }
+        if is_sequence_item(self.content(line)) {
+            self.parse_sequence(actual)
+        } else {
+            self.parse_mapping(actual)
+        }
+    }
+
+    fn parse_mapping(&mut self, indent: usize) -> Result<Node> {
+        let mut entries: Vec<(String, Node)> = Vec::new();
+        while let Some((line, actual)) = self.peek() {
+            if actual < indent || (actual == indent && is_sequence_item(self.content(line))) {
+                break;
+            }
+            if actual > indent {
+                return Err(self.error(line, "unexpected indentation"));
+            }
+
+            let content = self.content(line).to_string();
+            let (key, rest) = split_key(&content).ok_or_else(|| self.error(line, "expected `key: value`"))?;
+            if entries.iter().any(|(existing, _)| *existing == key) {
+                return Err(self.error(line, format!("duplicate key '{}'", key)));
+            }
+            self.pos += 1;
+            let value = self.parse_value(rest, indent, line)?;
+            entries.push((key, value));
+        }
+        Ok(Node::Mapping(entries))
+    }
+
+    fn parse_sequence(&mut self, indent: usize) -> Result<Node> {
+        let mut items = Vec::new();
+        while let Some((line, actual)) = self.peek() {
+            if actual != indent || !is_sequence_item(self.content(line)) {
+                if actual > indent {
+                    return Err(self.error(line, "unexpected indentation"));
+                }
+                break;
+            }
+
+            let item = self.content(line)[1..].trim_start_matches(' ').to_string();
+            if split_key(&item).is_some() {
+                // `- key: value` starts a mapping indented past the dash
+                let offset = self.lines[line].len() - item.len();
+                self.lines[line] = format!("{}{}", " ".repeat(offset), item);
+                items.push(self.parse_mapping(offset)?);
+            } else {
+                self.pos += 1;
+                items.push(self.parse_value(&item, indent, line)?);
+            }
+        }
+        Ok(Node::Sequence(items))
+    }
+
+    /// Parse the value following a key or a dash on `line`, nested blocks being indented past `indent`
+    fn parse_value(&mut self, rest: &str, indent: usize, line: usize) -> Result<Node> {
+        let rest = strip_comment(rest);
+        match rest {
+            "" => match self.peek() {
+                Some((next, actual)) if actual > indent || (actual == indent && is_sequence_item(self.content(next))) => {
+                    self.parse_block(actual)
+                }
+                _ => Ok(Node::Scalar { value: Scalar::Null, line }),
+            },
+            "[]" => Ok(Node::Sequence(Vec::new())),
+            "{}" => Ok(Node::Mapping(Vec::new())),
+            _ if rest.starts_with('|') => {
+                let value = self.parse_block_scalar(rest, indent, line)?;
+                Ok(Node::Scalar { value: Scalar::String(value), line })
+            }
+            _ if rest.starts_with(['"', '\'']) => {
+                let (value, end) = parse_quoted(rest).ok_or_else(|| self.error(line, "unterminated quoted string"))?;
+                if !strip_comment(&rest[end..]).is_empty() {
+                    return Err(self.error(line, "unexpected content after a quoted string"));
+                }
+                Ok(Node::Scalar { value: Scalar::String(value), line })
+            }
+            _ if rest.starts_with(['[', '{', '&', '*', '!', '>']) => {
+                Err(self.error(line, format!("unsupported YAML syntax: {}", rest)))
+            }
+            _ => Ok(Node::Scalar { value: plain_scalar(rest), line }),
+        }
+    }
+
+    fn parse_block_scalar(&mut self, header: &str, indent: usize, line: usize) -> Result<String> {
+        let chomping = match header {
+            "|" => '=',
+            "|-" => '-',
+            "|+" => '+',
+            _ => return Err(self.error(line, format!("unsupported block scalar header: {}", header))),
+        };
+
+        let mut content_indent = None;
+        let mut lines = Vec::new();
+        while let Some(raw) = self.lines.get(self.pos) {
+            let content = raw.trim_start_matches(' ');
+            let actual = raw.len() - content.len();
+            if content.is_empty() {
+                lines.push(raw.get(content_indent.unwrap_or(usize::MAX)..).unwrap_or("").to_string());
+                self.pos += 1;
+                continue;
+            }
+            if actual <= indent {
+                break;
+            }
+            let content_indent = *content_indent.get_or_insert(actual);
+            if actual < content_indent {
+                return Err(self.error(self.pos, "block scalar line is less indented than the first one"));
+            }
+            lines.push(raw[content_indent..].to_string());
+            self.pos += 1;
+        }
+
+        // Blank lines after the block belong to it only with the `+` chomping
+        let mut text = lines.join("\n");
+        match chomping {
+            '-' => text.truncate(text.trim_end_matches('\n').len()),
+            '=' => {
+                text.truncate(text.trim_end_matches('\n').len());
+                text.push('\n');
+            }
+            _ => text.push('\n'),
+        }
+        Ok(text)
+    }
+}
+
+fn is_sequence_item(content: &str) -> bool {
+    content == "-" || content.starts_with("- ")
+}
+
+/// Remove a trailing ` # comment` from a plain value
+fn strip_comment(s: &str) -> &str {
+    if s.starts_with('#') {
+        return "";
+    }
+    match s.find(" #") {
+        Some(i) if !s.starts_with(['"', '\'']) => s[..i].trim_end(),
+        _ => s.trim_end(),
+    }
+}
+
+/// Split `key: value` or `key:` into the key and the rest of the line
+fn split_key(content: &str) -> Option<(String, &str)> {
+    if content.starts_with(['"', '\'']) {
+        let (key, end) = parse_quoted(content)?;
+        let rest = content[end..].strip_prefix(':')?;
+        return (rest.is_empty() || rest.starts_with(' ')).then(|| (key, rest.trim_start()));
+    }
+    if let Some(i) = content.find(": ") {
+        return Some((content[..i].to_string(), content[i + 2..].trim_start()));
+    }
+    content.strip_suffix(':').map(|key| (key.to_string(), ""))
+}
+
+/// Parse a double- or single-quoted string at the start of `s`
+///
+/// # Returns
+/// The string and the byte index just past the closing quote
+fn parse_quoted(s: &str) -> Option<(String, usize)> {
+    let quote = s.chars().next()?;
+    let mut value = String::new();
+    let mut chars = s.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\'' if quote == '\'' => {
+                if chars.peek().is_some_and(|(_, next)| *next == '\'') {
+                    chars.next();
+                    value.push('\'');
+                } else {
+                    return Some((value, i + 1));
+                }
+            }
+            '"' if quote == '"' => return Some((value, i + 1)),
+            '\\' if quote == '"' => {
+                let (_, escape) = chars.next()?;
+                let hex_digits = match escape {
+                    'x' => 2,
+                    'u' => 4,
+                    'U' => 8,
+                    _ => 0,
+                };
+                if hex_digits > 0 {
+                    let hex: String = (0..hex_digits).filter_map(|_| chars.next().map(|(_, c)| c)).collect();
+                    value.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                    continue;
+                }
+                value.push(match escape {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '0' => '\0',
+                    '"' => '"',
+                    '\\' => '\\',
+                    '/' => '/',
+                    ' ' => ' ',
+                    _ => return None,
+                });
+            }
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+fn document_from_node(root: Node) -> Result<JMapDocument> {
+    let layout = |message: &str| JMapError::YamlError(message.to_string());
+    let Node::Mapping(root) = root else {
+        return Err(layout("expected a mapping with `fields` and `entries`"));
+    };
+
+    let mut document = JMapDocument::default();
+    for (key, node) in root {
+        match (key.as_str(), node) {
+            ("fields", Node::Sequence(fields)) => {
+                for field in fields {
+                    document.fields.push(field_from_node(field)?);
+                }
+            }
+            ("entries", Node::Sequence(entries)) => {
+                for entry in entries {
+                    let Node::Mapping(values) = entry else {
+                        return Err(layout("each entry must be a mapping"));
+                    };
+                    let mut entry = IndexMap::with_capacity(values.len());
+                    for (name, node) in values {
+                        let value = value_from_node(node)?;
+                        entry.insert(name, value);
+                    }
+                    document.entries.push(entry);
+                }
+            }
+            (key, _) => return Err(JMapError::YamlError(format!("unexpected top-level key '{}'", key))),
+        }
+    }
+    Ok(document)
+}
+
+fn field_from_node(node: Node) -> Result<DocumentField> {
+    let Node::Mapping(keys) = node else {
+        return Err(JMapError::YamlError("each field must be a mapping".to_string()));
+    };
+
+    let (mut name, mut field_type, mut default, mut mask, mut shift) = (None, None, None, None, None);
+    for (key, node) in keys {
+        let line = match &node {
+            Node::Scalar { line, .. } => *line + 1,
+            _ => 0,
+        };
+        let invalid = |what: &str| JMapError::YamlError(format!("Line {}: invalid {}", line, what));
+        match key.as_str() {
+            "name" => name = Some(string_from_node(node).ok_or_else(|| invalid("name"))?),
+            "type" => {
+                let type_name = string_from_node(node).ok_or_else(|| invalid("type"))?;
+                field_type = Some(field_type_from_name(&type_name).ok_or_else(|| invalid("type"))?);
+            }
+            "default" => default = Some(value_from_node(node)?),
+            "mask" => mask = Some(int_from_node(node).and_then(|v| u32::try_from(v).ok()).ok_or_else(|| invalid("mask"))?),
+            "shift" => shift = Some(int_from_node(node).and_then(|v| u8::try_from(v).ok()).ok_or_else(|| invalid("shift"))?),
+            other => return Err(JMapError::YamlError(format!("Line {}: unexpected field key '{}'", line, other))),
+        }
+    }
+
+    let missing = |key: &str| JMapError::YamlError(format!("field without a `{}`", key));
+    let field_type = field_type.ok_or_else(|| missing("type"))?;
+    Ok(DocumentField {
+        name: name.ok_or_else(|| missing("name"))?,
+        field_type,
+        default: default.unwrap_or_else(|| FieldValue::default_for(field_type)),
+        mask,
+        shift,
+    })
+}
+
+fn field_type_from_name(name: &str) -> Option<FieldType> {
+    [
+        FieldType::Long,
+        FieldType::String,
+        FieldType::Float,
+        FieldType::UnsignedLong,
+        FieldType::Short,
+        FieldType::Char,
+        FieldType::StringOffset,
+    ]
+    .into_iter()
+    .find(|field_type| format!("{:?}", field_type) == name)
+}
+
+fn string_from_node(node: Node) -> Option<String> {
+    match node {
+        Node::Scalar { value: Scalar::String(s), .. } => Some(s),
+        _ => None,
+    }
+}
+
+fn int_from_node(node: Node) -> Option<i64> {
+    match node {
+        Node::Scalar { value: Scalar::Int(v), .. } => Some(v),
+        _ => None,
+    }
+}
+
+fn value_from_node(node: Node) -> Result<FieldValue> {
+    let Node::Scalar { value, line } = node else {
+        return Err(JMapError::YamlError("values must be scalars".to_string()));
+    };
+    let line = line + 1;
+    match value {
+        Scalar::Int(v) => {
+            if let Ok(v) = i32::try_from(v) {
+                Ok(FieldValue::Int(v))
+            } else {
+                u32::try_from(v)
+                    .map(FieldValue::UInt)
+                    .map_err(|_| JMapError::YamlError(format!("Line {}: {} does not fit in 32 bits", line, v)))
+            }
+        }
+        Scalar::Float(v) => Ok(FieldValue::Float(v as f32)),
+        Scalar::String(s) => Ok(FieldValue::String(s)),
+        Scalar::Null => Err(JMapError::YamlError(format!("Line {}: missing or unsupported value", line))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::Field;
+    use crate::hash::{calc_hash, smg_hash_table};
+
+    fn fixture() -> JMapInfo<crate::hash::FileHashTable> {
+        let mut jmap = JMapInfo::new(smg_hash_table());
+        jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+        jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+        jmap.create_field("Scale", FieldType::Float, FieldValue::Float(1.0)).unwrap();
+        let mut flags = Field::new(0xDEADBEEF, FieldType::Long);
+        flags.mask = 0xFF00;
+        flags.shift = 8;
+        jmap.fields_map_mut().insert(flags.hash, flags);
+
+        let entry = jmap.create_entry();
+        entry.set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(1));
+        entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String("Heavenly Beach\nGalaxy\n".into()));
+        entry.set_by_hash(calc_hash("Scale"), FieldValue::Float(0.1));
+        entry.set_by_hash(0xDEADBEEF, FieldValue::Int(3));
+        let entry = jmap.create_entry();
+        entry.set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(-2));
+        entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String("123".into()));
+        entry.set_by_hash(calc_hash("Scale"), FieldValue::Float(f32::INFINITY));
+        jmap
+    }
+
+    #[test]
+    fn test_yaml_snapshot() {
+        let expected = r#"fields:
+  - name: ScenarioNo
+    type: Long
+    default: 0
+  - name: ZoneName
+    type: StringOffset
+    default: ""
+  - name: Scale
+    type: Float
+    default: 1.0
+  - name: "[DEADBEEF]"
+    type: Long
+    default: 0
+    mask: 0x0000FF00
+    shift: 8
+entries:
+  - ScenarioNo: 1
+    ZoneName: |
+      Heavenly Beach
+      Galaxy
+    Scale: 0.1
+    "[DEADBEEF]": 3
+  - ScenarioNo: -2
+    ZoneName: "123"
+    Scale: .inf
+    "[DEADBEEF]": 0
+"#;
+        assert_eq!(to_yaml(&fixture()).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let mut jmap = fixture();
+        for text in ["a\nb", "a\n\n", "  leading\nspace", "quote \" and \\ backslash", "key: value # not a comment", "- dash", "true", "", "ギャラクシー"] {
+            jmap.create_entry().set_by_hash(calc_hash("ZoneName"), FieldValue::String(text.into()));
+        }
+        for v in [f32::NAN, 1e10, -3.0, 1e-40, f32::MAX] {
+            jmap.create_entry().set_by_hash(calc_hash("Scale"), FieldValue::Float(v));
+        }
+
+        let yaml = to_yaml(&jmap).unwrap();
+        let back = from_yaml(smg_hash_table(), &yaml).unwrap();
+        assert_eq!(back.entries(), jmap.entries());
+        assert_eq!(back.get_field_by_hash(0xDEADBEEF).map(|field| (field.mask, field.shift)), Some((0xFF00, 8)));
+        assert_eq!(to_yaml(&back).unwrap(), yaml);
+
+        let err = from_yaml(smg_hash_table(), "fields:\n  - name: A\n    type: Nope\n").unwrap_err();
+        assert_eq!(err.to_string(), "YAML error: Line 3: invalid type");
+    }
+}
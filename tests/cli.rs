@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_bcsv-jmap"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap()
+}
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_convert() {
+    let dir = scratch("bcsv_jmap_test_cli_convert");
+    let csv = dir.join("scenariodata.csv");
+    let bcsv = dir.join("scenariodata.bcsv");
+    let names = ["--names", "assets/strings_SMG.txt"];
+
+    let output = run(&[&["convert", "assets/examples/scenariodata.bcsv", csv.to_str().unwrap()], &names[..]].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(fs::read_to_string(&csv).unwrap().starts_with("ScenarioNo:Int:0,ScenarioName:String:0"));
+
+    let output = run(&[&["convert", csv.to_str().unwrap(), bcsv.to_str().unwrap()], &names[..]].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let again = dir.join("again.csv");
+    let output = run(&[&["convert", bcsv.to_str().unwrap(), again.to_str().unwrap()], &names[..]].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // Fields come back in layout order, so only the content is compared
+    let (again, csv) = (fs::read_to_string(&again).unwrap(), fs::read_to_string(&csv).unwrap());
+    assert_eq!(again.lines().count(), csv.lines().count());
+    assert!(again.contains("ErrorCheck:Int:0:0x00000002:1") && again.contains("ドーム６（ロフト）"));
+
+    let output = run(&["convert", "missing.bcsv", "missing.csv"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error: missing.bcsv: I/O error"));
+
+    assert_eq!(run(&["convert", "only_input.bcsv"]).status.code(), Some(2));
+    assert_eq!(run(&["convert", "a", "b", "--to", "xml"]).status.code(), Some(2));
+    assert_eq!(run(&["frobnicate"]).status.code(), Some(2));
+}
+
+#[test]
+fn test_convert_recursive() {
+    let src = scratch("bcsv_jmap_test_cli_recursive_src");
+    let dst = std::env::temp_dir().join("bcsv_jmap_test_cli_recursive_dst");
+    let _ = fs::remove_dir_all(&dst);
+    fs::create_dir_all(src.join("nested")).unwrap();
+    fs::copy("assets/examples/scenariodata.bcsv", src.join("nested/scenariodata.bcsv")).unwrap();
+
+    let output = run(&["convert", "--recursive", src.to_str().unwrap(), dst.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(dst.join("nested/scenariodata.csv").exists());
+
+    fs::write(src.join("broken.bcsv"), b"nope").unwrap();
+    let output = run(&["convert", "--recursive", src.to_str().unwrap(), dst.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("broken.bcsv"));
+}
+
+#[test]
+fn test_diff() {
+    let dir = scratch("bcsv_jmap_test_cli_diff");
+    let names = dir.join("names.txt");
+    fs::write(&names, "ScenarioNo\nZoneName\nComet\n").unwrap();
+    let files = [
+        ("vanilla", "ScenarioNo:Int:0,ZoneName:String:0,Comet:String:0\n1,Beach,\n2,Dome,Red\n"),
+        ("modified", "ScenarioNo:Int:0,ZoneName:String:0\n1,Beach\n2,Tower\n3,Garden\n"),
+    ];
+    for (name, csv) in files {
+        let csv_path = dir.join(format!("{}.csv", name));
+        fs::write(&csv_path, csv).unwrap();
+        let bcsv = dir.join(format!("{}.bcsv", name));
+        let output = run(&["convert", csv_path.to_str().unwrap(), bcsv.to_str().unwrap(), "--names", names.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    }
+    let (vanilla, modified) = (dir.join("vanilla.bcsv"), dir.join("modified.bcsv"));
+    let base = [
+        "diff",
+        vanilla.to_str().unwrap(),
+        modified.to_str().unwrap(),
+        "--names",
+        names.to_str().unwrap(),
+        "--key",
+        "ScenarioNo",
+    ];
+
+    let output = run(&base);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "- field Comet\n+ row [3]\nrow[2] ZoneName: \"Dome\" -> \"Tower\"\n"
+    );
+
+    let output = run(&[&base[..], &["--format", "json"]].concat());
+    assert_eq!(output.status.code(), Some(1));
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["removed_fields"], serde_json::json!(["Comet"]));
+    assert_eq!(json["added_rows"][0]["ZoneName"], "Garden");
+    assert_eq!(json["changed_rows"][0]["key"], serde_json::json!([2]));
+    assert_eq!(json["changed_rows"][0]["changes"][0]["after"], "Tower");
+
+    let output = run(&["diff", vanilla.to_str().unwrap(), vanilla.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    assert_eq!(run(&[&base[..], &["--key", "Nope"]].concat()).status.code(), Some(2));
+}
+
+#[test]
+fn test_inspect() {
+    let fixture = "assets/examples/scenariodata.bcsv";
+    let output = run(&["inspect", fixture, "--names", "assets/strings_SMG.txt", "--strict"]);
+    assert_eq!(output.status.code(), Some(0));
+    let expected = format!(
+        r#"lib-bcsv-jmap {}
+File: assets/examples/scenariodata.bcsv
+Size: 480 bytes
+Endianness: big-endian (detected)
+Entries: 6
+Fields: 9
+Entry size: 0x20
+Data offset: 0x7C
+String table: 164 bytes
+Resolved names: 9/9
+
++0x0  ScenarioNo         Int    mask=0xFFFFFFFF shift=0 default=0
++0x10 ScenarioName       String mask=0xFFFFFFFF shift=0 default=
++0x4  PowerStarId        Int    mask=0xFFFFFFFF shift=0 default=0
++0x14 AppearPowerStarObj String mask=0xFFFFFFFF shift=0 default=
++0x18 Comet              String mask=0xFFFFFFFF shift=0 default=
++0x8  LuigiModeTimer     Int    mask=0xFFFFFFFF shift=0 default=0
++0xC  AstroDome          Int    mask=0xFFFFFFFF shift=0 default=0
++0x1C IsHidden           Int    mask=0x00000001 shift=0 default=0
++0x1C ErrorCheck         Int    mask=0x00000002 shift=1 default=0
+
+Issues: 0
+"#,
+        env!("CARGO_PKG_VERSION")
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+
+    // Without names every field is unresolved, unless the names are bundled
+    let output = run(&["inspect", fixture]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let strict = run(&["inspect", fixture, "--strict"]).status.code();
+    if cfg!(feature = "bundled-names") {
+        assert!(stdout.contains("Resolved names: 9/9\n"));
+        assert!(stdout.contains("Issues: 0\n"));
+        assert_eq!(strict, Some(0));
+    } else {
+        assert!(stdout.contains("Issues: 9\n  field [ED08B591]: name not in the hash table\n"));
+        assert_eq!(strict, Some(1));
+    }
+
+    let output = run(&["inspect", fixture, "--names", "assets/strings_SMG.txt", "--dump"]);
+    let dump = String::from_utf8_lossy(&output.stdout);
+    assert!(dump.starts_with("entries = 6\nfields = 9\nentry_size = 0x20\n"));
+    assert!(dump.contains("entry[5].AstroDome = 32\n"));
+
+    let dir = scratch("bcsv_jmap_test_cli_inspect");
+    fs::write(dir.join("garbage.bcsv"), [0xFF; 32]).unwrap();
+    let output = run(&["inspect", dir.join("garbage.bcsv").to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid BCSV header"));
+}
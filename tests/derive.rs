@@ -0,0 +1,77 @@
+use lib_bcsv_jmap::{
+    calc_hash, smg_hash_table, FieldType, FieldValue, FromEntry, JMapError, JMapInfo, ToEntry,
+};
+
+#[derive(Debug, PartialEq, FromEntry, ToEntry)]
+struct ObjInfo {
+    #[bcsv(name = "name")]
+    obj_name: String,
+    pos_x: f32,
+    #[bcsv(hash = 0x1176D409)]
+    shape_model_no: i32,
+    #[bcsv(default)]
+    optional_flag: i32,
+    #[bcsv(name = "SW_APPEAR")]
+    switch: u32,
+    #[bcsv(name = "IsHidden")]
+    hidden: bool,
+    comment: Option<String>,
+}
+
+fn table() -> JMapInfo<lib_bcsv_jmap::FileHashTable> {
+    let mut jmap = JMapInfo::new(smg_hash_table());
+    jmap.create_field("name", FieldType::StringOffset, FieldValue::from("")).unwrap();
+    jmap.create_field("pos_x", FieldType::Float, FieldValue::Float(0.0)).unwrap();
+    jmap.create_field("SW_APPEAR", FieldType::UnsignedLong, FieldValue::UInt(u32::MAX)).unwrap();
+    jmap.create_field("IsHidden", FieldType::Char, FieldValue::Int(0)).unwrap();
+    jmap
+}
+
+#[test]
+fn test_round_trip() {
+    let mut jmap = table();
+    jmap.create_field("ShapeModelNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+    jmap.create_field("optional_flag", FieldType::Long, FieldValue::Int(0)).unwrap();
+    jmap.create_field("comment", FieldType::StringOffset, FieldValue::from("")).unwrap();
+
+    let row = ObjInfo {
+        obj_name: "Kuribo".to_string(),
+        pos_x: 12.5,
+        shape_model_no: 3,
+        optional_flag: 1,
+        switch: u32::MAX,
+        hidden: true,
+        comment: Some("note".to_string()),
+    };
+    jmap.extend_from_rows(&[row]);
+
+    let entry = &jmap.entries()[0];
+    assert_eq!(entry.get_int_by_hash(0x1176D409), Some(3));
+    assert_eq!(entry.get_by_hash(calc_hash("SW_APPEAR")), Some(&FieldValue::UInt(u32::MAX)));
+
+    let rows: Vec<ObjInfo> = jmap.collect_rows().unwrap();
+    assert_eq!(rows[0].obj_name, "Kuribo");
+    assert_eq!(rows[0].switch, u32::MAX);
+    assert_eq!(rows[0].comment.as_deref(), Some("note"));
+}
+
+#[test]
+fn test_missing_values() {
+    let mut jmap = table();
+    jmap.create_entry().set_by_hash(0x1176D409, FieldValue::Int(1));
+
+    // `default` and `Option` fields tolerate missing values
+    let row = ObjInfo::from_entry(&jmap.entries()[0], jmap.hash_table()).unwrap();
+    assert_eq!(row.optional_flag, 0);
+    assert_eq!(row.comment, None);
+
+    // Required fields do not
+    jmap.create_entry();
+    let err = jmap.collect_rows::<ObjInfo>().unwrap_err();
+    assert_eq!(err.to_string(), "Entry 1: Field not found: [1176D409]");
+
+    let mut entry = jmap.entries()[0].clone();
+    entry.set(jmap.hash_table(), "pos_x", FieldValue::Int(1));
+    let err = ObjInfo::from_entry(&entry, jmap.hash_table()).unwrap_err();
+    assert!(matches!(&err, JMapError::TypeMismatch { field, expected: "Float", got: "Int" } if field == "pos_x"));
+}
@@ -0,0 +1,384 @@
+"""Tests of the Python bindings.
+
+Build the module first, for example with `maturin develop`, then run from the repository root:
+
+    python -m unittest discover tests/python
+"""
+
+import os
+import tempfile
+import unittest
+
+import lib_bcsv_jmap
+
+ROOT = os.path.dirname(os.path.dirname(os.path.dirname(os.path.abspath(__file__))))
+NAMES = os.path.join(ROOT, "assets", "strings_SMG.txt")
+SCENARIO = os.path.join(ROOT, "assets", "examples", "scenariodata.bcsv")
+
+
+def load():
+    return lib_bcsv_jmap.JMap.from_file(NAMES, SCENARIO)
+
+
+class TestValues(unittest.TestCase):
+    def test_get_value(self):
+        jmap = load()
+        self.assertEqual(jmap.get_value(0, "ScenarioNo"), 1)
+        self.assertEqual(jmap.get_value(0, "ScenarioName"), "ドーム１（テラス）")
+        self.assertEqual(jmap.get_value(0, "[ED08B591]"), 1)
+
+    def test_set_value(self):
+        jmap = load()
+        jmap.set_value(0, "ScenarioNo", 7)
+        jmap.set_value(0, "ScenarioName", "Terrace")
+        self.assertEqual(jmap.get_value(0, "ScenarioNo"), 7)
+        self.assertEqual(jmap.get_value(0, "ScenarioName"), "Terrace")
+
+    def test_errors(self):
+        jmap = load()
+        with self.assertRaises(IndexError):
+            jmap.get_value(100, "ScenarioNo")
+        with self.assertRaises(KeyError):
+            jmap.get_value(0, "NotAField")
+        with self.assertRaises(TypeError):
+            jmap.set_value(0, "ScenarioNo", "seven")
+        with self.assertRaises(TypeError):
+            jmap.set_value(0, "ScenarioName", 7)
+        with self.assertRaises(ValueError):
+            jmap.set_value(0, "ScenarioNo", 2**40)
+
+    def test_rows(self):
+        jmap = load()
+        row = jmap.get_row(1)
+        self.assertEqual(list(row)[:2], ["ScenarioNo", "ScenarioName"])
+        self.assertEqual(row["AstroDome"], 2)
+
+        jmap.set_row(1, {"AstroDome": 5, "Comet": "Red"})
+        self.assertEqual(jmap.get_value(1, "AstroDome"), 5)
+        self.assertEqual(jmap.get_value(1, "Comet"), "Red")
+
+        with self.assertRaises(TypeError):
+            jmap.set_row(1, {"AstroDome": 6, "Comet": 1})
+        self.assertEqual(jmap.get_value(1, "AstroDome"), 5)
+
+
+class TestSequence(unittest.TestCase):
+    def test_indexing(self):
+        jmap = load()
+        self.assertEqual(len(jmap), 6)
+        self.assertEqual(jmap[-1]["ScenarioNo"], 6)
+        self.assertEqual(jmap[-1].index, 5)
+        self.assertEqual([entry.ScenarioNo for entry in jmap[1:5:2]], [2, 4])
+        with self.assertRaises(IndexError):
+            jmap[6]
+        with self.assertRaises(IndexError):
+            jmap[-7]
+
+    def test_iteration(self):
+        jmap = load()
+        rows = [dict(entry) for entry in jmap]
+        self.assertEqual(len(rows), len(jmap))
+        self.assertEqual(rows[2]["AstroDome"], 4)
+        self.assertIn("Comet", jmap)
+        self.assertNotIn("NotAField", jmap)
+        self.assertEqual(repr(jmap), "JMap(entries=6, fields=9)")
+
+    def test_write_through(self):
+        jmap = load()
+        entry = jmap[0]
+        entry.Comet = "Red"
+        entry["AstroDome"] = 9
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "out.csv")
+            jmap.to_csv(path)
+            with open(path, encoding="utf-8") as f:
+                first_row = f.read().splitlines()[1]
+        self.assertIn(",Red,", first_row)
+        self.assertEqual(jmap.get_value(0, "AstroDome"), 9)
+
+
+class TestFields(unittest.TestCase):
+    def test_create_field(self):
+        jmap = load()
+        jmap.create_field("SW_AWAKE", "Int", -1)
+        jmap.create_field("SW_APPEAR", "Float")
+        self.assertEqual(jmap.get_value(3, "SW_AWAKE"), -1)
+        jmap.set_value(3, "SW_AWAKE", 12)
+
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "out.bcsv")
+            jmap.to_file(path)
+            again = lib_bcsv_jmap.JMap.from_file(NAMES, path)
+        self.assertEqual(again.get_value(3, "SW_AWAKE"), 12)
+        self.assertEqual(again.get_value(0, "SW_APPEAR"), 0.0)
+        field = [f for f in again.fields() if f["name"] == "SW_AWAKE"][0]
+        self.assertEqual((field["type"], field["default"], field["hash"]), ("Int", 0, 0x4E1893CA))
+
+    def test_drop_and_rename(self):
+        jmap = load()
+        jmap.drop_field("Comet")
+        self.assertNotIn("Comet", jmap)
+        position = [field["name"] for field in jmap.fields()].index("AstroDome")
+        jmap.rename_field("AstroDome", "ZoneName")
+        self.assertEqual(jmap.fields()[position]["name"], "ZoneName")
+        self.assertEqual(jmap.get_value(2, "ZoneName"), 4)
+
+    def test_errors(self):
+        jmap = load()
+        with self.assertRaises(lib_bcsv_jmap.BcsvError):
+            jmap.create_field("ScenarioNo", "Int")
+        with self.assertRaises(ValueError):
+            jmap.create_field("SW_AWAKE", "Integer")
+        with self.assertRaises(TypeError):
+            jmap.create_field("SW_AWAKE", "Int", "zero")
+        with self.assertRaises(KeyError):
+            jmap.drop_field("NotAField")
+        with self.assertRaises(lib_bcsv_jmap.BcsvError):
+            jmap.rename_field("Comet", "ScenarioNo")
+
+
+class TestEntries(unittest.TestCase):
+    def test_build_from_scratch(self):
+        jmap = lib_bcsv_jmap.JMap(NAMES)
+        jmap.create_field("ZoneName", "String")
+        jmap.create_field("ScenarioNo", "Int", 1)
+        self.assertEqual(jmap.add_entry({"ZoneName": "Beach"}), 0)
+        self.assertEqual(jmap.add_entry(), 1)
+        self.assertEqual(jmap.duplicate_entry(0, {"ScenarioNo": 3}), 2)
+
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, "out.bcsv")
+            jmap.to_file(path)
+            again = lib_bcsv_jmap.JMap.from_file(NAMES, path)
+        self.assertEqual([dict(entry) for entry in again], [
+            {"ZoneName": "Beach", "ScenarioNo": 1},
+            {"ZoneName": "", "ScenarioNo": 1},
+            {"ZoneName": "Beach", "ScenarioNo": 3},
+        ])
+
+    def test_remove(self):
+        jmap = load()
+        jmap.remove_entry(0)
+        self.assertEqual(len(jmap), 5)
+        self.assertEqual(jmap[0]["ScenarioNo"], 2)
+        jmap.clear_entries()
+        self.assertEqual((len(jmap), jmap.num_fields()), (0, 9))
+
+    def test_errors(self):
+        jmap = load()
+        with self.assertRaises(KeyError):
+            jmap.add_entry({"NotAField": 1})
+        with self.assertRaises(TypeError):
+            jmap.add_entry({"ScenarioNo": "one"})
+        with self.assertRaises(IndexError):
+            jmap.duplicate_entry(6)
+        with self.assertRaises(TypeError):
+            jmap.duplicate_entry(0, {"ScenarioNo": 1.5})
+        with self.assertRaises(IndexError):
+            jmap.remove_entry(6)
+        self.assertEqual(len(jmap), 6)
+
+
+class TestBytes(unittest.TestCase):
+    def test_bytes_round_trip(self):
+        with open(SCENARIO, "rb") as f:
+            data = f.read()
+        names = ["ScenarioNo", "ScenarioName", "AstroDome"]
+        for source in (data, bytearray(data), memoryview(data)):
+            jmap = lib_bcsv_jmap.JMap.from_bytes(None, source, names=names)
+            self.assertEqual(len(jmap), 6)
+            self.assertEqual(jmap.get_value(0, "ScenarioName"), "ドーム１（テラス）")
+
+        jmap.set_value(0, "AstroDome", 7)
+        little = jmap.to_bytes(big_endian=False, encoding="utf-8")
+        self.assertIsInstance(little, bytes)
+        again = lib_bcsv_jmap.JMap.from_bytes(None, little, big_endian=False, encoding="utf-8", names=names)
+        self.assertEqual(again.get_value(0, "AstroDome"), 7)
+        self.assertEqual(again.get_value(0, "ScenarioName"), "ドーム１（テラス）")
+
+    def test_csv_string(self):
+        jmap = load()
+        text = jmap.to_csv_string()
+        self.assertTrue(text.startswith("ScenarioNo:Int:0,"))
+        again = lib_bcsv_jmap.JMap.from_csv_string(NAMES, text)
+        self.assertEqual([dict(entry) for entry in again], [dict(entry) for entry in jmap])
+
+    def test_errors(self):
+        with self.assertRaises(lib_bcsv_jmap.BcsvFormatError):
+            lib_bcsv_jmap.JMap.from_bytes(None, b"short")
+        with self.assertRaises(ValueError):
+            load().to_bytes(encoding="latin-1")
+        with self.assertRaises(TypeError):
+            lib_bcsv_jmap.JMap.from_bytes(None, "not bytes")
+
+
+class TestRecords(unittest.TestCase):
+    def test_to_records(self):
+        jmap = load()
+        records = jmap.to_records()
+        self.assertEqual(len(records), 6)
+        self.assertEqual(list(records[0]), [field["name"] for field in jmap.fields()])
+        self.assertEqual(records[1]["ScenarioNo"], 2)
+
+    def test_round_trip(self):
+        jmap = load()
+        # The shape of DataFrame.to_dict("records") after filtering and editing a column
+        records = [dict(record) for record in jmap.to_records() if record["ScenarioNo"] != 3]
+        for record in records:
+            record["PowerStarId"] = record["ScenarioNo"] * 10
+        records.append({"ScenarioNo": 9, "ScenarioName": "new"})
+        jmap.update_from_records(records)
+
+        again = lib_bcsv_jmap.JMap.from_csv_string(NAMES, jmap.to_csv_string())
+        self.assertEqual(len(again), 6)
+        self.assertEqual([entry["ScenarioNo"] for entry in again], [1, 2, 4, 5, 6, 9])
+        self.assertEqual(again[4]["PowerStarId"], 60)
+        self.assertEqual(again[5]["ScenarioName"], "new")
+        self.assertEqual(again[5]["PowerStarId"], 0)
+
+    def test_strict(self):
+        jmap = load()
+        records = jmap.to_records()
+        records[0]["Extra"] = 1
+        with self.assertRaises(KeyError):
+            jmap.update_from_records(records)
+        records[1]["ScenarioNo"] = "two"
+        with self.assertRaises(TypeError):
+            jmap.update_from_records(records, strict=False)
+        self.assertEqual(jmap[1]["ScenarioNo"], 2)
+        records[1]["ScenarioNo"] = 20
+        jmap.update_from_records(records, strict=False)
+        self.assertEqual(jmap[1]["ScenarioNo"], 20)
+
+
+class TestExceptions(unittest.TestCase):
+    def test_classes(self):
+        for name in ("BcsvFormatError", "BcsvTypeError", "BcsvEncodingError", "BcsvLookupError"):
+            self.assertTrue(issubclass(getattr(lib_bcsv_jmap, name), lib_bcsv_jmap.BcsvError))
+
+    def raised(self, function, *args, **kwargs):
+        with self.assertRaises(lib_bcsv_jmap.BcsvError) as context:
+            function(*args, **kwargs)
+        return context.exception
+
+    def test_variants(self):
+        error = self.raised(lib_bcsv_jmap.JMap.from_csv_string, NAMES, "ScenarioNo:Int:0\nabc\n")
+        self.assertIsInstance(error, lib_bcsv_jmap.BcsvFormatError)
+        self.assertIn("Cannot parse 'abc' as integer", str(error))
+
+        error = self.raised(lib_bcsv_jmap.JMap.from_file, "missing_names.txt", SCENARIO)
+        self.assertIsInstance(error, lib_bcsv_jmap.BcsvLookupError)
+        self.assertEqual(str(error), "Lookup file not found: missing_names.txt")
+
+        with open(SCENARIO, "rb") as f:
+            data = f.read()
+        error = self.raised(lib_bcsv_jmap.JMap.from_bytes, None, data, encoding="utf-8")
+        self.assertIsInstance(error, lib_bcsv_jmap.BcsvEncodingError)
+
+        with self.assertRaises(FileNotFoundError):
+            lib_bcsv_jmap.JMap.from_file(NAMES, "missing.bcsv")
+
+
+class TestHashes(unittest.TestCase):
+    def test_calc_hash(self):
+        self.assertEqual(lib_bcsv_jmap.calc_hash("ScenarioNo"), 0xED08B591)
+        self.assertEqual(lib_bcsv_jmap.calc_hash("SW_AWAKE"), 0x4E1893CA)
+
+    def read(self, names=None):
+        with open(SCENARIO, "rb") as f:
+            return lib_bcsv_jmap.JMap.from_bytes(None, f.read(), names=names)
+
+    def test_names(self):
+        jmap = self.read()
+        self.assertEqual(len(jmap.unresolved_hashes()), 9)
+        self.assertIn(0xED08B591, jmap.unresolved_hashes())
+        self.assertEqual(jmap.resolve_hash(0xED08B591), "[ED08B591]")
+
+        self.assertEqual(jmap.add_name("ScenarioNo"), 0xED08B591)
+        self.assertEqual(jmap.resolve_hash(0xED08B591), "ScenarioNo")
+        self.assertEqual(jmap[0]["ScenarioNo"], 1)
+        self.assertEqual(len(jmap.unresolved_hashes()), 8)
+
+    def test_save_names(self):
+        jmap = self.read(["ScenarioNo", "ScenarioName"])
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "names.txt")
+            jmap.save_names(path)
+            with open(path) as f:
+                self.assertEqual(f.read(), "ScenarioName\nScenarioNo\n")
+            again = lib_bcsv_jmap.JMap.from_file(path, SCENARIO)
+        self.assertEqual(again[0]["ScenarioNo"], 1)
+
+
+class TestSortFilter(unittest.TestCase):
+    def table(self):
+        jmap = lib_bcsv_jmap.JMap(None, ["Group", "Scale", "Name"])
+        jmap.create_field("Group", "Int")
+        jmap.create_field("Scale", "Float")
+        jmap.create_field("Name", "String")
+        for group, scale, name in [(2, 0.5, "a"), (1, 2.0, "DebugMovePos"), (2, -1.5, "c"), (1, 0.25, "d")]:
+            jmap.add_entry({"Group": group, "Scale": scale, "Name": name})
+        return jmap
+
+    def test_sort(self):
+        jmap = self.table()
+        jmap.sort("Scale")
+        self.assertEqual([entry["Scale"] for entry in jmap], [-1.5, 0.25, 0.5, 2.0])
+        jmap.sort("Group", "Scale", descending=True)
+        self.assertEqual([entry["Name"] for entry in jmap], ["a", "c", "DebugMovePos", "d"])
+        with self.assertRaises(KeyError):
+            jmap.sort("Missing")
+
+    def test_filter(self):
+        jmap = self.table()
+        self.assertEqual(jmap.retain(lambda row: row["Name"] != "DebugMovePos"), 1)
+        self.assertEqual(jmap.remove_where(lambda row: row["Scale"] < 0), 1)
+        self.assertEqual([entry["Name"] for entry in jmap], ["a", "d"])
+        self.assertEqual(jmap.find("Group", 1), [1])
+        self.assertEqual(jmap.find("Name", "b"), [])
+        with self.assertRaises(TypeError):
+            jmap.find("Group", "one")
+
+    def test_predicate_error(self):
+        jmap = self.table()
+
+        def predicate(row):
+            if row["Name"] == "c":
+                raise RuntimeError("stop")
+            return False
+
+        with self.assertRaises(RuntimeError):
+            jmap.retain(predicate)
+        self.assertEqual(len(jmap), 4)
+
+
+class TestJson(unittest.TestCase):
+    def test_round_trip(self):
+        jmap = load()
+        jmap.create_field("Flags", "UnsignedInt")
+        jmap.set_value(0, "Flags", 0xFFFFFFFF)
+        again = lib_bcsv_jmap.JMap.from_json(None, jmap.to_json())
+        self.assertEqual(again.to_records(), jmap.to_records())
+        self.assertEqual(again.get_value(0, "ScenarioName"), "ドーム１（テラス）")
+        self.assertEqual(again.get_value(0, "Flags"), 0xFFFFFFFF)
+
+        with tempfile.TemporaryDirectory() as directory:
+            path = os.path.join(directory, "scenariodata.json")
+            jmap.to_json_file(path, pretty=True)
+            again = lib_bcsv_jmap.JMap.from_json_file(None, path)
+        self.assertEqual(again.to_records(), jmap.to_records())
+
+    def test_errors(self):
+        fields = '"fields": [{"name": "ScenarioNo", "type": "Long", "default": 0}]'
+        with self.assertRaises(lib_bcsv_jmap.BcsvTypeError) as context:
+            lib_bcsv_jmap.JMap.from_json(None, '{%s, "entries": [{}, {"ScenarioNo": "one"}]}' % fields)
+        self.assertIn("entries[1].ScenarioNo", str(context.exception))
+        with self.assertRaises(lib_bcsv_jmap.BcsvLookupError) as context:
+            lib_bcsv_jmap.JMap.from_json(None, '{%s, "entries": [{"Other": 1}]}' % fields)
+        self.assertIn("entries[0].Other", str(context.exception))
+        with self.assertRaises(lib_bcsv_jmap.BcsvFormatError):
+            lib_bcsv_jmap.JMap.from_json(None, "{")
+
+
+if __name__ == "__main__":
+    unittest.main()
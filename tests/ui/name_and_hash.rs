@@ -0,0 +1,9 @@
+use lib_bcsv_jmap::ToEntry;
+
+#[derive(ToEntry)]
+struct Row {
+    #[bcsv(name = "Value", hash = 0x12345678)]
+    value: i32,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use lib_bcsv_jmap::FromEntry;
+
+#[derive(FromEntry)]
+struct Row(i32);
+
+fn main() {}
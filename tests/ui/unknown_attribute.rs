@@ -0,0 +1,9 @@
+use lib_bcsv_jmap::FromEntry;
+
+#[derive(FromEntry)]
+struct Row {
+    #[bcsv(rename = "Other")]
+    value: i32,
+}
+
+fn main() {}
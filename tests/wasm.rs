@@ -0,0 +1,59 @@
+//! Tests of the WebAssembly bindings, run with `wasm-pack test --node --features wasm`
+#![cfg(target_arch = "wasm32")]
+
+use lib_bcsv_jmap::wasm::JsJMap;
+use lib_bcsv_jmap::{calc_hash, smg_hash_table, to_buffer, FieldType, FieldValue, IoOptions, JMapInfo};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn sample() -> Vec<u8> {
+    let mut jmap = JMapInfo::new(smg_hash_table());
+    jmap.create_field("ScenarioNo", FieldType::Long, FieldValue::Int(0)).unwrap();
+    jmap.create_field("ZoneName", FieldType::StringOffset, FieldValue::String(String::new())).unwrap();
+    jmap.create_field("Scale", FieldType::Float, FieldValue::Float(1.0)).unwrap();
+    for (no, zone) in [(1, "RedBlueExGalaxy"), (2, "HeavensDoorGalaxy")] {
+        let entry = jmap.create_entry();
+        entry.set_by_hash(calc_hash("ScenarioNo"), FieldValue::Int(no));
+        entry.set_by_hash(calc_hash("ZoneName"), FieldValue::String(zone.to_string()));
+    }
+    to_buffer(&jmap, &IoOptions::default()).unwrap()
+}
+
+/// Get the message of a thrown error
+fn message(err: wasm_bindgen::JsError) -> String {
+    JsValue::from(err).unchecked_into::<js_sys::Error>().message().into()
+}
+
+#[wasm_bindgen_test]
+fn test_round_trip() {
+    let data = sample();
+    let mut jmap = JsJMap::new(&data, Some("# SMG\nScenarioNo\nZoneName\n".to_string()), JsValue::UNDEFINED).unwrap();
+    assert_eq!(jmap.entry_count(), 2);
+    assert_eq!(jmap.field_count(), 3);
+    // Scale is not in the names
+    let mut names = jmap.field_names();
+    names.sort();
+    assert_eq!(names, ["ScenarioNo", "ZoneName", &format!("[{:08X}]", calc_hash("Scale"))]);
+    assert_eq!(jmap.get(1, "ZoneName").unwrap().as_string().as_deref(), Some("HeavensDoorGalaxy"));
+    assert_eq!(jmap.get(0, "ScenarioNo").unwrap().as_f64(), Some(1.0));
+
+    // Unchanged tables are written back byte for byte
+    assert_eq!(jmap.to_bytes(JsValue::UNDEFINED).unwrap(), data);
+
+    jmap.set(0, "ScenarioNo", JsValue::from(5)).unwrap();
+    let little_endian = js_sys::Object::new();
+    js_sys::Reflect::set(&little_endian, &"bigEndian".into(), &JsValue::FALSE).unwrap();
+    let written = jmap.to_bytes(little_endian.clone().into()).unwrap();
+    let read = JsJMap::new(&written, Some("ScenarioNo".to_string()), little_endian.into()).unwrap();
+    assert_eq!(read.get(0, "ScenarioNo").unwrap().as_f64(), Some(5.0));
+    assert!(read.to_csv_string().unwrap().contains("HeavensDoorGalaxy"));
+}
+
+#[wasm_bindgen_test]
+fn test_errors() {
+    let mut jmap = JsJMap::new(&sample(), Some("ScenarioNo".to_string()), JsValue::UNDEFINED).unwrap();
+    assert_eq!(message(jmap.get(2, "ScenarioNo").unwrap_err()), "Entry index out of bounds: 2 (len: 2)");
+    assert_eq!(message(jmap.get(0, "Missing").unwrap_err()), "Field not found: Missing");
+    assert!(message(jmap.set(0, "ScenarioNo", JsValue::from(1.5)).unwrap_err()).contains("out of range"));
+    assert!(JsJMap::new(b"not a bcsv", None, JsValue::UNDEFINED).is_err());
+}